@@ -0,0 +1,89 @@
+//! The ways of showing the tray icon on each platform. Linux desktops show it
+//! through AppIndicator, and everything else uses GTK's own status icon, which
+//! GTK shows as a native status item in the menu bar on macOS.
+use gtk3::Menu;
+
+/// A way of showing the tray icon.
+pub trait TrayBackend {
+    /// Show `status` as the current sync status.
+    fn set_status(&mut self, status: &str);
+
+    /// Show the icon named `icon`.
+    fn set_icon(&mut self, icon: &str);
+}
+
+/// Show the tray icon with `icon` and `menu` on the current platform.
+#[cfg(target_os = "linux")]
+pub fn new(icon: &str, menu: &mut Menu) -> impl TrayBackend {
+    appindicator::AppIndicatorTray::new(icon, menu)
+}
+
+/// Show the tray icon with `icon` and `menu` on the current platform.
+#[cfg(not(target_os = "linux"))]
+pub fn new(icon: &str, menu: &mut Menu) -> impl TrayBackend {
+    status_icon::StatusIconTray::new(icon, menu)
+}
+
+#[cfg(target_os = "linux")]
+mod appindicator {
+    use super::TrayBackend;
+    use gtk3::Menu;
+    use libappindicator::{AppIndicator, AppIndicatorStatus};
+
+    /// The tray icon, shown through AppIndicator.
+    pub struct AppIndicatorTray {
+        indicator: AppIndicator,
+    }
+
+    impl AppIndicatorTray {
+        pub fn new(icon: &str, menu: &mut Menu) -> Self {
+            let mut indicator = AppIndicator::new("Celeste", icon);
+            indicator.set_status(AppIndicatorStatus::Active);
+            indicator.set_menu(menu);
+            Self { indicator }
+        }
+    }
+
+    impl TrayBackend for AppIndicatorTray {
+        fn set_status(&mut self, status: &str) {
+            self.indicator.set_title(status);
+        }
+
+        fn set_icon(&mut self, icon: &str) {
+            self.indicator.set_icon(icon);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod status_icon {
+    use super::TrayBackend;
+    use gtk3::{prelude::*, Menu, StatusIcon};
+
+    /// The tray icon, shown through GTK's status icon.
+    pub struct StatusIconTray {
+        status_icon: StatusIcon,
+    }
+
+    impl StatusIconTray {
+        pub fn new(icon: &str, menu: &mut Menu) -> Self {
+            let status_icon = StatusIcon::from_icon_name(icon);
+            status_icon.set_title("Celeste");
+            let menu = menu.clone();
+            status_icon.connect_popup_menu(move |_, button, activate_time| {
+                menu.popup_easy(button, activate_time);
+            });
+            Self { status_icon }
+        }
+    }
+
+    impl TrayBackend for StatusIconTray {
+        fn set_status(&mut self, status: &str) {
+            self.status_icon.set_tooltip_text(Some(status));
+        }
+
+        fn set_icon(&mut self, icon: &str) {
+            self.status_icon.set_from_icon_name(icon);
+        }
+    }
+}
@@ -1,50 +1,62 @@
+mod backend;
+
+use backend::TrayBackend;
 use gtk3::{glib, prelude::*, Menu, MenuItem};
-use libappindicator::{AppIndicator, AppIndicatorStatus};
 use std::sync::Mutex;
 use zbus::blocking::Connection;
 
-lazy_static::lazy_static! {
-    static ref CLOSE_REQUEST: Mutex<bool> = Mutex::new(false);
-    static ref SYNC_ICON_REQUEST: Mutex<bool> = Mutex::new(false);
-    static ref WARNING_ICON_REQUEST: Mutex<bool> = Mutex::new(false);
-    static ref DONE_ICON_REQUEST: Mutex<bool> = Mutex::new(false);
-    static ref CURRENT_STATUS: Mutex<String> = Mutex::new(String::new());
+/// The requests that can be sent to the tray icon.
+enum TrayRequest {
+    Close,
+    UpdateStatus(String),
+    SetIcon(&'static str),
+}
+
+struct TrayIcon {
+    // zbus needs the interface to be [`Sync`], which [`glib::Sender`] isn't, so
+    // wrap it in a [`Mutex`].
+    sender: Mutex<glib::Sender<TrayRequest>>,
 }
 
-struct TrayIcon;
+impl TrayIcon {
+    fn send(&self, request: TrayRequest) {
+        self.sender.lock().unwrap().send(request).unwrap();
+    }
+}
 
 #[zbus::dbus_interface(name = "com.hunterwittenborn.Celeste.Tray")]
 impl TrayIcon {
     async fn close(&self) {
-        *(*CLOSE_REQUEST).lock().unwrap() = true;
+        self.send(TrayRequest::Close);
     }
 
     async fn update_status(&self, status: &str) {
-        *(*CURRENT_STATUS).lock().unwrap() = status.to_string();
+        self.send(TrayRequest::UpdateStatus(status.to_string()));
     }
 
     async fn set_syncing_icon(&self) {
-        *(*SYNC_ICON_REQUEST).lock().unwrap() = true;
+        self.send(TrayRequest::SetIcon(
+            "com.hunterwittenborn.Celeste.CelesteTraySyncing-symbolic",
+        ));
     }
 
     async fn set_warning_icon(&self) {
-        *(*WARNING_ICON_REQUEST).lock().unwrap() = true;
+        self.send(TrayRequest::SetIcon(
+            "com.hunterwittenborn.Celeste.CelesteTrayWarning-symbolic",
+        ));
     }
 
     async fn set_done_icon(&self) {
-        *(*DONE_ICON_REQUEST).lock().unwrap() = true;
+        self.send(TrayRequest::SetIcon(
+            "com.hunterwittenborn.Celeste.CelesteTrayDone-symbolic",
+        ));
     }
 }
 
 fn main() {
     gtk3::init().unwrap();
-
-    // The indicator.
-    let mut indicator = AppIndicator::new(
-        "Celeste",
-        "com.hunterwittenborn.Celeste.CelesteTrayLoading-symbolic",
-    );
-    indicator.set_status(AppIndicatorStatus::Active);
+    tr::tr_init!(libceleste::get_locale_dir());
+    libceleste::init_translations();
 
     let mut menu = Menu::new();
     let menu_sync_status = MenuItem::builder()
@@ -56,13 +68,27 @@ fn main() {
     menu.append(&menu_sync_status);
     menu.append(&menu_open);
     menu.append(&menu_quit);
-    indicator.set_menu(&mut menu);
+
+    // The indicator.
+    let mut indicator = backend::new(
+        "com.hunterwittenborn.Celeste.CelesteTrayLoading-symbolic",
+        &mut menu,
+    );
+
+    // Requests get processed on the main loop as they come in, so the tray stays
+    // idle otherwise.
+    let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
 
     // Our DBus connection to receive messages from the main application.
     let connection = Connection::session().unwrap();
     connection
         .object_server()
-        .at(libceleste::DBUS_TRAY_OBJECT, TrayIcon)
+        .at(
+            libceleste::DBUS_TRAY_OBJECT,
+            TrayIcon {
+                sender: Mutex::new(sender.clone()),
+            },
+        )
         .unwrap();
     connection.request_name(libceleste::TRAY_ID).unwrap();
 
@@ -81,50 +107,43 @@ fn main() {
     menu_open.connect_activate(glib::clone!(@strong call_fn => move |_| {
         call_fn("Open").unwrap();
     }));
-    menu_quit.connect_activate(|_| {
-        *(*CLOSE_REQUEST).lock().unwrap() = true;
+    menu_quit.connect_activate(move |_| {
+        sender.send(TrayRequest::Close).unwrap();
     });
 
+    receiver.attach(
+        None,
+        glib::clone!(@strong menu_sync_status, @strong menu_quit => move |request| {
+            match request {
+                TrayRequest::UpdateStatus(status) => {
+                    indicator.set_status(&status);
+                    menu_sync_status.set_label(&status);
+                }
+                TrayRequest::SetIcon(icon) => indicator.set_icon(icon),
+                TrayRequest::Close => {
+                    // Set up the quit label.
+                    menu_quit.set_sensitive(false);
+                    menu_quit.set_label(&tr::tr!("Quitting..."));
+
+                    // Notify the tray icon to close.
+                    // I'm not sure when this can fail, so output an error if one is received.
+                    if let Err(err) = call_fn("Close") {
+                        hw_msg::warningln!(
+                            "Got error while sending close request to main application: '{err}'."
+                        );
+                    };
+
+                    // And then quit the application.
+                    gtk3::main_quit();
+                    return glib::Continue(false);
+                }
+            }
+
+            glib::Continue(true)
+        }),
+    );
+
     // Start up the application.
     menu.show_all();
-
-    loop {
-        #[allow(clippy::if_same_then_else)]
-        if gtk3::main_iteration_do(false) {
-        } else {
-        };
-
-        let status = (*(*CURRENT_STATUS).lock().unwrap()).clone();
-        indicator.set_title(&status);
-        menu_sync_status.set_label(&status);
-
-        if *(*SYNC_ICON_REQUEST).lock().unwrap() {
-            indicator.set_icon("com.hunterwittenborn.Celeste.CelesteTraySyncing-symbolic");
-        } else if *(*DONE_ICON_REQUEST).lock().unwrap() {
-            indicator.set_icon("com.hunterwittenborn.Celeste.CelesteTrayDone-symbolic");
-        } else if *(*WARNING_ICON_REQUEST).lock().unwrap() {
-            indicator.set_icon("com.hunterwittenborn.Celeste.CelesteTrayWarning-symbolic");
-        }
-
-        *(*SYNC_ICON_REQUEST).lock().unwrap() = false;
-        *(*WARNING_ICON_REQUEST).lock().unwrap() = false;
-        *(*DONE_ICON_REQUEST).lock().unwrap() = false;
-
-        if *(*CLOSE_REQUEST).lock().unwrap() {
-            // Set up the quit label.
-            menu_quit.set_sensitive(false);
-            menu_quit.set_label(&tr::tr!("Quitting..."));
-
-            // Notify the tray icon to close.
-            // I'm not sure when this can fail, so output an error if one is received.
-            if let Err(err) = call_fn("Close") {
-                hw_msg::warningln!(
-                    "Got error while sending close request to main application: '{err}'."
-                );
-            };
-
-            // And then quit the application.
-            break;
-        }
-    }
+    gtk3::main();
 }
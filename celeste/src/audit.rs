@@ -0,0 +1,829 @@
+//! The audit log, an append-only record of every destructive operation done
+//! while syncing. Nothing in it ever gets changed or removed, so it can be used
+//! to work out what happened to an item that went missing.
+//!
+//! Copies of the items removed or replaced during a sync pass are kept in the
+//! sync directory's trash and versions directories for as many days as its
+//! remote is set to keep them for, and the most recent pass can be undone from
+//! them.
+use crate::{
+    db,
+    entities::{
+        AuditLogActiveModel, AuditLogColumn, AuditLogEntity, AuditLogModel, RemotesModel,
+        SyncDirsModel, SyncItemsActiveModel, SyncItemsColumn, SyncItemsEntity,
+    },
+    exclusions, paths,
+    rclone::{RcloneError, RcloneListFilter},
+    sync_backend::SyncBackend,
+    sync_decision::InitialSync,
+};
+use adw::{
+    gio, glib,
+    gtk::{
+        Align, Box, Label, ListBox, Orientation, PolicyType, ScrolledWindow, SearchEntry,
+        SelectionMode,
+    },
+    prelude::*,
+    ActionRow, Application, ApplicationWindow, HeaderBar,
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, Condition, DatabaseConnection, EntityTrait,
+    QueryFilter, QueryOrder, QuerySelect,
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// The most entries to show in the audit log window at once.
+const WINDOW_ENTRY_LIMIT: u64 = 500;
+
+/// The number of seconds in a day.
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// How often [`prune_kept_copies`] needs to be run for each sync directory.
+pub const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// An operation recorded in the audit log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditAction {
+    /// A local item was deleted.
+    DeleteLocal,
+    /// A remote item was deleted.
+    DeleteRemote,
+    /// A local item was replaced with the remote one.
+    OverwriteLocal,
+    /// A remote item was replaced with the local one.
+    OverwriteRemote,
+    /// A local item was put back from the copy kept of it.
+    RestoreLocal,
+    /// A remote item was put back from the copy kept of it.
+    RestoreRemote,
+}
+
+impl AuditAction {
+    /// Get an action from its name, as stored in the database.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "delete_local" => Some(Self::DeleteLocal),
+            "delete_remote" => Some(Self::DeleteRemote),
+            "overwrite_local" => Some(Self::OverwriteLocal),
+            "overwrite_remote" => Some(Self::OverwriteRemote),
+            "restore_local" => Some(Self::RestoreLocal),
+            "restore_remote" => Some(Self::RestoreRemote),
+            _ => None,
+        }
+    }
+
+    /// Get the name of the action, as stored in the database.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::DeleteLocal => "delete_local",
+            Self::DeleteRemote => "delete_remote",
+            Self::OverwriteLocal => "overwrite_local",
+            Self::OverwriteRemote => "overwrite_remote",
+            Self::RestoreLocal => "restore_local",
+            Self::RestoreRemote => "restore_remote",
+        }
+    }
+
+    /// Whether the action was done to the local item, instead of the remote
+    /// one.
+    pub fn is_local(&self) -> bool {
+        matches!(
+            self,
+            Self::DeleteLocal | Self::OverwriteLocal | Self::RestoreLocal
+        )
+    }
+
+    /// A description of the action, as shown to the user.
+    pub fn description(&self) -> String {
+        match self {
+            Self::DeleteLocal => tr::tr!("Deleted locally"),
+            Self::DeleteRemote => tr::tr!("Deleted on the remote"),
+            Self::OverwriteLocal => tr::tr!("Replaced locally with the remote copy"),
+            Self::OverwriteRemote => tr::tr!("Replaced on the remote with the local copy"),
+            Self::RestoreLocal => tr::tr!("Restored locally"),
+            Self::RestoreRemote => tr::tr!("Restored on the remote"),
+        }
+    }
+}
+
+/// Why an [`AuditAction`] was done.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditReason {
+    /// The local item changed since the last sync, and the remote one didn't.
+    ChangedLocally,
+    /// The remote item changed since the last sync, and the local one didn't.
+    ChangedOnRemote,
+    /// Both items existed without having been synced before, and the local one
+    /// was newer.
+    NewerLocally,
+    /// Both items existed without having been synced before, and the remote
+    /// one was newer.
+    NewerOnRemote,
+    /// The item was deleted locally since the last sync.
+    DeletedLocally,
+    /// The item was deleted on the remote since the last sync.
+    DeletedOnRemote,
+    /// The item was an empty directory, and the sync directory is set to remove
+    /// those.
+    EmptyDirectory,
+    /// The user chose to keep the local item when resolving a conflict.
+    KeptLocal,
+    /// The user chose to keep the remote item when resolving a conflict.
+    KeptRemote,
+    /// The user merged the local and remote items by hand when resolving a
+    /// conflict.
+    Merged,
+    /// The user chose to make the remote folder match the local one when first
+    /// syncing it.
+    MatchedLocal,
+    /// The user chose to make the local folder match the remote one when first
+    /// syncing it.
+    MatchedRemote,
+    /// The user undid the sync pass that removed or replaced the item.
+    Undo,
+}
+
+impl AuditReason {
+    /// Get a reason from its name, as stored in the database.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "changed_locally" => Some(Self::ChangedLocally),
+            "changed_on_remote" => Some(Self::ChangedOnRemote),
+            "newer_locally" => Some(Self::NewerLocally),
+            "newer_on_remote" => Some(Self::NewerOnRemote),
+            "deleted_locally" => Some(Self::DeletedLocally),
+            "deleted_on_remote" => Some(Self::DeletedOnRemote),
+            "empty_directory" => Some(Self::EmptyDirectory),
+            "kept_local" => Some(Self::KeptLocal),
+            "kept_remote" => Some(Self::KeptRemote),
+            "merged" => Some(Self::Merged),
+            "matched_local" => Some(Self::MatchedLocal),
+            "matched_remote" => Some(Self::MatchedRemote),
+            "undo" => Some(Self::Undo),
+            _ => None,
+        }
+    }
+
+    /// Get the name of the reason, as stored in the database.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ChangedLocally => "changed_locally",
+            Self::ChangedOnRemote => "changed_on_remote",
+            Self::NewerLocally => "newer_locally",
+            Self::NewerOnRemote => "newer_on_remote",
+            Self::DeletedLocally => "deleted_locally",
+            Self::DeletedOnRemote => "deleted_on_remote",
+            Self::EmptyDirectory => "empty_directory",
+            Self::KeptLocal => "kept_local",
+            Self::KeptRemote => "kept_remote",
+            Self::Merged => "merged",
+            Self::MatchedLocal => "matched_local",
+            Self::MatchedRemote => "matched_remote",
+            Self::Undo => "undo",
+        }
+    }
+
+    /// A description of the reason, as shown to the user.
+    pub fn description(&self) -> String {
+        match self {
+            Self::ChangedLocally => tr::tr!("it changed locally since the last sync"),
+            Self::ChangedOnRemote => tr::tr!("it changed on the remote since the last sync"),
+            Self::NewerLocally => tr::tr!("the local copy was newer"),
+            Self::NewerOnRemote => tr::tr!("the remote copy was newer"),
+            Self::DeletedLocally => tr::tr!("it was deleted locally"),
+            Self::DeletedOnRemote => tr::tr!("it was deleted on the remote"),
+            Self::EmptyDirectory => tr::tr!("it was an empty folder"),
+            Self::KeptLocal => tr::tr!("the local copy was kept in a conflict"),
+            Self::KeptRemote => tr::tr!("the remote copy was kept in a conflict"),
+            Self::Merged => tr::tr!("both copies were merged in a conflict"),
+            Self::MatchedLocal => tr::tr!("the remote folder was set to match the local one"),
+            Self::MatchedRemote => tr::tr!("the local folder was set to match the remote one"),
+            Self::Undo => tr::tr!("the sync that changed it was undone"),
+        }
+    }
+
+    /// Get the reason for replacing the remote copy of an item that hadn't been
+    /// synced before, according to the directory's `initial_sync` choice.
+    pub fn for_overwrite_remote(initial_sync: InitialSync) -> Self {
+        match initial_sync {
+            InitialSync::KeepLocal => Self::MatchedLocal,
+            _ => Self::NewerLocally,
+        }
+    }
+
+    /// Get the reason for replacing the local copy of an item that hadn't been
+    /// synced before, according to the directory's `initial_sync` choice.
+    pub fn for_overwrite_local(initial_sync: InitialSync) -> Self {
+        match initial_sync {
+            InitialSync::KeepRemote => Self::MatchedRemote,
+            _ => Self::NewerOnRemote,
+        }
+    }
+}
+
+/// The current UNIX timestamp.
+fn now() -> i64 {
+    crate::launch::system_time_to_timestamp(SystemTime::now())
+}
+
+/// The timestamps recorded for an item restored over a newer copy, which never
+/// match either side's. This makes the next sync treat both sides as changed,
+/// so that the user gets asked which to keep.
+const CONFLICT_TIMESTAMP: i64 = i64::MIN;
+
+/// Where the copies of removed and replaced items get kept, inside a directory
+/// for the sync pass that removed or replaced them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeptCopies {
+    /// Items that got deleted.
+    Trash,
+    /// The previous versions of items that got replaced.
+    Versions,
+}
+
+impl KeptCopies {
+    /// Get the name of the directory the copies get kept in.
+    fn dir_name(&self) -> &'static str {
+        match self {
+            Self::Trash => exclusions::TRASH_DIR_NAME,
+            Self::Versions => exclusions::VERSIONS_DIR_NAME,
+        }
+    }
+
+    /// Get the path the copy of the local item at `local_path` from the sync
+    /// pass started at `pass_started` is kept at. Returns [`None`] if the item
+    /// isn't inside of `sync_dir`.
+    fn local_path(
+        &self,
+        sync_dir: &SyncDirsModel,
+        pass_started: i64,
+        local_path: &str,
+    ) -> Option<PathBuf> {
+        let relative_path = paths::relative_local_path(sync_dir, Path::new(local_path))?;
+        Some(
+            Path::new(&sync_dir.local_path)
+                .join(self.dir_name())
+                .join(pass_started.to_string())
+                .join(relative_path),
+        )
+    }
+
+    /// Get the path the copy of the remote item at `remote_path` from the sync
+    /// pass started at `pass_started` is kept at. Returns [`None`] if the item
+    /// isn't inside of `sync_dir`.
+    fn remote_path(
+        &self,
+        sync_dir: &SyncDirsModel,
+        pass_started: i64,
+        remote_path: &str,
+    ) -> Option<String> {
+        let relative_path = paths::relative_remote_path(sync_dir, remote_path)?;
+        let pass_dir = paths::join_remote(
+            &paths::join_remote(&sync_dir.remote_path, self.dir_name()),
+            &pass_started.to_string(),
+        );
+        Some(paths::join_remote(&pass_dir, relative_path))
+    }
+}
+
+/// Remove the local item at `path`, whether it's a file or a directory.
+fn remove_local_item(path: &Path) -> io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Move the files inside of the remote directory `src` to `dst`, and then
+/// remove `src`. Empty directories inside of `src` don't get moved.
+fn move_remote_dir(backend: &dyn SyncBackend, src: &str, dst: &str) -> Result<(), RcloneError> {
+    for item in backend.list(src, true, RcloneListFilter::Files)? {
+        let relative_path = item.path.strip_prefix(src).unwrap_or(&item.path);
+        backend.move_file(&item.path, &paths::join_remote(dst, relative_path))?;
+    }
+    backend.purge(src)
+}
+
+/// Whether the copies kept in the pass directory named `name` are older than
+/// `keep_days` days, as of the UNIX timestamp `now`. Directories that aren't
+/// named after a pass are left alone.
+fn is_expired(name: &str, now: i64, keep_days: i32) -> bool {
+    name.parse::<i64>().map_or(false, |pass_started| {
+        now - pass_started >= i64::from(keep_days) * SECS_PER_DAY
+    })
+}
+
+/// Remove the copies kept by the sync passes of `sync_dir` from more than
+/// `keep_days` days ago, both locally and on the remote. This is kept separate
+/// from syncing, so that the copies from earlier passes are still there after
+/// later ones remove or replace anything.
+pub fn prune_kept_copies(backend: &dyn SyncBackend, sync_dir: &SyncDirsModel, keep_days: i32) {
+    let now = now();
+
+    for store in [KeptCopies::Trash, KeptCopies::Versions] {
+        let store_dir = Path::new(&sync_dir.local_path).join(store.dir_name());
+        for entry in fs::read_dir(store_dir).into_iter().flatten().flatten() {
+            if entry
+                .file_name()
+                .to_str()
+                .map_or(false, |name| is_expired(name, now, keep_days))
+                && let Err(err) = remove_local_item(&entry.path())
+            {
+                hw_msg::warningln!(
+                    "Unable to remove '{}' from an earlier sync: '{err}'.",
+                    entry.path().display()
+                );
+            }
+        }
+
+        let store_dir = paths::join_remote(&sync_dir.remote_path, store.dir_name());
+        let Ok(items) = backend.list(&store_dir, false, RcloneListFilter::All) else {
+            continue;
+        };
+        for item in items
+            .into_iter()
+            .filter(|item| is_expired(&item.name, now, keep_days))
+        {
+            if let Err(err) = backend.purge(&item.path) {
+                hw_msg::warningln!(
+                    "Unable to remove '{}' from an earlier sync: '{err}'.",
+                    item.path
+                );
+            }
+        }
+    }
+}
+
+/// Records the destructive operations of a single sync pass, and keeps copies
+/// of the items they remove or replace so that the pass can be undone.
+pub struct AuditLog {
+    /// When the sync pass started, as a UNIX timestamp.
+    pass_started: i64,
+    /// Whether removed local items go in the desktop's trash instead of the
+    /// sync directory's, where they can be restored like any other deleted
+    /// file (though not by undoing the sync).
+    system_trash: bool,
+    /// Whether copies of replaced remote items are kept on the remote.
+    remote_versions: bool,
+    /// Whether copies of removed remote items are kept on the remote.
+    remote_trash: bool,
+}
+
+impl AuditLog {
+    /// Start recording a new sync pass.
+    pub fn start() -> Self {
+        Self {
+            pass_started: now(),
+            system_trash: false,
+            remote_versions: true,
+            remote_trash: true,
+        }
+    }
+
+    /// Set whether removed local items go in the desktop's trash.
+    pub fn with_system_trash(mut self, system_trash: bool) -> Self {
+        self.system_trash = system_trash;
+        self
+    }
+
+    /// Set which copies of remote items are kept from `remote`'s options. None
+    /// are kept if it's set not to keep them, and removed items aren't kept if
+    /// it's set to delete them permanently, as they'd still be recoverable
+    /// otherwise.
+    pub fn with_remote_options(mut self, remote: &RemotesModel) -> Self {
+        self.remote_versions = remote.keep_remote_copies;
+        self.remote_trash = remote.keep_remote_copies && !remote.permanent_delete;
+        self
+    }
+
+    /// Record that `action` was done to the item at `local_path` and
+    /// `remote_path` for `reason`.
+    pub fn record(
+        &self,
+        db: &DatabaseConnection,
+        sync_dir: &SyncDirsModel,
+        action: AuditAction,
+        reason: AuditReason,
+        local_path: &str,
+        remote_path: &str,
+    ) {
+        db::expect(|| {
+            AuditLogActiveModel {
+                sync_dir_id: ActiveValue::Set(sync_dir.id),
+                pass_started: ActiveValue::Set(self.pass_started),
+                timestamp: ActiveValue::Set(now()),
+                action: ActiveValue::Set(action.name().to_owned()),
+                reason: ActiveValue::Set(reason.name().to_owned()),
+                local_path: ActiveValue::Set(local_path.to_owned()),
+                remote_path: ActiveValue::Set(remote_path.to_owned()),
+                ..Default::default()
+            }
+            .insert(db)
+        });
+    }
+
+    /// Remove the local item at `local_path` by moving it into `store`.
+    pub fn remove_local(
+        &self,
+        sync_dir: &SyncDirsModel,
+        store: KeptCopies,
+        local_path: &str,
+    ) -> io::Result<()> {
+        let Some(copy_path) = store.local_path(sync_dir, self.pass_started, local_path) else {
+            return remove_local_item(Path::new(local_path));
+        };
+
+        // Not every file system has a trash, so fall back to our own on those.
+        if store == KeptCopies::Trash && self.system_trash {
+            match gio::File::for_path(local_path).trash(gio::Cancellable::NONE) {
+                Ok(()) => return Ok(()),
+                Err(err) if err.matches(gio::IOErrorEnum::NotSupported) => hw_msg::warningln!(
+                    "Unable to move '{local_path}' to the trash, so keeping it with the sync directory instead: '{err}'."
+                ),
+                Err(err) => return Err(io::Error::other(err.to_string())),
+            }
+        }
+
+        fs::create_dir_all(copy_path.parent().unwrap())?;
+        fs::rename(local_path, copy_path)
+    }
+
+    /// Keep a copy of the local file at `local_path` before it gets replaced.
+    pub fn keep_local_version(&self, sync_dir: &SyncDirsModel, local_path: &str) -> io::Result<()> {
+        let Some(copy_path) =
+            KeptCopies::Versions.local_path(sync_dir, self.pass_started, local_path)
+        else {
+            return Ok(());
+        };
+
+        fs::create_dir_all(copy_path.parent().unwrap())?;
+        fs::copy(local_path, copy_path).map(|_| ())
+    }
+
+    /// Whether copies of remote items get kept in `store`.
+    fn keeps_remote(&self, store: KeptCopies) -> bool {
+        match store {
+            KeptCopies::Trash => self.remote_trash,
+            KeptCopies::Versions => self.remote_versions,
+        }
+    }
+
+    /// Remove the remote item at `remote_path` by moving it into `store`.
+    /// Moving items would mean transferring them again on remotes that can't
+    /// copy them server-side, so they're just deleted on those, as well as
+    /// when the remote isn't keeping copies in `store`.
+    pub fn remove_remote(
+        &self,
+        backend: &dyn SyncBackend,
+        sync_dir: &SyncDirsModel,
+        store: KeptCopies,
+        remote_path: &str,
+        is_dir: bool,
+    ) -> Result<(), RcloneError> {
+        let copy_path = match store.remote_path(sync_dir, self.pass_started, remote_path) {
+            Some(copy_path) if self.keeps_remote(store) && backend.supports_server_side_copy() => {
+                copy_path
+            }
+            _ => return backend.purge(remote_path),
+        };
+
+        if is_dir {
+            move_remote_dir(backend, remote_path, &copy_path)
+        } else {
+            backend.move_file(remote_path, &copy_path)
+        }
+    }
+
+    /// Keep a copy of the remote file at `remote_path` before it gets replaced,
+    /// if the remote is keeping such and can copy it server-side.
+    pub fn keep_remote_version(
+        &self,
+        backend: &dyn SyncBackend,
+        sync_dir: &SyncDirsModel,
+        remote_path: &str,
+    ) -> Result<(), RcloneError> {
+        let copy_path =
+            match KeptCopies::Versions.remote_path(sync_dir, self.pass_started, remote_path) {
+                Some(copy_path)
+                    if self.keeps_remote(KeptCopies::Versions)
+                        && backend.supports_server_side_copy() =>
+                {
+                    copy_path
+                }
+                _ => return Ok(()),
+            };
+
+        backend.copy_file(remote_path, &copy_path)
+    }
+
+    /// Undo the most recent sync pass of `sync_dir` before this one, by putting
+    /// back the copies kept of the items it removed or replaced. Items without
+    /// a kept copy get skipped.
+    ///
+    /// Removed items get synced again as new items once they're restored.
+    /// Replaced items still have a newer copy on the other side, so the user
+    /// gets asked which to keep on the next sync.
+    ///
+    /// Returns the path and error message of any items that couldn't be
+    /// restored.
+    pub fn undo_previous_pass(
+        &self,
+        backend: &dyn SyncBackend,
+        sync_dir: &SyncDirsModel,
+        db: &DatabaseConnection,
+    ) -> Vec<(String, String)> {
+        let Some(pass_started) = last_pass(db, sync_dir.id, self.pass_started) else {
+            return vec![];
+        };
+        let entries = db::expect(|| {
+            AuditLogEntity::find()
+                .filter(AuditLogColumn::SyncDirId.eq(sync_dir.id))
+                .filter(AuditLogColumn::PassStarted.eq(pass_started))
+                .order_by_desc(AuditLogColumn::Id)
+                .all(db)
+        });
+        let mut errors = vec![];
+
+        for entry in entries {
+            let (store, restored_action) = match AuditAction::from_name(&entry.action) {
+                Some(AuditAction::DeleteLocal) => (KeptCopies::Trash, AuditAction::RestoreLocal),
+                Some(AuditAction::DeleteRemote) => (KeptCopies::Trash, AuditAction::RestoreRemote),
+                Some(AuditAction::OverwriteLocal) => {
+                    (KeptCopies::Versions, AuditAction::RestoreLocal)
+                }
+                Some(AuditAction::OverwriteRemote) => {
+                    (KeptCopies::Versions, AuditAction::RestoreRemote)
+                }
+                _ => continue,
+            };
+            let result = if restored_action == AuditAction::RestoreLocal {
+                restore_local(sync_dir, store, pass_started, &entry.local_path)
+                    .map_err(|err| (entry.local_path.clone(), err))
+            } else {
+                restore_remote(backend, sync_dir, store, pass_started, &entry.remote_path)
+                    .map_err(|err| (entry.remote_path.clone(), err))
+            };
+
+            match result {
+                Ok(true) => (),
+                Ok(false) => continue,
+                Err(err) => {
+                    errors.push(err);
+                    continue;
+                }
+            }
+
+            // Removed items (and anything inside of them) need to be synced as new
+            // items, while replaced ones need to conflict with the copy on the other
+            // side.
+            db::expect(|| {
+                SyncItemsEntity::delete_many()
+                    .filter(SyncItemsColumn::SyncDirId.eq(sync_dir.id))
+                    .filter(
+                        Condition::any()
+                            .add(SyncItemsColumn::LocalPath.eq(entry.local_path.clone()))
+                            .add(
+                                SyncItemsColumn::LocalPath
+                                    .starts_with(&format!("{}/", entry.local_path)),
+                            ),
+                    )
+                    .exec(db)
+            });
+            if store == KeptCopies::Versions {
+                db::expect(|| {
+                    SyncItemsActiveModel {
+                        sync_dir_id: ActiveValue::Set(sync_dir.id),
+                        local_path: ActiveValue::Set(entry.local_path.clone()),
+                        remote_path: ActiveValue::Set(entry.remote_path.clone()),
+                        last_local_timestamp: ActiveValue::Set(CONFLICT_TIMESTAMP),
+                        last_remote_timestamp: ActiveValue::Set(CONFLICT_TIMESTAMP),
+                        local_inode: ActiveValue::Set(None),
+                        local_size: ActiveValue::Set(None),
+                        ..Default::default()
+                    }
+                    .insert(db)
+                });
+            }
+
+            self.record(
+                db,
+                sync_dir,
+                restored_action,
+                AuditReason::Undo,
+                &entry.local_path,
+                &entry.remote_path,
+            );
+        }
+
+        errors
+    }
+}
+
+/// Get when the most recent sync pass of the sync directory with the ID
+/// `sync_dir_id` that was started before `before` and recorded anything was
+/// started.
+pub fn last_pass(db: &DatabaseConnection, sync_dir_id: i32, before: i64) -> Option<i64> {
+    db::expect(|| {
+        AuditLogEntity::find()
+            .filter(AuditLogColumn::SyncDirId.eq(sync_dir_id))
+            .filter(AuditLogColumn::PassStarted.lt(before))
+            .order_by_desc(AuditLogColumn::PassStarted)
+            .one(db)
+    })
+    .map(|entry| entry.pass_started)
+}
+
+/// Put the local item at `local_path` back from the copy kept of it in
+/// `store` by the sync pass started at `pass_started`. Returns whether a copy
+/// was kept.
+fn restore_local(
+    sync_dir: &SyncDirsModel,
+    store: KeptCopies,
+    pass_started: i64,
+    local_path: &str,
+) -> Result<bool, String> {
+    let copy_path = match store.local_path(sync_dir, pass_started, local_path) {
+        Some(copy_path) if copy_path.symlink_metadata().is_ok() => copy_path,
+        _ => return Ok(false),
+    };
+    let path = Path::new(local_path);
+
+    // A replaced item's newer copy is still on the remote, but anything that
+    // showed up where a removed item was is new.
+    if path.symlink_metadata().is_ok() {
+        if store == KeptCopies::Trash {
+            return Err(tr::tr!("Another item has been created in its place since."));
+        }
+        remove_local_item(path).map_err(|err| err.to_string())?;
+    }
+
+    fs::create_dir_all(path.parent().unwrap())
+        .and_then(|_| fs::rename(copy_path, path))
+        .map_err(|err| err.to_string())?;
+    Ok(true)
+}
+
+/// The same as [`restore_local`], for the remote item at `remote_path`.
+fn restore_remote(
+    backend: &dyn SyncBackend,
+    sync_dir: &SyncDirsModel,
+    store: KeptCopies,
+    pass_started: i64,
+    remote_path: &str,
+) -> Result<bool, String> {
+    let Some(copy_path) = store.remote_path(sync_dir, pass_started, remote_path) else {
+        return Ok(false);
+    };
+    let Some(copy_item) = backend.stat(&copy_path).map_err(|err| err.to_string())? else {
+        return Ok(false);
+    };
+
+    // The same as in `restore_local`. Files can be moved over each other, but
+    // anything else has to be removed first.
+    if let Some(item) = backend.stat(remote_path).map_err(|err| err.to_string())? {
+        if store == KeptCopies::Trash {
+            return Err(tr::tr!("Another item has been created in its place since."));
+        }
+        if item.is_dir || copy_item.is_dir {
+            backend.purge(remote_path).map_err(|err| err.to_string())?;
+        }
+    }
+
+    let result = if copy_item.is_dir {
+        move_remote_dir(backend, &copy_path, remote_path)
+    } else {
+        backend.move_file(&copy_path, remote_path)
+    };
+    result.map(|_| true).map_err(|err| err.to_string())
+}
+
+/// Format a UNIX timestamp in the user's locale and time zone.
+pub fn format_timestamp(timestamp: i64) -> String {
+    glib::DateTime::from_unix_local(timestamp)
+        .and_then(|date_time| date_time.format("%c"))
+        .map_or_else(|_| timestamp.to_string(), |time| time.to_string())
+}
+
+/// Get the row shown for `entry` in the audit log window.
+fn entry_row(entry: &AuditLogModel) -> ActionRow {
+    let action = AuditAction::from_name(&entry.action);
+    let path = if action.map_or(true, |action| action.is_local()) {
+        libceleste::fmt_home(&entry.local_path)
+    } else {
+        entry.remote_path.clone()
+    };
+    let action_description =
+        action.map_or_else(|| entry.action.clone(), |action| action.description());
+    let reason_description = AuditReason::from_name(&entry.reason)
+        .map_or_else(|| entry.reason.clone(), |reason| reason.description());
+    let time = format_timestamp(entry.timestamp);
+
+    let row = ActionRow::builder()
+        .title(&path)
+        .subtitle(&tr::tr!(
+            "{}, as {}.",
+            action_description,
+            reason_description
+        ))
+        .use_markup(false)
+        .build();
+    row.add_suffix(
+        &Label::builder()
+            .label(&time)
+            .css_classes(vec!["caption".to_string(), "dim-label".to_string()])
+            .build(),
+    );
+    row
+}
+
+/// Show the audit log, newest entries first.
+pub fn audit_log_window(app: &Application, db: &DatabaseConnection) {
+    let search_entry = SearchEntry::builder()
+        .placeholder_text(&tr::tr!("Search by path"))
+        .hexpand(true)
+        .build();
+    let header_bar = HeaderBar::builder().title_widget(&search_entry).build();
+    let entries_list = ListBox::builder()
+        .selection_mode(SelectionMode::None)
+        .valign(Align::Start)
+        .css_classes(vec!["boxed-list".to_string()])
+        .build();
+    entries_list.set_placeholder(Some(
+        &Label::builder()
+            .label(&tr::tr!(
+                "Nothing has been deleted or replaced while syncing."
+            ))
+            .margin_top(10)
+            .margin_bottom(10)
+            .css_classes(vec!["dim-label".to_string()])
+            .build(),
+    ));
+    let entries_scrolled = ScrolledWindow::builder()
+        .child(&entries_list)
+        .hscrollbar_policy(PolicyType::Never)
+        .vexpand(true)
+        .css_classes(vec!["celeste-global-padding".to_string()])
+        .build();
+    let sections = Box::builder().orientation(Orientation::Vertical).build();
+    sections.append(&header_bar);
+    sections.append(&entries_scrolled);
+
+    // Fill the list with the entries whose paths contain `search`.
+    let fill_entries = glib::clone!(@strong db, @weak entries_list => move |search: &str| {
+        while let Some(row) = entries_list.first_child() {
+            entries_list.remove(&row);
+        }
+
+        let mut query = AuditLogEntity::find()
+            .order_by_desc(AuditLogColumn::Id)
+            .limit(WINDOW_ENTRY_LIMIT);
+        if !search.is_empty() {
+            query = query.filter(
+                Condition::any()
+                    .add(AuditLogColumn::LocalPath.contains(search))
+                    .add(AuditLogColumn::RemotePath.contains(search)),
+            );
+        }
+        for entry in db::expect(|| query.clone().all(&db)) {
+            entries_list.append(&entry_row(&entry));
+        }
+    });
+    fill_entries("");
+    search_entry.connect_search_changed(glib::clone!(@strong fill_entries => move |search_entry| {
+        fill_entries(&search_entry.text());
+    }));
+
+    let window = ApplicationWindow::builder()
+        .application(app)
+        .title(&libceleste::get_title!("Audit Log"))
+        .default_width(600)
+        .default_height(500)
+        .content(&sections)
+        .build();
+    window.show();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expired_pass_dirs() {
+        let now = 1_700_000_000;
+        let keep_for = 30 * SECS_PER_DAY;
+        assert!(!is_expired(&now.to_string(), now, 30));
+        assert!(!is_expired(&(now - keep_for + 1).to_string(), now, 30));
+        assert!(is_expired(&(now - keep_for).to_string(), now, 30));
+        assert!(!is_expired(&(now - keep_for).to_string(), now, 31));
+        assert!(is_expired(&(now - SECS_PER_DAY).to_string(), now, 1));
+        assert!(is_expired("0", now, 30));
+        // Only directories named after a pass get removed.
+        assert!(!is_expired("notes", now, 30));
+        assert!(!is_expired("", now, 30));
+    }
+}
@@ -0,0 +1,174 @@
+//! Backups of the database, taken before migrations change it, so that a bad
+//! migration or a crash partway through one doesn't lose the sync state.
+use crate::{
+    db, encryption, gtk_util,
+    migrations::{Migrator, MigratorTrait},
+    mpsc,
+};
+use adw::{glib, prelude::*};
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbErr, Statement};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// The most backups to keep. The oldest ones get removed when new ones are
+/// made.
+const MAX_BACKUPS: usize = 5;
+
+/// Get the directory backups are kept in.
+fn backup_dir() -> PathBuf {
+    libceleste::get_config_dir().join("backups")
+}
+
+/// Get the existing backups, newest first.
+fn list() -> Vec<PathBuf> {
+    let mut backups: Vec<PathBuf> = fs::read_dir(backup_dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "db"))
+        .collect();
+    // Backups are named after when they were made, so this sorts them by such.
+    backups.sort();
+    backups.reverse();
+    backups
+}
+
+/// Back up the database to a new file named after the current time, removing
+/// the oldest backups past [`MAX_BACKUPS`].
+fn create(db: &DatabaseConnection) -> Result<PathBuf, String> {
+    let dir = backup_dir();
+    fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+
+    let timestamp = glib::DateTime::now_local()
+        .and_then(|now| now.format("%Y-%m-%d_%H-%M-%S"))
+        .map_err(|err| err.to_string())?;
+    let path = dir.join(format!("celeste_{timestamp}.db"));
+
+    // SQLite makes the copy itself, so that it's consistent even if something else
+    // is writing to the database.
+    let sql = format!(
+        "VACUUM INTO '{}';",
+        path.to_string_lossy().replace('\'', "''")
+    );
+    let stmt = Statement::from_string(db.get_database_backend(), sql);
+    libceleste::await_future(db.execute(stmt)).map_err(|err| err.to_string())?;
+
+    // A backup of an encrypted database that isn't encrypted itself would leave
+    // its contents readable, so it isn't kept.
+    if encryption::is_encrypted(&db::path()).unwrap_or(false)
+        && !encryption::is_encrypted(&path).unwrap_or(false)
+    {
+        let _ = fs::remove_file(&path);
+        return Err("the backup wasn't encrypted along with the database".to_owned());
+    }
+
+    for old_backup in list().into_iter().skip(MAX_BACKUPS) {
+        if let Err(err) = fs::remove_file(&old_backup) {
+            hw_msg::warningln!(
+                "Unable to remove old database backup '{}': '{err}'.",
+                old_backup.display()
+            );
+        }
+    }
+
+    Ok(path)
+}
+
+/// Remove every backup, such as when they're in a format the database isn't
+/// kept in anymore.
+pub fn remove_all() {
+    for backup in list() {
+        if let Err(err) = fs::remove_file(&backup) {
+            hw_msg::warningln!(
+                "Unable to remove database backup '{}': '{err}'.",
+                backup.display()
+            );
+        }
+    }
+}
+
+/// Back up the database if there are migrations to run on it. A new database
+/// doesn't have anything in it worth backing up, so it's skipped.
+pub fn before_migrations(db: &DatabaseConnection) -> Result<Option<PathBuf>, DbErr> {
+    let pending = libceleste::await_future(Migrator::get_pending_migrations(db))?;
+    let applied = libceleste::await_future(Migrator::get_applied_migrations(db))?;
+    if pending.is_empty() || applied.is_empty() {
+        return Ok(None);
+    }
+
+    match create(db) {
+        Ok(path) => Ok(Some(path)),
+        Err(err) => {
+            hw_msg::warningln!("Unable to back up the database before migrating it: '{err}'.");
+            Ok(None)
+        }
+    }
+}
+
+/// Replace the database at `db_path` with `backup`.
+fn restore(backup: &Path, db_path: &Path) -> io::Result<()> {
+    // Leftover journal files belong to the database being replaced.
+    db::remove_journal_files(db_path)?;
+    fs::copy(backup, db_path)?;
+    Ok(())
+}
+
+/// Tell the user that the database couldn't be opened because of `err`. If
+/// there's a backup of it, offer to restore the database at `db_path` from
+/// the most recent one. Returns `true` if the backup was restored, in which
+/// case opening the database can be tried again.
+pub fn offer_restore(err: &str, db_path: &Path) -> bool {
+    let Some(backup) = list().into_iter().next() else {
+        gtk_util::show_error(err, None);
+        return false;
+    };
+
+    let backup_time = fs::metadata(&backup)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .and_then(|duration| glib::DateTime::from_unix_local(duration.as_secs() as i64).ok())
+        .and_then(|time| time.format("%c").ok())
+        .map(|time| time.to_string())
+        .unwrap_or_else(|| backup.file_name().unwrap().to_string_lossy().into_owned());
+
+    let (sender, mut receiver) = mpsc::channel::<bool>();
+    let dialog = adw::MessageDialog::builder()
+        .heading(err)
+        .body(&tr::tr!(
+            "The database can be restored from its most recent backup, made {}. Anything recorded since then will be lost, and files changed since then may show up as conflicts on the next sync.",
+            backup_time
+        ))
+        .modal(true)
+        .build();
+    dialog.add_response("quit", &tr::tr!("Quit"));
+    dialog.add_response("restore", &tr::tr!("Restore from Backup"));
+    dialog.set_response_appearance("restore", adw::ResponseAppearance::Destructive);
+    dialog.set_default_response(Some("quit"));
+    dialog.set_close_response("quit");
+    dialog.connect_response(
+        None,
+        glib::clone!(@strong sender => move |dialog, resp| {
+            dialog.close();
+            sender.send(resp == "restore");
+        }),
+    );
+    dialog.show();
+    if !receiver.recv() {
+        return false;
+    }
+
+    if let Err(err) = restore(&backup, db_path) {
+        gtk_util::show_error(
+            &tr::tr!("Unable to restore the database from its backup [{}].", err),
+            None,
+        );
+        return false;
+    }
+
+    true
+}
@@ -0,0 +1,264 @@
+//! A benchmark for syncing, run with `celeste bench`. This runs the sync
+//! engine ([`crate::launch::sync_directory`]) over a generated tree of files
+//! against Rclone's local backend, so that the cost of a sync pass can be
+//! measured without a cloud account. Everything but the GUI is the same as in
+//! a real sync pass.
+use crate::{
+    audit::AuditLog,
+    entities::{RemotesActiveModel, SettingsModel, SyncDirsActiveModel, SyncDirsModel},
+    launch::{self, SpaceCheck, UploadedFiles},
+    migrations::{Migrator, MigratorTrait},
+    rclone::{RcloneError, RcloneListFilter, RcloneRemoteItem},
+    sync_backend::{ListStream, RcloneBackend, SyncBackend},
+    sync_decision::TimestampComparison,
+};
+use clap::Args;
+use sea_orm::{ActiveModelTrait, ActiveValue, Database, DatabaseConnection};
+use std::{
+    cell::Cell,
+    fs,
+    path::Path,
+    process,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// The options for `celeste bench`.
+#[derive(Args)]
+pub struct BenchOptions {
+    /// The number of files to generate.
+    #[arg(long, default_value_t = 1000)]
+    files: usize,
+
+    /// How many directories deep to nest the generated files.
+    #[arg(long, default_value_t = 3)]
+    depth: usize,
+
+    /// The size of each generated file, in bytes.
+    #[arg(long, default_value_t = 4096)]
+    size: usize,
+}
+
+/// A [`SyncBackend`] that counts the calls made to it.
+struct CountingBackend<B: SyncBackend> {
+    backend: B,
+    calls: Cell<usize>,
+}
+
+impl<B: SyncBackend> CountingBackend<B> {
+    fn new(backend: B) -> Self {
+        Self {
+            backend,
+            calls: Cell::new(0),
+        }
+    }
+
+    /// Count a call, returning the backend to make it with.
+    fn count(&self) -> &B {
+        self.calls.set(self.calls.get() + 1);
+        &self.backend
+    }
+}
+
+impl<B: SyncBackend> SyncBackend for CountingBackend<B> {
+    fn stat(&self, path: &str) -> Result<Option<RcloneRemoteItem>, RcloneError> {
+        self.count().stat(path)
+    }
+
+    fn stat_with_hashes(&self, path: &str) -> Result<Option<RcloneRemoteItem>, RcloneError> {
+        self.count().stat_with_hashes(path)
+    }
+
+    fn list(
+        &self,
+        path: &str,
+        recursive: bool,
+        filter: RcloneListFilter,
+    ) -> Result<Vec<RcloneRemoteItem>, RcloneError> {
+        self.count().list(path, recursive, filter)
+    }
+
+    fn list_files_with_hashes(&self, path: &str) -> Result<Vec<RcloneRemoteItem>, RcloneError> {
+        self.count().list_files_with_hashes(path)
+    }
+
+    fn list_stream(&self, path: &str) -> ListStream {
+        self.count().list_stream(path)
+    }
+
+    fn free_space(&self) -> Result<Option<u64>, RcloneError> {
+        self.count().free_space()
+    }
+
+    fn mkdir(&self, path: &str) -> Result<(), RcloneError> {
+        self.count().mkdir(path)
+    }
+
+    fn delete(&self, path: &str) -> Result<(), RcloneError> {
+        self.count().delete(path)
+    }
+
+    fn purge(&self, path: &str) -> Result<(), RcloneError> {
+        self.count().purge(path)
+    }
+
+    fn move_file(&self, src: &str, dst: &str) -> Result<(), RcloneError> {
+        self.count().move_file(src, dst)
+    }
+
+    fn supports_server_side_copy(&self) -> bool {
+        self.count().supports_server_side_copy()
+    }
+
+    fn copy_file(&self, src: &str, dst: &str) -> Result<(), RcloneError> {
+        self.count().copy_file(src, dst)
+    }
+
+    fn copy_to_remote(
+        &self,
+        local_file: &str,
+        remote_destination: &str,
+    ) -> Result<(), RcloneError> {
+        self.count().copy_to_remote(local_file, remote_destination)
+    }
+
+    fn copy_to_local(&self, local_destination: &str, remote_file: &str) -> Result<(), RcloneError> {
+        self.count().copy_to_local(local_destination, remote_file)
+    }
+}
+
+/// How long before the benchmark starts the generated files were modified, so
+/// that the sync engine doesn't leave them for a later pass for having just
+/// changed.
+const GENERATED_FILE_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// The results of a single sync pass.
+struct PassResults {
+    duration: Duration,
+    api_calls: usize,
+    transfers: i32,
+    errors: usize,
+}
+
+/// Generate a tree of `options.files` files under `root`.
+fn generate_tree(root: &Path, options: &BenchOptions) {
+    let modified = SystemTime::now() - GENERATED_FILE_AGE;
+
+    for index in 0..options.files {
+        let mut path = root.to_owned();
+        let mut dir_index = index;
+
+        // Spread the files out over ten directories at each level.
+        for _ in 0..options.depth {
+            path.push(format!("dir-{}", dir_index % 10));
+            dir_index /= 10;
+        }
+
+        fs::create_dir_all(&path).unwrap();
+        path.push(format!("file-{index}.txt"));
+        fs::write(&path, vec![b'a' + (index % 26) as u8; options.size]).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(modified))
+            .unwrap();
+    }
+}
+
+/// Run a sync pass over `sync_dir`.
+fn run_pass(
+    backend: &CountingBackend<RcloneBackend>,
+    sync_dir: &SyncDirsModel,
+    db: &DatabaseConnection,
+    timestamps: TimestampComparison,
+) -> PassResults {
+    let calls_before = backend.calls.get();
+    let start = Instant::now();
+    let stats = launch::PassStats::default();
+    let errors = Cell::new(0);
+
+    launch::sync_directory(
+        backend,
+        sync_dir,
+        db,
+        &SpaceCheck::default(),
+        &UploadedFiles::default(),
+        &AuditLog::start(),
+        &stats,
+        false,
+        timestamps,
+        |err| {
+            hw_msg::warningln!("{err:?}");
+            errors.set(errors.get() + 1);
+        },
+        || (),
+        || (),
+        |_: &str| (),
+    );
+
+    PassResults {
+        duration: start.elapsed(),
+        api_calls: backend.calls.get() - calls_before,
+        transfers: stats.files_transferred(),
+        errors: errors.get(),
+    }
+}
+
+/// Run the benchmark.
+pub fn run(options: &BenchOptions) {
+    librclone::initialize();
+
+    let bench_dir = std::env::temp_dir().join(format!("celeste-bench-{}", process::id()));
+    let local_root = bench_dir.join("local");
+    let remote_root = bench_dir.join("remote");
+    fs::create_dir_all(&local_root).unwrap();
+    fs::create_dir_all(&remote_root).unwrap();
+
+    println!(
+        "Generating {} files of {} bytes, {} directories deep...",
+        options.files, options.size, options.depth
+    );
+    generate_tree(&local_root, options);
+
+    // Use a database of our own, so the user's sync records don't get touched.
+    let db_path = bench_dir.join("celeste.db");
+    fs::File::create(&db_path).unwrap();
+    let db = libceleste::await_future(Database::connect(format!("sqlite://{}", db_path.display())))
+        .unwrap();
+    libceleste::await_future(Migrator::up(&db, None)).unwrap();
+    let remote = libceleste::await_future(
+        RemotesActiveModel {
+            name: ActiveValue::Set(remote_root.to_string_lossy().into_owned()),
+            ..Default::default()
+        }
+        .insert(&db),
+    )
+    .unwrap();
+    let sync_dir_model = libceleste::await_future(
+        SyncDirsActiveModel {
+            remote_id: ActiveValue::Set(remote.id),
+            local_path: ActiveValue::Set(local_root.to_string_lossy().into_owned()),
+            remote_path: ActiveValue::Set(String::new()),
+            remove_empty_dirs: ActiveValue::Set(false),
+            ..Default::default()
+        }
+        .insert(&db),
+    )
+    .unwrap();
+
+    let backend = CountingBackend::new(RcloneBackend::local(&remote_root));
+    let timestamps =
+        TimestampComparison::with_tolerance(SettingsModel::get(&db).timestamp_tolerance.into(), 1);
+
+    // The first pass uploads everything, and the second should find nothing to
+    // do.
+    for name in ["Initial sync", "Unchanged sync"] {
+        let results = run_pass(&backend, &sync_dir_model, &db, timestamps);
+        println!(
+            "{name}: {:.2?}, {} API calls, {} transfers, {} errors",
+            results.duration, results.api_calls, results.transfers, results.errors
+        );
+    }
+
+    drop(db);
+    fs::remove_dir_all(&bench_dir).unwrap();
+}
@@ -0,0 +1,96 @@
+//! What each remote supports, checked through Rclone once per remote and kept
+//! in the database. The sync engine uses this to pick how to do things, and
+//! the UI uses it to disable what a remote can't do, instead of either of them
+//! finding out by failing.
+use crate::{
+    db,
+    entities::{RemotesActiveModel, RemotesColumn, RemotesEntity, RemotesModel},
+    launch::VERIFY_HASH_TYPES,
+    rclone::{self, RcloneError},
+};
+use sea_orm::{entity::prelude::*, ActiveValue, DatabaseConnection};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// The precision Rclone reports for remotes that modification times can't be
+/// set on.
+const MOD_TIME_NOT_SUPPORTED: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+/// The optional features a remote supports.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct RemoteCapabilities {
+    /// The types of hashes the remote has for its files.
+    pub hashes: Vec<String>,
+    /// The precision the remote stores modification times with, or [`None`]
+    /// if they can't be set on its files.
+    pub mod_time_precision: Option<Duration>,
+    /// Whether the remote can copy files server-side, without their contents
+    /// being transferred again.
+    pub server_side_copy: bool,
+    /// Whether the remote can report how much space is free on it.
+    pub about: bool,
+    /// Whether the remote can make share links to its items.
+    pub public_link: bool,
+}
+
+impl RemoteCapabilities {
+    /// Check what the remote `remote_name` supports through Rclone.
+    pub fn probe(remote_name: &str) -> Result<Self, RcloneError> {
+        let info = rclone::sync::fsinfo(remote_name)?;
+        let feature = |name: &str| info.features.get(name) == Some(&true);
+        let precision = Duration::from_nanos(info.precision.max(0) as u64);
+
+        Ok(Self {
+            hashes: info.hashes.unwrap_or_default(),
+            mod_time_precision: (precision < MOD_TIME_NOT_SUPPORTED).then_some(precision),
+            server_side_copy: feature("Copy"),
+            about: feature("About"),
+            public_link: feature("PublicLink"),
+        })
+    }
+
+    /// Get what `remote` supports, checking and storing it the first time.
+    /// Returns [`None`] if it hasn't been checked and can't be right now, such
+    /// as when the remote can't be reached.
+    pub fn get(db: &DatabaseConnection, remote: &RemotesModel) -> Option<Self> {
+        if let Some(capabilities) = remote
+            .capabilities
+            .as_deref()
+            .and_then(|json| serde_json::from_str(json).ok())
+        {
+            return Some(capabilities);
+        }
+
+        let capabilities = match Self::probe(&remote.name) {
+            Ok(capabilities) => capabilities,
+            Err(err) => {
+                hw_msg::warningln!("Unable to check what '{}' supports: '{err}'.", remote.name);
+                return None;
+            }
+        };
+        let mut active_model: RemotesActiveModel = remote.clone().into();
+        active_model.capabilities =
+            ActiveValue::Set(Some(serde_json::to_string(&capabilities).unwrap()));
+        db::expect(|| active_model.clone().update(db));
+        Some(capabilities)
+    }
+
+    /// Get what the remote named `remote_name` supports. See [`Self::get`].
+    pub fn get_by_name(db: &DatabaseConnection, remote_name: &str) -> Option<Self> {
+        let remote = db::expect(|| {
+            RemotesEntity::find()
+                .filter(RemotesColumn::Name.eq(remote_name))
+                .one(db)
+        })?;
+        Self::get(db, &remote)
+    }
+
+    /// Get the type of hash to compare the remote's files with, if it has one
+    /// that can also be made for local files.
+    pub fn preferred_hash(&self) -> Option<&'static str> {
+        VERIFY_HASH_TYPES
+            .iter()
+            .copied()
+            .find(|hash_type| self.hashes.iter().any(|hash| hash == hash_type))
+    }
+}
@@ -0,0 +1,358 @@
+//! Comparing the local and remote copies of a conflicting text file side by
+//! side, so that the user can see how they differ before choosing which one to
+//! keep, or merging them by hand instead.
+use crate::{gtk_util, rclone};
+use adw::{
+    glib,
+    gtk::{
+        Align, Box, Button, Label, Orientation, PolicyType, ScrolledWindow, TextBuffer, TextView,
+        Window, WrapMode,
+    },
+    prelude::*,
+    ApplicationWindow, HeaderBar,
+};
+use similar::{DiffTag, TextDiff};
+use std::{fs, path::Path};
+use tempfile::NamedTempFile;
+
+/// The largest file that can be compared. Larger ones are unlikely to be
+/// something the user wants to read through, and would be slow to show.
+const MAX_COMPARE_SIZE: u64 = 1024 * 1024;
+
+/// The tag for lines that differ between the two copies.
+static CHANGED_TAG: &str = "changed";
+
+/// The tag for the blank lines added to one side to keep it lined up with the
+/// other.
+static FILLER_TAG: &str = "filler";
+
+/// The markers put around the two sides of each difference when merging, in
+/// the same format as Git's.
+static LOCAL_MARKER: &str = "<<<<<<< Local";
+static SEPARATOR_MARKER: &str = "=======";
+static REMOTE_MARKER: &str = ">>>>>>> Remote";
+
+/// Read the file at `path`, if it's a text file that's small enough to compare.
+fn read_text(path: &Path) -> Option<String> {
+    let metadata = fs::metadata(path).ok()?;
+    if !metadata.is_file() || metadata.len() > MAX_COMPARE_SIZE {
+        return None;
+    }
+
+    // Text files don't have any null bytes, while nearly all binary files do.
+    let contents = fs::read(path).ok()?;
+    if contents.contains(&0) {
+        return None;
+    }
+    String::from_utf8(contents).ok()
+}
+
+/// Whether the local file at `local_path` and `remote_item` can be compared.
+/// Only the size of the remote copy is known before downloading it, so it can
+/// still turn out not to be text.
+pub fn can_compare(local_path: &Path, remote_item: &rclone::RcloneRemoteItem) -> bool {
+    !remote_item.is_dir
+        && remote_item.size <= MAX_COMPARE_SIZE as i64
+        && read_text(local_path).is_some()
+}
+
+/// Download the remote copy of a file and read it. Returns [`None`] if it
+/// isn't a text file that's small enough to compare.
+fn read_remote_text(remote_name: &str, remote_path: &str) -> Result<Option<String>, String> {
+    let file = NamedTempFile::new().map_err(|err| err.to_string())?;
+    let file_path = file.path().to_string_lossy().into_owned();
+    let remote_name = remote_name.to_owned();
+    let remote_path = remote_path.to_owned();
+    libceleste::run_in_background(move || {
+        rclone::sync::copy_to_local(&file_path, &remote_name, &remote_path)
+    })
+    .map_err(|err| err.message().to_owned())?;
+
+    Ok(read_text(file.path()))
+}
+
+/// Read both copies of a conflicting file, showing an error and returning
+/// [`None`] if either can't be compared.
+pub fn read_both(
+    local_path: &str,
+    remote_name: &str,
+    remote_path: &str,
+) -> Option<(String, String)> {
+    let Some(local) = read_text(Path::new(local_path)) else {
+        gtk_util::show_error(
+            &tr::tr!(
+                "'{}' isn't a text file that can be compared.",
+                libceleste::fmt_home(local_path)
+            ),
+            None,
+        );
+        return None;
+    };
+    match read_remote_text(remote_name, remote_path) {
+        Ok(Some(remote)) => Some((local, remote)),
+        Ok(None) => {
+            gtk_util::show_error(
+                &tr::tr!(
+                    "'{}' on the remote isn't a text file that can be compared.",
+                    remote_path
+                ),
+                None,
+            );
+            None
+        }
+        Err(err) => {
+            gtk_util::show_error(
+                &tr::tr!("Unable to download '{}' from the remote.", remote_path),
+                Some(&err),
+            );
+            None
+        }
+    }
+}
+
+/// Make a text view for one side of a comparison, along with a heading for it.
+fn side_view(heading: &str, buffer: &TextBuffer, editable: bool) -> (Box, ScrolledWindow) {
+    let view = TextView::builder()
+        .buffer(buffer)
+        .editable(editable)
+        .monospace(true)
+        .wrap_mode(WrapMode::None)
+        .build();
+    let scrolled = ScrolledWindow::builder()
+        .child(&view)
+        .hscrollbar_policy(PolicyType::Automatic)
+        .vexpand(true)
+        .hexpand(true)
+        .build();
+    let section = Box::builder()
+        .orientation(Orientation::Vertical)
+        .spacing(6)
+        .build();
+    section.append(
+        &Label::builder()
+            .label(heading)
+            .halign(Align::Start)
+            .css_classes(vec!["heading".to_string()])
+            .build(),
+    );
+    section.append(&scrolled);
+    (section, scrolled)
+}
+
+/// Make a buffer with the tags used to show the differences in it.
+fn diff_buffer() -> TextBuffer {
+    let buffer = TextBuffer::new(None);
+    buffer.create_tag(
+        Some(CHANGED_TAG),
+        &[("paragraph-background", &"rgba(246, 211, 45, 0.3)")],
+    );
+    buffer.create_tag(
+        Some(FILLER_TAG),
+        &[("paragraph-background", &"rgba(154, 153, 150, 0.2)")],
+    );
+    buffer
+}
+
+/// Add `lines` to the end of `buffer`, tagged with `tag` if it's given.
+fn append_lines(buffer: &TextBuffer, lines: &[&str], tag: Option<&str>) {
+    for line in lines {
+        let mut line = line.to_string();
+        if !line.ends_with('\n') {
+            line.push('\n');
+        }
+
+        match tag {
+            Some(tag) => buffer.insert_with_tags_by_name(&mut buffer.end_iter(), &line, &[tag]),
+            None => buffer.insert(&mut buffer.end_iter(), &line),
+        }
+    }
+}
+
+/// Fill `local_buffer` and `remote_buffer` with the two copies of a file, with
+/// the lines that differ highlighted and lined up with each other.
+fn fill_diff(local: &str, remote: &str, local_buffer: &TextBuffer, remote_buffer: &TextBuffer) {
+    let diff = TextDiff::from_lines(local, remote);
+    for op in diff.ops() {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        let local_lines = &diff.old_slices()[old_range];
+        let remote_lines = &diff.new_slices()[new_range];
+
+        if tag == DiffTag::Equal {
+            append_lines(local_buffer, local_lines, None);
+            append_lines(remote_buffer, remote_lines, None);
+            continue;
+        }
+
+        append_lines(local_buffer, local_lines, Some(CHANGED_TAG));
+        append_lines(remote_buffer, remote_lines, Some(CHANGED_TAG));
+        let filler = vec![""; local_lines.len().abs_diff(remote_lines.len())];
+        if local_lines.len() < remote_lines.len() {
+            append_lines(local_buffer, &filler, Some(FILLER_TAG));
+        } else {
+            append_lines(remote_buffer, &filler, Some(FILLER_TAG));
+        }
+    }
+}
+
+/// Get the text to start merging `local` and `remote` from. This has the lines
+/// they share, with both sides of each difference between conflict markers.
+fn merge_text(local: &str, remote: &str) -> String {
+    let diff = TextDiff::from_lines(local, remote);
+    let mut text = String::new();
+    let mut push_lines = |lines: &[&str]| {
+        for line in lines {
+            text.push_str(line);
+            if !line.ends_with('\n') {
+                text.push('\n');
+            }
+        }
+    };
+
+    for op in diff.ops() {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        let local_lines = &diff.old_slices()[old_range];
+        let remote_lines = &diff.new_slices()[new_range];
+
+        if tag == DiffTag::Equal {
+            push_lines(local_lines);
+        } else {
+            push_lines(&[LOCAL_MARKER]);
+            push_lines(local_lines);
+            push_lines(&[SEPARATOR_MARKER]);
+            push_lines(remote_lines);
+            push_lines(&[REMOTE_MARKER]);
+        }
+    }
+
+    text
+}
+
+/// Whether `text` still has any of the conflict markers from [`merge_text`].
+fn has_conflict_markers(text: &str) -> bool {
+    text.lines()
+        .any(|line| line == LOCAL_MARKER || line == SEPARATOR_MARKER || line == REMOTE_MARKER)
+}
+
+/// Make the views of the local and remote copies of a file, lined up with each
+/// other and with their differences highlighted.
+fn diff_views(local_path: &str, remote_path: &str, local: &str, remote: &str) -> (Box, Box) {
+    let local_buffer = diff_buffer();
+    let remote_buffer = diff_buffer();
+    fill_diff(local, remote, &local_buffer, &remote_buffer);
+
+    let (local_section, local_scrolled) = side_view(
+        &tr::tr!("Local: {}", libceleste::fmt_home(local_path)),
+        &local_buffer,
+        false,
+    );
+    let (remote_section, remote_scrolled) =
+        side_view(&tr::tr!("Remote: /{}", remote_path), &remote_buffer, false);
+    // Both sides are lined up, so they scroll together.
+    remote_scrolled.set_vadjustment(Some(&local_scrolled.vadjustment()));
+
+    (local_section, remote_section)
+}
+
+/// Make a window on top of `parent` showing `sections` side by side, with
+/// `header_bar` above them.
+fn sections_window(
+    parent: &impl IsA<Window>,
+    title: &str,
+    header_bar: &HeaderBar,
+    sections: &[&Box],
+) -> ApplicationWindow {
+    let sides = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .spacing(10)
+        .homogeneous(true)
+        .css_classes(vec!["celeste-global-padding".to_string()])
+        .build();
+    for section in sections {
+        sides.append(*section);
+    }
+    let content = Box::builder().orientation(Orientation::Vertical).build();
+    content.append(header_bar);
+    content.append(&sides);
+
+    ApplicationWindow::builder()
+        .title(title)
+        .transient_for(parent)
+        .modal(true)
+        .default_width(450 * sections.len() as i32)
+        .default_height(600)
+        .content(&content)
+        .build()
+}
+
+/// Show the local and remote copies of the conflicting file at `local_path`
+/// side by side, on top of `parent`.
+pub fn compare_window(
+    parent: &impl IsA<Window>,
+    local_path: &str,
+    remote_name: &str,
+    remote_path: &str,
+) {
+    let Some((local, remote)) = read_both(local_path, remote_name, remote_path) else {
+        return;
+    };
+
+    let (local_section, remote_section) = diff_views(local_path, remote_path, &local, &remote);
+    let window = sections_window(
+        parent,
+        &libceleste::get_title!("Compare"),
+        &HeaderBar::new(),
+        &[&local_section, &remote_section],
+    );
+    window.show();
+}
+
+/// Let the user merge the local and remote copies of the conflicting file at
+/// `local_path` by hand, with both copies shown on either side of the merged
+/// one. `save` gets called with the merged text once the user is done, and
+/// returns whether it was saved. The window closes if it was.
+pub fn merge_window<F: Fn(String) -> bool + 'static>(
+    parent: &impl IsA<Window>,
+    local_path: &str,
+    remote_name: &str,
+    remote_path: &str,
+    save: F,
+) {
+    let Some((local, remote)) = read_both(local_path, remote_name, remote_path) else {
+        return;
+    };
+
+    let (local_section, remote_section) = diff_views(local_path, remote_path, &local, &remote);
+    let merged_buffer = TextBuffer::new(None);
+    merged_buffer.set_text(&merge_text(&local, &remote));
+    let (merged_section, _) = side_view(&tr::tr!("Merged"), &merged_buffer, true);
+
+    let save_button = Button::builder()
+        .label(&tr::tr!("Save"))
+        .css_classes(vec!["suggested-action".to_string()])
+        .build();
+    let header_bar = HeaderBar::new();
+    header_bar.pack_end(&save_button);
+    let window = sections_window(
+        parent,
+        &libceleste::get_title!("Merge"),
+        &header_bar,
+        &[&local_section, &merged_section, &remote_section],
+    );
+    save_button.connect_clicked(glib::clone!(@weak window, @weak merged_buffer => move |_| {
+        let merged = merged_buffer
+            .text(&merged_buffer.start_iter(), &merged_buffer.end_iter(), false)
+            .to_string();
+        if has_conflict_markers(&merged) {
+            gtk_util::show_error(
+                &tr::tr!("The merged file still has conflict markers"),
+                Some(&tr::tr!("Choose what to keep between each '{}' and '{}' line, and then remove the markers.", LOCAL_MARKER, REMOTE_MARKER)),
+            );
+            return;
+        }
+
+        if save(merged) {
+            window.close();
+        }
+    }));
+    window.show();
+}
@@ -0,0 +1,187 @@
+//! Fallible access to the database. SQLite refuses access while another
+//! connection (such as another instance of Celeste) is writing to it, so such
+//! operations get retried for a bit before giving up. Anything else that goes
+//! wrong is reported to the user, instead of crashing.
+use crate::{encryption, gtk_util};
+use adw::glib::{self, MainContext};
+use sea_orm::{
+    sqlx::{
+        self,
+        sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous},
+    },
+    DatabaseConnection, DbErr, RuntimeErr, SqlxSqliteConnector,
+};
+use std::{
+    cell::Cell,
+    fs,
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    process, thread,
+    time::Duration,
+};
+
+/// The most times to try an operation while the database is busy.
+const MAX_ATTEMPTS: u32 = 10;
+
+/// How long to wait before retrying an operation the first time the database
+/// is busy. This doubles after each attempt, up to [`MAX_RETRY_DELAY`].
+const RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// The longest to wait before retrying an operation.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// How long SQLite itself waits for another connection to finish writing
+/// before an operation fails as busy. This blocks whichever thread is running
+/// the operation, so it's kept short and most of the waiting is done by
+/// retrying instead (see [`wait`]).
+const BUSY_TIMEOUT: Duration = Duration::from_millis(250);
+
+thread_local! {
+    /// Whether failures are currently being caught by [`catch_failures`].
+    static CATCHING_FAILURES: Cell<bool> = Cell::new(false);
+}
+
+/// Get the path of Celeste's database.
+pub fn path() -> PathBuf {
+    libceleste::get_config_dir().join("celeste.db")
+}
+
+/// Get the options that every connection to the database at `path` is made
+/// with, instead of relying on the driver's defaults for them:
+/// - Write-ahead logging, so that reading doesn't block writing (such as the
+///   CLI reading the database while a sync is writing to it), along with only
+///   syncing to disk at checkpoints, which is still safe with it.
+/// - A busy timeout, so that SQLite waits for other writers before giving up.
+/// - Foreign key constraints.
+/// - The database's key, if it's encrypted.
+pub fn connect_options(path: &Path) -> Result<SqliteConnectOptions, DbErr> {
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Normal)
+        .busy_timeout(BUSY_TIMEOUT)
+        .foreign_keys(true);
+
+    Ok(match encryption::key(path)? {
+        Some(key) => options.pragma("key", encryption::key_pragma(&key)),
+        None => options,
+    })
+}
+
+/// Connect to the database at `path`.
+pub fn connect(path: &Path) -> Result<DatabaseConnection, DbErr> {
+    let options = connect_options(path)?;
+    let pool = libceleste::await_future(SqlitePoolOptions::new().connect_with(options))
+        .map_err(sqlx_err)?;
+    Ok(SqlxSqliteConnector::from_sqlx_sqlite_pool(pool))
+}
+
+/// Convert an error from the database driver into one from the ORM.
+pub fn sqlx_err(err: sqlx::Error) -> DbErr {
+    DbErr::Conn(RuntimeErr::SqlxError(err))
+}
+
+/// Remove the journal files SQLite keeps next to the database at `path`, such
+/// as before replacing it, since SQLite would apply them to the replacement
+/// otherwise.
+pub fn remove_journal_files(path: &Path) -> io::Result<()> {
+    for suffix in ["-wal", "-shm", "-journal"] {
+        let mut journal_path = path.as_os_str().to_owned();
+        journal_path.push(suffix);
+        match fs::remove_file(journal_path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `err` is from the database being busy, in which case the operation
+/// can be retried.
+pub fn is_busy(err: &DbErr) -> bool {
+    let msg = err.to_string();
+    msg.contains("database is locked") || msg.contains("SQLITE_BUSY")
+}
+
+/// Wait for `delay` before retrying an operation. Nearly every operation is
+/// run from the thread running the UI, so this keeps it responsive while
+/// waiting there.
+fn wait(delay: Duration) {
+    let context = MainContext::default();
+
+    if context.is_owner() {
+        context.block_on(glib::timeout_future(delay));
+    } else {
+        thread::sleep(delay);
+    }
+}
+
+/// Run a database operation, retrying it while the database is busy.
+/// `operation` gets called to start each attempt.
+pub fn run<T, Fut, F>(mut operation: F) -> Result<T, DbErr>
+where
+    Fut: Future<Output = Result<T, DbErr>>,
+    F: FnMut() -> Fut,
+{
+    let mut delay = RETRY_DELAY;
+
+    for _ in 1..MAX_ATTEMPTS {
+        match libceleste::await_future(operation()) {
+            Err(err) if is_busy(&err) => {
+                hw_msg::warningln!("The database is busy, retrying in {delay:?}: '{err}'.");
+                wait(delay);
+                delay = (delay * 2).min(MAX_RETRY_DELAY);
+            }
+            result => return result,
+        }
+    }
+
+    libceleste::await_future(operation())
+}
+
+/// Run a database operation that can't be carried on from if it fails. Inside
+/// of [`catch_failures`] a failure stops the closure given to it, and anywhere
+/// else the user gets told what went wrong before Celeste quits.
+pub fn expect<T, Fut, F>(operation: F) -> T
+where
+    Fut: Future<Output = Result<T, DbErr>>,
+    F: FnMut() -> Fut,
+{
+    run(operation).unwrap_or_else(|err| fail(&err))
+}
+
+/// Report the failure of a database operation with `err`.
+fn fail(err: &DbErr) -> ! {
+    let msg = tr::tr!("Unable to access the database [{}].", err);
+
+    if CATCHING_FAILURES.with(Cell::get) {
+        panic!("{msg}");
+    }
+
+    gtk_util::show_error(
+        &msg,
+        Some(&tr::tr!(
+            "Celeste will now quit. If the disk is full, free up some space before starting Celeste again."
+        )),
+    );
+    process::exit(1);
+}
+
+/// Resets [`CATCHING_FAILURES`] when dropped, including while unwinding.
+struct CatchingGuard(bool);
+
+impl Drop for CatchingGuard {
+    fn drop(&mut self) {
+        CATCHING_FAILURES.with(|catching| catching.set(self.0));
+    }
+}
+
+/// Run `f`, letting failed database operations in it panic so that the caller
+/// can catch them and recover, such as by reporting an error for the sync
+/// directory being synced and trying again on the next sync.
+pub fn catch_failures<T, F: FnOnce() -> T>(f: F) -> T {
+    let _guard = CatchingGuard(CATCHING_FAILURES.with(|catching| catching.replace(true)));
+    f()
+}
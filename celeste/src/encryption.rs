@@ -0,0 +1,193 @@
+//! Encrypting the database with SQLCipher, since the names and paths of synced
+//! files are readable by anyone with access to it otherwise. The key is kept
+//! in the keyring, and the database only gets encrypted or decrypted on
+//! startup, since doing so replaces the database file.
+use crate::{backup, db};
+use sea_orm::{
+    sqlx::{
+        self,
+        sqlite::{SqliteConnectOptions, SqliteConnection},
+        ConnectOptions, Connection,
+    },
+    DbErr,
+};
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::Path,
+};
+
+/// The keyring entry the database's key is saved in.
+static KEY_KEYRING_USER: &str = "database-key";
+
+/// The start of every SQLite database that isn't encrypted.
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+/// Get the keyring entry the database's key is saved in.
+fn keyring_entry() -> keyring::Result<keyring::Entry> {
+    keyring::Entry::new(libceleste::APP_ID, KEY_KEYRING_USER)
+}
+
+/// Convert an error from accessing the database's files into one from the ORM.
+fn io_err(err: io::Error) -> DbErr {
+    DbErr::Custom(err.to_string())
+}
+
+/// Whether the database at `path` is encrypted. Encrypted databases don't
+/// start with SQLite's usual header, while new ones don't have anything in them
+/// yet.
+pub fn is_encrypted(path: &Path) -> io::Result<bool> {
+    let mut header = vec![];
+    File::open(path)?
+        .take(SQLITE_HEADER.len() as u64)
+        .read_to_end(&mut header)?;
+    Ok(!header.is_empty() && header != SQLITE_HEADER)
+}
+
+/// Get the key of the database at `path`, if it's encrypted.
+pub fn key(path: &Path) -> Result<Option<String>, DbErr> {
+    if !is_encrypted(path).map_err(io_err)? {
+        return Ok(None);
+    }
+
+    keyring_entry()
+        .and_then(|entry| entry.get_password())
+        .map(Some)
+        .map_err(|err| {
+            DbErr::Custom(tr::tr!(
+                "Unable to get the database's key from the keyring [{}].",
+                err
+            ))
+        })
+}
+
+/// Format `key` for SQLCipher. Keys are given in hex so that SQLCipher uses
+/// them as they are, instead of deriving them from a passphrase on every
+/// connection.
+pub fn key_pragma(key: &str) -> String {
+    format!("\"x'{key}'\"")
+}
+
+/// Encrypt or decrypt the database at `path` to match
+/// [`crate::entities::SettingsModel::encrypt_database`]. This has to be done
+/// before anything else connects to it, since the database gets replaced with
+/// a converted copy of itself.
+pub fn update(path: &Path) -> Result<(), DbErr> {
+    let encrypted = is_encrypted(path).map_err(io_err)?;
+    let mut conn =
+        libceleste::await_future(db::connect_options(path)?.connect()).map_err(db::sqlx_err)?;
+
+    // Databases from before the setting existed don't have it yet, and never
+    // need converting.
+    let encrypt = libceleste::await_future(
+        sqlx::query_scalar::<_, bool>("SELECT encrypt_database FROM settings;")
+            .fetch_one(&mut conn),
+    )
+    .unwrap_or(false);
+    if encrypt == encrypted {
+        return libceleste::await_future(conn.close()).map_err(db::sqlx_err);
+    }
+
+    let converted_path = path.with_extension("db.converting");
+    let key = libceleste::await_future(export(conn, &converted_path, encrypt))?;
+    if let Err(err) = libceleste::await_future(verify(&converted_path, key.as_deref())) {
+        let _ = fs::remove_file(&converted_path);
+        return Err(err);
+    }
+
+    // The key gets saved before the database is replaced, so that the database
+    // is never encrypted with a key that isn't saved anywhere.
+    if let Some(key) = &key
+        && let Err(err) = keyring_entry().and_then(|entry| entry.set_password(key))
+    {
+        let _ = fs::remove_file(&converted_path);
+        return Err(DbErr::Custom(tr::tr!(
+            "Unable to save the database's key in the keyring [{}].",
+            err
+        )));
+    }
+
+    db::remove_journal_files(path).map_err(io_err)?;
+    fs::rename(&converted_path, path).map_err(io_err)?;
+
+    if key.is_none()
+        && let Err(err) = keyring_entry().and_then(|entry| entry.delete_password())
+    {
+        hw_msg::warningln!("Unable to remove the database's key from the keyring: '{err}'.");
+    }
+
+    // The backups are still in the format the database was just converted from,
+    // and the converted database was checked before it replaced the old one, so
+    // they aren't needed to recover from a bad conversion.
+    backup::remove_all();
+    Ok(())
+}
+
+/// Export the database `conn` is connected to into a new one at
+/// `converted_path`, which gets encrypted with a new key if `encrypt` is set.
+/// Returns the new key if so.
+async fn export(
+    mut conn: SqliteConnection,
+    converted_path: &Path,
+    encrypt: bool,
+) -> Result<Option<String>, DbErr> {
+    // This would be left over from an export that got interrupted.
+    match fs::remove_file(converted_path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(io_err(err)),
+        _ => (),
+    }
+
+    let key = if encrypt {
+        let key = sqlx::query_scalar::<_, String>("SELECT lower(hex(randomblob(32)));")
+            .fetch_one(&mut conn)
+            .await
+            .map_err(db::sqlx_err)?;
+        Some(key)
+    } else {
+        None
+    };
+
+    let attach = format!(
+        "ATTACH DATABASE '{}' AS converted KEY {};",
+        converted_path.to_string_lossy().replace('\'', "''"),
+        key.as_deref().map_or_else(|| "''".to_owned(), key_pragma)
+    );
+    for sql in [
+        attach.as_str(),
+        "SELECT sqlcipher_export('converted');",
+        "DETACH DATABASE converted;",
+    ] {
+        sqlx::query(sql)
+            .execute(&mut conn)
+            .await
+            .map_err(db::sqlx_err)?;
+    }
+
+    conn.close().await.map_err(db::sqlx_err)?;
+    Ok(key)
+}
+
+/// Check that the database exported to `converted_path` opens with `key` and
+/// isn't corrupted, before it replaces the database.
+async fn verify(converted_path: &Path, key: Option<&str>) -> Result<(), DbErr> {
+    let options = SqliteConnectOptions::new().filename(converted_path);
+    let options = match key {
+        Some(key) => options.pragma("key", key_pragma(key)),
+        None => options,
+    };
+    let mut conn = options.connect().await.map_err(db::sqlx_err)?;
+    let result = sqlx::query_scalar::<_, String>("PRAGMA integrity_check;")
+        .fetch_one(&mut conn)
+        .await
+        .map_err(db::sqlx_err)?;
+    conn.close().await.map_err(db::sqlx_err)?;
+
+    if result == "ok" {
+        Ok(())
+    } else {
+        Err(DbErr::Custom(tr::tr!(
+            "The converted database is corrupted [{}].",
+            result
+        )))
+    }
+}
@@ -0,0 +1,520 @@
+//! The sync engine, run without any windows. It owns the database, Rclone and
+//! the DBus and socket APIs, and keeps syncing until it's asked to quit, so
+//! that frontends (see [`crate::frontend`]) can be closed, restarted or
+//! upgraded without interrupting it.
+//!
+//! Anything that needs the user to make a choice, such as resolving a
+//! conflict or relinking a directory that went missing, is left for the main
+//! window, which can be used once the engine has been stopped. Such
+//! directories are skipped with a status saying why, and any errors are counted
+//! in the status sent to frontends.
+use crate::{
+    audit::AuditLog,
+    capabilities::RemoteCapabilities,
+    db,
+    entities::{
+        RemotesColumn, RemotesEntity, RemotesModel, SettingsModel, SyncItemsColumn, SyncItemsEntity,
+    },
+    health,
+    ipc::{self, DirStatus, EngineStatus},
+    launch::{self, PassStats, SpaceCheck, SyncError, UploadedFiles},
+    metrics, path_status, platform, rclone,
+    remote_state::Manifest,
+    scheduler::{Scheduler, SyncPair},
+    sync_backend::{RcloneBackend, SyncBackend},
+    sync_decision::TimestampComparison,
+    webhooks::{self, SyncEvent},
+};
+use indexmap::IndexMap;
+use sd_notify::NotifyState;
+use sea_orm::{entity::prelude::*, DatabaseConnection, QueryOrder};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    env,
+    path::Path,
+    process::{self, Command, Stdio},
+    time::{Instant, SystemTime},
+};
+
+/// Options for running the engine.
+pub struct EngineOptions {
+    /// Whether to report readiness and watchdog pings to systemd.
+    pub service: bool,
+    /// The port to serve Prometheus metrics on, if any.
+    pub metrics_port: Option<u16>,
+}
+
+/// Open the frontend, for when something asks the engine to open its window.
+fn open_frontend() {
+    let mut command = match env::current_exe() {
+        Ok(exe) => Command::new(exe),
+        Err(err) => {
+            hw_msg::warningln!("Unable to open Celeste's frontend: '{err}'.");
+            return;
+        }
+    };
+    command
+        .arg("frontend")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    platform::separate_process_group(&mut command);
+    if let Err(err) = command.spawn() {
+        hw_msg::warningln!("Unable to open Celeste's frontend: '{err}'.");
+    }
+}
+
+/// Get everything the engine needs before it can start syncing, exiting with
+/// an error if any of it can't be.
+fn setup() -> DatabaseConnection {
+    let fail = |msg: &str| -> ! {
+        hw_msg::errorln!("{msg}");
+        process::exit(1);
+    };
+
+    if let Err((err_msg, install_msg)) = launch::check_rclone_binary() {
+        fail(&format!("{err_msg} {install_msg}"));
+    }
+
+    // The engine can't ask for the config's password, so it has to have been saved
+    // in the keyring by the main window already.
+    if rclone::config_is_encrypted() && !launch::unlock_rclone_config_from_keyring() {
+        fail(&tr::tr!(
+            "Celeste's Rclone config is encrypted. Open Celeste to unlock it before starting the sync engine."
+        ));
+    }
+
+    let db_path = launch::create_database().unwrap_or_else(|err| fail(&err));
+    let db = launch::open_database(&db_path).unwrap_or_else(|err| fail(&err));
+
+    // Repairing the database needs the user to agree to it, so leave that for the
+    // main window.
+    match health::check(&db) {
+        Ok(issues) if !issues.is_empty() => hw_msg::warningln!(
+            "The database doesn't agree with Rclone's config. Open Celeste to repair it."
+        ),
+        Ok(_) => (),
+        Err(err) => fail(&tr::tr!("Unable to check the database [{}].", err)),
+    }
+
+    db
+}
+
+/// Run the engine until it's asked to quit.
+pub fn run(options: &EngineOptions) {
+    let db = setup();
+
+    // Quit cleanly when asked to by a signal, such as from systemd.
+    launch::handle_close_signals();
+
+    // Keep the connection around for as long as we're running, so the API stays
+    // available on it.
+    let _dbus = launch::connect_dbus(&db)
+        .map_err(|err| {
+            hw_msg::warningln!("Unable to connect to the DBus session bus: '{err}'.");
+        })
+        .ok();
+    let _socket = ipc::serve();
+    if let Some(port) = options.metrics_port {
+        metrics::serve(port);
+    }
+    if options.service
+        && let Err(err) = sd_notify::notify(false, &[NotifyState::Ready])
+    {
+        hw_msg::warningln!("Unable to notify systemd that Celeste is ready: '{err}'.");
+    }
+
+    let mut status = EngineStatus {
+        message: tr::tr!("Awaiting sync checks..."),
+        headless: true,
+        ..Default::default()
+    };
+    launch::set_engine_status(status.clone());
+
+    // The status of each directory, by their IDs.
+    let mut dir_statuses: IndexMap<i32, DirStatus> = IndexMap::new();
+    // The errors each directory ran into during its last pass.
+    let mut dir_errors: HashMap<i32, Vec<SyncError>> = HashMap::new();
+    // The remotes whose Rclone options are in use, as they were when applied.
+    let mut applied_remotes: HashMap<i32, RemotesModel> = HashMap::new();
+    // The IDs of the directories that had nothing left to sync after their last
+    // pass.
+    let mut settled_dirs: HashSet<i32> = HashSet::new();
+    // Remotes whose storage quota has been used up, along with when to try
+    // uploading to them again.
+    let mut full_remotes: HashMap<String, Instant> = HashMap::new();
+    let mut scheduler = Scheduler::default();
+    let mut last_pruned: HashMap<i32, Instant> = HashMap::new();
+    // What each directory's pre-sync command printed when it last skipped a pass.
+    let mut pre_sync_outputs: HashMap<i32, String> = HashMap::new();
+
+    while !launch::close_requested() {
+        if options.service {
+            launch::ping_watchdog();
+        }
+        if launch::take_open_request() {
+            open_frontend();
+        }
+        launch::wait_for_next_pass();
+
+        if launch::sync_paused() {
+            status.message = tr::tr!("Syncing is paused.");
+            launch::set_engine_status(status.clone());
+            continue;
+        }
+
+        let remotes = db::expect(|| {
+            RemotesEntity::find()
+                .order_by_asc(RemotesColumn::Position)
+                .order_by_asc(RemotesColumn::Id)
+                .all(&db)
+        });
+        let config_remotes = rclone::get_remote_names();
+        let mut pairs = vec![];
+        let mut missing_remote = false;
+        // The IDs of every directory being synced, including those that can't be
+        // right now.
+        let mut dir_ids = HashSet::new();
+        for remote in &remotes {
+            let remote_pairs = launch::remote_sync_pairs(&db, remote);
+            dir_ids.extend(remote_pairs.iter().map(|pair| pair.sync_dir.id));
+            if config_remotes.contains(&remote.name) {
+                // Use the remote's extra Rclone options, picking up any changes made to them
+                // from the main window.
+                if applied_remotes.get(&remote.id) != Some(remote) {
+                    if let Err(err) = launch::apply_remote_options(remote) {
+                        hw_msg::warningln!(
+                            "Unable to use the Rclone options for '{}', so ignoring them: '{err}'.",
+                            remote.name
+                        );
+                    }
+                    applied_remotes.insert(remote.id, remote.clone());
+                }
+                pairs.extend(remote_pairs);
+                continue;
+            }
+
+            // Everything done with a remote that's missing from Rclone's config fails, so
+            // don't try syncing it until it's back.
+            missing_remote = true;
+            for pair in remote_pairs {
+                set_dir_status(
+                    &mut dir_statuses,
+                    &pair,
+                    tr::tr!(
+                        "This remote is missing from Celeste's Rclone config, so it can't be synced."
+                    ),
+                    0,
+                );
+            }
+        }
+
+        // Forget about directories that have stopped being synced.
+        dir_statuses.retain(|id, _| dir_ids.contains(id));
+        dir_errors.retain(|id, _| dir_ids.contains(id));
+        settled_dirs.retain(|id| dir_ids.contains(id));
+
+        let mut queue = scheduler.queue(pairs);
+        while let Some(pair) = scheduler.next(&mut queue) {
+            if launch::close_requested() {
+                break;
+            }
+            if !pair.sync_dir.exists(&db) {
+                continue;
+            }
+
+            status.message = tr::tr!("Syncing '{}'...", pair.remote.name);
+            launch::set_engine_status(EngineStatus {
+                dirs: dir_statuses.values().cloned().collect(),
+                ..status.clone()
+            });
+
+            let (dir_status, errors, settled) = sync_pair(
+                &db,
+                &pair,
+                &mut scheduler,
+                &mut last_pruned,
+                &mut full_remotes,
+                &mut pre_sync_outputs,
+                dir_errors.remove(&pair.sync_dir.id).unwrap_or_default(),
+                options.service,
+            );
+            set_dir_status(&mut dir_statuses, &pair, dir_status, errors.len());
+            dir_errors.insert(pair.sync_dir.id, errors);
+            if settled {
+                settled_dirs.insert(pair.sync_dir.id);
+            } else {
+                settled_dirs.remove(&pair.sync_dir.id);
+            }
+        }
+
+        let error_count: usize = dir_errors.values().map(Vec::len).sum();
+        status.message = if error_count == 0 {
+            tr::tr!("Finished sync checks.")
+        } else {
+            tr::tr!(
+                "Finished sync checks with {n} error."
+                    | "Finished sync checks with {n} errors." % error_count
+            )
+        };
+        status.dirs = dir_statuses.values().cloned().collect();
+        launch::set_engine_status(status.clone());
+        launch::record_sync_check(
+            error_count == 0
+                && !missing_remote
+                && dir_ids.iter().all(|id| settled_dirs.contains(id)),
+        );
+    }
+
+    if options.service
+        && let Err(err) = sd_notify::notify(false, &[NotifyState::Stopping])
+    {
+        hw_msg::warningln!("Unable to notify systemd that Celeste is stopping: '{err}'.");
+    }
+}
+
+/// Record the status of the directory in `pair`, along with how many errors it
+/// has.
+fn set_dir_status(
+    dir_statuses: &mut IndexMap<i32, DirStatus>,
+    pair: &SyncPair,
+    status: String,
+    errors: usize,
+) {
+    dir_statuses.insert(
+        pair.sync_dir.id,
+        DirStatus {
+            remote_name: pair.remote.name.clone(),
+            local_path: pair.sync_dir.local_path.clone(),
+            remote_path: pair.sync_dir.remote_path.clone(),
+            status,
+            errors,
+        },
+    );
+}
+
+/// Run a sync pass of the directory in `pair`, whose last pass ran into
+/// `last_errors`. Returns the directory's status, the errors this pass ran
+/// into, and whether the directory had nothing left to sync.
+#[allow(clippy::too_many_arguments)]
+fn sync_pair(
+    db: &DatabaseConnection,
+    pair: &SyncPair,
+    scheduler: &mut Scheduler,
+    last_pruned: &mut HashMap<i32, Instant>,
+    full_remotes: &mut HashMap<String, Instant>,
+    pre_sync_outputs: &mut HashMap<i32, String>,
+    last_errors: Vec<SyncError>,
+    service: bool,
+) -> (String, Vec<SyncError>, bool) {
+    let SyncPair {
+        remote,
+        sync_dir,
+        overlapping_dir,
+    } = pair;
+
+    if let Some(retry_at) = scheduler.backing_off(sync_dir) {
+        let status = tr::tr!(
+            "Syncing keeps failing. Will retry at {}.",
+            launch::format_instant(retry_at)
+        );
+        return (status, last_errors, false);
+    }
+
+    // Errors get found again by each pass until they're fixed, so forget about
+    // the ones from the last pass.
+    for error in &last_errors {
+        path_status::clear_error(error.path());
+    }
+    let pre_sync_result =
+        launch::run_pre_sync_command(&launch::refresh_sync_dir(sync_dir, db), &remote.name);
+    launch::log_pre_sync_result(sync_dir, &pre_sync_result, pre_sync_outputs);
+    if let Err(skip) = pre_sync_result {
+        return (skip.msg, vec![], false);
+    }
+
+    let write_sync_status = SettingsModel::get(db).sync_status_xattrs
+        && launch::xattrs_supported(Path::new(&sync_dir.local_path));
+    let timestamps = TimestampComparison::new(remote, db);
+    let stats = PassStats::with_transfer_limit(SettingsModel::get(db).transfer_limit);
+    let errors = RefCell::new(vec![]);
+    let add_error = |error: SyncError| {
+        // Snoozed errors aren't reported until their snooze runs out.
+        if launch::is_snoozed(db, sync_dir, &error) {
+            stats.record_snoozed_error();
+            return;
+        }
+
+        stats.record_error();
+        if let SyncError::BothMoreCurrent(local_item, remote_item) = &error
+            && !last_errors.contains(&error)
+        {
+            let event = SyncEvent::ConflictDetected {
+                local_item: local_item.clone(),
+                remote_item: remote_item.clone(),
+            };
+            webhooks::send(db, sync_dir, &remote.name, event);
+        }
+        let error_path = error.path();
+        path_status::record_error(error_path);
+        if write_sync_status && Path::new(error_path).starts_with(&sync_dir.local_path) {
+            launch::set_sync_status(Path::new(error_path), "error");
+        }
+
+        // There's no window to show errors in, so log the ones that weren't already.
+        if !last_errors.contains(&error) {
+            hw_msg::warningln!("{error:?}");
+        }
+        errors.borrow_mut().push(error);
+    };
+    let finish = |status: String, settled: bool| (status, errors.take(), settled);
+    // Skip syncing the directory for something that won't fix itself.
+    let skip = |scheduler: &mut Scheduler, msg: String| {
+        scheduler.finished(sync_dir, false, true);
+        finish(msg, false)
+    };
+
+    if let Some(overlapping_dir) = overlapping_dir {
+        return skip(
+            scheduler,
+            tr::tr!(
+                "This directory's remote folder overlaps the one '{}' is synced with, so it can't be synced until one of them is removed.",
+                overlapping_dir
+            ),
+        );
+    }
+    if !Path::new(&sync_dir.local_path).exists() {
+        return skip(
+            scheduler,
+            tr::tr!("The local folder doesn't exist anymore. Stop the sync engine and open Celeste to choose what to do about it."),
+        );
+    }
+    if launch::local_path_has_config(Path::new(&sync_dir.local_path)) {
+        return skip(
+            scheduler,
+            tr::tr!(
+                "This directory contains Celeste's configuration at '{}', so it can't be synced until it's removed.",
+                libceleste::get_config_dir().display()
+            ),
+        );
+    }
+
+    // Lock the directory while we sync it, so that another instance of Celeste
+    // doesn't sync it at the same time.
+    let _lock = match launch::lock_sync_dir(sync_dir) {
+        Ok(lock) => lock,
+        Err(msg) => return finish(msg, false),
+    };
+
+    let backend =
+        RcloneBackend::new(&remote.name).with_capabilities(RemoteCapabilities::get(db, remote));
+    let remote_full = full_remotes
+        .get(&remote.name)
+        .map_or(false, |retry_at| Instant::now() < *retry_at);
+    let space = SpaceCheck::for_remote(remote_full);
+    let uploaded = UploadedFiles::default();
+    let audit = AuditLog::start()
+        .with_system_trash(SettingsModel::get(db).system_trash)
+        .with_remote_options(remote);
+    let started = launch::system_time_to_timestamp(SystemTime::now());
+    let start = Instant::now();
+
+    let has_records = db::expect(|| {
+        SyncItemsEntity::find()
+            .filter(SyncItemsColumn::SyncDirId.eq(sync_dir.id))
+            .one(db)
+    })
+    .is_some();
+    if has_records
+        && !sync_dir.remote_path.is_empty()
+        && matches!(backend.stat(&sync_dir.remote_path), Ok(None))
+    {
+        return skip(
+            scheduler,
+            tr::tr!("The remote folder doesn't exist anymore. Stop the sync engine and open Celeste to choose what to do about it."),
+        );
+    }
+    if !has_records {
+        match Manifest::load(&backend, sync_dir) {
+            Ok(Some(manifest)) => launch::seed_sync_records(&manifest, sync_dir, db, timestamps),
+            Ok(None) => (),
+            Err(err) => hw_msg::warningln!(
+                "Unable to load the manifest for '{}': '{err}'.",
+                sync_dir.local_path
+            ),
+        }
+    }
+
+    let sync_result = launch::catch_panic(|| {
+        launch::recover_interrupted_transfers(&backend, sync_dir, db, &add_error);
+        launch::sync_directory(
+            &backend,
+            sync_dir,
+            db,
+            &space,
+            &uploaded,
+            &audit,
+            &stats,
+            write_sync_status,
+            timestamps,
+            &add_error,
+            &|| {
+                if service {
+                    launch::ping_watchdog();
+                }
+                if launch::take_open_request() {
+                    open_frontend();
+                }
+            },
+            // Directories only get removed from the main window.
+            &|| (),
+            &|_: &str| (),
+        );
+    });
+    if let Err(msg) = sync_result {
+        add_error(SyncError::General(
+            sync_dir.local_path.clone(),
+            tr::tr!("An unexpected error occurred while syncing [{}].", msg),
+        ));
+    }
+    if launch::close_requested() || !sync_dir.exists(db) {
+        return finish(tr::tr!("Awaiting sync checks..."), false);
+    }
+
+    let settled = launch::finish_sync_pass(
+        &backend,
+        remote,
+        sync_dir,
+        db,
+        &space,
+        &audit,
+        &stats,
+        started,
+        start.elapsed(),
+        scheduler,
+        last_pruned,
+        &add_error,
+    );
+
+    // Pause uploads to the remote for a while if its storage got full.
+    if space.remote_full() {
+        if !remote_full {
+            full_remotes.insert(
+                remote.name.clone(),
+                Instant::now() + launch::QUOTA_RETRY_INTERVAL,
+            );
+        }
+    } else {
+        full_remotes.remove(&remote.name);
+    }
+
+    let mut status = launch::pass_status(&space, &stats);
+    if !errors.borrow().is_empty() {
+        status += " ";
+        status += &tr::tr!(
+            "Stop the sync engine and open Celeste to resolve the reported syncing issues."
+        );
+    }
+    finish(status, settled)
+}
@@ -1,13 +1,44 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.3
+mod audit_log;
+mod file_blocks;
+mod pending_transfers;
 mod remotes;
+mod settings;
+mod snoozed_errors;
 mod sync_dirs;
 mod sync_items;
+mod sync_passes;
+
+pub use audit_log::ActiveModel as AuditLogActiveModel;
+pub use audit_log::Column as AuditLogColumn;
+pub use audit_log::Entity as AuditLogEntity;
+pub use audit_log::Model as AuditLogModel;
+
+pub use file_blocks::ActiveModel as FileBlocksActiveModel;
+pub use file_blocks::Column as FileBlocksColumn;
+pub use file_blocks::Entity as FileBlocksEntity;
+pub use file_blocks::Model as FileBlocksModel;
+
+pub use pending_transfers::ActiveModel as PendingTransfersActiveModel;
+pub use pending_transfers::Column as PendingTransfersColumn;
+pub use pending_transfers::Entity as PendingTransfersEntity;
+pub use pending_transfers::Model as PendingTransfersModel;
 
 pub use remotes::ActiveModel as RemotesActiveModel;
 pub use remotes::Column as RemotesColumn;
 pub use remotes::Entity as RemotesEntity;
 pub use remotes::Model as RemotesModel;
 
+pub use settings::ActiveModel as SettingsActiveModel;
+pub use settings::Column as SettingsColumn;
+pub use settings::Entity as SettingsEntity;
+pub use settings::Model as SettingsModel;
+
+pub use snoozed_errors::ActiveModel as SnoozedErrorsActiveModel;
+pub use snoozed_errors::Column as SnoozedErrorsColumn;
+pub use snoozed_errors::Entity as SnoozedErrorsEntity;
+pub use snoozed_errors::Model as SnoozedErrorsModel;
+
 pub use sync_dirs::ActiveModel as SyncDirsActiveModel;
 pub use sync_dirs::Column as SyncDirsColumn;
 pub use sync_dirs::Entity as SyncDirsEntity;
@@ -17,3 +48,8 @@ pub use sync_items::ActiveModel as SyncItemsActiveModel;
 pub use sync_items::Column as SyncItemsColumn;
 pub use sync_items::Entity as SyncItemsEntity;
 pub use sync_items::Model as SyncItemsModel;
+
+pub use sync_passes::ActiveModel as SyncPassesActiveModel;
+pub use sync_passes::Column as SyncPassesColumn;
+pub use sync_passes::Entity as SyncPassesEntity;
+pub use sync_passes::Model as SyncPassesModel;
@@ -8,6 +8,35 @@ pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
     pub name: String,
+    /// The local directory the remote gets mounted at, if mounting is enabled.
+    pub mount_point: Option<String>,
+    /// Extra Rclone flags to use for every operation on the remote, as entered
+    /// by the user.
+    pub rclone_flags: String,
+    /// The formats to export Google Docs in, for Google Drive remotes. Google
+    /// Docs are skipped if this is empty.
+    pub google_docs_formats: String,
+    /// The folder on the remote that Celeste is restricted to, or an empty
+    /// string to use the whole remote.
+    pub root_folder: String,
+    /// Whether items deleted on the remote skip its trash, for remotes that
+    /// have one that Rclone can skip.
+    pub permanent_delete: bool,
+    /// Whether copies of the remote items removed or replaced while syncing get
+    /// kept on the remote, where they take up its storage.
+    pub keep_remote_copies: bool,
+    /// How many days the copies of removed and replaced items are kept for,
+    /// both locally and on the remote.
+    pub keep_copies_days: i32,
+    /// What the remote supports, as a JSON-encoded
+    /// [`crate::capabilities::RemoteCapabilities`]. [`None`] if it hasn't been
+    /// checked yet.
+    pub capabilities: Option<String>,
+    /// Where the remote is shown in the sidebar. Remotes that have never been
+    /// reordered have a position of 0, and are shown after the others in the
+    /// order they were added. Reordering gives every remote a negative
+    /// position, so that ones added afterwards still go at the end.
+    pub position: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
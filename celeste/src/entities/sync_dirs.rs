@@ -1,8 +1,10 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.3
+use crate::db;
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
 #[sea_orm(table_name = "sync_dirs")]
 pub struct Model {
     #[sea_orm(primary_key)]
@@ -14,21 +16,57 @@ pub struct Model {
     /// The remote path being synced, as an absolute path (though it won't start
     /// with `/`).
     pub remote_path: String,
+    /// Whether empty directories should be removed after each sync.
+    pub remove_empty_dirs: bool,
+    /// The name of the file in `local_path` that exclusion rules are stored in.
+    pub ignore_file_name: String,
+    /// Whether exclusion rules are stored in `exclusions` instead of the ignore
+    /// file.
+    pub exclusions_in_db: bool,
+    /// The exclusion rules, one per line, when `exclusions_in_db` is set.
+    pub exclusions: String,
+    /// The patterns of the only files to sync, separated by `;`. Everything is
+    /// synced when this is empty.
+    pub inclusions: String,
+    /// How many days ago files can have last been modified and still get
+    /// synced. Files modified before then are left alone on both sides. There's
+    /// no limit when this is `0`.
+    pub max_file_age_days: i32,
+    /// Whether this directory gets synced before the others, and more often
+    /// while they're being synced.
+    pub high_priority: bool,
+    /// Which side wins when this directory gets synced for the first time, as a
+    /// [`crate::sync_decision::InitialSync`] name. This gets cleared once the
+    /// first sync finishes.
+    pub initial_sync: Option<String>,
+    /// A shell command to run after each sync pass that transferred files or
+    /// ran into errors, or an empty string for none.
+    pub post_sync_command: String,
+    /// A shell command to run before each sync pass, which skips the pass if it
+    /// fails, or an empty string for none.
+    pub pre_sync_command: String,
+    /// Where the directory is shown in its remote's list, in the same way as
+    /// [`super::remotes::Model::position`].
+    pub position: i32,
 }
 
 impl Model {
     // See if this item still exists in the database (i.e. the struct was created
     // and the item was later deleted).
     pub fn exists(&self, db: &DatabaseConnection) -> bool {
-        libceleste::await_future(
+        db::expect(|| {
             Entity::find()
                 .filter(Column::LocalPath.eq(self.local_path.clone()))
                 .filter(Column::RemotePath.eq(self.remote_path.clone()))
-                .one(db),
-        )
-        .unwrap()
+                .one(db)
+        })
         .is_some()
     }
+
+    // Get the path of this directory's ignore file.
+    pub fn ignore_file_path(&self) -> PathBuf {
+        Path::new(&self.local_path).join(&self.ignore_file_name)
+    }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -13,10 +13,17 @@ pub struct Model {
     /// The remote path being synced, relative to the directory of the matching
     /// `SyncDirs::sync_dir` specified by `Self::sync_dir_id`.
     pub remote_path: String,
-    /// The local UNIX timestamp of the item when last synced.
-    pub last_local_timestamp: i32,
+    /// The local UNIX timestamp of the item when last synced. SQLite's
+    /// `INTEGER` columns hold 64-bit values, so the column didn't need changing
+    /// for these to go past 2038.
+    pub last_local_timestamp: i64,
     /// The remote UNIX timestamp of the item when last synced.
-    pub last_remote_timestamp: i32,
+    pub last_remote_timestamp: i64,
+    /// The inode of the local item when last synced, used to detect renames.
+    pub local_inode: Option<i64>,
+    /// The size in bytes of the local item when last synced, used to detect
+    /// renames.
+    pub local_size: Option<i64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -0,0 +1,174 @@
+//! Deciding which items in a sync directory get synced. Some items are never
+//! synced, no matter what a sync directory's exclusion rules are. These are
+//! files that Celeste (or Rclone on its behalf) creates itself, which only
+//! matter on the machine they were created on.
+use crate::{entities::SyncDirsModel, launch};
+use glob::{Pattern, PatternError};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::time::SystemTime;
+
+/// The default name for a sync directory's exclusion file.
+pub static DEFAULT_IGNORE_FILE_NAME: &str = ".sync-exclude.lst";
+
+/// The lock file held in a sync directory while it's being synced, so that
+/// another instance of Celeste can't sync it at the same time.
+pub static SYNC_LOCK_NAME: &str = ".celeste.lock";
+
+/// The file kept in the remote folder of a sync directory that records the
+/// devices syncing it. See [`crate::remote_state`].
+pub static REMOTE_STATE_NAME: &str = ".celeste-state.json";
+
+/// The file kept in the remote folder of a sync directory that lists the files
+/// in it as of the last sync. See [`crate::remote_state::Manifest`].
+pub static MANIFEST_NAME: &str = ".celeste-manifest.json";
+
+/// The directory that deleted items get moved into instead of being removed
+/// outright.
+pub static TRASH_DIR_NAME: &str = ".celeste-trash";
+
+/// The directory that previous versions of overwritten items get kept in.
+pub static VERSIONS_DIR_NAME: &str = ".celeste-versions";
+
+/// The marker added to the name of a conflict copy, such as
+/// `notes.celeste-conflict-20230422T101500.txt`.
+pub static CONFLICT_COPY_MARKER: &str = ".celeste-conflict-";
+
+/// Patterns for the temporary files that editors and browsers make while
+/// working on a file, such as Vim's swap files and partial downloads. These
+/// get created and removed again while a sync is running, so syncing them only
+/// churns the remote. They're left out unless the user turns that off.
+pub static DEFAULT_EXCLUSIONS: &[&str] = &["*.swp", "~$*", ".~lock.*#", "*.part", "*.crdownload"];
+
+/// The number of seconds in a day, for a sync directory's
+/// [`SyncDirsModel::max_file_age_days`].
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+
+lazy_static! {
+    /// The names Rclone gives files while they're being downloaded, such as
+    /// `notes.txt.1a2b3c4d.partial`.
+    static ref PARTIAL_DOWNLOAD_REGEX: Regex = Regex::new(r"\.[[:alnum:]]{8}\.partial$").unwrap();
+}
+
+/// Whether the item at `relative_path` (relative to the root of `sync_dir`,
+/// and split on `/`) should never be synced.
+pub fn is_builtin_exclusion(sync_dir: &SyncDirsModel, relative_path: &str) -> bool {
+    // Files kept in the root of the sync directory.
+    if relative_path == SYNC_LOCK_NAME
+        || relative_path == REMOTE_STATE_NAME
+        || relative_path == MANIFEST_NAME
+        || relative_path == DEFAULT_IGNORE_FILE_NAME
+        || relative_path == sync_dir.ignore_file_name
+    {
+        return true;
+    }
+
+    relative_path.split('/').any(|name| {
+        name == TRASH_DIR_NAME
+            || name == VERSIONS_DIR_NAME
+            || name.contains(CONFLICT_COPY_MARKER)
+            || PARTIAL_DOWNLOAD_REGEX.is_match(name)
+    })
+}
+
+/// Split a sync directory's include-only patterns, which are separated by `;`.
+pub fn split_inclusions(inclusions: &str) -> impl Iterator<Item = &str> {
+    inclusions
+        .split(';')
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+}
+
+/// Check that each of a sync directory's include-only patterns is valid.
+pub fn validate_inclusions(inclusions: &str) -> Result<(), PatternError> {
+    split_inclusions(inclusions).try_for_each(|pattern| Pattern::new(pattern).map(|_| ()))
+}
+
+/// The user's rules for which items in a sync directory get synced. The same
+/// rules are used in both directions.
+pub struct SyncFilter {
+    /// Items matching any of these aren't synced.
+    exclusions: Vec<Pattern>,
+    /// If there's any of these, only files matching one of them are synced.
+    inclusions: Vec<Pattern>,
+    /// Items whose names match any of these aren't synced. These are the
+    /// [`DEFAULT_EXCLUSIONS`], if they're used.
+    default_exclusions: Vec<Pattern>,
+    /// Files last modified before this UNIX timestamp aren't synced, if it's
+    /// set.
+    modified_after: Option<i64>,
+}
+
+impl SyncFilter {
+    /// Create the filter for `sync_dir`. `exclusion_rules` are its exclusion
+    /// rules, one per line, as they're stored separately from the directory.
+    pub fn new(sync_dir: &SyncDirsModel, exclusion_rules: &str) -> Self {
+        Self {
+            exclusions: exclusion_rules
+                .lines()
+                .filter_map(|line| Pattern::new(line).ok())
+                .collect(),
+            inclusions: split_inclusions(&sync_dir.inclusions)
+                .filter_map(|pattern| Pattern::new(pattern).ok())
+                .collect(),
+            default_exclusions: vec![],
+            modified_after: (sync_dir.max_file_age_days > 0).then(|| {
+                launch::system_time_to_timestamp(SystemTime::now())
+                    - i64::from(sync_dir.max_file_age_days) * SECS_PER_DAY
+            }),
+        }
+    }
+
+    /// Leave out the [`DEFAULT_EXCLUSIONS`] too if `enabled` is set.
+    pub fn with_default_exclusions(mut self, enabled: bool) -> Self {
+        self.default_exclusions = if enabled {
+            DEFAULT_EXCLUSIONS
+                .iter()
+                .map(|pattern| Pattern::new(pattern).unwrap())
+                .collect()
+        } else {
+            vec![]
+        };
+        self
+    }
+
+    /// Whether the item at `relative_path` (relative to the root of the sync
+    /// directory) should be synced. Directories are never left out by the
+    /// include-only patterns, so that the matching files inside of them still
+    /// get found.
+    pub fn includes(&self, relative_path: &str, is_dir: bool) -> bool {
+        if self
+            .exclusions
+            .iter()
+            .any(|pattern| pattern.matches(relative_path))
+        {
+            return false;
+        }
+
+        // The default exclusions are matched against just the item's name, so that
+        // they apply in every folder.
+        let name = relative_path.rsplit('/').next().unwrap();
+        if !is_dir
+            && self
+                .default_exclusions
+                .iter()
+                .any(|pattern| pattern.matches(name))
+        {
+            return false;
+        }
+
+        is_dir
+            || self.inclusions.is_empty()
+            || self
+                .inclusions
+                .iter()
+                .any(|pattern| pattern.matches(relative_path))
+    }
+    /// Whether a file last modified at `timestamp` (a UNIX timestamp) is recent
+    /// enough to be synced. This is checked separately from [`Self::includes`],
+    /// since it needs the file's modification time on the side it's on.
+    pub fn includes_modified(&self, timestamp: i64) -> bool {
+        self.modified_after
+            .map_or(true, |modified_after| timestamp >= modified_after)
+    }
+}
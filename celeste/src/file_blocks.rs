@@ -0,0 +1,98 @@
+//! Block-level hashes of large files. When a large file's timestamp changes,
+//! these let its contents be checked for whether they actually changed, without
+//! having to compare it against the copy on the remote. They're also laid out
+//! so that only the changed blocks of a file could be uploaded in the future,
+//! for remotes that support such.
+use crate::{
+    db,
+    entities::{FileBlocksActiveModel, FileBlocksColumn, FileBlocksEntity},
+};
+use adw::glib::{self, ChecksumType};
+use sea_orm::{ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder};
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+/// The size of each hashed block, in bytes.
+const BLOCK_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Files smaller than this don't get hashed in blocks, as re-uploading them
+/// doesn't take long enough to be worth it.
+const MIN_FILE_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Hash each block of the file at `path`, calling `f` with the hash of each
+/// one in order. Hashing stops early if `f` returns `false`.
+fn hash_blocks<F: FnMut(String) -> bool>(path: &Path, mut f: F) -> io::Result<()> {
+    let mut file = File::open(path)?;
+    let mut block = Vec::new();
+
+    loop {
+        block.clear();
+        (&mut file).take(BLOCK_SIZE).read_to_end(&mut block)?;
+        if block.is_empty() {
+            return Ok(());
+        }
+
+        let hash = glib::compute_checksum_for_data(ChecksumType::Sha256, &block)
+            .unwrap()
+            .to_string();
+        if !f(hash) {
+            return Ok(());
+        }
+    }
+}
+
+/// Whether the file at `path` has the same contents as when its blocks were
+/// last recorded for the sync item with the ID of `sync_item_id`. Files
+/// without any recorded blocks never count as the same.
+pub fn unchanged(db: &DatabaseConnection, sync_item_id: i32, path: &Path) -> bool {
+    let blocks = db::expect(|| {
+        FileBlocksEntity::find()
+            .filter(FileBlocksColumn::SyncItemId.eq(sync_item_id))
+            .order_by_asc(FileBlocksColumn::BlockIndex)
+            .all(db)
+    });
+    if blocks.is_empty() {
+        return false;
+    }
+
+    let mut blocks = blocks.into_iter();
+    let mut same = true;
+    let result = hash_blocks(path, |hash| {
+        same = blocks.next().map_or(false, |block| block.hash == hash);
+        same
+    });
+
+    // The file could have also been cut short.
+    result.is_ok() && same && blocks.next().is_none()
+}
+
+/// Record the blocks of the file at `path` for the sync item with the ID of
+/// `sync_item_id`, replacing any recorded before. Nothing gets recorded for
+/// files that are too small to be worth it.
+pub fn record(db: &DatabaseConnection, sync_item_id: i32, path: &Path) -> io::Result<()> {
+    db::expect(|| {
+        FileBlocksEntity::delete_many()
+            .filter(FileBlocksColumn::SyncItemId.eq(sync_item_id))
+            .exec(db)
+    });
+    if path.metadata()?.len() < MIN_FILE_SIZE {
+        return Ok(());
+    }
+
+    let mut blocks = vec![];
+    hash_blocks(path, |hash| {
+        blocks.push(FileBlocksActiveModel {
+            sync_item_id: ActiveValue::Set(sync_item_id),
+            block_index: ActiveValue::Set(blocks.len().try_into().unwrap()),
+            hash: ActiveValue::Set(hash),
+            ..Default::default()
+        });
+        true
+    })?;
+    db::expect(|| FileBlocksEntity::insert_many(blocks.clone()).exec(db));
+
+    Ok(())
+}
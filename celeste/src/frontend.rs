@@ -0,0 +1,227 @@
+//! A thin frontend that shows what the sync engine (see [`crate::engine`]) is
+//! syncing, from a separate process. It only talks to the engine over its
+//! socket (see [`crate::ipc`]), so the frontend can be closed, restarted or
+//! upgraded without interrupting syncing.
+use crate::{
+    gtk_util,
+    ipc::{self, EngineStatus, IpcCommand},
+    platform,
+};
+use adw::{
+    glib::{self, MainContext},
+    gtk::{
+        Align, Box, Button, Image, Label, ListBox, Orientation, PolicyType, ScrolledWindow,
+        SelectionMode,
+    },
+    prelude::*,
+    ActionRow, Application, ApplicationWindow, HeaderBar, WindowTitle,
+};
+use std::{
+    cell::{Cell, RefCell},
+    env, io,
+    process::{Command, Stdio},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+/// How often to get the engine's status.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long to wait for the engine to send its status.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long to wait for an engine started by the frontend to start listening
+/// on its socket.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Start the engine in the background, for when it isn't running. It's put in
+/// its own process group, so that it keeps running after the frontend quits.
+fn start_engine() -> io::Result<()> {
+    let mut command = Command::new(env::current_exe()?);
+    command
+        .arg("engine")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    platform::separate_process_group(&mut command);
+    command.spawn().map(|_| ())
+}
+
+/// Send `command` to the engine, showing an error if it couldn't be.
+fn send(command: IpcCommand) {
+    if let Err(err) = ipc::send(command) {
+        gtk_util::show_error(
+            &tr::tr!("Unable to reach Celeste's sync engine"),
+            Some(&err.to_string()),
+        );
+    }
+}
+
+/// Get the row showing the status of the sync directory `dir`.
+fn dir_row(dir: &ipc::DirStatus) -> ActionRow {
+    let row = ActionRow::builder()
+        .title(&libceleste::fmt_home(&dir.local_path))
+        .subtitle(&dir.status)
+        .build();
+    if dir.errors > 0 {
+        let icon = Image::builder()
+            .icon_name("dialog-warning-symbolic")
+            .tooltip_text(&tr::tr!("{n} error" | "{n} errors" % dir.errors))
+            .build();
+        row.add_prefix(&icon);
+    }
+    row.add_suffix(
+        &Label::builder()
+            .label(&format!("{}:/{}", dir.remote_name, dir.remote_path))
+            .css_classes(vec!["caption".to_string(), "dim-label".to_string()])
+            .build(),
+    );
+    row
+}
+
+/// Show the engine's status, refreshing it until the window is closed.
+fn status_window(app: &Application) {
+    let window_title = WindowTitle::new(&libceleste::get_title!("Status"), "");
+    let header_bar = HeaderBar::builder().title_widget(&window_title).build();
+    let open_button = Button::with_label(&tr::tr!("Open Celeste"));
+    let pause_button = Button::with_label(&tr::tr!("Pause Syncing"));
+    let sync_now_button = Button::with_label(&tr::tr!("Sync Now"));
+    sync_now_button.add_css_class("suggested-action");
+    header_bar.pack_start(&open_button);
+    header_bar.pack_end(&sync_now_button);
+    header_bar.pack_end(&pause_button);
+
+    let dirs_list = ListBox::builder()
+        .selection_mode(SelectionMode::None)
+        .valign(Align::Start)
+        .css_classes(vec!["boxed-list".to_string()])
+        .build();
+    dirs_list.set_placeholder(Some(
+        &Label::builder()
+            .label(&tr::tr!("No directories are being synced."))
+            .margin_top(10)
+            .margin_bottom(10)
+            .css_classes(vec!["dim-label".to_string()])
+            .build(),
+    ));
+    let dirs_scrolled = ScrolledWindow::builder()
+        .child(&dirs_list)
+        .hscrollbar_policy(PolicyType::Never)
+        .vexpand(true)
+        .css_classes(vec!["celeste-global-padding".to_string()])
+        .build();
+    let sections = Box::builder().orientation(Orientation::Vertical).build();
+    sections.append(&header_bar);
+    sections.append(&dirs_scrolled);
+
+    // Syncing now also resumes syncing, so the pause button does that while it's
+    // paused.
+    let paused = Rc::new(Cell::new(false));
+    open_button.connect_clicked(|_| send(IpcCommand::Open));
+    sync_now_button.connect_clicked(|_| send(IpcCommand::SyncNow));
+    pause_button.connect_clicked(glib::clone!(@strong paused => move |_| {
+        if paused.get() {
+            send(IpcCommand::SyncNow);
+        } else {
+            send(IpcCommand::PauseSyncing);
+        }
+    }));
+
+    // When the engine was started by us, if it has been.
+    let engine_started: Cell<Option<Instant>> = Cell::new(None);
+    let last_status: RefCell<Option<EngineStatus>> = RefCell::new(None);
+    // Returns whether the window is still open.
+    let refresh = glib::clone!(@weak window_title, @weak dirs_list, @weak pause_button, @weak open_button, @weak sync_now_button, @strong paused => @default-return false, move |status: io::Result<EngineStatus>| {
+        let status = match status {
+            Ok(status) => status,
+            Err(err) => {
+                // Only start the engine when nothing is listening on its socket, as one that
+                // was just started through socket activation can take a while to respond.
+                let not_running = matches!(
+                    err.kind(),
+                    io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+                );
+                let subtitle = match engine_started.get() {
+                    None if not_running => match start_engine() {
+                        Ok(()) => {
+                            engine_started.set(Some(Instant::now()));
+                            tr::tr!("Starting Celeste...")
+                        }
+                        Err(err) => tr::tr!("Unable to start Celeste [{}].", err),
+                    },
+                    Some(started) if started.elapsed() < STARTUP_TIMEOUT => {
+                        tr::tr!("Starting Celeste...")
+                    }
+                    _ => tr::tr!("Unable to connect to Celeste [{}].", err),
+                };
+                window_title.set_subtitle(&subtitle);
+                *last_status.borrow_mut() = None;
+                for button in [&open_button, &pause_button, &sync_now_button] {
+                    button.set_sensitive(false);
+                }
+                return true;
+            }
+        };
+
+        engine_started.set(None);
+        for button in [&open_button, &pause_button, &sync_now_button] {
+            button.set_sensitive(true);
+        }
+        if last_status.borrow().as_ref() == Some(&status) {
+            return true;
+        }
+
+        // The engine can only open its main window when it's running in one.
+        open_button.set_visible(!status.headless);
+        window_title.set_subtitle(&status.message);
+        paused.set(status.paused);
+        pause_button.set_label(&if status.paused {
+            tr::tr!("Resume Syncing")
+        } else {
+            tr::tr!("Pause Syncing")
+        });
+        while let Some(row) = dirs_list.first_child() {
+            dirs_list.remove(&row);
+        }
+        for dir in &status.dirs {
+            dirs_list.append(&dir_row(dir));
+        }
+        *last_status.borrow_mut() = Some(status);
+        true
+    });
+
+    // Waiting for the engine's status would block the window from redrawing, so
+    // get it in the background.
+    MainContext::default().spawn_local(async move {
+        loop {
+            let status = blocking::unblock(|| ipc::status(RESPONSE_TIMEOUT)).await;
+            if !refresh(status) {
+                break;
+            }
+            glib::timeout_future(REFRESH_INTERVAL).await;
+        }
+    });
+
+    let window = ApplicationWindow::builder()
+        .application(app)
+        .title(&libceleste::get_title!("Status"))
+        .default_width(600)
+        .default_height(500)
+        .content(&sections)
+        .build();
+    window.show();
+}
+
+/// Run the frontend, starting the engine in the background if it isn't
+/// running. The frontend has its own application ID, so that it can run
+/// alongside the engine, with running it again just bringing up its window.
+pub fn run() {
+    let app = Application::builder()
+        .application_id(&format!("{}.Frontend", libceleste::APP_ID))
+        .build();
+    app.connect_activate(|app| match app.windows().first() {
+        Some(window) => window.present(),
+        None => status_window(app),
+    });
+    app.run_with_args::<&str>(&[]);
+}
@@ -1,10 +1,13 @@
 use crate::mpsc;
 use adw::{
     glib,
-    gtk::{Orientation, ScrolledWindow, Separator, TextBuffer, TextView},
+    gtk::{
+        AccessibleProperty, Orientation, ScrolledWindow, Separator, TextBuffer, TextView, Widget,
+    },
     prelude::*,
     MessageDialog,
 };
+use std::process::Command;
 
 /// Show an error screen.
 pub fn show_error(primary_text: &str, secondary_text: Option<&str>) {
@@ -82,3 +85,27 @@ pub fn separator() -> Separator {
         .css_classes(vec!["spacer".to_string()])
         .build()
 }
+
+/// Give an icon-only widget a label for screen readers, showing it as a tooltip
+/// too.
+pub fn set_accessible_label(widget: &impl IsA<Widget>, label: &str) {
+    widget.set_tooltip_text(Some(label));
+    widget.update_property(&[(AccessibleProperty::Label, &label)]);
+}
+
+/// Open a URI (or a local path) with the user's default application, showing an
+/// error if that fails.
+pub fn open_uri(uri: &str) {
+    let uri_owned = uri.to_owned();
+    let result =
+        libceleste::run_in_background(move || Command::new("xdg-open").arg(uri_owned).output());
+
+    match result {
+        Ok(output) if !output.status.success() => show_codeblock_error(
+            &tr::tr!("Unable to open '{}'", uri),
+            &String::from_utf8_lossy(&output.stderr),
+        ),
+        Err(err) => show_error(&tr::tr!("Unable to open '{}'", uri), Some(&err.to_string())),
+        _ => (),
+    }
+}
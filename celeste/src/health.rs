@@ -0,0 +1,206 @@
+//! A check run on startup that Celeste's database agrees with Rclone's config,
+//! so that remotes and directories only one of them knows about can be
+//! repaired before anything tries to use them.
+use crate::{
+    db,
+    entities::{
+        PendingTransfersColumn, PendingTransfersEntity, RemotesActiveModel, RemotesEntity,
+        RemotesModel, SnoozedErrorsColumn, SnoozedErrorsEntity, SyncDirsColumn, SyncDirsEntity,
+        SyncDirsModel, SyncItemsColumn, SyncItemsEntity,
+    },
+    mpsc, rclone,
+};
+use adw::{
+    glib,
+    gtk::{ListBox, SelectionMode},
+    prelude::*,
+    ActionRow,
+};
+use sea_orm::{entity::prelude::*, ActiveValue, DatabaseConnection};
+
+/// Something that doesn't match between the database and Rclone's config.
+#[derive(Clone, Debug)]
+pub enum HealthIssue {
+    /// A remote in the database that's missing from Rclone's config.
+    MissingFromConfig(RemotesModel),
+    /// A remote of a supported type in Rclone's config that isn't in the
+    /// database.
+    MissingFromDb(String),
+    /// A sync directory whose remote isn't in the database.
+    OrphanedSyncDir(SyncDirsModel),
+}
+
+impl HealthIssue {
+    /// Get a short description of the issue.
+    fn title(&self) -> String {
+        match self {
+            Self::MissingFromConfig(remote) => {
+                tr::tr!("'{}' is missing from Rclone's config", remote.name)
+            }
+            Self::MissingFromDb(remote_name) => {
+                tr::tr!("'{}' is only in Rclone's config", remote_name)
+            }
+            Self::OrphanedSyncDir(sync_dir) => tr::tr!(
+                "'{}' is synced with a remote that no longer exists",
+                libceleste::fmt_home(&sync_dir.local_path)
+            ),
+        }
+    }
+
+    /// Get what repairing the issue does.
+    fn repair_description(&self) -> String {
+        match self {
+            Self::MissingFromConfig(_) => {
+                tr::tr!("The remote and its directories will be removed from Celeste.")
+            }
+            Self::MissingFromDb(_) => tr::tr!("The remote will be added to Celeste."),
+            Self::OrphanedSyncDir(_) => tr::tr!("The directory will stop being synced."),
+        }
+    }
+
+    /// Get the name of the remote this issue is for, if it's for one.
+    pub fn remote_name(&self) -> Option<&str> {
+        match self {
+            Self::MissingFromConfig(remote) => Some(&remote.name),
+            Self::MissingFromDb(remote_name) => Some(remote_name),
+            Self::OrphanedSyncDir(_) => None,
+        }
+    }
+
+    /// Repair the issue.
+    pub fn repair(&self, db: &DatabaseConnection) -> Result<(), DbErr> {
+        db::run(|| async {
+            match self {
+                Self::MissingFromConfig(remote) => delete_remote(db, remote.clone()).await?,
+                Self::MissingFromDb(remote_name) => {
+                    RemotesActiveModel {
+                        name: ActiveValue::Set(remote_name.clone()),
+                        ..Default::default()
+                    }
+                    .insert(db)
+                    .await?;
+                }
+                Self::OrphanedSyncDir(sync_dir) => delete_sync_dir(db, sync_dir.clone()).await?,
+            }
+
+            Ok(())
+        })
+    }
+}
+
+/// Delete `sync_dir` from the database, along with everything recorded for it.
+/// Those records reference the directory, so they have to be deleted first.
+pub async fn delete_sync_dir(
+    db: &DatabaseConnection,
+    sync_dir: SyncDirsModel,
+) -> Result<(), DbErr> {
+    SyncItemsEntity::delete_many()
+        .filter(SyncItemsColumn::SyncDirId.eq(sync_dir.id))
+        .exec(db)
+        .await?;
+    PendingTransfersEntity::delete_many()
+        .filter(PendingTransfersColumn::SyncDirId.eq(sync_dir.id))
+        .exec(db)
+        .await?;
+    SnoozedErrorsEntity::delete_many()
+        .filter(SnoozedErrorsColumn::SyncDirId.eq(sync_dir.id))
+        .exec(db)
+        .await?;
+    sync_dir.delete(db).await?;
+    Ok(())
+}
+
+/// Delete `remote` from the database, along with its sync directories (see
+/// [`delete_sync_dir`]).
+pub async fn delete_remote(db: &DatabaseConnection, remote: RemotesModel) -> Result<(), DbErr> {
+    let sync_dirs = SyncDirsEntity::find()
+        .filter(SyncDirsColumn::RemoteId.eq(remote.id))
+        .all(db)
+        .await?;
+    for sync_dir in sync_dirs {
+        delete_sync_dir(db, sync_dir).await?;
+    }
+    remote.delete(db).await?;
+    Ok(())
+}
+
+/// Find everything that doesn't match between the database and Rclone's
+/// config.
+pub fn check(db: &DatabaseConnection) -> Result<Vec<HealthIssue>, DbErr> {
+    let remotes = db::run(|| RemotesEntity::find().all(db))?;
+    let sync_dirs = db::run(|| SyncDirsEntity::find().all(db))?;
+    let config_remotes = rclone::get_remote_names();
+    let mut issues = vec![];
+
+    // When Rclone's config can't be read (such as when it's encrypted and hasn't
+    // been unlocked), every remote would look like it's missing from it, and
+    // repairing that would remove all of them.
+    let config_readable = rclone::config_is_readable();
+    if !config_readable {
+        hw_msg::warningln!(
+            "Unable to read Rclone's config, so remotes missing from it won't be checked for."
+        );
+    }
+
+    for remote in &remotes {
+        if config_readable && !config_remotes.contains(&remote.name) {
+            issues.push(HealthIssue::MissingFromConfig(remote.clone()));
+        }
+    }
+    for remote_name in config_remotes {
+        if !remotes.iter().any(|remote| remote.name == remote_name)
+            && rclone::get_remote(&remote_name).is_some()
+        {
+            issues.push(HealthIssue::MissingFromDb(remote_name));
+        }
+    }
+    for sync_dir in sync_dirs {
+        if !remotes.iter().any(|remote| remote.id == sync_dir.remote_id) {
+            issues.push(HealthIssue::OrphanedSyncDir(sync_dir));
+        }
+    }
+
+    Ok(issues)
+}
+
+/// Show the user what doesn't match between the database and Rclone's config,
+/// and ask whether to repair it. Returns `true` if the user chose to repair
+/// the issues.
+pub fn ask_repair(issues: &[HealthIssue]) -> bool {
+    let (sender, mut receiver) = mpsc::channel::<bool>();
+
+    let issue_list = ListBox::builder()
+        .selection_mode(SelectionMode::None)
+        .css_classes(vec!["boxed-list".to_string()])
+        .build();
+    for issue in issues {
+        let row = ActionRow::builder()
+            .title(&issue.title())
+            .subtitle(&issue.repair_description())
+            .build();
+        issue_list.append(&row);
+    }
+
+    let dialog = adw::MessageDialog::builder()
+        .heading(&tr::tr!("Celeste's data doesn't match Rclone's config"))
+        .body(&tr::tr!(
+            "This can happen when Rclone's config is changed from outside of Celeste. Remotes missing from Rclone's config won't be synced until they're repaired or added back to it."
+        ))
+        .extra_child(&issue_list)
+        .modal(true)
+        .build();
+    dialog.add_response("ignore", &tr::tr!("Ignore"));
+    dialog.add_response("repair", &tr::tr!("Repair"));
+    dialog.set_response_appearance("repair", adw::ResponseAppearance::Destructive);
+    dialog.set_default_response(Some("ignore"));
+    dialog.set_close_response("ignore");
+    dialog.connect_response(
+        None,
+        glib::clone!(@strong sender => move |dialog, resp| {
+            dialog.close();
+            sender.send(resp == "repair");
+        }),
+    );
+    dialog.show();
+    receiver.recv()
+}
@@ -0,0 +1,407 @@
+//! A private Unix socket for controlling a running instance of Celeste, which
+//! works alongside the DBus session bus for environments where that isn't
+//! available (such as some sandboxes and containers).
+//!
+//! Each message is a single line of JSON. Requests and responses both carry the
+//! version of the protocol they use, so that clients can tell when they're
+//! talking to an instance too old to understand them. The socket can also be
+//! passed in through systemd's socket activation.
+use crate::launch::{self, AppAction};
+use adw::glib;
+use nix::unistd;
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fs::{self, Permissions},
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        io::FromRawFd,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+
+/// The version of the protocol spoken over the socket. This only needs to be
+/// bumped for changes that older instances can't safely ignore.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The first file descriptor passed in through systemd's socket activation.
+const LISTEN_FDS_START: i32 = 3;
+
+/// The socket passed in through systemd's socket activation, once it's been
+/// taken by [`take_activated_listener`].
+static ACTIVATED_LISTENER: Mutex<Option<UnixListener>> = Mutex::new(None);
+
+/// A command that can be sent to the running instance.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IpcCommand {
+    /// Open the main window.
+    Open,
+    /// Quit the application.
+    Close,
+    /// Start the next sync pass right away, resuming syncing if it's paused.
+    SyncNow,
+    /// Pause syncing until it's resumed.
+    PauseSyncing,
+    /// Wait until every directory has been fully synced without any errors,
+    /// for up to `timeout_secs` seconds if given.
+    Wait { timeout_secs: Option<u64> },
+    /// Get what's currently being synced, as an [`EngineStatus`].
+    Status,
+}
+
+/// What a running instance is currently syncing, for frontends running in a
+/// separate process to show.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct EngineStatus {
+    /// The overall status message, as shown in the tray icon.
+    pub message: String,
+    /// Whether syncing is paused.
+    pub paused: bool,
+    /// Whether the instance is the sync engine running without any windows,
+    /// in which case it can't open a main window of its own.
+    #[serde(default)]
+    pub headless: bool,
+    /// The status of each sync directory, in the order they're shown in.
+    pub dirs: Vec<DirStatus>,
+}
+
+/// The status of a sync directory, as of the end of the last sync check.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DirStatus {
+    /// The name of the remote the directory is synced with.
+    pub remote_name: String,
+    pub local_path: String,
+    pub remote_path: String,
+    /// The directory's status message.
+    pub status: String,
+    /// The number of errors the directory has.
+    pub errors: usize,
+}
+
+impl From<AppAction> for IpcCommand {
+    fn from(action: AppAction) -> Self {
+        match action {
+            AppAction::SyncNow => Self::SyncNow,
+            AppAction::PauseSyncing => Self::PauseSyncing,
+        }
+    }
+}
+
+/// A request sent to the running instance.
+#[derive(Debug, Deserialize, Serialize)]
+struct Request {
+    version: u32,
+    command: IpcCommand,
+}
+
+/// The running instance's response to a [`Request`].
+#[derive(Debug, Deserialize, Serialize)]
+struct Response {
+    version: u32,
+    /// Why the request failed, if it did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// The instance's status, for [`IpcCommand::Status`] requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    status: Option<EngineStatus>,
+}
+
+impl Response {
+    /// Respond to a request that succeeded.
+    fn ok() -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            error: None,
+            status: None,
+        }
+    }
+
+    /// Respond to a request that failed because of `error`.
+    fn error(error: String) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            error: Some(error),
+            status: None,
+        }
+    }
+}
+
+/// Get the path of the socket.
+pub fn socket_path() -> PathBuf {
+    glib::user_runtime_dir().join(format!("{}.sock", libceleste::APP_ID))
+}
+
+/// Take the socket passed in through systemd's socket activation, if there is
+/// one, for [`serve`] to listen on. This removes its environment variables, so
+/// it has to be called before any threads are started.
+pub fn take_activated_listener() {
+    *ACTIVATED_LISTENER.lock().unwrap() = activated_listener();
+}
+
+/// Get the socket passed in through systemd's socket activation, if there is
+/// one. Celeste relaunches itself in a subprocess (see `main`), so sockets
+/// passed to the parent process are accepted as well.
+fn activated_listener() -> Option<UnixListener> {
+    let listen_pid: i32 = env::var("LISTEN_PID").ok()?.parse().ok()?;
+    let listen_fds: i32 = env::var("LISTEN_FDS").ok()?.parse().ok()?;
+    if listen_fds < 1
+        || (listen_pid != unistd::getpid().as_raw() && listen_pid != unistd::getppid().as_raw())
+    {
+        return None;
+    }
+
+    // Make sure nothing we start thinks the socket is meant for them.
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    Some(unsafe { UnixListener::from_raw_fd(LISTEN_FDS_START) })
+}
+
+/// Run `command` in this instance, returning the response to send back, or
+/// why it failed if it did.
+fn run(command: IpcCommand) -> Result<Response, String> {
+    match command {
+        IpcCommand::Open => launch::request_open(),
+        IpcCommand::Close => launch::request_close(),
+        IpcCommand::SyncNow => AppAction::SyncNow.run(),
+        IpcCommand::PauseSyncing => AppAction::PauseSyncing.run(),
+        IpcCommand::Wait { timeout_secs } => {
+            if !launch::wait_until_idle(timeout_secs.map(Duration::from_secs)) {
+                return Err("Timed out waiting for syncing to finish.".to_owned());
+            }
+        }
+        IpcCommand::Status => {
+            return Ok(Response {
+                status: Some(launch::engine_status()),
+                ..Response::ok()
+            });
+        }
+    }
+
+    Ok(Response::ok())
+}
+
+/// Whether the client connected on `stream` is run by the same user as us.
+#[cfg(target_os = "linux")]
+fn same_user(stream: &UnixStream) -> bool {
+    use nix::sys::socket::{getsockopt, sockopt::PeerCredentials};
+    use std::os::unix::io::AsRawFd;
+
+    getsockopt(stream.as_raw_fd(), PeerCredentials)
+        .map_or(false, |creds| creds.uid() == unistd::getuid().as_raw())
+}
+
+/// Whether the client connected on `stream` is run by the same user as us.
+/// Peer credentials are only checked on Linux, so this relies on the socket
+/// only being accessible to us elsewhere.
+#[cfg(not(target_os = "linux"))]
+fn same_user(_stream: &UnixStream) -> bool {
+    true
+}
+
+/// Handle the requests sent by a client, until it disconnects.
+fn handle_client(stream: UnixStream) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+
+    for line in BufReader::new(stream).lines() {
+        let response = match serde_json::from_str::<Request>(&line?) {
+            Ok(request) if request.version > PROTOCOL_VERSION => Response::error(format!(
+                "Protocol version {} isn't supported, as the newest supported version is {PROTOCOL_VERSION}.",
+                request.version
+            )),
+            Ok(request) => run(request.command).unwrap_or_else(Response::error),
+            Err(err) => Response::error(format!("Invalid request: {err}")),
+        };
+
+        serde_json::to_writer(&mut writer, &response)?;
+        writer.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+/// The socket being listened on by [`serve`], which gets removed when this is
+/// dropped if it was bound by us.
+pub struct Socket {
+    /// The path of the socket, if it was bound by us. Sockets passed in through
+    /// systemd's socket activation are left for systemd to remove.
+    path: Option<PathBuf>,
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            fs::remove_file(path).unwrap_or(());
+        }
+    }
+}
+
+/// Bind to the socket at `path`, making sure only we can connect to it.
+fn bind(path: &Path) -> io::Result<UnixListener> {
+    // An instance that didn't quit cleanly leaves its socket behind, which would
+    // stop us from binding to it.
+    if path.exists() && UnixStream::connect(path).is_err() {
+        fs::remove_file(path).unwrap_or(());
+    }
+
+    let listener = UnixListener::bind(path)?;
+    if let Err(err) = fs::set_permissions(path, Permissions::from_mode(0o600)) {
+        fs::remove_file(path).unwrap_or(());
+        return Err(err);
+    }
+    Ok(listener)
+}
+
+/// Start listening for commands on the socket in the background, until the
+/// returned [`Socket`] is dropped. The socket is only an addition to the DBus
+/// connection, so any issues setting it up are just warned about.
+pub fn serve() -> Socket {
+    let activated = ACTIVATED_LISTENER.lock().unwrap().take();
+    let (listener, socket) = match activated {
+        Some(listener) => (listener, Socket { path: None }),
+        None => {
+            let path = socket_path();
+            match bind(&path) {
+                Ok(listener) => (listener, Socket { path: Some(path) }),
+                Err(err) => {
+                    hw_msg::warningln!(
+                        "Unable to listen on the socket at '{}': '{err}'.",
+                        path.display()
+                    );
+                    return Socket { path: None };
+                }
+            }
+        }
+    };
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // Other users can't be trusted with controlling our syncing.
+            if !same_user(&stream) {
+                hw_msg::warningln!("Refused a connection on the socket from another user.");
+                continue;
+            }
+
+            thread::spawn(move || {
+                if let Err(err) = handle_client(stream) {
+                    hw_msg::warningln!("Got error while handling a client on the socket: '{err}'.");
+                }
+            });
+        }
+    });
+    socket
+}
+
+/// Send `command` to the running instance over the socket, giving up on
+/// waiting for its response after `timeout` if given. This fails if nothing is
+/// listening on the socket, or if the instance rejected the command.
+fn request(command: IpcCommand, timeout: Option<Duration>) -> io::Result<Response> {
+    let mut stream = UnixStream::connect(socket_path())?;
+    stream.set_read_timeout(timeout)?;
+    let request = Request {
+        version: PROTOCOL_VERSION,
+        command,
+    };
+    serde_json::to_writer(&mut stream, &request)?;
+    stream.write_all(b"\n")?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let response: Response = serde_json::from_str(&line)?;
+
+    match response.error {
+        Some(err) => Err(io::Error::other(err)),
+        None => Ok(response),
+    }
+}
+
+/// Send `command` to the running instance over the socket. This fails if
+/// nothing is listening on the socket, or if the instance rejected the
+/// command.
+pub fn send(command: IpcCommand) -> io::Result<()> {
+    request(command, None).map(|_| ())
+}
+
+/// Get what the running instance is currently syncing. This fails if nothing
+/// is listening on the socket, or if it doesn't respond within `timeout`.
+pub fn status(timeout: Duration) -> io::Result<EngineStatus> {
+    request(IpcCommand::Status, Some(timeout))?
+        .status
+        .ok_or_else(|| io::Error::other("The running instance didn't send its status."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_response_round_trip() {
+        let status = EngineStatus {
+            message: "Finished sync checks.".to_owned(),
+            paused: false,
+            headless: true,
+            dirs: vec![DirStatus {
+                remote_name: "drive".to_owned(),
+                local_path: "/home/user/Documents".to_owned(),
+                remote_path: "Documents".to_owned(),
+                status: "Directory has finished sync checks.".to_owned(),
+                errors: 2,
+            }],
+        };
+        let response = Response {
+            status: Some(status.clone()),
+            ..Response::ok()
+        };
+        let line = serde_json::to_string(&response).unwrap();
+        let parsed: Response = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed.status, Some(status));
+        assert_eq!(parsed.error, None);
+    }
+
+    #[test]
+    fn responses_without_status_parse() {
+        // Responses from before the status command existed don't have it.
+        let parsed: Response = serde_json::from_str(r#"{"version":1}"#).unwrap();
+        assert_eq!(parsed.status, None);
+        assert!(!serde_json::to_string(&Response::ok())
+            .unwrap()
+            .contains("status"));
+    }
+
+    #[test]
+    fn bound_socket_is_private() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("celeste.sock");
+        let listener = bind(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // We're the same user as ourselves.
+        let client = UnixStream::connect(&path).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        assert!(same_user(&server));
+        drop(client);
+
+        drop(Socket {
+            path: Some(path.clone()),
+        });
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn status_request_format() {
+        let request = Request {
+            version: PROTOCOL_VERSION,
+            command: IpcCommand::Status,
+        };
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"version":1,"command":"status"}"#
+        );
+    }
+}
@@ -1,50 +1,130 @@
 use crate::{
+    audit::{self, AuditAction, AuditLog, AuditReason, KeptCopies},
+    backup,
+    capabilities::RemoteCapabilities,
+    conflicts, db, encryption,
     entities::{
-        RemotesColumn, RemotesEntity, RemotesModel, SyncDirsActiveModel, SyncDirsColumn,
-        SyncDirsEntity, SyncDirsModel, SyncItemsActiveModel, SyncItemsColumn, SyncItemsEntity,
+        PendingTransfersActiveModel, PendingTransfersColumn, PendingTransfersEntity,
+        RemotesActiveModel, RemotesColumn, RemotesEntity, RemotesModel, SettingsModel,
+        SnoozedErrorsActiveModel, SnoozedErrorsColumn, SnoozedErrorsEntity, SyncDirsActiveModel,
+        SyncDirsColumn, SyncDirsEntity, SyncDirsModel, SyncItemsActiveModel, SyncItemsColumn,
+        SyncItemsEntity, SyncItemsModel, SyncPassesActiveModel, SyncPassesColumn, SyncPassesEntity,
     },
-    gtk_util,
+    exclusions, file_blocks, gtk_util,
+    health::{self, HealthIssue},
+    ipc::{self, DirStatus, EngineStatus},
     login::{self},
+    metrics,
     migrations::{Migrator, MigratorTrait},
+    mount::Mount,
+    mpsc,
+    path_status::{self, PathStatus},
+    paths, platform,
     rclone::{self, RcloneListFilter},
+    remote_state::{Manifest, RemoteState},
+    scheduler::{Scheduler, SyncPair},
+    sync_backend::{RcloneBackend, SyncBackend},
+    sync_decision::{self, InitialSync, ItemMeta, SyncDecision, SyncRecord, TimestampComparison},
+    sync_preview::{FileCount, SyncPreview, Transfers},
+    webhooks::{self, SyncEvent},
 };
 use adw::{
-    glib,
+    gio::{Notification, NotificationPriority, SimpleAction},
+    glib::{self, MainContext, UserDirectory},
     gtk::{
-        pango::EllipsizeMode, Align, Box, Button, ButtonsType, Entry, EntryCompletion,
+        gdk::{ContentProvider, DragAction, FileList},
+        pango::EllipsizeMode,
+        Adjustment, Align, Box, Button, ButtonsType, CallbackAction, CheckButton, DragSource,
+        DropTarget, Entry, EntryCompletion, EntryIconPosition, FileChooserAction,
         FileChooserDialog, FileFilter, GestureClick, Image, Inhibit, Label, ListBox, ListBoxRow,
-        ListStore, MessageDialog, Orientation, PolicyType, Popover, PositionType, ResponseType,
-        ScrolledWindow, SelectionMode, Separator, Spinner, Stack, StackSidebar,
-        StackTransitionType, Widget,
+        ListStore, MessageDialog, Orientation, PasswordEntry, PickFlags, PolicyType, Popover,
+        PositionType, ResponseType, ScrolledWindow, SelectionMode, Separator, Shortcut,
+        ShortcutController, ShortcutTrigger, SpinButton, Spinner, Stack, StackPage, StackSidebar,
+        StackTransitionType, StringList, Switch, TextBuffer, TextView, ToggleButton, Widget,
+        WidgetPaintable, WrapMode,
     },
     prelude::*,
-    Application, ApplicationWindow, Bin, EntryRow, HeaderBar, Leaflet, LeafletTransitionType,
-    WindowTitle,
+    ActionRow, Application, ApplicationWindow, Bin, ComboRow, EntryRow, HeaderBar, Leaflet,
+    LeafletTransitionType, Squeezer, WindowTitle,
 };
 use file_lock::{FileLock, FileOptions};
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
 use libceleste::traits::prelude::*;
-use sea_orm::{entity::prelude::*, ActiveValue, Database, DatabaseConnection};
+use nix::{errno::Errno, sys::signal::Signal};
+use sd_notify::NotifyState;
+use sea_orm::{entity::prelude::*, ActiveValue, DatabaseConnection, QueryOrder};
 use tempfile::NamedTempFile;
-use zbus::blocking::Connection;
+use zbus::{
+    blocking::{fdo::DBusProxy, Connection},
+    zvariant::Value,
+};
 
 use std::{
     boxed,
-    cell::RefCell,
-    collections::HashMap,
-    fs::{self, OpenOptions},
-    io::Write,
-    os::unix::fs::PermissionsExt,
+    cell::{Cell, OnceCell, RefCell},
+    collections::{HashMap, HashSet},
+    env, fs,
+    io::{self, Read, Write},
+    mem,
+    panic::{self, AssertUnwindSafe},
     path::{Path, PathBuf},
-    process::{Child, Command},
+    process::{self, Child, Command, Output, Stdio},
     rc::Rc,
     sync::{Arc, Mutex},
     thread,
-    time::{Duration, SystemTime},
+    time::{Duration, Instant, SystemTime},
 };
 
-// The location for file ignore lists.
-static FILE_IGNORE_NAME: &str = ".sync-exclude.lst";
+// The DBus name of the service that hosts tray icons.
+static STATUS_NOTIFIER_WATCHER: &str = "org.kde.StatusNotifierWatcher";
+
+// The extended attribute that records a local item's sync status, so other
+// tools can read it.
+static SYNC_STATUS_XATTR: &str = "user.celeste.status";
+
+// How long to wait for the tray icon to respond after starting it.
+static TRAY_STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How long to let the current transfer finish after a close request, before
+// quitting anyway.
+static CLOSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+// The most errors to add to a directory's error list at once. Any others are
+// kept until there's room for them or the user asks to see them, so that a
+// large number of errors (such as from the remote being down) doesn't lock up
+// the UI.
+const MAX_SHOWN_ERRORS: usize = 200;
+
+// How long the tray icon needs to run before its restart backoff gets reset.
+static TRAY_STABLE_DURATION: Duration = Duration::from_secs(60);
+
+// The most restarts of the tray icon to double the backoff for, giving a
+// maximum backoff of 2^6 = 64 seconds.
+static TRAY_MAX_BACKOFF_RESTARTS: u32 = 6;
+
+/// How long a local file has to go without changing before it gets uploaded,
+/// so that one that's still being saved isn't uploaded half-written and then
+/// again right after.
+static UPLOAD_QUIET_PERIOD: Duration = Duration::from_secs(5);
+
+/// How long typing has to pause for before checking whether a remote directory
+/// being entered exists.
+static REMOTE_PATH_CHECK_DELAY: Duration = Duration::from_millis(500);
+
+/// How long to wait before trying to upload to a remote again after its
+/// storage quota was used up.
+pub static QUOTA_RETRY_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+/// The share of a sync directory's recorded items that have to be in another
+/// folder for the directory to look like it was moved there.
+const MOVED_DIR_MATCH_RATIO: f64 = 0.5;
+
+/// How long a sync directory's pre-sync command can run for before it gets
+/// killed, and the sync pass skipped.
+static PRE_SYNC_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// How often to check whether a command being run with a timeout has exited.
+static COMMAND_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 // A [`HashMap`] containing the status and progress for a directory sync label.
 // This is done here because if we try to get the child from a `Box` or
@@ -59,18 +139,154 @@ type RemoteDeletionQueue = Rc<RefCell<Vec<String>>>;
 // occurring.
 type SyncDirDeletionQueue = Rc<RefCell<Vec<(String, String, String)>>>;
 
+// A [`Vec`] of directories (in the same format as [`SyncDirDeletionQueue`]) to
+// undo the last sync of, at the start of their next sync.
+type SyncDirUndoQueue = Rc<RefCell<Vec<(String, String, String)>>>;
+
+// A [`HashMap`] of remote names to their currently mounted remotes.
+type MountMap = Rc<RefCell<HashMap<String, RemoteMount>>>;
+
+// A [`HashMap`] of remote names to the banners on their pages for when their
+// storage is full.
+type StorageBannerMap = Rc<RefCell<HashMap<String, StorageBanner>>>;
+
+// A [`HashMap`] of remote names to the closures that add a directory to their
+// pages, taking the remote name and the local and remote directory.
+type DirAdderMap = Rc<RefCell<HashMap<String, Rc<dyn Fn(String, String, String)>>>>;
+
+/// What to do when the main window gets closed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum CloseAction {
+    /// Hide the window, leaving the application running in the background.
+    Hide,
+    /// Minimize the window.
+    Minimize,
+    /// Quit the application.
+    Quit,
+}
+
+impl CloseAction {
+    /// Get an action from its name, as stored in the database.
+    pub fn from_setting(name: &str) -> Option<Self> {
+        match name {
+            "hide" => Some(Self::Hide),
+            "minimize" => Some(Self::Minimize),
+            "quit" => Some(Self::Quit),
+            _ => None,
+        }
+    }
+
+    /// Get the name of an action, as stored in the database.
+    pub fn setting_name(&self) -> &'static str {
+        match self {
+            Self::Hide => "hide",
+            Self::Minimize => "minimize",
+            Self::Quit => "quit",
+        }
+    }
+
+    /// Run the action on the main window.
+    fn run(&self, window: &ApplicationWindow) {
+        match self {
+            Self::Hide => window.hide(),
+            Self::Minimize => window.minimize(),
+            Self::Quit => *(*CLOSE_REQUEST).lock().unwrap() = true,
+        }
+    }
+}
+
+/// Actions that can be sent to a running instance of the application, such as
+/// from the desktop entry's actions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum AppAction {
+    /// Start the next sync pass right away, resuming syncing if it's paused.
+    SyncNow,
+    /// Pause syncing until it's resumed.
+    PauseSyncing,
+}
+
+impl AppAction {
+    /// Get the name of the application action that handles this action.
+    pub fn action_name(&self) -> &'static str {
+        match self {
+            Self::SyncNow => "sync-now",
+            Self::PauseSyncing => "pause-syncing",
+        }
+    }
+
+    /// Run the action in this instance of the application.
+    pub fn run(&self) {
+        match self {
+            Self::SyncNow => {
+                *(*SYNC_PAUSED).lock().unwrap() = false;
+                *(*SYNC_NOW_REQUEST).lock().unwrap() = true;
+            }
+            Self::PauseSyncing => *(*SYNC_PAUSED).lock().unwrap() = true,
+        }
+    }
+}
+
+/// The options to launch the application with.
+#[derive(Clone, Debug)]
+pub struct LaunchOptions {
+    /// Whether to start in the background.
+    pub background: bool,
+    /// Whether to run without a tray icon, for desktops that don't support one.
+    pub no_tray: bool,
+    /// What to do when the window is closed, or [`None`] to use the user's
+    /// preference.
+    pub close_action: Option<CloseAction>,
+    /// Whether we're running as a systemd user service, and should send it
+    /// readiness and watchdog notifications.
+    pub service: bool,
+    /// The port to serve Prometheus metrics on, if they should be served.
+    pub metrics_port: Option<u16>,
+}
+
 /// The errors that can be found while syncing.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
-enum SyncError {
+pub enum SyncError {
     /// A general catch-all error. A tuple of the path the error happened at,
     /// and the error message itself.
     General(String, String),
     /// An error when both the local and remote file are more current than at
     /// the last sync. A tuple of the local and remote file.
     BothMoreCurrent(String, String),
+    /// An error when a file's copy didn't match the original after
+    /// transferring it. A tuple of the local and remote file.
+    ChecksumMismatch(String, String),
 }
 
 impl SyncError {
+    /// The path of the item the error is for. This is the local item for
+    /// errors that are about both a local and remote item.
+    pub fn path(&self) -> &str {
+        match self {
+            Self::General(path, _) => path,
+            Self::BothMoreCurrent(local_path, _) | Self::ChecksumMismatch(local_path, _) => {
+                local_path
+            }
+        }
+    }
+
+    /// The name of the kind of error, as stored in the database.
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::General(_, _) => "general",
+            Self::BothMoreCurrent(_, _) => "both_more_current",
+            Self::ChecksumMismatch(_, _) => "checksum_mismatch",
+        }
+    }
+
+    /// The error for an item whose path isn't valid UTF-8, which Rclone and the
+    /// database need it to be.
+    fn invalid_path(path: &Path) -> Self {
+        Self::General(
+            path.to_string_lossy().into_owned(),
+            tr::tr!("The name of this item isn't valid UTF-8, so it can't be synced."),
+        )
+    }
+
     fn generate_ui(&self) -> Box {
         let error_container = Box::builder()
             .orientation(Orientation::Vertical)
@@ -110,11 +326,91 @@ impl SyncError {
                     .build();
                 error_container.append(&err_label);
             }
+            SyncError::ChecksumMismatch(local_path, remote_path) => {
+                let err_msg = tr::tr!(
+                    "'{}' and '{}' didn't match after syncing.",
+                    local_path,
+                    remote_path
+                );
+                let err_label = Label::builder()
+                    .label(&err_msg)
+                    .halign(Align::Start)
+                    .ellipsize(EllipsizeMode::End)
+                    .build();
+                let info_label = Label::builder()
+                    .label(&tr::tr!(
+                        "The file was corrupted while transferring it, so it will be transferred again once this error is dismissed."
+                    ))
+                    .halign(Align::Start)
+                    .ellipsize(EllipsizeMode::End)
+                    .css_classes(vec!["caption".to_string(), "dim-label".to_string()])
+                    .build();
+                error_container.append(&err_label);
+                error_container.append(&info_label);
+            }
         }
 
         error_container
     }
 }
+
+/// How long an error can be snoozed for, which it won't be reported during.
+fn snooze_durations() -> [(String, Duration); 2] {
+    [
+        (
+            tr::tr!("Snooze for 24 hours"),
+            Duration::from_secs(24 * 60 * 60),
+        ),
+        (
+            tr::tr!("Snooze for 1 week"),
+            Duration::from_secs(7 * 24 * 60 * 60),
+        ),
+    ]
+}
+
+/// Snooze `error` in `sync_dir` for `duration`, so that it isn't reported
+/// again until then.
+fn snooze_error(
+    db: &DatabaseConnection,
+    sync_dir: &SyncDirsModel,
+    error: &SyncError,
+    duration: Duration,
+) {
+    let now = system_time_to_timestamp(SystemTime::now());
+    db::expect(|| async {
+        // Snoozes that have run out aren't needed anymore.
+        SnoozedErrorsEntity::delete_many()
+            .filter(SnoozedErrorsColumn::SyncDirId.eq(sync_dir.id))
+            .filter(SnoozedErrorsColumn::SnoozedUntil.lte(now))
+            .exec(db)
+            .await?;
+        SnoozedErrorsActiveModel {
+            sync_dir_id: ActiveValue::Set(sync_dir.id),
+            path: ActiveValue::Set(error.path().to_owned()),
+            kind: ActiveValue::Set(error.kind().to_owned()),
+            snoozed_until: ActiveValue::Set(now + duration.as_secs() as i64),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    });
+}
+
+/// Whether `error` in `sync_dir` has been snoozed, and the snooze hasn't run
+/// out yet.
+pub fn is_snoozed(db: &DatabaseConnection, sync_dir: &SyncDirsModel, error: &SyncError) -> bool {
+    let now = system_time_to_timestamp(SystemTime::now());
+    db::expect(|| {
+        SnoozedErrorsEntity::find()
+            .filter(SnoozedErrorsColumn::SyncDirId.eq(sync_dir.id))
+            .filter(SnoozedErrorsColumn::Path.eq(error.path()))
+            .filter(SnoozedErrorsColumn::Kind.eq(error.kind()))
+            .filter(SnoozedErrorsColumn::SnoozedUntil.gt(now))
+            .one(db)
+    })
+    .is_some()
+}
+
 /// A struct representing all the data that belongs to a sync directory.
 struct SyncDir {
     /// The parent stack for [`Self::container`], this contains all the UI
@@ -135,8 +431,144 @@ struct SyncDir {
     error_list: ListBox,
     /// The list of error items, as generated by 'SyncError::generate_ui' above.
     error_items: HashMap<SyncError, Box>,
+    /// The errors that didn't fit in the error list, in the order they were
+    /// found. These get shown as errors in the list are resolved, or when the
+    /// user asks for them.
+    hidden_errors: IndexSet<SyncError>,
+    /// The button for showing the errors that aren't in the error list.
+    hidden_errors_button: Button,
+    /// A closure to add an error to the error list. This gets set once the
+    /// directory starts syncing.
+    show_error_row: Option<Rc<dyn Fn(SyncError)>>,
+    /// A closure to remove an error from the error list, or from the errors
+    /// that didn't fit in it. This gets set once the directory starts syncing.
+    remove_error: Option<Rc<dyn Fn(&SyncError)>>,
+    /// The button for resolving all of the directory's conflicts at once.
+    resolve_conflicts_button: Button,
     /// A closure to update the UI error listing.
     update_error_ui: boxed::Box<dyn Fn()>,
+    /// A closure to add a rule to the directory's exclusion list.
+    add_exclusion: boxed::Box<dyn Fn(String)>,
+}
+
+impl SyncDir {
+    /// Get the number of errors found in the directory, including those that
+    /// aren't shown in the error list.
+    fn error_count(&self) -> usize {
+        self.error_items.len() + self.hidden_errors.len()
+    }
+
+    /// Update the button for showing the errors that aren't in the error list.
+    fn update_hidden_errors_button(&self) {
+        let num_hidden = self.hidden_errors.len();
+        self.hidden_errors_button.set_visible(num_hidden != 0);
+        self.hidden_errors_button.set_label(&tr::tr!(
+            "Show {n} more error" | "Show {n} more errors" % num_hidden
+        ));
+    }
+
+    /// Get the conflicts found in the directory, including those that aren't
+    /// shown in the error list.
+    fn conflicts(&self) -> Vec<SyncError> {
+        self.error_items
+            .keys()
+            .chain(&self.hidden_errors)
+            .filter(|error| matches!(error, SyncError::BothMoreCurrent(_, _)))
+            .cloned()
+            .collect()
+    }
+
+    /// Only show the button for resolving all conflicts when there's more than
+    /// one of them.
+    fn update_conflicts_button(&self) {
+        self.resolve_conflicts_button
+            .set_visible(self.conflicts().len() > 1);
+    }
+}
+
+/// A mounted remote, along with the UI elements that show its status.
+struct RemoteMount {
+    /// The mount itself.
+    mount: Mount,
+    /// The button for toggling the mount.
+    button: ToggleButton,
+    /// The label for reporting the mount's status.
+    status_text: Label,
+}
+
+/// The banner shown on a remote's page while its storage is full, in place of
+/// an error for each file that couldn't be uploaded.
+#[derive(Clone)]
+struct StorageBanner {
+    container: Box,
+    title: Label,
+    details: Label,
+}
+
+impl StorageBanner {
+    fn new() -> Self {
+        let container = Box::builder()
+            .orientation(Orientation::Horizontal)
+            .spacing(10)
+            .visible(false)
+            .css_classes(vec!["card".to_string(), "celeste-banner".to_string()])
+            .build();
+        let text = Box::builder()
+            .orientation(Orientation::Vertical)
+            .spacing(2)
+            .build();
+        let title = Label::builder()
+            .halign(Align::Start)
+            .css_classes(vec!["heading".to_string()])
+            .build();
+        let details = Label::builder()
+            .halign(Align::Start)
+            .wrap(true)
+            .css_classes(vec!["caption".to_string(), "dim-label".to_string()])
+            .build();
+        text.append(&title);
+        text.append(&details);
+        container.append(&get_image("dialog-warning-symbolic"));
+        container.append(&text);
+
+        Self {
+            container,
+            title,
+            details,
+        }
+    }
+
+    /// Show that the storage on the remote named `remote_name` is full.
+    fn show(&self, remote_name: &str) {
+        let provider = rclone::get_remote(remote_name)
+            .map(|remote| remote.provider_name())
+            .unwrap_or_else(|| remote_name.to_owned());
+        self.title
+            .set_label(&tr::tr!("Storage full on {}", provider));
+
+        let retry_msg = tr::tr!(
+            "Uploads are paused, and will be tried again in a while. Downloads carry on as usual."
+        );
+        let details = match rclone::sync::about(remote_name) {
+            Ok(rclone::RcloneAbout {
+                used: Some(used),
+                total: Some(total),
+                ..
+            }) => tr::tr!(
+                "{} of {} used. {}",
+                glib::format_size(used.max(0) as u64),
+                glib::format_size(total.max(0) as u64),
+                retry_msg
+            ),
+            _ => retry_msg,
+        };
+        self.details.set_label(&details);
+        self.container.set_visible(true);
+    }
+
+    fn hide(&self) {
+        self.container.set_visible(false);
+    }
 }
 
 lazy_static::lazy_static! {
@@ -144,10 +576,118 @@ lazy_static::lazy_static! {
     static ref CLOSE_REQUEST: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
     // A [`Mutex`] to keep track of open requests from the tray icon.
     static ref OPEN_REQUEST: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
+    // A [`Mutex`] to keep track of requests to start the next sync pass right away.
+    static ref SYNC_NOW_REQUEST: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
+    // A [`Mutex`] to keep track of whether syncing is currently paused.
+    static ref SYNC_PAUSED: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
+    // A [`Mutex`] to keep track of whether a file is currently being transferred.
+    static ref TRANSFER_IN_PROGRESS: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
+    // A [`Mutex`] to keep track of whether everything has been synced, for anything
+    // waiting on such.
+    static ref IDLE_STATE: Arc<Mutex<IdleState>> = Arc::new(Mutex::new(IdleState::default()));
+    // A [`Mutex`] to keep track of what's being synced, for frontends running in a
+    // separate process.
+    static ref ENGINE_STATUS: Arc<Mutex<EngineStatus>> = Arc::new(Mutex::new(EngineStatus::default()));
+}
+
+/// Whether everything had been synced as of the last check of every directory.
+#[derive(Clone, Copy, Debug, Default)]
+struct IdleState {
+    /// The number of times every directory has been checked for changes.
+    checks: u64,
+    /// Whether every directory was fully synced without any errors after the
+    /// last check.
+    idle: bool,
+}
+
+/// Wait until every directory has been fully synced without any errors, as
+/// found by a check started after this was called. Returns `false` if that
+/// didn't happen within `timeout`.
+pub fn wait_until_idle(timeout: Option<Duration>) -> bool {
+    let start = Instant::now();
+    // The check in progress might have started before whatever the caller wants
+    // synced was changed, so wait for the one after it.
+    let wanted_checks = IDLE_STATE.lock().unwrap().checks + 2;
+
+    loop {
+        let state = *IDLE_STATE.lock().unwrap();
+        if state.checks >= wanted_checks && state.idle {
+            return true;
+        }
+        if timeout.map_or(false, |timeout| start.elapsed() >= timeout) {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Get what's currently being synced.
+pub fn engine_status() -> EngineStatus {
+    let mut status = ENGINE_STATUS.lock().unwrap().clone();
+    status.paused = *(*SYNC_PAUSED).lock().unwrap();
+    status
+}
+
+/// Wait a bit before the next sync pass, unless the user asks to sync now.
+/// Returns whether they did.
+pub fn wait_for_next_pass() -> bool {
+    libceleste::run_in_background(|| {
+        let wait_start = Instant::now();
+
+        while wait_start.elapsed() < Duration::from_millis(500)
+            && !*(*SYNC_NOW_REQUEST).lock().unwrap()
+        {
+            thread::sleep(Duration::from_millis(50));
+        }
+    });
+    mem::take(&mut *(*SYNC_NOW_REQUEST).lock().unwrap())
+}
+
+/// Record that every directory has been checked for changes, and whether
+/// everything was synced without any errors as of such, for anything waiting on
+/// that.
+pub fn record_sync_check(idle: bool) {
+    let mut idle_state = IDLE_STATE.lock().unwrap();
+    idle_state.checks += 1;
+    idle_state.idle = idle;
 }
 
-// The DBus application so we can receive close requests from the tray icon.
-struct ZbusApp;
+/// Let frontends know what's currently being synced. Whether syncing is paused
+/// is filled in when it's asked for.
+pub fn set_engine_status(status: EngineStatus) {
+    *ENGINE_STATUS.lock().unwrap() = status;
+}
+
+/// Whether the application has been asked to quit.
+pub fn close_requested() -> bool {
+    *(*CLOSE_REQUEST).lock().unwrap()
+}
+
+/// Whether syncing is paused.
+pub fn sync_paused() -> bool {
+    *(*SYNC_PAUSED).lock().unwrap()
+}
+
+/// Take any request to open the main window, returning whether there was one.
+pub fn take_open_request() -> bool {
+    mem::take(&mut *(*OPEN_REQUEST).lock().unwrap())
+}
+
+/// Ask the application to quit, once it's finished up what it's doing.
+pub fn request_close() {
+    *(*CLOSE_REQUEST).lock().unwrap() = true;
+}
+
+/// Ask the application to open its main window.
+pub fn request_open() {
+    *(*OPEN_REQUEST).lock().unwrap() = true;
+}
+
+// The DBus application so we can receive close requests from the tray icon, and
+// answer scripts asking about the status of synced paths.
+struct ZbusApp {
+    db: DatabaseConnection,
+}
 
 // For some reason this has to be in a separate module or we get some compiler
 // errors :P.
@@ -155,30 +695,215 @@ mod zbus_app {
     #[zbus::dbus_interface(name = "com.hunterwittenborn.Celeste.App")]
     impl super::ZbusApp {
         async fn close(&self) {
-            *(*super::CLOSE_REQUEST).lock().unwrap() = true;
+            super::request_close();
         }
 
         async fn open(&self) {
-            *(*super::OPEN_REQUEST).lock().unwrap() = true;
+            super::request_open();
+        }
+
+        /// Get the sync status of the local item at `path`. This is given as
+        /// its state, when its directory last finished syncing as a UNIX
+        /// timestamp (or zero if it never has), and the remote item it's
+        /// synced with (or an empty string if it isn't in a synced directory).
+        fn get_path_status(&self, path: String) -> zbus::fdo::Result<(String, i64, String)> {
+            let status = super::PathStatus::get(&self.db, std::path::Path::new(&path))
+                .map_err(|err| zbus::fdo::Error::Failed(err.to_string()))?;
+            Ok((
+                status.state.name().to_owned(),
+                status.last_synced.unwrap_or(0),
+                status.remote.unwrap_or_default(),
+            ))
+        }
+
+        /// Wait until every directory has been fully synced without any errors,
+        /// for up to `timeout` seconds, or without a limit if it's zero.
+        /// Returns whether that happened in time.
+        async fn wait_until_idle(&self, timeout: u64) -> bool {
+            let timeout = (timeout > 0).then(|| std::time::Duration::from_secs(timeout));
+            blocking::unblock(move || super::wait_until_idle(timeout)).await
         }
     }
 }
 
+/// Connect to the DBus session bus, and serve [`ZbusApp`] on it.
+pub fn connect_dbus(db: &DatabaseConnection) -> zbus::Result<Connection> {
+    let dbus = Connection::session()?;
+    dbus.object_server()
+        .at(libceleste::DBUS_APP_OBJECT, ZbusApp { db: db.clone() })?;
+    dbus.request_name(libceleste::DBUS_APP_ID)?;
+    Ok(dbus)
+}
+
+/// Ask the tray icon to close.
+fn close_tray(dbus: &Connection) {
+    // I'm not sure when this can fail, so output an error if one is received.
+    if let Err(err) = dbus.call_method(
+        Some(libceleste::TRAY_ID),
+        libceleste::DBUS_TRAY_OBJECT,
+        Some(libceleste::TRAY_ID),
+        "Close",
+        &(),
+    ) {
+        hw_msg::warningln!("Got error while sending close request to tray icon: '{err}'.");
+    }
+}
+
+/// Let systemd know we're still running, so its watchdog doesn't restart us.
+pub fn ping_watchdog() {
+    if let Err(err) = sd_notify::notify(false, &[NotifyState::Watchdog]) {
+        hw_msg::warningln!("Unable to send watchdog ping to systemd: '{err}'.");
+    }
+}
+
+/// Quit when a SIGTERM or SIGINT is received, the same way as when the tray
+/// icon asks us to.
+pub fn handle_close_signals() {
+    for signal in [Signal::SIGTERM, Signal::SIGINT] {
+        glib::unix_signal_add_local(signal as i32, || {
+            *(*CLOSE_REQUEST).lock().unwrap() = true;
+            glib::Continue(true)
+        });
+    }
+}
+
+/// Connect to the database at `db_path` and run any pending migrations on it,
+/// backing it up first. The database gets encrypted or decrypted beforehand if
+/// that's been changed in the preferences. Returns a message for the user on
+/// failure.
+pub fn open_database(db_path: &Path) -> Result<DatabaseConnection, String> {
+    encryption::update(db_path)
+        .map_err(|err| tr::tr!("Unable to update the database's encryption [{}].", err))?;
+    let db =
+        db::connect(db_path).map_err(|err| tr::tr!("Unable to connect to database [{}].", err))?;
+
+    backup::before_migrations(&db)
+        .and_then(|_| libceleste::await_future(Migrator::up(&db, None)))
+        .map_err(|err| tr::tr!("Unable to run database migrations [{}]", err))?;
+    Ok(db)
+}
+
+/// Whether the tray icon can be shown. Wayland sessions can only show it
+/// through a StatusNotifierItem host, which not every desktop has (such as
+/// GNOME without the AppIndicator extension).
+fn tray_supported(dbus: &Connection) -> bool {
+    if !cfg!(target_os = "linux") || env::var_os("WAYLAND_DISPLAY").is_none() {
+        return true;
+    }
+
+    let Ok(proxy) = DBusProxy::new(dbus) else {
+        return false;
+    };
+    proxy
+        .name_has_owner(STATUS_NOTIFIER_WATCHER.try_into().unwrap())
+        .unwrap_or(false)
+}
+
+/// Show a status message in the desktop's list of apps running in the
+/// background, through the background portal. Not every desktop supports this,
+/// so errors are ignored.
+#[cfg(target_os = "linux")]
+fn set_background_status(dbus: &Connection, message: &str) {
+    // The portal rejects messages longer than 96 characters.
+    let message: String = message.chars().take(96).collect();
+    let options = HashMap::from([("message", Value::from(message))]);
+    let _ = dbus.call_method(
+        Some("org.freedesktop.portal.Desktop"),
+        "/org/freedesktop/portal/desktop",
+        Some("org.freedesktop.portal.Background"),
+        "SetStatus",
+        &options,
+    );
+}
+
+/// Show a status message in the desktop's list of apps running in the
+/// background. The background portal is only available on Linux, so this does
+/// nothing elsewhere.
+#[cfg(not(target_os = "linux"))]
+fn set_background_status(_dbus: &Connection, _message: &str) {}
+
+/// Whether extended attributes can be set on items in `dir`.
+pub fn xattrs_supported(dir: &Path) -> bool {
+    xattr::SUPPORTED_PLATFORM
+        && match xattr::get(dir, SYNC_STATUS_XATTR) {
+            Ok(_) => true,
+            Err(err) => err.raw_os_error() != Some(Errno::ENOTSUP as i32),
+        }
+}
+
+/// Get when Rclone's config file was last modified, so that changes made to it
+/// from outside of Celeste can be noticed.
+fn rclone_config_modified() -> Option<SystemTime> {
+    fs::metadata(rclone::config_path())
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Record the sync status of a local item in its extended attributes. The
+/// status is only informational, so errors are ignored.
+pub fn set_sync_status(path: &Path, status: &str) {
+    let _ = xattr::set(path, SYNC_STATUS_XATTR, status.as_bytes());
+}
+
 /// Start the tray binary.
 /// We put this in a struct so we can manually kill the subprocess on [`Drop`],
 /// such as in the case of a panic.
-struct TrayApp(Child);
+struct TrayApp {
+    process: Child,
+    /// When the tray binary was last started.
+    started: Instant,
+    /// The number of times the tray binary has been restarted without running
+    /// stably in between.
+    restarts: u32,
+}
 
 impl TrayApp {
     fn start() -> Self {
+        Self {
+            process: Self::spawn(),
+            started: Instant::now(),
+            restarts: 0,
+        }
+    }
+
+    /// Restart the tray binary if it has stopped running. Consecutive restarts
+    /// are backed off exponentially, so a tray that keeps crashing doesn't get
+    /// restarted in a tight loop. Returns `true` if the tray was restarted.
+    fn restart_if_stopped(&mut self) -> bool {
+        match self.process.try_wait() {
+            Ok(None) => return false,
+            Ok(Some(status)) => {
+                if self.started.elapsed() >= TRAY_STABLE_DURATION {
+                    self.restarts = 0;
+                }
+
+                let backoff =
+                    Duration::from_secs(2u64.pow(self.restarts.min(TRAY_MAX_BACKOFF_RESTARTS)));
+                if self.started.elapsed() < backoff {
+                    return false;
+                }
+
+                hw_msg::warningln!("The tray binary stopped running ({status}), restarting it...");
+            }
+            Err(err) => {
+                hw_msg::warningln!("Unable to check if the tray binary is running: '{err}'.");
+                return false;
+            }
+        }
+
+        self.process = Self::spawn();
+        self.started = Instant::now();
+        self.restarts += 1;
+        true
+    }
+
+    fn spawn() -> Child {
         hw_msg::infoln!("Starting up tray binary...");
 
         let named_temp_file = NamedTempFile::new().unwrap();
         let temp_file = named_temp_file.path().to_owned();
         let mut file = named_temp_file.persist(&temp_file).unwrap();
-        let mut perms = file.metadata().unwrap().permissions();
-        perms.set_mode(0o755);
-        file.set_permissions(perms).unwrap();
+        platform::make_executable(&file).unwrap();
 
         #[cfg(debug_assertions)]
         let tray_file = include_bytes!("../../target/debug/celeste-tray");
@@ -187,111 +912,3833 @@ impl TrayApp {
 
         file.write_all(tray_file).unwrap();
         drop(file);
-        Self(Command::new(&temp_file).spawn().unwrap())
+
+        // Run the tray binary in its own process group, so that pressing Ctrl+C in a
+        // terminal doesn't kill it before we can ask it to close. Once it's running
+        // the binary isn't needed anymore, so remove it instead of leaving it behind.
+        let process = platform::separate_process_group(&mut Command::new(&temp_file))
+            .spawn()
+            .unwrap();
+        if let Err(err) = fs::remove_file(&temp_file) {
+            hw_msg::warningln!("Unable to remove the temporary tray binary: '{err}'.");
+        }
+        process
     }
 }
 
 impl Drop for TrayApp {
     fn drop(&mut self) {
-        self.0.kill().unwrap_or(())
+        self.process.kill().unwrap_or(())
     }
 }
 
-/// Get an icon for use as the status icon for directory syncs.
-fn get_image(icon_name: &str) -> Image {
-    Image::builder()
-        .icon_name(icon_name)
-        .width_request(10)
-        .height_request(10)
-        .build()
+/// Get the path of an item relative to the root of its sync directory, as used
+/// in exclusion rules. `path` can be either the local or the remote path of the
+/// item.
+fn get_relative_path(sync_dir: &SyncDirsModel, path: &str) -> String {
+    paths::relative_local_path(sync_dir, Path::new(path))
+        .and_then(|relative_path| relative_path.to_str())
+        .or_else(|| paths::relative_remote_path(sync_dir, path))
+        .unwrap_or(path)
+        .to_owned()
 }
 
-pub fn launch(app: &Application, background: bool) {
-    // Create the configuration directory if it doesn't exist.
-    let config_path = libceleste::get_config_dir();
-    if !config_path.exists() && let Err(err) = fs::create_dir_all(&config_path) {
-        gtk_util::show_error(
-            &tr::tr!("Unable to create Celeste's config directory [{}].", err),
-            None
-        );
-        return;
+/// Get the latest copy of `sync_dir` from the database, falling back to
+/// `sync_dir` itself if it's since been deleted.
+pub fn refresh_sync_dir(sync_dir: &SyncDirsModel, db: &DatabaseConnection) -> SyncDirsModel {
+    db::expect(|| SyncDirsEntity::find_by_id(sync_dir.id).one(db))
+        .unwrap_or_else(|| sync_dir.clone())
+}
+
+/// Read the exclusion rules for a sync directory, one per line, from wherever
+/// the user has chosen to store them.
+pub fn read_exclusions(sync_dir: &SyncDirsModel, db: &DatabaseConnection) -> String {
+    let sync_dir = refresh_sync_dir(sync_dir, db);
+    if sync_dir.exclusions_in_db {
+        return sync_dir.exclusions;
     }
 
-    // Create the database file if it doesn't exist.
-    let mut db_path = config_path;
-    db_path.push("celeste.db");
-    if !db_path.exists() {
-        if let Err(err) = fs::File::create(&db_path) {
-            gtk_util::show_error(
-                &tr::tr!("Unable to create Celeste's database file [{}].", err),
-                None,
+    let ignore_file_path = sync_dir.ignore_file_path();
+    if !ignore_file_path.exists() {
+        return String::new();
+    }
+    let _lock = match FileLock::lock(
+        &ignore_file_path,
+        true,
+        FileOptions::new().write(true).read(true),
+    ) {
+        Ok(lock) => lock,
+        Err(err) => {
+            hw_msg::warningln!(
+                "Unable to lock ignore file '{}': '{err}'.",
+                ignore_file_path.display()
             );
-            return;
+            return String::new();
         }
     };
+    fs::read_to_string(&ignore_file_path).unwrap_or_default()
+}
 
-    // Connect to the database.
-    let db = libceleste::await_future(Database::connect(format!("sqlite://{}", db_path.display())));
-    if let Err(err) = &db {
-        gtk_util::show_error(&tr::tr!("Unable to connect to database [{}].", err), None);
-        return;
-    };
-    let db = db.unwrap();
-
-    // Run migrations.
-    if let Err(err) = libceleste::await_future(Migrator::up(&db, None)) {
-        gtk_util::show_error(
-            &tr::tr!("Unable to run database migrations [{}]", err),
-            None,
-        );
-        return;
+/// Write out the exclusion rules for a sync directory to wherever the user has
+/// chosen to store them.
+fn write_exclusions(
+    sync_dir: &SyncDirsModel,
+    db: &DatabaseConnection,
+    rules: &str,
+) -> io::Result<()> {
+    let sync_dir = refresh_sync_dir(sync_dir, db);
+    if sync_dir.exclusions_in_db {
+        let mut active_model: SyncDirsActiveModel = sync_dir.into();
+        active_model.exclusions = ActiveValue::Set(rules.to_owned());
+        db::expect(|| active_model.clone().update(db));
+        return Ok(());
     }
 
-    // Set up our DBus connection.
-    let dbus = Connection::session().unwrap();
-    dbus.object_server()
-        .at(libceleste::DBUS_APP_OBJECT, ZbusApp)
-        .unwrap();
-    dbus.request_name(libceleste::DBUS_APP_ID).unwrap();
+    let mut lock = FileLock::lock(
+        sync_dir.ignore_file_path(),
+        true,
+        FileOptions::new().create(true).write(true).truncate(true),
+    )?;
+    lock.file.write_all(rules.as_bytes())
+}
 
-    // Get our remotes.
-    let mut remotes = libceleste::await_future(RemotesEntity::find().all(&db)).unwrap();
+// The formats Google Docs get exported in by default, which are the same ones
+// Rclone uses.
+static DEFAULT_GOOGLE_DOCS_FORMATS: &str = "docx,xlsx,pptx,svg";
+
+/// The ways Google Docs, Sheets, and Slides can be synced from a Google Drive
+/// remote, as labels and the formats they're exported in. These files can
+/// only be downloaded as exported copies, and an empty list of formats skips
+/// them instead.
+fn google_docs_choices() -> [(String, &'static str); 4] {
+    [
+        (tr::tr!("Don't sync"), ""),
+        (
+            tr::tr!("Export as Microsoft Office files"),
+            DEFAULT_GOOGLE_DOCS_FORMATS,
+        ),
+        (tr::tr!("Export as OpenDocument files"), "odt,ods,odp,svg"),
+        (tr::tr!("Export as PDFs"), "pdf"),
+    ]
+}
 
-    if remotes.is_empty() {
-        if login::login(app, &db).is_none() {
-            return;
+/// Validate the extra Rclone options for `remote` (its flags, its root folder,
+/// how it syncs Google Docs, and whether deletions skip its trash), and use
+/// them for every operation on it from now on.
+pub fn apply_remote_options(remote: &RemotesModel) -> Result<(), String> {
+    let rclone_remote = rclone::get_remote(&remote.name)
+        .ok_or_else(|| tr::tr!("The type of this remote isn't known."))?;
+    let mut options = vec![];
+
+    if let rclone::Remote::GDrive(_) = rclone_remote {
+        if remote.google_docs_formats.is_empty() {
+            options.push(("skip_gdocs".to_owned(), "true".to_owned()));
+        } else {
+            options.push((
+                "export_formats".to_owned(),
+                remote.google_docs_formats.clone(),
+            ));
         }
 
-        remotes = libceleste::await_future(RemotesEntity::find().all(&db)).unwrap();
+        // Deleted items go in the trash unless the user has asked otherwise.
+        options.push((
+            "use_trash".to_owned(),
+            (!remote.permanent_delete).to_string(),
+        ));
     }
 
-    // Create the main UI.
-    let window = ApplicationWindow::builder()
-        .application(app)
-        .title(&libceleste::get_title!("Servers"))
-        .build();
-    window.add_css_class("celeste-global-padding");
-    let stack_sidebar = StackSidebar::builder()
-        .width_request(150)
-        .height_request(500)
-        .vexpand_set(true)
-        .vexpand(true)
-        .build();
-    let stack = Stack::new();
-    stack_sidebar.set_stack(&stack);
+    options.extend(rclone::parse_flags(
+        rclone_remote.backend_type(),
+        &remote.rclone_flags,
+    )?);
+    rclone::set_remote_options(&remote.name, options);
+    rclone::set_remote_root(&remote.name, &remote.root_folder);
+    Ok(())
+}
 
-    let directory_map: DirectoryMap = Rc::new(RefCell::new(IndexMap::new()));
+/// The direction of a transfer between a local item and a remote one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TransferDirection {
+    Upload,
+    Download,
+}
 
-    // Store any remote deletions (values of the remote names) in a queue so they
-    // can be processed when syncing is at a good point of stopping.
-    let remote_deletion_queue: RemoteDeletionQueue = Rc::new(RefCell::new(vec![]));
+impl TransferDirection {
+    /// Get the name of the direction, as stored in the database.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Upload => "upload",
+            Self::Download => "download",
+        }
+    }
+}
 
-    // Store any sync deletions (the remote + local directory + remote directory) in
-    // a queue so they can be processed when syncing is at a good point of stopping.
-    let sync_dir_deletion_queue: SyncDirDeletionQueue = Rc::new(RefCell::new(vec![]));
+/// Run `transfer`, recording it in the database while it runs. If Celeste stops
+/// partway through the transfer, the record gets left behind so that
+/// [`recover_interrupted_transfers`] can deal with it.
+fn run_transfer<T, F: FnOnce() -> T>(
+    db: &DatabaseConnection,
+    sync_dir: &SyncDirsModel,
+    local_path: &str,
+    remote_path: &str,
+    direction: TransferDirection,
+    transfer: F,
+) -> T {
+    let pending_transfer = db::expect(|| {
+        PendingTransfersActiveModel {
+            sync_dir_id: ActiveValue::Set(sync_dir.id),
+            local_path: ActiveValue::Set(local_path.to_owned()),
+            remote_path: ActiveValue::Set(remote_path.to_owned()),
+            direction: ActiveValue::Set(direction.name().to_owned()),
+            ..Default::default()
+        }
+        .insert(db)
+    });
+    if SettingsModel::get(db).inhibit_suspend
+        && let Err(err) = platform::inhibit_suspend()
+    {
+        hw_msg::warningln!(
+            "Unable to keep the computer from suspending during transfers: '{err}'."
+        );
+    }
+    *(*TRANSFER_IN_PROGRESS).lock().unwrap() = true;
+    let result = transfer();
+    *(*TRANSFER_IN_PROGRESS).lock().unwrap() = false;
+    db::expect(|| pending_transfer.clone().delete(db));
+    result
+}
 
-    // Add servers.
-    let gen_remote_window = glib::clone!(@strong window, @strong remote_deletion_queue, @strong sync_dir_deletion_queue, @strong directory_map, @strong db => move |remote: RemotesModel| {
+/// Deal with any of `sync_dir`'s transfers that got interrupted, such as by a
+/// crash. The copy that was being written might be incomplete and the
+/// timestamps recorded for it can't be trusted, so the copy gets removed and
+/// its record forgotten, letting the item get transferred again.
+pub fn recover_interrupted_transfers<F: Fn(SyncError)>(
+    backend: &dyn SyncBackend,
+    sync_dir: &SyncDirsModel,
+    db: &DatabaseConnection,
+    add_error: &F,
+) {
+    let pending_transfers = db::expect(|| {
+        PendingTransfersEntity::find()
+            .filter(PendingTransfersColumn::SyncDirId.eq(sync_dir.id))
+            .all(db)
+    });
+
+    for pending_transfer in pending_transfers {
+        hw_msg::warningln!(
+            "The transfer of '{}' was interrupted, so it will be transferred again.",
+            pending_transfer.local_path
+        );
+
+        let direction = if pending_transfer.direction == TransferDirection::Download.name() {
+            TransferDirection::Download
+        } else {
+            TransferDirection::Upload
+        };
+        if let Err(err) = discard_transfer(
+            backend,
+            sync_dir,
+            db,
+            &pending_transfer.local_path,
+            &pending_transfer.remote_path,
+            direction,
+        ) {
+            add_error(err);
+            continue;
+        }
+
+        db::expect(|| pending_transfer.clone().delete(db));
+    }
+}
+
+/// Remove the copy written by a transfer and forget the item's sync record, so
+/// that the item gets transferred again on the next sync.
+fn discard_transfer(
+    backend: &dyn SyncBackend,
+    sync_dir: &SyncDirsModel,
+    db: &DatabaseConnection,
+    local_path: &str,
+    remote_path: &str,
+    direction: TransferDirection,
+) -> Result<(), SyncError> {
+    match direction {
+        TransferDirection::Download => {
+            if let Err(err) = fs::remove_file(local_path)
+                && err.kind() != io::ErrorKind::NotFound
+            {
+                return Err(SyncError::General(local_path.to_owned(), err.to_string()));
+            }
+        }
+        TransferDirection::Upload => {
+            let result = match backend.stat(remote_path) {
+                Ok(Some(_)) => backend.delete(remote_path),
+                Ok(None) => Ok(()),
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                return Err(SyncError::General(remote_path.to_owned(), err.to_string()));
+            }
+        }
+    }
+
+    db::expect(|| {
+        SyncItemsEntity::delete_many()
+            .filter(SyncItemsColumn::SyncDirId.eq(sync_dir.id))
+            .filter(SyncItemsColumn::LocalPath.eq(local_path))
+            .filter(SyncItemsColumn::RemotePath.eq(remote_path))
+            .exec(db)
+    });
+    Ok(())
+}
+
+/// The hash types to check transferred files with, in order of preference.
+/// These are all supported for local files, so whichever one the remote also
+/// has can be compared.
+pub static VERIFY_HASH_TYPES: &[&str] = &["md5", "sha1", "sha256", "crc32", "dropbox", "quickxor"];
+
+/// Compare the contents of the local file at `local_path` with `remote_item`,
+/// using the first hash in [`VERIFY_HASH_TYPES`] the remote has for it. Returns
+/// [`None`] if there's no hash to compare them by.
+fn hashes_match(
+    local_path: &str,
+    remote_item: &rclone::RcloneRemoteItem,
+) -> Result<Option<bool>, rclone::RcloneError> {
+    let Some((hash_type, remote_hash)) = VERIFY_HASH_TYPES.iter().find_map(|hash_type| {
+        remote_item
+            .hashes
+            .get(*hash_type)
+            .filter(|hash| !hash.is_empty())
+            .map(|hash| (*hash_type, hash))
+    }) else {
+        return Ok(None);
+    };
+
+    let local_item = rclone::sync::stat_with_hashes("/", local_path, &[hash_type])?;
+    let local_hash = local_item.and_then(|item| item.hashes.get(hash_type).cloned());
+    Ok(local_hash.map(|local_hash| local_hash.eq_ignore_ascii_case(remote_hash)))
+}
+
+/// Check that a file was transferred intact, by comparing its size on both
+/// sides (and its hash, if the remote has one). If it wasn't, the corrupt copy
+/// is discarded so that it gets transferred again.
+fn verify_transfer(
+    backend: &dyn SyncBackend,
+    sync_dir: &SyncDirsModel,
+    db: &DatabaseConnection,
+    local_path: &str,
+    remote_path: &str,
+    direction: TransferDirection,
+) -> Result<(), SyncError> {
+    let local_size = fs::metadata(local_path)
+        .map_err(|err| SyncError::General(local_path.to_owned(), err.to_string()))?
+        .len();
+    let remote_item = match backend.stat_with_hashes(remote_path) {
+        Ok(Some(item)) => item,
+        Ok(None) => {
+            return Err(SyncError::General(
+                remote_path.to_owned(),
+                tr::tr!("The file is missing from the remote after transferring it."),
+            ))
+        }
+        Err(err) => return Err(SyncError::General(remote_path.to_owned(), err.to_string())),
+    };
+
+    // Some remotes don't know the size of some of their files, which they report
+    // as a negative size.
+    let mut matches = remote_item.size < 0 || remote_item.size as u64 == local_size;
+
+    if matches {
+        matches = hashes_match(local_path, &remote_item)
+            .map_err(|err| SyncError::General(local_path.to_owned(), err.to_string()))?
+            .unwrap_or(true);
+    }
+
+    if matches {
+        return Ok(());
+    }
+
+    hw_msg::warningln!("'{local_path}' and '{remote_path}' didn't match after transferring, so discarding the copy.");
+    discard_transfer(backend, sync_dir, db, local_path, remote_path, direction)?;
+    Err(SyncError::ChecksumMismatch(
+        local_path.to_owned(),
+        remote_path.to_owned(),
+    ))
+}
+
+/// Checks that there's enough free space for each transfer before starting it,
+/// so that running out of space pauses syncing a directory with one clear
+/// error, instead of failing every transfer after it.
+#[derive(Default)]
+pub struct SpaceCheck {
+    /// The free space on the remote, if it reports such. Checking this is an
+    /// API call of its own, so it's only fetched for the first upload of a sync
+    /// pass.
+    remote_free: OnceCell<Option<u64>>,
+    /// The number of bytes uploaded since `remote_free` was fetched.
+    uploaded: Cell<u64>,
+    /// The message for the transfer that didn't fit, if one didn't.
+    shortage: RefCell<Option<String>>,
+    /// Whether the remote's storage quota is used up. Uploads are skipped
+    /// while it is, without an error for each of them.
+    remote_full: Cell<bool>,
+}
+
+impl SpaceCheck {
+    /// Check the space for a pass on a remote, whose storage quota is already
+    /// known to be used up if `remote_full` is set.
+    pub fn for_remote(remote_full: bool) -> Self {
+        Self {
+            remote_full: Cell::new(remote_full),
+            ..Default::default()
+        }
+    }
+
+    /// Whether the remote's storage quota is used up, in which case uploads
+    /// should be skipped.
+    pub fn remote_full(&self) -> bool {
+        self.remote_full.get()
+    }
+
+    /// Record the error an upload failed with, noting if it was from the
+    /// remote's storage quota being used up.
+    fn record_upload_error(&self, err: &rclone::RcloneError) {
+        if let rclone::RcloneError::QuotaExceeded(_) = err {
+            self.remote_full.set(true);
+        }
+    }
+
+    /// The message for the transfer that didn't fit, if one didn't. Syncing
+    /// should stop for the rest of the pass once this is set.
+    fn shortage(&self) -> Option<String> {
+        self.shortage.borrow().clone()
+    }
+
+    /// Record that a transfer to `path` didn't fit, returning the error for
+    /// it.
+    fn set_shortage(&self, path: &str, needed: u64, available: u64) -> SyncError {
+        let msg = tr::tr!(
+            "Not enough space (need {}, have {}).",
+            glib::format_size(needed),
+            glib::format_size(available)
+        );
+        *self.shortage.borrow_mut() = Some(msg.clone());
+        SyncError::General(path.to_owned(), msg)
+    }
+
+    /// Check that a remote file of `size` bytes can be downloaded to
+    /// `local_path`, replacing anything already there.
+    fn check_download(&self, local_path: &str, size: i64) -> Result<(), SyncError> {
+        let replaced_size = fs::metadata(local_path)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+        let needed = u64::try_from(size)
+            .unwrap_or(0)
+            .saturating_sub(replaced_size);
+        let parent = Path::new(local_path).parent().unwrap();
+        let available = platform::available_space(parent)
+            .map_err(|err| SyncError::General(local_path.to_owned(), err.to_string()))?;
+
+        if let Some(available) = available
+            && needed > available
+        {
+            return Err(self.set_shortage(local_path, needed, available));
+        }
+        Ok(())
+    }
+
+    /// Check that `local_path` can be uploaded to `remote_path`, replacing a
+    /// file of `replaced_size` bytes if one is already there.
+    fn check_upload(
+        &self,
+        backend: &dyn SyncBackend,
+        local_path: &str,
+        remote_path: &str,
+        replaced_size: Option<i64>,
+    ) -> Result<(), SyncError> {
+        let remote_free = self.remote_free.get_or_init(|| {
+            backend.free_space().unwrap_or_else(|err| {
+                hw_msg::warningln!("Unable to get the free space on the remote: '{err}'.");
+                None
+            })
+        });
+        let available = match remote_free {
+            Some(remote_free) => remote_free.saturating_sub(self.uploaded.get()),
+            None => return Ok(()),
+        };
+        let size = fs::metadata(local_path)
+            .map_err(|err| SyncError::General(local_path.to_owned(), err.to_string()))?
+            .len();
+        let replaced_size = replaced_size
+            .and_then(|size| u64::try_from(size).ok())
+            .unwrap_or(0);
+        let needed = size.saturating_sub(replaced_size);
+
+        if needed > available {
+            return Err(self.set_shortage(remote_path, needed, available));
+        }
+        self.uploaded.set(self.uploaded.get() + needed);
+        Ok(())
+    }
+}
+
+/// The items synced so far while syncing a sync directory, so that nothing
+/// gets synced twice between `sync_local_directory` and
+/// `sync_remote_directory`. This also prevents errors from showing up twice
+/// when they occur.
+///
+/// Items are only kept by their local path, as the remote path follows from it
+/// within a sync directory.
+#[derive(Default)]
+struct SyncedItems(RefCell<HashSet<Box<str>>>);
+
+impl SyncedItems {
+    /// Record that the item at `local_path` has been synced.
+    fn insert(&self, local_path: &str) {
+        self.0.borrow_mut().insert(local_path.into());
+    }
+
+    /// Whether the item at `local_path` has been synced.
+    fn contains(&self, local_path: &str) -> bool {
+        self.0.borrow().contains(local_path)
+    }
+}
+
+/// Remembers the files uploaded during a sync pass, so that another local
+/// copy of one (such as a hard link, or a duplicate photo) can be copied on the
+/// remote instead of having its contents uploaded again.
+#[derive(Default)]
+pub struct UploadedFiles {
+    /// The remote path of each uploaded file, keyed by its device and inode
+    /// numbers.
+    by_inode: RefCell<HashMap<(u64, u64), String>>,
+    /// The local and remote paths of each uploaded file, keyed by its size.
+    by_size: RefCell<HashMap<u64, Vec<(String, String)>>>,
+    /// The MD5 hashes of the local files that have been hashed so far. Files
+    /// are only hashed once another file of the same size has been uploaded.
+    hashes: RefCell<HashMap<String, Option<String>>>,
+}
+
+impl UploadedFiles {
+    /// Record that `local_path` was uploaded to `remote_path`.
+    fn record(&self, local_path: &str, remote_path: &str) {
+        let Ok(metadata) = fs::metadata(local_path) else {
+            return;
+        };
+
+        if let Some(file_id) = platform::unique_file_id(&metadata) {
+            self.by_inode
+                .borrow_mut()
+                .insert(file_id, remote_path.to_owned());
+        }
+        self.by_size
+            .borrow_mut()
+            .entry(metadata.len())
+            .or_default()
+            .push((local_path.to_owned(), remote_path.to_owned()));
+    }
+
+    /// Get the MD5 hash of a local file.
+    fn hash(&self, local_path: &str) -> Option<String> {
+        self.hashes
+            .borrow_mut()
+            .entry(local_path.to_owned())
+            .or_insert_with(|| {
+                rclone::sync::stat_with_hashes("/", local_path, &["md5"])
+                    .ok()
+                    .flatten()
+                    .and_then(|item| item.hashes.get("md5").cloned())
+                    .filter(|hash| !hash.is_empty())
+            })
+            .clone()
+    }
+
+    /// Find the remote path of an uploaded file with the same contents as
+    /// `local_path`, if there is one.
+    fn find_duplicate(&self, local_path: &str) -> Option<String> {
+        let metadata = fs::metadata(local_path).ok()?;
+
+        if let Some(remote_path) = platform::unique_file_id(&metadata)
+            .and_then(|file_id| self.by_inode.borrow().get(&file_id).cloned())
+        {
+            return Some(remote_path);
+        }
+
+        // Empty files have nothing to gain from being copied.
+        if metadata.len() == 0 {
+            return None;
+        }
+
+        let candidates = self.by_size.borrow().get(&metadata.len())?.clone();
+        let hash = self.hash(local_path)?;
+        candidates
+            .into_iter()
+            .find(|(candidate_path, _)| self.hash(candidate_path).as_ref() == Some(&hash))
+            .map(|(_, remote_path)| remote_path)
+    }
+}
+
+/// Upload `local_path` to `remote_path`. If a file with the same contents was
+/// already uploaded during this sync pass, it gets copied server-side instead,
+/// if the remote supports such.
+fn upload_file(
+    backend: &dyn SyncBackend,
+    uploaded: &UploadedFiles,
+    local_path: &str,
+    remote_path: &str,
+) -> Result<(), rclone::RcloneError> {
+    if backend.supports_server_side_copy()
+        && let Some(duplicate) = uploaded.find_duplicate(local_path)
+    {
+        match backend.copy_file(&duplicate, remote_path) {
+            Ok(()) => return Ok(()),
+            Err(err) => hw_msg::warningln!(
+                "Unable to copy '{duplicate}' to '{remote_path}' on the remote, so uploading it instead: '{err}'."
+            ),
+        }
+    }
+
+    backend.copy_to_remote(local_path, remote_path)
+}
+
+/// Get a command that runs `command` through the shell in `sync_dir`, with the
+/// directory's local and remote paths in its environment. The password of
+/// Rclone's config file is left out of it, in case the user started Celeste
+/// with it set.
+fn sync_dir_command(sync_dir: &SyncDirsModel, remote_name: &str, command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell
+        .arg("-c")
+        .arg(command)
+        .current_dir(&sync_dir.local_path)
+        .env("CELESTE_LOCAL_PATH", &sync_dir.local_path)
+        .env(
+            "CELESTE_REMOTE",
+            format!("{remote_name}:{}", sync_dir.remote_path),
+        )
+        .env_remove(rclone::CONFIG_PASSWORD_VAR);
+    platform::separate_process_group(&mut shell);
+    shell
+}
+
+/// Why a sync directory's pre-sync command skipped a sync pass.
+pub struct PreSyncSkip {
+    /// The message to show for the directory, with the last line the command
+    /// printed.
+    pub msg: String,
+    /// Everything the command printed to its standard output and error, which
+    /// is empty if it couldn't be run.
+    pub output: String,
+}
+
+/// Run `command` like [`Command::output`], but kill it along with everything it
+/// started if it's still running after `timeout`. Returns its output, along
+/// with whether it had to be killed.
+fn output_with_timeout(command: &mut Command, timeout: Duration) -> io::Result<(Output, bool)> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // The output has to be read while waiting, as the command would block once a
+    // pipe's buffer fills up.
+    fn read_in_background(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<Vec<u8>> {
+        thread::spawn(move || {
+            let mut output = vec![];
+            let _ = pipe.read_to_end(&mut output);
+            output
+        })
+    }
+    let stdout = read_in_background(child.stdout.take().unwrap());
+    let stderr = read_in_background(child.stderr.take().unwrap());
+
+    let started = Instant::now();
+    let mut timed_out = false;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() >= timeout {
+            timed_out = true;
+            platform::kill_process_group(&mut child)?;
+            break child.wait()?;
+        }
+        thread::sleep(COMMAND_POLL_INTERVAL);
+    };
+
+    let output = Output {
+        status,
+        stdout: stdout.join().unwrap(),
+        stderr: stderr.join().unwrap(),
+    };
+    Ok((output, timed_out))
+}
+
+/// Run `sync_dir`'s pre-sync command if it has one, waiting for it in the
+/// background. The sync pass gets skipped if the command fails or doesn't
+/// finish within [`PRE_SYNC_TIMEOUT`], in which case the returned
+/// [`PreSyncSkip`] says why.
+pub fn run_pre_sync_command(
+    sync_dir: &SyncDirsModel,
+    remote_name: &str,
+) -> Result<(), PreSyncSkip> {
+    let pre_sync_command = sync_dir.pre_sync_command.trim();
+    if pre_sync_command.is_empty() {
+        return Ok(());
+    }
+
+    let mut command = sync_dir_command(sync_dir, remote_name, pre_sync_command);
+    let (output, timed_out) =
+        libceleste::run_in_background(move || output_with_timeout(&mut command, PRE_SYNC_TIMEOUT))
+            .map_err(|err| PreSyncSkip {
+                msg: tr::tr!(
+                    "Skipped syncing, as the pre-sync command couldn't be run [{}].",
+                    err
+                ),
+                output: String::new(),
+            })?;
+    if output.status.success() && !timed_out {
+        return Ok(());
+    }
+
+    let printed = String::from_utf8_lossy(&output.stdout).into_owned()
+        + &String::from_utf8_lossy(&output.stderr);
+    let last_line = printed
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .last();
+    let msg = match last_line {
+        _ if timed_out => tr::tr!(
+            "Skipped syncing, as the pre-sync command didn't finish within {} minutes.",
+            PRE_SYNC_TIMEOUT.as_secs() / 60
+        ),
+        Some(line) => tr::tr!("Skipped syncing: {}", line),
+        None => tr::tr!(
+            "Skipped syncing, as the pre-sync command exited with {}.",
+            output.status
+        ),
+    };
+    Err(PreSyncSkip {
+        msg,
+        output: printed.trim_end().to_owned(),
+    })
+}
+
+/// Log why `sync_dir`'s pre-sync command skipped a pass, along with everything
+/// it printed. The command gets run every pass, so this is only done when what
+/// it printed changes from the last pass, which is kept in `last_outputs`.
+pub fn log_pre_sync_result(
+    sync_dir: &SyncDirsModel,
+    result: &Result<(), PreSyncSkip>,
+    last_outputs: &mut HashMap<i32, String>,
+) {
+    let Err(skip) = result else {
+        last_outputs.remove(&sync_dir.id);
+        return;
+    };
+    if last_outputs.get(&sync_dir.id) == Some(&skip.output) {
+        return;
+    }
+
+    if skip.output.is_empty() {
+        hw_msg::warningln!("{} ('{}')", skip.msg, sync_dir.local_path);
+    } else {
+        hw_msg::warningln!(
+            "The pre-sync command for '{}' skipped syncing, after printing:\n{}",
+            sync_dir.local_path,
+            skip.output
+        );
+    }
+    last_outputs.insert(sync_dir.id, skip.output.clone());
+}
+
+/// Counts the transfers and errors of a sync pass, which get saved for the
+/// statistics window once the pass finishes.
+#[derive(Default)]
+pub struct PassStats {
+    files_uploaded: Cell<i32>,
+    bytes_uploaded: Cell<i64>,
+    files_downloaded: Cell<i32>,
+    bytes_downloaded: Cell<i64>,
+    errors: Cell<i32>,
+    /// The number of errors that weren't reported because they've been snoozed.
+    snoozed_errors: Cell<i32>,
+    /// The number of files left for the next pass because they were still
+    /// changing.
+    files_settling: Cell<i32>,
+    /// The most files the pass can transfer, if there's a limit.
+    transfer_limit: Option<i32>,
+}
+
+impl PassStats {
+    /// Count the transfers of a pass that can transfer up to `transfer_limit`
+    /// files, where zero means there's no limit.
+    pub fn with_transfer_limit(transfer_limit: i32) -> Self {
+        Self {
+            transfer_limit: (transfer_limit > 0).then_some(transfer_limit),
+            ..Default::default()
+        }
+    }
+
+    /// Whether the pass has transferred as many files as it can. Syncing should
+    /// stop for the rest of the pass once this is the case, leaving the other
+    /// changes for the next one.
+    fn limit_reached(&self) -> bool {
+        self.transfer_limit
+            .map_or(false, |limit| self.files_transferred() >= limit)
+    }
+
+    /// The number of files transferred so far, in either direction.
+    pub fn files_transferred(&self) -> i32 {
+        self.files_uploaded.get() + self.files_downloaded.get()
+    }
+
+    /// Check whether the local file at `local_path` has stopped changing, so
+    /// that it can be uploaded. Files that haven't are counted, to be uploaded
+    /// on a later pass.
+    fn check_settled(&self, local_path: &Path) -> bool {
+        let changing = fs::metadata(local_path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .map_or(false, |elapsed| elapsed < UPLOAD_QUIET_PERIOD);
+
+        if changing {
+            self.files_settling.set(self.files_settling.get() + 1);
+        }
+        !changing
+    }
+
+    /// Record that an error was reported.
+    pub fn record_error(&self) {
+        self.errors.set(self.errors.get() + 1);
+    }
+
+    /// Record that an error was run into, but not reported as it's been
+    /// snoozed.
+    pub fn record_snoozed_error(&self) {
+        self.snoozed_errors.set(self.snoozed_errors.get() + 1);
+    }
+
+    /// Whether the pass ran into any errors, including snoozed ones.
+    fn failed(&self) -> bool {
+        self.errors.get() + self.snoozed_errors.get() > 0
+    }
+
+    /// Record that `local_path` was transferred in `direction`.
+    fn record(&self, direction: TransferDirection, local_path: &str) {
+        let size = fs::metadata(local_path).map_or(0, |metadata| metadata.len());
+        let size = i64::try_from(size).unwrap_or(i64::MAX);
+        let (files, bytes) = match direction {
+            TransferDirection::Upload => (&self.files_uploaded, &self.bytes_uploaded),
+            TransferDirection::Download => (&self.files_downloaded, &self.bytes_downloaded),
+        };
+        files.set(files.get() + 1);
+        bytes.set(bytes.get().saturating_add(size));
+    }
+
+    /// Get the number of errors the last saved pass over `sync_dir` ran into,
+    /// if one has been saved.
+    fn last_saved_errors(db: &DatabaseConnection, sync_dir: &SyncDirsModel) -> Option<i32> {
+        let last_pass = db::expect(|| {
+            SyncPassesEntity::find()
+                .filter(SyncPassesColumn::SyncDirId.eq(sync_dir.id))
+                .order_by_desc(SyncPassesColumn::Id)
+                .one(db)
+        });
+        last_pass.map(|pass| pass.errors)
+    }
+
+    /// Save the statistics of a finished sync pass over `sync_dir`, and add
+    /// them to the metrics.
+    fn save(
+        &self,
+        db: &DatabaseConnection,
+        sync_dir: &SyncDirsModel,
+        started: i64,
+        duration: Duration,
+    ) {
+        let result = db::run(|| {
+            SyncPassesActiveModel {
+                remote_id: ActiveValue::Set(sync_dir.remote_id),
+                sync_dir_id: ActiveValue::Set(sync_dir.id),
+                started: ActiveValue::Set(started),
+                duration_ms: ActiveValue::Set(duration.as_millis().try_into().unwrap_or(i64::MAX)),
+                files_uploaded: ActiveValue::Set(self.files_uploaded.get()),
+                bytes_uploaded: ActiveValue::Set(self.bytes_uploaded.get()),
+                files_downloaded: ActiveValue::Set(self.files_downloaded.get()),
+                bytes_downloaded: ActiveValue::Set(self.bytes_downloaded.get()),
+                errors: ActiveValue::Set(self.errors.get()),
+                ..Default::default()
+            }
+            .insert(db)
+        });
+
+        if let Err(err) = result {
+            hw_msg::warningln!("Unable to save the statistics for this sync pass: '{err}'.");
+        }
+
+        metrics::record_pass(
+            &sync_dir.local_path,
+            duration,
+            self.files_uploaded.get(),
+            self.bytes_uploaded.get(),
+            self.files_downloaded.get(),
+            self.bytes_downloaded.get(),
+            self.errors.get(),
+        );
+    }
+
+    /// Run `sync_dir`'s post-sync command if it has one, and if the pass
+    /// transferred any files or ran into errors. The command is run through the
+    /// shell in the background, with the pass's results in its environment.
+    fn run_post_sync_command(&self, sync_dir: &SyncDirsModel, remote_name: &str) {
+        let post_sync_command = sync_dir.post_sync_command.trim();
+        let files_transferred = self.files_uploaded.get() + self.files_downloaded.get();
+        if post_sync_command.is_empty() || (files_transferred == 0 && self.errors.get() == 0) {
+            return;
+        }
+
+        let status = if self.errors.get() == 0 {
+            "success"
+        } else {
+            "error"
+        };
+        let mut command = sync_dir_command(sync_dir, remote_name, post_sync_command);
+        for (name, value) in [
+            (
+                "CELESTE_FILES_UPLOADED",
+                self.files_uploaded.get().to_string(),
+            ),
+            (
+                "CELESTE_BYTES_UPLOADED",
+                self.bytes_uploaded.get().to_string(),
+            ),
+            (
+                "CELESTE_FILES_DOWNLOADED",
+                self.files_downloaded.get().to_string(),
+            ),
+            (
+                "CELESTE_BYTES_DOWNLOADED",
+                self.bytes_downloaded.get().to_string(),
+            ),
+            ("CELESTE_ERRORS", self.errors.get().to_string()),
+            ("CELESTE_STATUS", status.to_owned()),
+        ] {
+            command.env(name, value);
+        }
+        let result = command.spawn();
+
+        let local_path = sync_dir.local_path.clone();
+        match result {
+            Ok(mut child) => {
+                // Wait on the command from another thread, so that syncing can carry on
+                // while it runs.
+                thread::spawn(move || match child.wait() {
+                    Ok(status) if !status.success() => hw_msg::warningln!(
+                        "The post-sync command for '{local_path}' failed with {status}."
+                    ),
+                    Err(err) => hw_msg::warningln!(
+                        "Unable to wait for the post-sync command for '{local_path}': '{err}'."
+                    ),
+                    _ => (),
+                });
+            }
+            Err(err) => hw_msg::warningln!(
+                "Unable to run the post-sync command for '{local_path}': '{err}'."
+            ),
+        }
+    }
+}
+
+/// Convert a local item's modification time into a UNIX timestamp, as stored
+/// in the database. Like remote timestamps, this rounds down to the second, so
+/// times from before 1970 give negative timestamps.
+pub fn system_time_to_timestamp(time: SystemTime) -> i64 {
+    match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs().try_into().unwrap(),
+        Err(err) => {
+            let duration = err.duration();
+            let secs: i64 = duration.as_secs().try_into().unwrap();
+            -secs - i64::from(duration.subsec_nanos() > 0)
+        }
+    }
+}
+
+/// Whether the remote paths `first` and `second` are the same, or one is inside
+/// of the other. An empty path is the root of the remote, which contains every
+/// other path.
+fn remote_paths_overlap(first: &str, second: &str) -> bool {
+    let contains = |parent: &str, child: &str| {
+        parent.is_empty() || child == parent || child.starts_with(&format!("{parent}/"))
+    };
+    contains(first, second) || contains(second, first)
+}
+
+/// Whether the local directory `local_path` contains Celeste's config
+/// directory (and so its database and Rclone's config), or is inside of it.
+/// Syncing those would corrupt Celeste's state on any other machine syncing the
+/// same remote folder.
+pub fn local_path_has_config(local_path: &Path) -> bool {
+    let canonicalize = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_owned());
+    let local_path = canonicalize(local_path);
+    let config_dir = canonicalize(&libceleste::get_config_dir());
+    config_dir.starts_with(&local_path) || local_path.starts_with(&config_dir)
+}
+
+/// Get the inode and size of a local item, which are stored in the database so
+/// that renamed items can be detected.
+fn get_local_file_id(path: &Path) -> (Option<i64>, Option<i64>) {
+    match path.metadata() {
+        Ok(metadata) => (
+            platform::file_id(&metadata).and_then(|file_id| file_id.try_into().ok()),
+            metadata.len().try_into().ok(),
+        ),
+        Err(_) => (None, None),
+    }
+}
+
+/// Whether `remote_item` at `remote_path` is the file recorded in `db_model`,
+/// after it got moved there on the remote. The local copy of the file gets
+/// moved to match, so it has to be unchanged since it was last synced.
+fn is_moved_remote_file(
+    backend: &dyn SyncBackend,
+    db_model: &SyncItemsModel,
+    remote_path: &str,
+    remote_item: &rclone::RcloneRemoteItem,
+) -> bool {
+    // Empty files all look the same, so there's no telling which one was moved.
+    if remote_item.is_dir
+        || remote_item.size <= 0
+        || db_model.local_size != Some(remote_item.size)
+        || db_model.last_remote_timestamp != remote_item.mod_time.unix_timestamp()
+    {
+        return false;
+    }
+
+    let local_unchanged = fs::metadata(&db_model.local_path).map_or(false, |metadata| {
+        metadata.len() as i64 == remote_item.size
+            && metadata.modified().map_or(false, |modified| {
+                system_time_to_timestamp(modified) == db_model.last_local_timestamp
+            })
+    });
+    if !local_unchanged {
+        return false;
+    }
+
+    // Files copied in a batch can share their sizes and timestamps, so compare
+    // their contents too if the remote has hashes for them.
+    match backend.stat_with_hashes(remote_path) {
+        Ok(Some(remote_item)) => hashes_match(&db_model.local_path, &remote_item)
+            .map_or(false, |matches| matches.unwrap_or(true)),
+        Ok(None) | Err(_) => false,
+    }
+}
+
+/// Record the local files of `sync_dir` that match the ones in `manifest` as
+/// already being synced. This is for directories without any sync records
+/// (such as ones just set up on a new device), so that only the files that
+/// actually differ get transferred, instead of every file whose local and
+/// remote timestamps don't line up.
+pub fn seed_sync_records(
+    manifest: &Manifest,
+    sync_dir: &SyncDirsModel,
+    db: &DatabaseConnection,
+    timestamps: TimestampComparison,
+) {
+    for (relative_path, entry) in &manifest.files {
+        let local_path = Path::new(&sync_dir.local_path).join(relative_path);
+        let Some(local_path_string) = local_path.to_str() else {
+            continue;
+        };
+        let Ok(metadata) = local_path.metadata() else {
+            continue;
+        };
+        if !metadata.is_file() || (entry.size >= 0 && metadata.len() != entry.size as u64) {
+            continue;
+        }
+
+        // Files with the same timestamps are taken to be the same, like they are
+        // while syncing. Otherwise the hashes have to match.
+        let local_timestamp = system_time_to_timestamp(metadata.modified().unwrap());
+        let same_hash = |(hash_type, remote_hash): &(String, String)| {
+            rclone::sync::stat_with_hashes("/", local_path_string, &[hash_type.as_str()])
+                .ok()
+                .flatten()
+                .and_then(|item| item.hashes.get(hash_type).cloned())
+                .map_or(false, |local_hash| {
+                    local_hash.eq_ignore_ascii_case(remote_hash)
+                })
+        };
+        let same_file = timestamps.matches(local_timestamp, entry.mod_time)
+            || entry.hash.as_ref().map_or(false, same_hash);
+        if !same_file {
+            continue;
+        }
+
+        let (local_inode, local_size) = get_local_file_id(&local_path);
+        db::expect(|| {
+            SyncItemsActiveModel {
+                sync_dir_id: ActiveValue::Set(sync_dir.id),
+                local_path: ActiveValue::Set(local_path_string.to_owned()),
+                remote_path: ActiveValue::Set(paths::join_remote(
+                    &sync_dir.remote_path,
+                    relative_path,
+                )),
+                last_local_timestamp: ActiveValue::Set(local_timestamp),
+                last_remote_timestamp: ActiveValue::Set(entry.mod_time),
+                local_inode: ActiveValue::Set(local_inode),
+                local_size: ActiveValue::Set(local_size),
+                ..Default::default()
+            }
+            .insert(db)
+        });
+    }
+}
+
+/// Remove any empty directories inside of `dir`, both locally and on the
+/// remote. `dir` itself is never removed.
+fn remove_empty_dirs<F: Fn(SyncError)>(
+    dir: &Path,
+    remote: &RemotesModel,
+    sync_dir: &SyncDirsModel,
+    db: &DatabaseConnection,
+    audit: &AuditLog,
+    add_error: &F,
+) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            add_error(SyncError::General(
+                dir.to_string_lossy().into_owned(),
+                err.to_string(),
+            ));
+            return;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+
+        remove_empty_dirs(&path, remote, sync_dir, db, audit, add_error);
+
+        let is_empty = fs::read_dir(&path)
+            .map(|mut entries| entries.next().is_none())
+            .unwrap_or(false);
+        if !is_empty {
+            continue;
+        }
+
+        // Only remove the directory from the remote if it got synced there, which it
+        // can't have been if its path isn't valid UTF-8.
+        let db_item = match path.to_str() {
+            Some(path_string) => db::expect(|| {
+                SyncItemsEntity::find()
+                    .filter(SyncItemsColumn::SyncDirId.eq(sync_dir.id))
+                    .filter(SyncItemsColumn::LocalPath.eq(path_string))
+                    .one(db)
+            }),
+            None => None,
+        };
+
+        if let Some(db_item) = db_item {
+            if let Err(err) = rclone::sync::rmdir(&remote.name, &db_item.remote_path) {
+                add_error(SyncError::General(
+                    db_item.remote_path.clone(),
+                    err.to_string(),
+                ));
+                continue;
+            }
+
+            audit.record(
+                db,
+                sync_dir,
+                AuditAction::DeleteRemote,
+                AuditReason::EmptyDirectory,
+                &db_item.local_path,
+                &db_item.remote_path,
+            );
+            db::expect(|| db_item.clone().delete(db));
+        }
+
+        match fs::remove_dir(&path) {
+            Ok(()) => audit.record(
+                db,
+                sync_dir,
+                AuditAction::DeleteLocal,
+                AuditReason::EmptyDirectory,
+                &path.to_string_lossy(),
+                &paths::local_to_remote(sync_dir, &path).unwrap_or_default(),
+            ),
+            Err(err) => add_error(SyncError::General(
+                path.to_string_lossy().into_owned(),
+                err.to_string(),
+            )),
+        }
+    }
+}
+
+/// Run a closure, catching any panics that occur in it. On a panic, the panic's
+/// message is returned.
+pub fn catch_panic<F: FnOnce()>(f: F) -> Result<(), String> {
+    panic::catch_unwind(AssertUnwindSafe(|| db::catch_failures(f))).map_err(|payload| {
+        if let Some(msg) = payload.downcast_ref::<&str>() {
+            msg.to_string()
+        } else if let Some(msg) = payload.downcast_ref::<String>() {
+            msg.clone()
+        } else {
+            tr::tr!("Unknown error")
+        }
+    })
+}
+
+/// Ask the user what to do now that the main window has been closed, optionally
+/// remembering their choice for next time. `background_action` is run if they
+/// choose to keep Celeste running.
+fn ask_close_action(
+    window: &ApplicationWindow,
+    db: &DatabaseConnection,
+    background_action: CloseAction,
+) {
+    let dialog = MessageDialog::builder()
+        .transient_for(window)
+        .modal(true)
+        .text(&tr::tr!("Keep Celeste running in the background?"))
+        .secondary_text(&tr::tr!(
+            "Celeste can continue syncing your files after its window is closed."
+        ))
+        .build();
+    dialog.add_button(&tr::tr!("Quit"), ResponseType::No);
+    dialog.add_button(&tr::tr!("Keep Running"), ResponseType::Yes);
+    let remember_button = CheckButton::builder()
+        .label(&tr::tr!("Remember my choice"))
+        .build();
+    dialog
+        .message_area()
+        .downcast::<Box>()
+        .unwrap()
+        .append(&remember_button);
+
+    dialog.connect_response(
+        glib::clone!(@weak window, @strong db, @weak remember_button => move |dialog, resp| {
+            let close_action = match resp {
+                ResponseType::Yes => background_action,
+                ResponseType::No => CloseAction::Quit,
+                _ => {
+                    dialog.close();
+                    return;
+                }
+            };
+
+            if remember_button.is_active() {
+                SettingsModel::update(&db, |settings| {
+                    settings.close_action =
+                        ActiveValue::Set(Some(close_action.setting_name().to_owned()));
+                });
+            }
+
+            dialog.close();
+            close_action.run(&window);
+        }),
+    );
+    dialog.show();
+}
+
+/// Copy a share link for an item on a remote to the clipboard.
+fn copy_share_link<W: IsA<Widget>>(widget: &W, remote_name: &str, remote_path: &str) {
+    match rclone::sync::public_link(remote_name, remote_path) {
+        Ok(link) => widget.clipboard().set_text(&link),
+        Err(err) => gtk_util::show_error(
+            &tr::tr!("Unable to get a share link for '/{}'.", remote_path),
+            Some(err.message()),
+        ),
+    }
+}
+
+/// Whether the remote named `remote_name` can make share links. What Rclone
+/// reports for the remote is used if it's known, and what remotes of its type
+/// support otherwise.
+fn supports_links(db: &DatabaseConnection, remote_name: &str) -> bool {
+    match RemoteCapabilities::get_by_name(db, remote_name) {
+        Some(capabilities) => capabilities.public_link,
+        None => rclone::get_remote(remote_name)
+            .map(|remote| remote.supports_links())
+            .unwrap_or(false),
+    }
+}
+
+/// Get the position to save for the item at `index` of `len` reordered items.
+/// See [`RemotesModel::position`] for why these are negative.
+fn reordered_position(index: usize, len: usize) -> i32 {
+    index as i32 - len as i32
+}
+
+/// Get the name of the remote whose row in `stack_sidebar` is at `x`, `y`. The
+/// sidebar's rows are in the same order as its stack's pages, so they're
+/// matched up by their index.
+fn sidebar_remote_at(stack_sidebar: &StackSidebar, x: f64, y: f64) -> Option<String> {
+    let row = stack_sidebar
+        .pick(x, y, PickFlags::DEFAULT)?
+        .ancestor(ListBoxRow::static_type())?
+        .downcast::<ListBoxRow>()
+        .ok()?;
+    let page = stack_sidebar
+        .stack()?
+        .pages()
+        .item(row.index().try_into().ok()?)?
+        .downcast::<StackPage>()
+        .ok()?;
+    page.name().map(|name| name.to_string())
+}
+
+/// Put the remotes' pages in `stack` in the order of `names`, and save it as
+/// the order to show them in.
+fn reorder_remotes(stack: &Stack, names: &[String], db: &DatabaseConnection) {
+    // Stacks can only add pages at the end, so every page gets moved there in
+    // turn.
+    let visible_name = stack.visible_child_name();
+    for name in names {
+        let Some(child) = stack.child_by_name(name) else {
+            continue;
+        };
+        let title = stack.page(&child).title().unwrap_or_default();
+        stack.remove(&child);
+        stack.add_titled(&child, Some(name), &title);
+    }
+    if let Some(visible_name) = visible_name {
+        stack.set_visible_child_name(&visible_name);
+    }
+
+    db::expect(|| async {
+        for (index, name) in names.iter().enumerate() {
+            let Some(remote) = RemotesEntity::find()
+                .filter(RemotesColumn::Name.eq(name.clone()))
+                .one(db)
+                .await?
+            else {
+                continue;
+            };
+            let mut active_model: RemotesActiveModel = remote.into();
+            active_model.position = ActiveValue::Set(reordered_position(index, names.len()));
+            active_model.update(db).await?;
+        }
+
+        Ok::<_, DbErr>(())
+    });
+}
+
+/// The keyring entry the password of an encrypted Rclone config is saved in.
+static RCLONE_CONFIG_KEYRING_USER: &str = "rclone-config";
+
+/// Ask the user for the password of Rclone's config, returning [`None`] if
+/// they cancel. `error` is why the last password given didn't work.
+fn ask_rclone_config_password(error: Option<&str>) -> Option<String> {
+    let (sender, mut receiver) = mpsc::channel::<Option<String>>();
+    let password_entry = PasswordEntry::builder()
+        .show_peek_icon(true)
+        .activates_default(true)
+        .build();
+    let body = match error {
+        Some(err) => tr::tr!("That password didn't work [{}].", err),
+        None => tr::tr!(
+            "Celeste's Rclone config is encrypted. Its password will be saved in your keyring once it's been unlocked."
+        ),
+    };
+    let dialog = adw::MessageDialog::builder()
+        .heading(&tr::tr!("Enter the password for the Rclone config"))
+        .body(&body)
+        .extra_child(&password_entry)
+        .modal(true)
+        .build();
+    dialog.add_response("cancel", &tr::tr!("Cancel"));
+    dialog.add_response("unlock", &tr::tr!("Unlock"));
+    dialog.set_response_appearance("unlock", adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("unlock"));
+    dialog.set_close_response("cancel");
+    dialog.connect_response(
+        None,
+        glib::clone!(@strong sender, @weak password_entry => move |dialog, resp| {
+            let password = (resp == "unlock").then(|| password_entry.text().to_string());
+            dialog.close();
+            sender.send(password);
+        }),
+    );
+    dialog.show();
+    receiver.recv()
+}
+
+/// Unlock Rclone's config with the password saved in the keyring, returning
+/// whether that worked.
+pub fn unlock_rclone_config_from_keyring() -> bool {
+    keyring::Entry::new(libceleste::APP_ID, RCLONE_CONFIG_KEYRING_USER)
+        .and_then(|entry| entry.get_password())
+        .map_or(false, |password| {
+            rclone::sync::unlock_config(&password).is_ok()
+        })
+}
+
+/// Unlock Rclone's config with the password saved in the keyring, or with one
+/// from the user if that doesn't work. Returns whether the config got unlocked.
+fn unlock_rclone_config() -> bool {
+    if unlock_rclone_config_from_keyring() {
+        return true;
+    }
+
+    let keyring_entry = keyring::Entry::new(libceleste::APP_ID, RCLONE_CONFIG_KEYRING_USER);
+
+    let mut error = None;
+    loop {
+        let Some(password) = ask_rclone_config_password(error.as_deref()) else {
+            return false;
+        };
+
+        match rclone::sync::unlock_config(&password) {
+            Ok(()) => {
+                if let Err(err) = keyring_entry.and_then(|entry| entry.set_password(&password)) {
+                    hw_msg::warningln!(
+                        "Unable to save the Rclone config's password in the keyring: '{err}'."
+                    );
+                }
+                return true;
+            }
+            Err(err) => error = Some(err.to_string()),
+        }
+    }
+}
+
+/// Describe a number of files and their size, such as "12 files (3.4 MB)".
+fn describe_file_count(count: &FileCount) -> String {
+    let files = tr::tr!("{n} file" | "{n} files" % count.files);
+    format!("{files} ({})", glib::format_size(count.bytes))
+}
+
+/// Describe what a sync would transfer and delete, one action per line.
+fn describe_transfers(transfers: &Transfers) -> String {
+    let mut lines = vec![];
+    if !transfers.uploads.is_empty() {
+        lines.push(tr::tr!(
+            "Upload {}",
+            describe_file_count(&transfers.uploads)
+        ));
+    }
+    if !transfers.downloads.is_empty() {
+        lines.push(tr::tr!(
+            "Download {}",
+            describe_file_count(&transfers.downloads)
+        ));
+    }
+    if !transfers.local_deletions.is_empty() {
+        lines.push(tr::tr!(
+            "Delete {} locally",
+            describe_file_count(&transfers.local_deletions)
+        ));
+    }
+    if !transfers.remote_deletions.is_empty() {
+        lines.push(tr::tr!(
+            "Delete {} on the remote",
+            describe_file_count(&transfers.remote_deletions)
+        ));
+    }
+
+    if lines.is_empty() {
+        tr::tr!("Nothing needs to be transferred")
+    } else {
+        lines.join("\n")
+    }
+}
+
+/// Ask the user which side should win the first sync of `sync_dir`, whose
+/// local and remote folders both have files that differ. Returns [`None`] if
+/// they cancelled adding the directory.
+fn ask_initial_sync(sync_dir: &SyncDirsModel, preview: &SyncPreview) -> Option<InitialSync> {
+    let (sender, mut receiver) = mpsc::channel::<Option<InitialSync>>();
+    let choices = [
+        (
+            InitialSync::Merge,
+            "merge",
+            tr::tr!("Merge Both"),
+            tr::tr!("Keep the files from both sides, with the newer copy of each file winning."),
+        ),
+        (
+            InitialSync::KeepLocal,
+            "keep_local",
+            tr::tr!("Match Local"),
+            tr::tr!("Make the remote folder match the local one."),
+        ),
+        (
+            InitialSync::KeepRemote,
+            "keep_remote",
+            tr::tr!("Match Remote"),
+            tr::tr!("Make the local folder match the remote one."),
+        ),
+    ];
+
+    // Show what each choice would do, so the user knows what they're agreeing to.
+    let choice_list = ListBox::builder()
+        .selection_mode(SelectionMode::None)
+        .css_classes(vec!["boxed-list".to_string()])
+        .build();
+    for (initial_sync, _, label, description) in &choices {
+        let row = ActionRow::builder()
+            .title(label)
+            .subtitle(&format!(
+                "{description}\n{}",
+                describe_transfers(&preview.transfers(*initial_sync))
+            ))
+            .build();
+        choice_list.append(&row);
+    }
+
+    let dialog = adw::MessageDialog::builder()
+        .heading(&tr::tr!("Both folders already have files in them"))
+        .body(&tr::tr!(
+            "'{}' and '{}' have files that differ. Choose how they should be synced for the first time.",
+            libceleste::fmt_home(&sync_dir.local_path),
+            sync_dir.remote_path
+        ))
+        .extra_child(&choice_list)
+        .modal(true)
+        .build();
+    dialog.add_response("cancel", &tr::tr!("Cancel"));
+    for (_, id, label, _) in &choices {
+        dialog.add_response(id, label);
+    }
+    dialog.set_response_appearance("merge", adw::ResponseAppearance::Suggested);
+    dialog.set_response_appearance("keep_local", adw::ResponseAppearance::Destructive);
+    dialog.set_response_appearance("keep_remote", adw::ResponseAppearance::Destructive);
+    dialog.set_default_response(Some("merge"));
+    dialog.set_close_response("cancel");
+    dialog.connect_response(
+        None,
+        glib::clone!(@strong sender => move |dialog, resp| {
+            let initial_sync = choices
+                .iter()
+                .find(|(_, id, _, _)| *id == resp)
+                .map(|(initial_sync, _, _, _)| *initial_sync);
+            dialog.close();
+            sender.send(initial_sync);
+        }),
+    );
+    dialog.show();
+    receiver.recv()
+}
+
+/// Let the user know how much the first sync of `sync_dir` will transfer, and
+/// ask them whether to go ahead with it. Returns `true` right away if there's
+/// nothing to transfer.
+fn confirm_first_sync(sync_dir: &SyncDirsModel, transfers: &Transfers) -> bool {
+    let describe = |count: &FileCount| {
+        (
+            format!("~{}", glib::format_size(count.bytes)),
+            tr::tr!("{n} file" | "{n} files" % count.files),
+        )
+    };
+    let body = match (transfers.uploads.is_empty(), transfers.downloads.is_empty()) {
+        (true, true) => return true,
+        (false, true) => {
+            let (size, files) = describe(&transfers.uploads);
+            tr::tr!("This will upload {} across {}. Continue?", size, files)
+        }
+        (true, false) => {
+            let (size, files) = describe(&transfers.downloads);
+            tr::tr!("This will download {} across {}. Continue?", size, files)
+        }
+        (false, false) => {
+            let (upload_size, upload_files) = describe(&transfers.uploads);
+            let (download_size, download_files) = describe(&transfers.downloads);
+            tr::tr!(
+                "This will upload {} across {}, and download {} across {}. Continue?",
+                upload_size,
+                upload_files,
+                download_size,
+                download_files
+            )
+        }
+    };
+
+    let (sender, mut receiver) = mpsc::channel::<bool>();
+    let dialog = adw::MessageDialog::builder()
+        .heading(&tr::tr!(
+            "Start syncing '{}'?",
+            libceleste::fmt_home(&sync_dir.local_path)
+        ))
+        .body(&body)
+        .modal(true)
+        .build();
+    dialog.add_response("cancel", &tr::tr!("Cancel"));
+    dialog.add_response("continue", &tr::tr!("Continue"));
+    dialog.set_response_appearance("continue", adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("continue"));
+    dialog.set_close_response("cancel");
+    dialog.connect_response(
+        None,
+        glib::clone!(@strong sender => move |dialog, resp| {
+            dialog.close();
+            sender.send(resp == "continue");
+        }),
+    );
+    dialog.show();
+    receiver.recv()
+}
+
+/// Ask the user whether to create the remote directory at `remote_path`, since
+/// it doesn't exist yet.
+fn ask_create_remote_dir(remote_path: &str) -> bool {
+    let (sender, mut receiver) = mpsc::channel::<bool>();
+    let dialog = adw::MessageDialog::builder()
+        .heading(&tr::tr!("The specified remote directory doesn't exist"))
+        .body(&tr::tr!(
+            "Create '/{}' on the remote and sync with it?",
+            remote_path
+        ))
+        .modal(true)
+        .build();
+    dialog.add_response("cancel", &tr::tr!("Cancel"));
+    dialog.add_response("create", &tr::tr!("Create It"));
+    dialog.set_response_appearance("create", adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("create"));
+    dialog.set_close_response("cancel");
+    dialog.connect_response(
+        None,
+        glib::clone!(@strong sender => move |dialog, resp| {
+            dialog.close();
+            sender.send(resp == "create");
+        }),
+    );
+    dialog.show();
+    receiver.recv()
+}
+
+/// Describe both copies of a conflicting item for the user to choose between,
+/// from the local item at `local_path` and the remote item.
+fn conflict_details(local_path: &Path, remote_item: Option<&rclone::RcloneRemoteItem>) -> String {
+    let describe = |modified: i64, size: i64, modified_by: Option<&str>| {
+        let time = audit::format_timestamp(modified);
+        let size = glib::format_size(size.max(0) as u64);
+        match modified_by {
+            Some(user) => tr::tr!("Modified {} by {}, {}", time, user, size),
+            None => tr::tr!("Modified {}, {}", time, size),
+        }
+    };
+    let local = local_path.metadata().ok().map_or_else(
+        || tr::tr!("Doesn't exist anymore"),
+        |metadata| {
+            let modified = metadata.modified().map_or(0, system_time_to_timestamp);
+            describe(modified, metadata.len() as i64, None)
+        },
+    );
+    let remote = remote_item.map_or_else(
+        || tr::tr!("Doesn't exist anymore"),
+        |item| {
+            let modified = item.mod_time.unix_timestamp();
+            describe(modified, item.size, item.modified_by())
+        },
+    );
+
+    format!(
+        "{}\n{}\n\n{}",
+        tr::tr!("Local: {}", local),
+        tr::tr!("Remote: {}", remote),
+        tr::tr!("Which item would you like to keep?")
+    )
+}
+
+/// Which copy of each conflicting item to keep when resolving all of a
+/// directory's conflicts at once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ConflictChoice {
+    Local,
+    Remote,
+    /// Whichever copy was modified most recently.
+    Newest,
+}
+
+/// Ask the user which copy to keep for all `num_conflicts` conflicts in a
+/// directory. Returns [`None`] if they cancelled.
+fn ask_conflict_choice(num_conflicts: usize) -> Option<ConflictChoice> {
+    let (sender, mut receiver) = mpsc::channel::<Option<ConflictChoice>>();
+    let dialog = adw::MessageDialog::builder()
+        .heading(&tr::tr!(
+            "Resolve {n} conflict?" | "Resolve all {n} conflicts?" % num_conflicts
+        ))
+        .body(&tr::tr!(
+            "Choose which copy of each item to keep. The other copy will be overwritten, and can't be chosen again."
+        ))
+        .modal(true)
+        .build();
+    dialog.add_response("cancel", &tr::tr!("Cancel"));
+    dialog.add_response("local", &tr::tr!("Local"));
+    dialog.add_response("remote", &tr::tr!("Remote"));
+    dialog.add_response("newest", &tr::tr!("Newest"));
+    dialog.set_close_response("cancel");
+    dialog.connect_response(
+        None,
+        glib::clone!(@strong sender => move |dialog, resp| {
+            dialog.close();
+            sender.send(match resp {
+                "local" => Some(ConflictChoice::Local),
+                "remote" => Some(ConflictChoice::Remote),
+                "newest" => Some(ConflictChoice::Newest),
+                _ => None,
+            });
+        }),
+    );
+    dialog.show();
+    receiver.recv()
+}
+
+/// Record that `local_item` and `remote_item` match again, after the conflict
+/// between them was resolved.
+fn mark_conflict_resolved(
+    db: &DatabaseConnection,
+    remote_name: &str,
+    local_item: &str,
+    remote_item: &str,
+) {
+    let local_timestamp = system_time_to_timestamp(
+        Path::new(local_item)
+            .metadata()
+            .unwrap()
+            .modified()
+            .unwrap(),
+    );
+    let remote_timestamp = rclone::sync::stat(remote_name, remote_item)
+        .unwrap()
+        .unwrap()
+        .mod_time
+        .unix_timestamp();
+    let mut active_model: SyncItemsActiveModel = db::expect(|| {
+        SyncItemsEntity::find()
+            .filter(SyncItemsColumn::LocalPath.eq(local_item))
+            .filter(SyncItemsColumn::RemotePath.eq(remote_item))
+            .one(db)
+    })
+    .unwrap()
+    .into();
+    active_model.last_local_timestamp = ActiveValue::Set(local_timestamp);
+    active_model.last_remote_timestamp = ActiveValue::Set(remote_timestamp);
+    db::expect(|| active_model.clone().update(db));
+
+    if SettingsModel::get(db).sync_status_xattrs {
+        set_sync_status(Path::new(local_item), "synced");
+    }
+}
+
+/// Resolve the conflict between `local_item` and `remote_item` by keeping the
+/// copy `choice` picks, without asking the user. If only one of the copies
+/// still exists, that one gets kept instead. Returns why the conflict couldn't
+/// be resolved on errors.
+fn resolve_conflict(
+    db: &DatabaseConnection,
+    sync_dir: &SyncDirsModel,
+    remote: &RemotesModel,
+    local_item: &str,
+    remote_item: &str,
+    choice: ConflictChoice,
+) -> Result<(), String> {
+    let local_modified = fs::metadata(local_item)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .map(system_time_to_timestamp);
+    let remote_modified = rclone::sync::stat(&remote.name, remote_item)
+        .map_err(|err| err.message().to_owned())?
+        .map(|item| item.mod_time.unix_timestamp());
+
+    let direction = match (local_modified, remote_modified, choice) {
+        // Neither copy exists anymore, so there's nothing left to resolve.
+        (None, None, _) => return Ok(()),
+        (Some(_), None, _) | (Some(_), Some(_), ConflictChoice::Local) => TransferDirection::Upload,
+        (None, Some(_), _) | (Some(_), Some(_), ConflictChoice::Remote) => {
+            TransferDirection::Download
+        }
+        (Some(local_time), Some(remote_time), ConflictChoice::Newest) => {
+            if local_time >= remote_time {
+                TransferDirection::Upload
+            } else {
+                TransferDirection::Download
+            }
+        }
+    };
+    let (action, reason) = match direction {
+        TransferDirection::Upload => (AuditAction::OverwriteRemote, AuditReason::KeptLocal),
+        TransferDirection::Download => (AuditAction::OverwriteLocal, AuditReason::KeptRemote),
+    };
+
+    let transfer = || match direction {
+        TransferDirection::Upload => {
+            rclone::sync::copy_to_remote(local_item, &remote.name, remote_item)
+        }
+        TransferDirection::Download => {
+            rclone::sync::copy_to_local(local_item, &remote.name, remote_item)
+        }
+    };
+    run_transfer(db, sync_dir, local_item, remote_item, direction, transfer)
+        .map_err(|err| err.message().to_owned())?;
+    let backend =
+        RcloneBackend::new(&remote.name).with_capabilities(RemoteCapabilities::get(db, remote));
+    if verify_transfer(&backend, sync_dir, db, local_item, remote_item, direction).is_err() {
+        return Err(tr::tr!("The copies didn't match after syncing."));
+    }
+
+    AuditLog::start().record(db, sync_dir, action, reason, local_item, remote_item);
+    mark_conflict_resolved(db, &remote.name, local_item, remote_item);
+    Ok(())
+}
+
+/// What to do about a sync directory whose remote folder doesn't exist anymore.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum MissingRemoteAction {
+    /// Create the remote folder again, and upload the local directory to it.
+    Recreate,
+    /// Stop syncing the directory.
+    StopSyncing,
+    /// Sync the directory with this remote folder instead.
+    Relink(String),
+    /// Leave the directory alone until its remote folder is back, or the user
+    /// chooses to sync now.
+    Skip,
+}
+
+/// Ask the user what to do about the remote folder of `sync_dir` on
+/// `remote_name` not existing anymore. `error` is why the remote folder they
+/// last chose to sync with instead can't be used, if they did.
+fn ask_missing_remote_action(
+    sync_dir: &SyncDirsModel,
+    remote_name: &str,
+    error: Option<&str>,
+) -> MissingRemoteAction {
+    let (sender, mut receiver) = mpsc::channel::<MissingRemoteAction>();
+    let remote_entry = Entry::builder()
+        .placeholder_text(&tr::tr!("Other remote folder"))
+        .build();
+    let body = match error {
+        Some(err) => err.to_owned(),
+        None => tr::tr!(
+            "'/{}' was deleted or moved on '{}'. You can create it again and upload this directory to it, stop syncing this directory, or sync it with another remote folder instead.",
+            sync_dir.remote_path,
+            remote_name
+        ),
+    };
+    let dialog = adw::MessageDialog::builder()
+        .heading(&tr::tr!(
+            "The remote folder for '{}' doesn't exist anymore",
+            libceleste::fmt_home(&sync_dir.local_path)
+        ))
+        .body(&body)
+        .extra_child(&remote_entry)
+        .modal(true)
+        .build();
+    dialog.add_response("skip", &tr::tr!("Not Now"));
+    dialog.add_response("stop", &tr::tr!("Stop Syncing"));
+    dialog.add_response("relink", &tr::tr!("Use Other Folder"));
+    dialog.add_response("recreate", &tr::tr!("Recreate"));
+    dialog.set_response_appearance("stop", adw::ResponseAppearance::Destructive);
+    dialog.set_response_appearance("recreate", adw::ResponseAppearance::Suggested);
+    dialog.set_response_enabled("relink", false);
+    dialog.set_close_response("skip");
+    remote_entry.connect_changed(glib::clone!(@weak dialog => move |remote_entry| {
+        dialog.set_response_enabled("relink", !remote_entry.text().trim().is_empty());
+    }));
+    dialog.connect_response(
+        None,
+        glib::clone!(@strong sender, @weak remote_entry => move |dialog, resp| {
+            dialog.close();
+            sender.send(match resp {
+                "recreate" => MissingRemoteAction::Recreate,
+                "stop" => MissingRemoteAction::StopSyncing,
+                "relink" => MissingRemoteAction::Relink(
+                    libceleste::strip_slashes(remote_entry.text().trim()),
+                ),
+                _ => MissingRemoteAction::Skip,
+            });
+        }),
+    );
+    dialog.show();
+    receiver.recv()
+}
+
+/// Check that `sync_dir` can be synced with `remote_path` on `remote` instead
+/// of its current remote folder. Returns why it can't be otherwise.
+fn check_relink_remote_path(
+    db: &DatabaseConnection,
+    remote: &RemotesModel,
+    sync_dir: &SyncDirsModel,
+    remote_path: &str,
+) -> Result<(), String> {
+    let overlapping_dir = db::expect(|| {
+        SyncDirsEntity::find()
+            .filter(SyncDirsColumn::RemoteId.eq(remote.id))
+            .filter(SyncDirsColumn::Id.ne(sync_dir.id))
+            .all(db)
+    })
+    .into_iter()
+    .find(|other| remote_paths_overlap(&other.remote_path, remote_path));
+    if let Some(overlapping_dir) = overlapping_dir {
+        return Err(tr::tr!(
+            "'/{}' overlaps the remote folder '{}' is synced with.",
+            remote_path,
+            libceleste::fmt_home(&overlapping_dir.local_path)
+        ));
+    }
+
+    // The root of the remote always exists.
+    if remote_path.is_empty() {
+        return Ok(());
+    }
+    match rclone::sync::stat(&remote.name, remote_path) {
+        Ok(Some(item)) if item.is_dir => Ok(()),
+        Ok(Some(_)) => Err(tr::tr!("'/{}' isn't a folder.", remote_path)),
+        Ok(None) => Err(tr::tr!("'/{}' doesn't exist either.", remote_path)),
+        Err(err) => Err(tr::tr!(
+            "Unable to check '/{}' on the remote [{}].",
+            remote_path,
+            err.message()
+        )),
+    }
+}
+
+/// Point `sync_dir` at `local_path` and `remote_path` instead, moving the
+/// records of its items along with it so that they don't look new or deleted.
+/// Returns the updated directory.
+fn relink_sync_dir(
+    db: &DatabaseConnection,
+    sync_dir: &SyncDirsModel,
+    local_path: &str,
+    remote_path: &str,
+) -> SyncDirsModel {
+    db::expect(|| async {
+        let items = SyncItemsEntity::find()
+            .filter(SyncItemsColumn::SyncDirId.eq(sync_dir.id))
+            .all(db)
+            .await?;
+        for item in items {
+            let relinked_local_path =
+                paths::relative_local_path(sync_dir, Path::new(&item.local_path))
+                    .map(|relative_path| Path::new(local_path).join(relative_path));
+            let relinked_remote_path = paths::relative_remote_path(sync_dir, &item.remote_path)
+                .map(|relative_path| paths::join_remote(remote_path, relative_path));
+            let mut active_model: SyncItemsActiveModel = item.clone().into();
+            if let Some(relinked_local_path) = relinked_local_path {
+                active_model.local_path =
+                    ActiveValue::Set(relinked_local_path.to_string_lossy().into_owned());
+            }
+            if let Some(relinked_remote_path) = relinked_remote_path {
+                active_model.remote_path = ActiveValue::Set(relinked_remote_path);
+            }
+            active_model.update(db).await?;
+        }
+
+        let mut active_model: SyncDirsActiveModel = sync_dir.clone().into();
+        active_model.local_path = ActiveValue::Set(local_path.to_owned());
+        active_model.remote_path = ActiveValue::Set(remote_path.to_owned());
+        active_model.update(db).await
+    })
+}
+
+/// What to do about a sync directory whose local directory doesn't exist
+/// anymore.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum MissingLocalAction {
+    /// Sync the folder the directory was found to have been moved to.
+    UseFound,
+    /// Let the user pick the folder the directory was moved to.
+    Locate,
+    /// Stop syncing the directory.
+    StopSyncing,
+    /// Leave the directory alone until it's back, or the user chooses to sync
+    /// now.
+    Skip,
+}
+
+/// Whether `candidate` has most of the items recorded for `sync_dir` in
+/// `items`, so that the directory looks like it was moved there.
+fn looks_like_moved_dir(
+    sync_dir: &SyncDirsModel,
+    items: &[SyncItemsModel],
+    candidate: &Path,
+) -> bool {
+    let matching = items
+        .iter()
+        .filter_map(|item| paths::relative_local_path(sync_dir, Path::new(&item.local_path)))
+        .filter(|relative_path| candidate.join(relative_path).exists())
+        .count();
+    !items.is_empty() && matching as f64 >= items.len() as f64 * MOVED_DIR_MATCH_RATIO
+}
+
+/// Look for where the missing local directory of `sync_dir` was moved to, by
+/// checking the folders next to where it was for one with its items. This
+/// finds directories that were renamed, which is the most common way of them
+/// going missing.
+fn find_moved_local_dir(sync_dir: &SyncDirsModel, items: &[SyncItemsModel]) -> Option<PathBuf> {
+    let parent = Path::new(&sync_dir.local_path).parent()?;
+    fs::read_dir(parent)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .find(|path| looks_like_moved_dir(sync_dir, items, path))
+}
+
+/// Ask the user what to do about the local directory of `sync_dir` not existing
+/// anymore. `found` is where it looks like it was moved to, if anywhere.
+/// `error` is why the folder they last picked can't be used, if they did.
+fn ask_missing_local_action(
+    sync_dir: &SyncDirsModel,
+    found: Option<&Path>,
+    error: Option<&str>,
+) -> MissingLocalAction {
+    let (sender, mut receiver) = mpsc::channel::<MissingLocalAction>();
+    let body = match (error, found) {
+        (Some(err), _) => err.to_owned(),
+        (None, Some(found)) => tr::tr!(
+            "It looks like it was moved to '{}'. Syncing it there keeps the records of what's already been synced, so nothing gets uploaded or deleted again.",
+            libceleste::fmt_home(&found.to_string_lossy())
+        ),
+        (None, None) => tr::tr!(
+            "If it was moved or renamed, locate it to keep syncing it there without anything getting uploaded or deleted again."
+        ),
+    };
+    let dialog = adw::MessageDialog::builder()
+        .heading(&tr::tr!(
+            "The local folder '{}' doesn't exist anymore",
+            libceleste::fmt_home(&sync_dir.local_path)
+        ))
+        .body(&body)
+        .modal(true)
+        .build();
+    dialog.add_response("skip", &tr::tr!("Not Now"));
+    dialog.add_response("stop", &tr::tr!("Stop Syncing"));
+    dialog.add_response("locate", &tr::tr!("Locate Moved Folder"));
+    dialog.set_response_appearance("stop", adw::ResponseAppearance::Destructive);
+    if found.is_some() {
+        dialog.add_response("use_found", &tr::tr!("Use Found Folder"));
+        dialog.set_response_appearance("use_found", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("use_found"));
+    } else {
+        dialog.set_response_appearance("locate", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("locate"));
+    }
+    dialog.set_close_response("skip");
+    dialog.connect_response(
+        None,
+        glib::clone!(@strong sender => move |dialog, resp| {
+            dialog.close();
+            sender.send(match resp {
+                "use_found" => MissingLocalAction::UseFound,
+                "locate" => MissingLocalAction::Locate,
+                "stop" => MissingLocalAction::StopSyncing,
+                _ => MissingLocalAction::Skip,
+            });
+        }),
+    );
+    dialog.show();
+    receiver.recv()
+}
+
+/// Let the user pick the folder a sync directory's local directory was moved
+/// to. Returns [`None`] if they cancelled.
+fn choose_moved_local_dir() -> Option<PathBuf> {
+    let (sender, mut receiver) = mpsc::channel::<Option<PathBuf>>();
+    let filter = FileFilter::new();
+    filter.add_mime_type("inode/directory");
+    let dialog = FileChooserDialog::builder()
+        .title(&libceleste::get_title!("Moved Folder Picker"))
+        .action(FileChooserAction::SelectFolder)
+        .select_multiple(false)
+        .filter(&filter)
+        .modal(true)
+        .build();
+    dialog.add_button(&tr::tr!("Cancel"), ResponseType::Cancel);
+    dialog.add_button(&tr::tr!("Ok"), ResponseType::Ok);
+    dialog.connect_response(glib::clone!(@strong sender => move |dialog, resp| {
+        let path = match resp {
+            ResponseType::Ok => dialog.file().and_then(|file| file.path()),
+            _ => None,
+        };
+        dialog.close();
+        sender.send(path);
+    }));
+    dialog.show();
+    receiver.recv()
+}
+
+/// Check that `sync_dir` can be synced from `local_path` instead of its missing
+/// local directory. Returns why it can't be otherwise.
+fn check_relink_local_path(
+    db: &DatabaseConnection,
+    sync_dir: &SyncDirsModel,
+    items: &[SyncItemsModel],
+    local_path: &Path,
+) -> Result<String, String> {
+    let formatted_path = libceleste::fmt_home(&local_path.to_string_lossy());
+    let Some(local_path_str) = local_path.to_str() else {
+        return Err(tr::tr!(
+            "The selected folder's path isn't valid UTF-8, so it can't be synced."
+        ));
+    };
+    let already_synced = db::expect(|| {
+        SyncDirsEntity::find()
+            .filter(SyncDirsColumn::RemoteId.eq(sync_dir.remote_id))
+            .filter(SyncDirsColumn::LocalPath.eq(local_path_str))
+            .filter(SyncDirsColumn::RemotePath.eq(sync_dir.remote_path.clone()))
+            .one(db)
+    })
+    .is_some();
+
+    if already_synced {
+        Err(tr::tr!(
+            "'{}' is already being synced with the same remote folder.",
+            formatted_path
+        ))
+    } else if local_path_has_config(local_path) {
+        Err(tr::tr!(
+            "'{}' contains Celeste's configuration, so it can't be synced.",
+            formatted_path
+        ))
+    } else if !looks_like_moved_dir(sync_dir, items, local_path) {
+        Err(tr::tr!(
+            "'{}' doesn't have most of the items that were in '{}', so it doesn't look like the same folder.",
+            formatted_path,
+            libceleste::fmt_home(&sync_dir.local_path)
+        ))
+    } else {
+        Ok(local_path_str.to_owned())
+    }
+}
+
+/// Replace the UI for the directory at `old_pair` on `remote_name` with one for
+/// `new_pair`, after it's been relinked. The directory keeps its place in the
+/// list.
+fn relink_dir_ui(
+    directory_map: &DirectoryMap,
+    dir_adders: &DirAdderMap,
+    remote_name: &str,
+    old_pair: &(String, String),
+    new_pair: (String, String),
+) {
+    let index = {
+        let mut dmap = directory_map.get_mut_ref();
+        let old_dir = dmap
+            .get_mut(remote_name)
+            .unwrap()
+            .shift_remove(old_pair)
+            .unwrap();
+        let index = old_dir.container.index();
+        old_dir.parent_list.remove(&old_dir.container);
+        index
+    };
+
+    let add_dir = dir_adders.get_ref()[remote_name].clone();
+    add_dir(
+        remote_name.to_owned(),
+        new_pair.0.clone(),
+        new_pair.1.clone(),
+    );
+
+    let dmap = directory_map.get_ref();
+    let new_dir = &dmap[remote_name][&new_pair];
+    new_dir.parent_list.remove(&new_dir.container);
+    new_dir.parent_list.insert(&new_dir.container, index);
+}
+
+/// Show on the row of `sync_dir` that it can't be synced because its local
+/// directory or remote folder doesn't exist anymore.
+fn show_missing_dir(
+    directory_map: &DirectoryMap,
+    remote_name: &str,
+    sync_dir: &SyncDirsModel,
+    msg: &str,
+) {
+    let path_pair = (sync_dir.local_path.clone(), sync_dir.remote_path.clone());
+    let item_ptr = directory_map.get_ref();
+    let item = &item_ptr[remote_name][&path_pair];
+    item.status_icon
+        .set_child(Some(&get_image("dialog-warning-symbolic")));
+    item.status_text.set_label(msg);
+}
+
+/// Format the time of day at `instant` for the user, such as `14:32`.
+pub fn format_instant(instant: Instant) -> String {
+    let seconds = instant
+        .saturating_duration_since(Instant::now())
+        .as_secs_f64();
+    glib::DateTime::now_local()
+        .and_then(|now| now.add_seconds(seconds))
+        .and_then(|time| time.format("%R"))
+        .map_or_else(|_| String::new(), |time| time.to_string())
+}
+
+/// Get an icon for use as the status icon for directory syncs.
+fn get_image(icon_name: &str) -> Image {
+    Image::builder()
+        .icon_name(icon_name)
+        .width_request(10)
+        .height_request(10)
+        .build()
+}
+
+/// Sync the local directory `local_dir` of `sync_dir`, along with everything
+/// inside of it. The status of each item being checked gets passed to
+/// `show_progress`, and any errors to `add_error`.
+#[allow(clippy::too_many_arguments)]
+fn sync_local_directory<
+    F1: Fn(SyncError) + Clone,
+    F2: Fn() + Clone,
+    F3: Fn() + Clone,
+    F4: Fn(&str) + Clone,
+>(
+    local_dir: &Path,
+    backend: &dyn SyncBackend,
+    sync_dir: &SyncDirsModel,
+    db: &DatabaseConnection,
+    synced_items: &SyncedItems,
+    remote_deletions: &RefCell<Vec<SyncItemsModel>>,
+    space: &SpaceCheck,
+    uploaded: &UploadedFiles,
+    audit: &AuditLog,
+    stats: &PassStats,
+    write_sync_status: bool,
+    timestamps: TimestampComparison,
+    add_error: F1,
+    check_open_requests: F2,
+    process_deletion_requests: F3,
+    show_progress: F4,
+) {
+    process_deletion_requests();
+
+    let dir_string = local_dir.to_string_lossy().into_owned();
+    let update_ui_progress = |dir: &str| {
+        // If this directory no longer exists in the database (i.e. from being
+        // deleted from the `sync_dir_deletion_queue`), then do nothing.
+        if !sync_dir.exists(db) {
+            return;
+        }
+
+        show_progress(&tr::tr!(
+            "Checking '{}' for changes...",
+            libceleste::fmt_home(dir)
+        ));
+    };
+    update_ui_progress(&dir_string);
+    let directory = match fs::read_dir(local_dir) {
+        Ok(ok_dir) => ok_dir,
+        Err(err) => {
+            add_error(SyncError::General(dir_string, err.to_string()));
+            return;
+        }
+    };
+
+    // Get the rules for which items get synced.
+    let filter = exclusions::SyncFilter::new(
+        &refresh_sync_dir(sync_dir, db),
+        &read_exclusions(sync_dir, db),
+    )
+    .with_default_exclusions(SettingsModel::get(db).exclude_temp_files);
+    // Which side wins for items that haven't been synced before.
+    let initial_sync = InitialSync::from_setting(sync_dir.initial_sync.as_deref());
+
+    for item in directory {
+        // If a close request was sent in, stop syncing this remote so we can quit
+        // the application in the 'main loop.
+        if *(*CLOSE_REQUEST).lock().unwrap() {
+            break;
+        }
+
+        // If we've run out of space, or have transferred as many files as we can
+        // this pass, stop syncing this directory.
+        if space.shortage().is_some() || stats.limit_reached() {
+            break;
+        }
+
+        // Check for open requests.
+        check_open_requests();
+
+        // If this directory no longer exists in the database (i.e. from being
+        // deleted from the `sync_dir_deletion_queue`), stop processing and return.
+        if !sync_dir.exists(db) {
+            break;
+        }
+
+        if let Err(err) = item {
+            add_error(SyncError::General(dir_string.clone(), err.to_string()));
+            continue;
+        }
+        let item = item.unwrap();
+        let local_path = match item.path().to_str() {
+            Some(path) => path.to_owned(),
+            None => {
+                add_error(SyncError::invalid_path(&item.path()));
+                continue;
+            }
+        };
+
+        // The path from the root of the remote.
+        let remote_path = paths::local_to_remote(sync_dir, &item.path()).unwrap();
+        // The above path, with `sync_dir.remote_path` stripped from it.
+        let stripped_remote_path = paths::relative_remote_path(sync_dir, &remote_path)
+            .unwrap()
+            .to_owned();
+
+        update_ui_progress(&local_path);
+        // Never sync any of our own files.
+        if exclusions::is_builtin_exclusion(sync_dir, &stripped_remote_path) {
+            continue;
+        }
+
+        // If this item is excluded by the user's rules, don't sync it.
+        if !filter.includes(&stripped_remote_path, item.path().is_dir()) {
+            continue;
+        }
+
+        // Files that haven't been modified recently enough are left alone.
+        if !item.path().is_dir()
+            && let Ok(metadata) = item.metadata()
+            && let Ok(modified) = metadata.modified()
+            && !filter.includes_modified(system_time_to_timestamp(modified))
+        {
+            continue;
+        }
+
+        synced_items.insert(&local_path);
+
+        let get_local_file_timestamp =
+            || system_time_to_timestamp(item.metadata().unwrap().modified().unwrap());
+        let local_utc_timestamp = get_local_file_timestamp();
+        let remote_item = match backend.stat(&remote_path) {
+            Ok(item) => item,
+            Err(err) => {
+                add_error(SyncError::General(remote_path.clone(), err.to_string()));
+                continue;
+            }
+        };
+        let remote_utc_timestamp = remote_item
+            .as_ref()
+            .map(|item| item.mod_time.unix_timestamp());
+        let db_item = db::expect(|| {
+            SyncItemsEntity::find()
+                .filter(SyncItemsColumn::LocalPath.eq(local_path.clone()))
+                .filter(SyncItemsColumn::RemotePath.eq(remote_path.clone()))
+                .one(db)
+        });
+
+        // Push the item to the remote. Returns the
+        // [`crate::rclone::sync::RcloneRemoteItem`] of the item on the remote, or
+        // an [`Err<()>`] if an issue occurred (all errors are automatically added
+        // via `add_errors`).
+        let push_local_to_remote = || -> Result<rclone::RcloneRemoteItem, ()> {
+            let file_type = item.file_type().unwrap();
+
+            // Leave files that are still being written for a later pass.
+            if !file_type.is_dir() && !stats.check_settled(&item.path()) {
+                return Err(());
+            }
+
+            if let Some(rclone_item) = &remote_item {
+                let same_type = file_type.is_dir() && rclone_item.is_dir;
+
+                if !same_type {
+                    if let Err(err) = audit.remove_remote(
+                        backend,
+                        sync_dir,
+                        KeptCopies::Versions,
+                        &remote_path,
+                        rclone_item.is_dir,
+                    ) {
+                        add_error(SyncError::General(remote_path.clone(), err.to_string()));
+                        return Err(());
+                    }
+                }
+            }
+
+            if file_type.is_dir() {
+                if let Err(err) = backend.mkdir(&remote_path) {
+                    add_error(SyncError::General(remote_path.clone(), err.to_string()));
+                    return Err(());
+                }
+                sync_local_directory(
+                    &item.path(),
+                    backend,
+                    sync_dir,
+                    db,
+                    synced_items,
+                    remote_deletions,
+                    space,
+                    uploaded,
+                    audit,
+                    stats,
+                    write_sync_status,
+                    timestamps,
+                    add_error.clone(),
+                    check_open_requests.clone(),
+                    process_deletion_requests.clone(),
+                    show_progress.clone(),
+                );
+                update_ui_progress(&local_path);
+            } else if space.remote_full() {
+                return Err(());
+            } else if let Err(err) = space.check_upload(
+                backend,
+                &local_path,
+                &remote_path,
+                remote_item
+                    .as_ref()
+                    .filter(|rclone_item| !rclone_item.is_dir)
+                    .map(|rclone_item| rclone_item.size),
+            ) {
+                add_error(err);
+                return Err(());
+            } else if let Err(err) = run_transfer(
+                db,
+                sync_dir,
+                &local_path,
+                &remote_path,
+                TransferDirection::Upload,
+                || upload_file(backend, uploaded, &local_path, &remote_path),
+            )
+            .map_err(|err| {
+                space.record_upload_error(&err);
+                SyncError::General(local_path.clone(), err.to_string())
+            })
+            .and_then(|_| {
+                verify_transfer(
+                    backend,
+                    sync_dir,
+                    db,
+                    &local_path,
+                    &remote_path,
+                    TransferDirection::Upload,
+                )
+            })
+            .map(|_| {
+                uploaded.record(&local_path, &remote_path);
+                stats.record(TransferDirection::Upload, &local_path);
+            }) {
+                // Uploads to a full remote get retried once there's space on it,
+                // which its page reports instead of an error for each file.
+                if !space.remote_full() {
+                    add_error(err);
+                }
+                return Err(());
+            }
+
+            Ok(backend.stat(&remote_path).unwrap().unwrap())
+        };
+        // Pull the item from the remote.
+        let pull_remote_to_local = || -> Result<(), ()> {
+            let file_type = item.file_type().unwrap();
+            let same_type = file_type.is_dir() && remote_item.as_ref().unwrap().is_dir;
+
+            if !same_type
+                && let Err(err) = audit.remove_local(sync_dir, KeptCopies::Versions, &local_path)
+            {
+                add_error(SyncError::General(local_path.clone(), err.to_string()));
+                return Err(());
+            }
+
+            if file_type.is_dir() {
+                sync_local_directory(
+                    &item.path(),
+                    backend,
+                    sync_dir,
+                    db,
+                    synced_items,
+                    remote_deletions,
+                    space,
+                    uploaded,
+                    audit,
+                    stats,
+                    write_sync_status,
+                    timestamps,
+                    add_error.clone(),
+                    check_open_requests.clone(),
+                    process_deletion_requests.clone(),
+                    show_progress.clone(),
+                );
+                update_ui_progress(&local_path);
+            } else if let Err(err) =
+                space.check_download(&local_path, remote_item.as_ref().unwrap().size)
+            {
+                add_error(err);
+                return Err(());
+            } else if let Err(err) = run_transfer(
+                db,
+                sync_dir,
+                &local_path,
+                &remote_path,
+                TransferDirection::Download,
+                || backend.copy_to_local(&local_path, &remote_path),
+            )
+            .map_err(|err| SyncError::General(remote_path.clone(), err.to_string()))
+            .and_then(|_| {
+                verify_transfer(
+                    backend,
+                    sync_dir,
+                    db,
+                    &local_path,
+                    &remote_path,
+                    TransferDirection::Download,
+                )
+            })
+            .map(|_| stats.record(TransferDirection::Download, &local_path))
+            {
+                add_error(err);
+                return Err(());
+            }
+
+            Ok(())
+        };
+        // Delete this item from the database.
+        let delete_db_entry = || {
+            db::expect(|| async {
+                SyncItemsEntity::find()
+                    .filter(SyncItemsColumn::SyncDirId.eq(sync_dir.id))
+                    .filter(SyncItemsColumn::LocalPath.eq(local_path.clone()))
+                    .filter(SyncItemsColumn::RemotePath.eq(remote_path.clone()))
+                    .one(db)
+                    .await?
+                    .unwrap()
+                    .delete(db)
+                    .await
+            })
+        };
+        // Record a destructive operation on this item in the audit log.
+        let audit_item =
+            |action, reason| audit.record(db, sync_dir, action, reason, &local_path, &remote_path);
+        // Whether syncing this item replaces the copy on the other side, instead
+        // of creating it or just syncing a directory's contents.
+        let overwrites = remote_item
+            .as_ref()
+            .map_or(false, |r_item| !(r_item.is_dir && item.path().is_dir()));
+
+        let decision = sync_decision::decide(
+            Some(ItemMeta {
+                timestamp: local_utc_timestamp,
+                is_dir: item.path().is_dir(),
+            }),
+            remote_item.as_ref().map(|r_item| ItemMeta {
+                timestamp: r_item.mod_time.unix_timestamp(),
+                is_dir: r_item.is_dir,
+            }),
+            db_item.as_ref().map(|db_model| SyncRecord {
+                local_timestamp: db_model.last_local_timestamp,
+                remote_timestamp: db_model.last_remote_timestamp,
+            }),
+            timestamps,
+            initial_sync,
+        );
+
+        // If we have a record of the last sync, use that to aid in timestamp
+        // checks.
+        if let Some(db_model) = db_item {
+            let update_db_item = |local_timestamp, remote_timestamp| {
+                let (local_inode, local_size) = get_local_file_id(&item.path());
+                if write_sync_status {
+                    set_sync_status(&item.path(), "synced");
+                }
+                let mut active_model: SyncItemsActiveModel = db_model.clone().into();
+                active_model.last_local_timestamp = ActiveValue::Set(local_timestamp);
+                active_model.last_remote_timestamp = ActiveValue::Set(remote_timestamp);
+                active_model.local_inode = ActiveValue::Set(local_inode);
+                active_model.local_size = ActiveValue::Set(local_size);
+                db::expect(|| active_model.clone().update(db));
+            };
+
+            // Record the blocks of large files once they're synced, if the user
+            // asked for such.
+            let block_hashing = SettingsModel::get(db).block_hashing && item.path().is_file();
+            let sync_item_id = db_model.id;
+            let record_blocks = || {
+                if block_hashing
+                    && let Err(err) = file_blocks::record(db, sync_item_id, &item.path())
+                {
+                    hw_msg::warningln!("Unable to hash the blocks of '{local_path}': '{err}'.");
+                }
+            };
+
+            match decision {
+                // Both items are more current than at the last transaction - we
+                // need to let the user decide which to keep.
+                SyncDecision::Conflict => add_error(SyncError::BothMoreCurrent(
+                    local_path.clone(),
+                    remote_path.clone(),
+                )),
+                // A large file whose blocks are all the same only had its
+                // timestamp changed, so there's nothing to upload.
+                SyncDecision::Upload
+                    if block_hashing && file_blocks::unchanged(db, sync_item_id, &item.path()) =>
+                {
+                    update_db_item(local_utc_timestamp, db_model.last_remote_timestamp);
+                }
+                SyncDecision::Upload => {
+                    if let Ok(rclone_item) = push_local_to_remote() {
+                        if overwrites {
+                            audit_item(AuditAction::OverwriteRemote, AuditReason::ChangedLocally);
+                        }
+                        update_db_item(
+                            get_local_file_timestamp(),
+                            rclone_item.mod_time.unix_timestamp(),
+                        );
+                        record_blocks();
+                    }
+                }
+                SyncDecision::Download => {
+                    if pull_remote_to_local().is_ok() {
+                        if overwrites {
+                            audit_item(AuditAction::OverwriteLocal, AuditReason::ChangedOnRemote);
+                        }
+                        update_db_item(get_local_file_timestamp(), remote_utc_timestamp.unwrap());
+                        record_blocks();
+                    }
+                }
+                // The item got deleted on the server, and we need to reflect such
+                // locally.
+                SyncDecision::DeleteLocal => {
+                    // Files might have just been moved on the remote, so let
+                    // `sync_remote_directory` check for that before deleting them.
+                    if item.path().is_file() {
+                        remote_deletions.borrow_mut().push(db_model);
+                        continue;
+                    }
+
+                    if let Err(err) = audit.remove_local(sync_dir, KeptCopies::Trash, &local_path) {
+                        add_error(SyncError::General(local_path.clone(), err.to_string()));
+                        continue;
+                    }
+
+                    audit_item(AuditAction::DeleteLocal, AuditReason::DeletedOnRemote);
+                    delete_db_entry();
+                }
+                SyncDecision::DeleteRemote | SyncDecision::Noop => (),
+            }
+        // Otherwise just check the local timestamps against
+        // those on the remote, and record our new transaction
+        // in the database.
+        } else {
+            // The local folder is being made to match the remote one, so remove
+            // anything that's only here.
+            if decision == SyncDecision::DeleteLocal {
+                if let Err(err) = audit.remove_local(sync_dir, KeptCopies::Trash, &local_path) {
+                    add_error(SyncError::General(local_path.clone(), err.to_string()));
+                } else {
+                    audit_item(AuditAction::DeleteLocal, AuditReason::MatchedRemote);
+                }
+                continue;
+            }
+
+            // If the item is a file that's missing from the remote, check if it
+            // was renamed locally since the last sync. If so, move it on the
+            // remote instead of uploading its contents again.
+            if remote_item.is_none()
+                && item.path().is_file()
+                && let (Some(local_inode), Some(local_size)) = get_local_file_id(&item.path())
+            {
+                let renamed_item = db::expect(|| {
+                    SyncItemsEntity::find()
+                        .filter(SyncItemsColumn::SyncDirId.eq(sync_dir.id))
+                        .filter(SyncItemsColumn::LocalInode.eq(local_inode))
+                        .filter(SyncItemsColumn::LocalSize.eq(local_size))
+                        .filter(SyncItemsColumn::LastLocalTimestamp.eq(local_utc_timestamp))
+                        .all(db)
+                })
+                .into_iter()
+                .find(|db_model| !Path::new(&db_model.local_path).exists())
+                // If the old copy on the remote was changed since the last sync,
+                // moving it would record those changes as synced, so it's uploaded
+                // normally instead.
+                .filter(|db_model| {
+                    matches!(
+                        backend.stat(&db_model.remote_path),
+                        Ok(Some(old_item)) if !old_item.is_dir
+                            && old_item.mod_time.unix_timestamp()
+                                == db_model.last_remote_timestamp
+                    )
+                });
+
+                if let Some(db_model) = renamed_item {
+                    if let Err(err) = backend.move_file(&db_model.remote_path, &remote_path) {
+                        add_error(SyncError::General(local_path.clone(), err.to_string()));
+                        continue;
+                    }
+
+                    let remote_timestamp = match backend.stat(&remote_path) {
+                        Ok(item) => item.unwrap().mod_time.unix_timestamp(),
+                        Err(err) => {
+                            add_error(SyncError::General(remote_path.clone(), err.to_string()));
+                            continue;
+                        }
+                    };
+                    let mut active_model: SyncItemsActiveModel = db_model.into();
+                    active_model.local_path = ActiveValue::Set(local_path.clone());
+                    active_model.remote_path = ActiveValue::Set(remote_path.clone());
+                    active_model.last_remote_timestamp = ActiveValue::Set(remote_timestamp);
+                    db::expect(|| active_model.clone().update(db));
+                    continue;
+                }
+            }
+
+            let result = match decision {
+                SyncDecision::Upload => push_local_to_remote().map(|_| ()),
+                SyncDecision::Download => pull_remote_to_local(),
+                _ => Ok(()),
+            };
+            if result.is_err() {
+                continue;
+            }
+            if overwrites {
+                match decision {
+                    SyncDecision::Upload => audit_item(
+                        AuditAction::OverwriteRemote,
+                        AuditReason::for_overwrite_remote(initial_sync),
+                    ),
+                    SyncDecision::Download => audit_item(
+                        AuditAction::OverwriteLocal,
+                        AuditReason::for_overwrite_local(initial_sync),
+                    ),
+                    _ => (),
+                }
+            }
+
+            // The remote item is now guaranteed to exist, so fetch it.
+            let remote_item_safe = match backend.stat(&remote_path) {
+                Ok(item) => item.unwrap(),
+                Err(err) => {
+                    add_error(SyncError::General(remote_path.clone(), err.to_string()));
+                    continue;
+                }
+            };
+            match backend.stat(&remote_path) {
+                Ok(item) => item.unwrap(),
+                Err(err) => {
+                    add_error(SyncError::General(remote_path.clone(), err.to_string()));
+                    continue;
+                }
+            };
+
+            // Record the current transaction's timestamps in the database.
+            let (local_inode, local_size) = get_local_file_id(&item.path());
+            if write_sync_status {
+                set_sync_status(&item.path(), "synced");
+            }
+            db::expect(|| {
+                SyncItemsActiveModel {
+                    sync_dir_id: ActiveValue::Set(sync_dir.id),
+                    local_path: ActiveValue::Set(local_path.clone()),
+                    remote_path: ActiveValue::Set(remote_path.clone()),
+                    last_local_timestamp: ActiveValue::Set(get_local_file_timestamp()),
+                    last_remote_timestamp: ActiveValue::Set(
+                        remote_item_safe.mod_time.unix_timestamp(),
+                    ),
+                    local_inode: ActiveValue::Set(local_inode),
+                    local_size: ActiveValue::Set(local_size),
+                    ..Default::default()
+                }
+                .insert(db)
+            });
+        }
+    }
+}
+
+/// Sync the remote directory `remote_dir` of `sync_dir`, along with everything
+/// inside of it, skipping anything [`sync_local_directory`] already synced.
+/// `remote_dir` should be the full path from the root of the remote, without
+/// any leading or trailing slashes.
+#[allow(clippy::too_many_arguments)]
+fn sync_remote_directory<
+    F1: Fn(SyncError) + Clone,
+    F2: Fn() + Clone,
+    F3: Fn() + Clone,
+    F4: Fn(&str) + Clone,
+>(
+    remote_dir: &str,
+    backend: &dyn SyncBackend,
+    sync_dir: &SyncDirsModel,
+    db: &DatabaseConnection,
+    synced_items: &SyncedItems,
+    remote_deletions: &RefCell<Vec<SyncItemsModel>>,
+    space: &SpaceCheck,
+    uploaded: &UploadedFiles,
+    audit: &AuditLog,
+    stats: &PassStats,
+    write_sync_status: bool,
+    timestamps: TimestampComparison,
+    add_error: F1,
+    check_open_requests: F2,
+    process_deletion_requests: F3,
+    show_progress: F4,
+) {
+    process_deletion_requests();
+
+    // Get the rules for which items get synced.
+    let filter = exclusions::SyncFilter::new(
+        &refresh_sync_dir(sync_dir, db),
+        &read_exclusions(sync_dir, db),
+    )
+    .with_default_exclusions(SettingsModel::get(db).exclude_temp_files);
+    // Which side wins for items that haven't been synced before.
+    let initial_sync = InitialSync::from_setting(sync_dir.initial_sync.as_deref());
+
+    let update_ui_progress = |dir: &str| {
+        // If this directory no longer exists in the database (i.e. from being
+        // deleted from the `sync_dir_deletion_queue`, do nothing).
+        if !sync_dir.exists(db) {
+            return;
+        }
+
+        show_progress(&tr::tr!("Checking '{}' on remote for changes...", dir));
+    };
+    update_ui_progress(remote_dir);
+
+    // Large directories can have far too many items to hold in memory at once,
+    // so process them as they come in from the remote.
+    for item in backend.list_stream(remote_dir) {
+        let item = match item {
+            Ok(item) => item,
+            Err(err) => {
+                add_error(SyncError::General(remote_dir.to_owned(), err.to_string()));
+                break;
+            }
+        };
+
+        // If a close request was sent in, stop syncing this remote so we can quit
+        // the application in the 'main loop.
+        if *(*CLOSE_REQUEST).lock().unwrap() {
+            break;
+        }
+
+        // If we've run out of space, or have transferred as many files as we can
+        // this pass, stop syncing this directory.
+        if space.shortage().is_some() || stats.limit_reached() {
+            break;
+        }
+
+        // Check for open requests.
+        check_open_requests();
+
+        // If this directory no longer exists in the database (i.e. from being
+        // deleted from the `sync_dir_deletion_queue`), stop processing and return.
+        if !sync_dir.exists(db) {
+            break;
+        }
+
+        // Never sync any of our own files.
+        let relative_path = get_relative_path(sync_dir, &item.path);
+        if exclusions::is_builtin_exclusion(sync_dir, &relative_path) {
+            continue;
+        }
+
+        // If this item is excluded by the user's rules, don't sync it. Rules are
+        // relative to the sync directory, so match against the relative path.
+        if !filter.includes(&relative_path, item.is_dir) {
+            continue;
+        }
+
+        // Files that haven't been modified recently enough are left alone.
+        if !item.is_dir && !filter.includes_modified(item.mod_time.unix_timestamp()) {
+            continue;
+        }
+
+        let remote_path_string = item.path.clone();
+        let local_path_string = paths::remote_to_local(sync_dir, &item.path)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_owned();
+        update_ui_progress(&remote_path_string);
+        // If we've already synced this directory from `fn sync_local_directory`
+        // above, don't sync it again.
+        if synced_items.contains(&local_path_string) {
+            continue;
+        }
+
+        let local_path = Path::new(&local_path_string);
+        let remote_timestamp = item.mod_time.unix_timestamp();
+        let get_local_file_timestamp = || {
+            local_path
+                .metadata()
+                .ok()
+                .map(|metadata| system_time_to_timestamp(metadata.modified().unwrap()))
+        };
+        let local_timestamp = get_local_file_timestamp();
+        let db_item = db::expect(|| {
+            SyncItemsEntity::find()
+                .filter(SyncItemsColumn::LocalPath.eq(local_path_string.clone()))
+                .filter(SyncItemsColumn::RemotePath.eq(remote_path_string.clone()))
+                .one(db)
+        });
+
+        // Push the item from the local machine to the remote machine. Returns the
+        // timestamp of the new file on the remote. Returns the
+        // [`crate::rclone::sync::RcloneRemoteItem`] of the item on the remote, or
+        // an [`Err<()>`] if an issue occurred (all errors are automatically added
+        // via `add_errors`).
+        let push_local_to_remote = || {
+            // Leave files that are still being written for a later pass.
+            if !local_path.is_dir() && !stats.check_settled(local_path) {
+                return Err(());
+            }
+
+            if local_path.is_dir() {
+                if !item.is_dir {
+                    if let Err(err) = audit.remove_remote(
+                        backend,
+                        sync_dir,
+                        KeptCopies::Versions,
+                        &remote_path_string,
+                        false,
+                    ) {
+                        add_error(SyncError::General(
+                            remote_path_string.clone(),
+                            err.to_string(),
+                        ));
+                        return Err(());
+                    }
+
+                    if let Err(err) = backend.mkdir(&remote_path_string) {
+                        add_error(SyncError::General(
+                            remote_path_string.clone(),
+                            err.to_string(),
+                        ));
+                        return Err(());
+                    }
+                }
+
+                sync_remote_directory(
+                    &item.path,
+                    backend,
+                    sync_dir,
+                    db,
+                    synced_items,
+                    remote_deletions,
+                    space,
+                    uploaded,
+                    audit,
+                    stats,
+                    write_sync_status,
+                    timestamps,
+                    add_error.clone(),
+                    check_open_requests.clone(),
+                    process_deletion_requests.clone(),
+                    show_progress.clone(),
+                );
+                update_ui_progress(&remote_path_string);
+            } else if space.remote_full() {
+                return Err(());
+            } else {
+                let result = if item.is_dir {
+                    audit.remove_remote(
+                        backend,
+                        sync_dir,
+                        KeptCopies::Versions,
+                        &remote_path_string,
+                        true,
+                    )
+                } else {
+                    audit.keep_remote_version(backend, sync_dir, &remote_path_string)
+                };
+                if let Err(err) = result {
+                    add_error(SyncError::General(
+                        remote_path_string.clone(),
+                        err.to_string(),
+                    ));
+                    return Err(());
+                }
+
+                if let Err(err) = space.check_upload(
+                    backend,
+                    &local_path_string,
+                    &remote_path_string,
+                    (!item.is_dir).then_some(item.size),
+                ) {
+                    add_error(err);
+                    return Err(());
+                }
+
+                if let Err(err) = run_transfer(
+                    db,
+                    sync_dir,
+                    &local_path_string,
+                    &remote_path_string,
+                    TransferDirection::Upload,
+                    || upload_file(backend, uploaded, &local_path_string, &remote_path_string),
+                )
+                .map_err(|err| {
+                    space.record_upload_error(&err);
+                    SyncError::General(remote_path_string.clone(), err.to_string())
+                })
+                .and_then(|_| {
+                    verify_transfer(
+                        backend,
+                        sync_dir,
+                        db,
+                        &local_path_string,
+                        &remote_path_string,
+                        TransferDirection::Upload,
+                    )
+                })
+                .map(|_| {
+                    uploaded.record(&local_path_string, &remote_path_string);
+                    stats.record(TransferDirection::Upload, &local_path_string);
+                }) {
+                    if !space.remote_full() {
+                        add_error(err);
+                    }
+                    return Err(());
+                }
+            }
+
+            Ok(backend.stat(&remote_path_string).unwrap().unwrap())
+        };
+
+        // Pull the item from the remote to the local machine.
+        let pull_remote_to_local = || {
+            // Make sure file types match up.
+            if local_path.exists() {
+                if local_path.is_dir() && !item.is_dir {
+                    if let Err(err) =
+                        audit.remove_local(sync_dir, KeptCopies::Versions, &local_path_string)
+                    {
+                        add_error(SyncError::General(
+                            local_path_string.clone(),
+                            err.to_string(),
+                        ));
+                        return Err(());
+                    }
+                } else if !local_path.is_dir() && item.is_dir {
+                    if let Err(err) =
+                        audit.remove_local(sync_dir, KeptCopies::Versions, &local_path_string)
+                    {
+                        add_error(SyncError::General(
+                            local_path_string.clone(),
+                            err.to_string(),
+                        ));
+                        return Err(());
+                    }
+
+                    if let Err(err) = fs::create_dir(local_path) {
+                        add_error(SyncError::General(
+                            local_path_string.clone(),
+                            err.to_string(),
+                        ));
+                        return Err(());
+                    }
+                } else if !item.is_dir
+                    && let Err(err) = audit.keep_local_version(sync_dir, &local_path_string)
+                {
+                    add_error(SyncError::General(
+                        local_path_string.clone(),
+                        err.to_string(),
+                    ));
+                    return Err(());
+                }
+            }
+
+            if item.is_dir {
+                if !local_path.exists()
+                    && let Err(err) = fs::create_dir_all(local_path)
+                {
+                    add_error(SyncError::General(
+                        local_path_string.clone(),
+                        err.to_string(),
+                    ));
+                    return Err(());
+                }
+
+                sync_remote_directory(
+                    &item.path,
+                    backend,
+                    sync_dir,
+                    db,
+                    synced_items,
+                    remote_deletions,
+                    space,
+                    uploaded,
+                    audit,
+                    stats,
+                    write_sync_status,
+                    timestamps,
+                    add_error.clone(),
+                    check_open_requests.clone(),
+                    process_deletion_requests.clone(),
+                    show_progress.clone(),
+                );
+                update_ui_progress(&remote_path_string);
+            } else if let Err(err) = space.check_download(&local_path_string, item.size) {
+                add_error(err);
+                return Err(());
+            } else if let Err(err) = run_transfer(
+                db,
+                sync_dir,
+                &local_path_string,
+                &remote_path_string,
+                TransferDirection::Download,
+                || backend.copy_to_local(&local_path_string, &remote_path_string),
+            )
+            .map_err(|err| SyncError::General(remote_path_string.clone(), err.to_string()))
+            .and_then(|_| {
+                verify_transfer(
+                    backend,
+                    sync_dir,
+                    db,
+                    &local_path_string,
+                    &remote_path_string,
+                    TransferDirection::Download,
+                )
+            })
+            .map(|_| stats.record(TransferDirection::Download, &local_path_string))
+            {
+                add_error(err);
+                return Err(());
+            }
+
+            Ok(())
+        };
+        // Delete this item from the database.
+        let delete_db_entry = || {
+            db::expect(|| async {
+                SyncItemsEntity::find()
+                    .filter(SyncItemsColumn::SyncDirId.eq(sync_dir.id))
+                    .filter(SyncItemsColumn::LocalPath.eq(local_path_string.clone()))
+                    .filter(SyncItemsColumn::RemotePath.eq(remote_path_string.clone()))
+                    .one(db)
+                    .await?
+                    .unwrap()
+                    .delete(db)
+                    .await
+            })
+        };
+        // Record a destructive operation on this item in the audit log.
+        let audit_item = |action, reason| {
+            audit.record(
+                db,
+                sync_dir,
+                action,
+                reason,
+                &local_path_string,
+                &remote_path_string,
+            )
+        };
+        // Whether syncing this item replaces the copy on the other side, instead
+        // of creating it or just syncing a directory's contents.
+        let overwrites = local_path.exists() && !(local_path.is_dir() && item.is_dir);
+
+        let decision = sync_decision::decide(
+            local_timestamp.map(|l_timestamp| ItemMeta {
+                timestamp: l_timestamp,
+                is_dir: local_path.is_dir(),
+            }),
+            Some(ItemMeta {
+                timestamp: remote_timestamp,
+                is_dir: item.is_dir,
+            }),
+            db_item.as_ref().map(|db_model| SyncRecord {
+                local_timestamp: db_model.last_local_timestamp,
+                remote_timestamp: db_model.last_remote_timestamp,
+            }),
+            timestamps,
+            initial_sync,
+        );
+
+        // If we have a database record, use that in checks.
+        if let Some(db_model) = db_item {
+            let update_db_item = |local_timestamp, remote_timestamp| {
+                let (local_inode, local_size) = get_local_file_id(local_path);
+                if write_sync_status {
+                    set_sync_status(local_path, "synced");
+                }
+                let mut active_model: SyncItemsActiveModel = db_model.clone().into();
+                active_model.last_local_timestamp = ActiveValue::Set(local_timestamp);
+                active_model.last_remote_timestamp = ActiveValue::Set(remote_timestamp);
+                active_model.local_inode = ActiveValue::Set(local_inode);
+                active_model.local_size = ActiveValue::Set(local_size);
+                db::expect(|| active_model.clone().update(db));
+            };
+
+            match decision {
+                SyncDecision::Conflict => add_error(SyncError::BothMoreCurrent(
+                    local_path_string.clone(),
+                    remote_path_string.clone(),
+                )),
+                SyncDecision::Upload => {
+                    if let Ok(rclone_item) = push_local_to_remote() {
+                        if overwrites {
+                            audit_item(AuditAction::OverwriteRemote, AuditReason::ChangedLocally);
+                        }
+                        update_db_item(
+                            get_local_file_timestamp().unwrap(),
+                            rclone_item.mod_time.unix_timestamp(),
+                        );
+                    }
+                }
+                SyncDecision::Download => {
+                    if pull_remote_to_local().is_ok() {
+                        if overwrites {
+                            audit_item(AuditAction::OverwriteLocal, AuditReason::ChangedOnRemote);
+                        }
+                        update_db_item(get_local_file_timestamp().unwrap(), remote_timestamp);
+                    }
+                }
+                // The item got deleted locally, and we need to reflect such on
+                // the server.
+                SyncDecision::DeleteRemote => {
+                    if let Err(err) = audit.remove_remote(
+                        backend,
+                        sync_dir,
+                        KeptCopies::Trash,
+                        &remote_path_string,
+                        item.is_dir,
+                    ) {
+                        add_error(SyncError::General(
+                            remote_path_string.clone(),
+                            err.to_string(),
+                        ));
+                        delete_db_entry();
+                    } else {
+                        audit_item(AuditAction::DeleteRemote, AuditReason::DeletedLocally);
+                    }
+                }
+                SyncDecision::DeleteLocal | SyncDecision::Noop => (),
+            }
+
+            continue;
+        // Otherwise just check the local timestamps against
+        // those on th remote, and record our new transaction in
+        // the database.
+        } else {
+            // The remote folder is being made to match the local one, so remove
+            // anything that's only on the remote.
+            if decision == SyncDecision::DeleteRemote {
+                if let Err(err) = audit.remove_remote(
+                    backend,
+                    sync_dir,
+                    KeptCopies::Trash,
+                    &remote_path_string,
+                    item.is_dir,
+                ) {
+                    add_error(SyncError::General(
+                        remote_path_string.clone(),
+                        err.to_string(),
+                    ));
+                } else {
+                    audit_item(AuditAction::DeleteRemote, AuditReason::MatchedLocal);
+                }
+                continue;
+            }
+
+            // If the local item exists, then sync whichever side is newer.
+            if local_timestamp.is_some() {
+                let result = match decision {
+                    SyncDecision::Upload => push_local_to_remote().map(|_| ()),
+                    SyncDecision::Download => pull_remote_to_local(),
+                    _ => Ok(()),
+                };
+                if result.is_err() {
+                    continue;
+                }
+                if overwrites {
+                    match decision {
+                        SyncDecision::Upload => audit_item(
+                            AuditAction::OverwriteRemote,
+                            AuditReason::for_overwrite_remote(initial_sync),
+                        ),
+                        SyncDecision::Download => audit_item(
+                            AuditAction::OverwriteLocal,
+                            AuditReason::for_overwrite_local(initial_sync),
+                        ),
+                        _ => (),
+                    }
+                }
+
+            // Otherwise the local item didn't exist. If it's a file that was
+            // just moved on the remote, move it locally too. Otherwise just
+            // sync it from the remote.
+            } else {
+                let moved_item = if item.is_dir {
+                    None
+                } else {
+                    let mut deletions = remote_deletions.borrow_mut();
+                    deletions
+                        .iter()
+                        .position(|db_model| {
+                            is_moved_remote_file(backend, db_model, &remote_path_string, &item)
+                        })
+                        .map(|index| deletions.remove(index))
+                };
+
+                if let Some(db_model) = moved_item {
+                    let result = fs::create_dir_all(local_path.parent().unwrap())
+                        .and_then(|_| fs::rename(&db_model.local_path, local_path));
+
+                    if let Err(err) = result {
+                        add_error(SyncError::General(
+                            local_path_string.clone(),
+                            err.to_string(),
+                        ));
+                        remote_deletions.borrow_mut().push(db_model);
+                        continue;
+                    }
+
+                    let (local_inode, local_size) = get_local_file_id(local_path);
+                    let mut active_model: SyncItemsActiveModel = db_model.into();
+                    active_model.local_path = ActiveValue::Set(local_path_string.clone());
+                    active_model.remote_path = ActiveValue::Set(remote_path_string.clone());
+                    active_model.local_inode = ActiveValue::Set(local_inode);
+                    active_model.local_size = ActiveValue::Set(local_size);
+                    db::expect(|| active_model.clone().update(db));
+                    continue;
+                } else if pull_remote_to_local().is_err() {
+                    continue;
+                }
+            }
+        }
+
+        // The local item is now guaranteed to exist. Also fetch the remote's
+        // timestamp in case it got updated above.
+        let l_timestamp = get_local_file_timestamp().unwrap();
+        let r_timestamp = match backend.stat(&remote_path_string) {
+            Ok(item) => item.unwrap().mod_time.unix_timestamp(),
+            Err(err) => {
+                add_error(SyncError::General(
+                    remote_path_string.clone(),
+                    err.to_string(),
+                ));
+                continue;
+            }
+        };
+
+        // Record the current transaction's timestamps in the database.
+        let (local_inode, local_size) = get_local_file_id(local_path);
+        if write_sync_status {
+            set_sync_status(local_path, "synced");
+        }
+        db::expect(|| {
+            SyncItemsActiveModel {
+                sync_dir_id: ActiveValue::Set(sync_dir.id),
+                local_path: ActiveValue::Set(local_path_string.clone()),
+                remote_path: ActiveValue::Set(remote_path_string.clone()),
+                last_local_timestamp: ActiveValue::Set(l_timestamp),
+                last_remote_timestamp: ActiveValue::Set(r_timestamp),
+                local_inode: ActiveValue::Set(local_inode),
+                local_size: ActiveValue::Set(local_size),
+                ..Default::default()
+            }
+            .insert(db)
+        });
+    }
+}
+
+/// Get the directories synced with `remote`, for the scheduler to pick the
+/// order they get synced in.
+pub fn remote_sync_pairs(db: &DatabaseConnection, remote: &RemotesModel) -> Vec<SyncPair> {
+    let sync_dirs = db::expect(|| {
+        SyncDirsEntity::find()
+            .filter(SyncDirsColumn::RemoteId.eq(remote.id))
+            .all(db)
+    });
+    // Directories synced with the same or nested remote folders fight over the
+    // items in them, so only the first one set up of such gets synced. This maps
+    // the IDs of the others to the local path of the first one they overlap.
+    let overlapping_dirs: HashMap<i32, String> = sync_dirs
+        .iter()
+        .filter_map(|sync_dir| {
+            sync_dirs
+                .iter()
+                .find(|other| {
+                    other.id < sync_dir.id
+                        && remote_paths_overlap(&other.remote_path, &sync_dir.remote_path)
+                })
+                .map(|other| (sync_dir.id, other.local_path.clone()))
+        })
+        .collect();
+
+    sync_dirs
+        .into_iter()
+        .map(|sync_dir| SyncPair {
+            remote: remote.clone(),
+            overlapping_dir: overlapping_dirs.get(&sync_dir.id).cloned(),
+            sync_dir,
+        })
+        .collect()
+}
+
+/// Lock `sync_dir` for syncing, so that another instance of Celeste (such as
+/// one running under a different profile, or the sync engine running without
+/// the GUI) doesn't sync it at the same time. The directory stays locked until
+/// the returned lock is dropped.
+pub fn lock_sync_dir(sync_dir: &SyncDirsModel) -> Result<FileLock, String> {
+    let lock_path = Path::new(&sync_dir.local_path).join(exclusions::SYNC_LOCK_NAME);
+    FileLock::lock(
+        &lock_path,
+        false,
+        FileOptions::new().create(true).write(true),
+    )
+    .map_err(|err| {
+        if err.kind() == io::ErrorKind::WouldBlock {
+            tr::tr!("This directory is already being synced by another instance of Celeste.")
+        } else {
+            tr::tr!("Unable to lock this directory for syncing [{}].", err)
+        }
+    })
+}
+
+/// Finish up a sync pass of `sync_dir` that started at the timestamp `started`
+/// and took `duration`: empty directories get removed if the user asked for
+/// such, the remote folder's sync state and manifest get updated, old kept
+/// copies get pruned, and the pass gets recorded. Returns whether the
+/// directory had nothing left to sync.
+#[allow(clippy::too_many_arguments)]
+pub fn finish_sync_pass<F: Fn(SyncError)>(
+    backend: &dyn SyncBackend,
+    remote: &RemotesModel,
+    sync_dir: &SyncDirsModel,
+    db: &DatabaseConnection,
+    space: &SpaceCheck,
+    audit: &AuditLog,
+    stats: &PassStats,
+    started: i64,
+    duration: Duration,
+    scheduler: &mut Scheduler,
+    last_pruned: &mut HashMap<i32, Instant>,
+    add_error: &F,
+) -> bool {
+    // Remove any empty directories if the user asked for such. Directories might
+    // not have been filled in yet if the transfer limit was reached though.
+    if sync_dir.remove_empty_dirs
+        && !stats.limit_reached()
+        && let Err(msg) = catch_panic(|| {
+            remove_empty_dirs(
+                Path::new(&sync_dir.local_path),
+                remote,
+                sync_dir,
+                db,
+                audit,
+                add_error,
+            )
+        })
+    {
+        add_error(SyncError::General(
+            sync_dir.local_path.clone(),
+            tr::tr!("An unexpected error occurred while syncing [{}].", msg),
+        ));
+    }
+
+    // Let the other devices syncing this remote folder know that this one has.
+    if let Err(err) = RemoteState::load(backend, sync_dir).and_then(|mut state| {
+        state.record_sync(
+            &SettingsModel::get(db).device_id,
+            system_time_to_timestamp(SystemTime::now()),
+        );
+        state.save(backend, sync_dir)
+    }) {
+        add_error(SyncError::General(
+            sync_dir.local_path.clone(),
+            tr::tr!("Unable to update the remote folder's sync state [{}].", err),
+        ));
+    }
+
+    // Save what's now in the remote folder, for devices that sync it for the first
+    // time.
+    if let Err(err) =
+        Manifest::generate(backend, sync_dir).and_then(|manifest| manifest.save(backend, sync_dir))
+    {
+        add_error(SyncError::General(
+            sync_dir.local_path.clone(),
+            tr::tr!("Unable to update the remote folder's manifest [{}].", err),
+        ));
+    }
+
+    // Remove the copies kept by sync passes too long ago to still be wanted.
+    if last_pruned
+        .get(&sync_dir.id)
+        .map_or(true, |pruned| pruned.elapsed() >= audit::PRUNE_INTERVAL)
+    {
+        audit::prune_kept_copies(backend, sync_dir, remote.keep_copies_days);
+        last_pruned.insert(sync_dir.id, Instant::now());
+    }
+
+    let last_errors = PassStats::last_saved_errors(db, sync_dir);
+    stats.save(db, sync_dir, started, duration);
+    stats.run_post_sync_command(&refresh_sync_dir(sync_dir, db), &remote.name);
+
+    // Let the webhook know how the pass went, if anything happened in it. Errors
+    // that haven't been fixed get found again by every pass, so they're only sent
+    // when their number changes.
+    if stats.files_uploaded.get() + stats.files_downloaded.get() > 0 {
+        let event = SyncEvent::PassFinished {
+            files_uploaded: stats.files_uploaded.get(),
+            bytes_uploaded: stats.bytes_uploaded.get(),
+            files_downloaded: stats.files_downloaded.get(),
+            bytes_downloaded: stats.bytes_downloaded.get(),
+            duration_ms: duration.as_millis().try_into().unwrap_or(i64::MAX),
+        };
+        webhooks::send(db, sync_dir, &remote.name, event);
+    }
+    if stats.errors.get() > 0 && last_errors != Some(stats.errors.get()) {
+        let event = SyncEvent::ErrorsOccurred {
+            errors: stats.errors.get(),
+        };
+        webhooks::send(db, sync_dir, &remote.name, event);
+    }
+    platform::allow_suspend();
+
+    // Once everything's been synced, every item has a record to sync it by, so the
+    // choice of which side wins the first sync isn't needed anymore.
+    if sync_dir.initial_sync.is_some()
+        && stats.errors.get() == 0
+        && !stats.limit_reached()
+        && space.shortage().is_none()
+    {
+        let mut active_model: SyncDirsActiveModel = sync_dir.clone().into();
+        active_model.initial_sync = ActiveValue::Set(None);
+        db::expect(|| active_model.clone().update(db));
+    }
+    scheduler.finished(
+        sync_dir,
+        stats.files_uploaded.get() > 0 || stats.files_settling.get() > 0 || stats.limit_reached(),
+        stats.failed(),
+    );
+
+    // Keep track of whether the directory has anything left to sync.
+    stats.files_uploaded.get() == 0
+        && stats.files_downloaded.get() == 0
+        && stats.files_settling.get() == 0
+        && stats.errors.get() == 0
+        && !stats.limit_reached()
+        && space.shortage().is_none()
+        && !space.remote_full()
+}
+
+/// The status to show for a directory once a sync pass of it has finished.
+pub fn pass_status(space: &SpaceCheck, stats: &PassStats) -> String {
+    match space.shortage() {
+        Some(shortage) => tr::tr!("Syncing paused. {}", shortage),
+        None if stats.limit_reached() => {
+            tr::tr!("Reached the limit of transfers per sync. More changes are pending.")
+        }
+        None if space.remote_full() => {
+            tr::tr!("Uploads paused, as the storage on the remote is full.")
+        }
+        None if stats.files_settling.get() > 0 => {
+            let settling = stats.files_settling.get();
+            tr::tr!(
+                "Waiting for {n} file to stop changing."
+                    | "Waiting for {n} files to stop changing." % settling
+            )
+        }
+        None => tr::tr!("Directory has finished sync checks."),
+    }
+}
+
+/// Run a sync pass of `sync_dir` against `backend`. This is the sync engine
+/// itself: local changes get synced by [`sync_local_directory`], then remote
+/// ones by [`sync_remote_directory`], and anything that went missing from the
+/// remote gets deleted locally. The status of each item being checked gets
+/// passed to `show_progress`, and any errors to `add_error`.
+#[allow(clippy::too_many_arguments)]
+pub fn sync_directory<
+    F1: Fn(SyncError) + Clone,
+    F2: Fn() + Clone,
+    F3: Fn() + Clone,
+    F4: Fn(&str) + Clone,
+>(
+    backend: &dyn SyncBackend,
+    sync_dir: &SyncDirsModel,
+    db: &DatabaseConnection,
+    space: &SpaceCheck,
+    uploaded: &UploadedFiles,
+    audit: &AuditLog,
+    stats: &PassStats,
+    write_sync_status: bool,
+    timestamps: TimestampComparison,
+    add_error: F1,
+    check_open_requests: F2,
+    process_deletion_requests: F3,
+    show_progress: F4,
+) {
+    let synced_items = SyncedItems::default();
+    // Items that went missing from the remote during `sync_local_directory`. They
+    // don't get deleted locally until after `sync_remote_directory` has run, so
+    // that items that were just moved on the remote can be moved locally instead.
+    let remote_deletions: RefCell<Vec<SyncItemsModel>> = RefCell::new(vec![]);
+
+    sync_local_directory(
+        Path::new(&sync_dir.local_path),
+        backend,
+        sync_dir,
+        db,
+        &synced_items,
+        &remote_deletions,
+        space,
+        uploaded,
+        audit,
+        stats,
+        write_sync_status,
+        timestamps,
+        add_error.clone(),
+        check_open_requests.clone(),
+        process_deletion_requests.clone(),
+        show_progress.clone(),
+    );
+    sync_remote_directory(
+        &sync_dir.remote_path,
+        backend,
+        sync_dir,
+        db,
+        &synced_items,
+        &remote_deletions,
+        space,
+        uploaded,
+        audit,
+        stats,
+        write_sync_status,
+        timestamps,
+        add_error.clone(),
+        check_open_requests,
+        process_deletion_requests,
+        show_progress,
+    );
+
+    // Any items that went missing from the remote and weren't moved elsewhere on
+    // it got deleted, so delete them locally too. If we're quitting, the remote
+    // might not have been fully checked for moves yet, so leave them for the
+    // next start. The same goes for if we stopped from running out of space, or
+    // from reaching the transfer limit.
+    if *(*CLOSE_REQUEST).lock().unwrap() || space.shortage().is_some() || stats.limit_reached() {
+        return;
+    }
+    for db_model in remote_deletions.take() {
+        if let Err(err) = audit.remove_local(sync_dir, KeptCopies::Trash, &db_model.local_path) {
+            add_error(SyncError::General(
+                db_model.local_path.clone(),
+                err.to_string(),
+            ));
+            continue;
+        }
+
+        audit.record(
+            db,
+            sync_dir,
+            AuditAction::DeleteLocal,
+            AuditReason::DeletedOnRemote,
+            &db_model.local_path,
+            &db_model.remote_path,
+        );
+        db::expect(|| db_model.clone().delete(db));
+    }
+}
+
+/// Make sure a usable Rclone binary is installed. On failure, returns the error
+/// and how to install one.
+pub fn check_rclone_binary() -> Result<(), (String, String)> {
+    let Err(err) = rclone::check_binary() else {
+        return Ok(());
+    };
+
+    let (min_major, min_minor, min_patch) = rclone::MIN_RCLONE_VERSION;
+    let min_version = format!("{min_major}.{min_minor}.{min_patch}");
+    let install_msg = tr::tr!(
+        "Celeste requires Rclone {} or newer. Install it from your distribution's package manager, or by following the instructions at https://rclone.org/install/.",
+        min_version
+    );
+    let err_msg = match err {
+        rclone::RcloneBinaryError::NotFound => tr::tr!("Unable to find Rclone"),
+        rclone::RcloneBinaryError::UnknownVersion(err) => {
+            tr::tr!(
+                "Unable to determine the installed version of Rclone [{}].",
+                err
+            )
+        }
+        rclone::RcloneBinaryError::TooOld(version) => {
+            tr::tr!("The installed version of Rclone ({}) is too old", version)
+        }
+    };
+    Err((err_msg, install_msg))
+}
+
+/// Create Celeste's config directory and database file if they don't exist,
+/// returning the path to the database. Returns a message for the user on
+/// failure.
+pub fn create_database() -> Result<PathBuf, String> {
+    let config_path = libceleste::get_config_dir();
+    if !config_path.exists() {
+        fs::create_dir_all(&config_path)
+            .map_err(|err| tr::tr!("Unable to create Celeste's config directory [{}].", err))?;
+    }
+
+    let db_path = db::path();
+    if !db_path.exists() {
+        fs::File::create(&db_path)
+            .map_err(|err| tr::tr!("Unable to create Celeste's database file [{}].", err))?;
+    }
+    Ok(db_path)
+}
+
+pub fn launch(app: &Application, options: &LaunchOptions) {
+    // Make sure a usable Rclone binary is installed.
+    if let Err((err_msg, install_msg)) = check_rclone_binary() {
+        gtk_util::show_error(&err_msg, Some(&install_msg));
+        return;
+    }
+
+    // Nothing can be done with Rclone's config while it's encrypted.
+    if rclone::config_is_encrypted() && !unlock_rclone_config() {
+        return;
+    }
+
+    // Create the configuration directory and database file if they don't exist.
+    let db_path = match create_database() {
+        Ok(db_path) => db_path,
+        Err(err) => {
+            gtk_util::show_error(&err, None);
+            return;
+        }
+    };
+
+    // Open the database. If that fails, such as from a migration that got
+    // interrupted, the user can restore it from a backup and try again.
+    let db = loop {
+        match open_database(&db_path) {
+            Ok(db) => break db,
+            Err(err) => {
+                if !backup::offer_restore(&err, &db_path) {
+                    return;
+                }
+            }
+        }
+    };
+
+    // Make sure the database agrees with Rclone's config, so that remotes and
+    // directories only one of them knows about get repaired before anything tries
+    // to use them.
+    let health_issues = match health::check(&db) {
+        Ok(issues) => issues,
+        Err(err) => {
+            gtk_util::show_error(&tr::tr!("Unable to check the database [{}].", err), None);
+            return;
+        }
+    };
+    if !health_issues.is_empty() && health::ask_repair(&health_issues) {
+        for issue in &health_issues {
+            if let Err(err) = issue.repair(&db) {
+                gtk_util::show_error(&tr::tr!("Unable to repair the database [{}].", err), None);
+                return;
+            }
+        }
+    }
+
+    // Quit cleanly when asked to by a signal, such as from systemd or pressing
+    // Ctrl+C in a terminal.
+    handle_close_signals();
+
+    // Set up our DBus connection. The tray icon can't be used without one, so
+    // handle open requests via a Unix signal instead if it's unavailable.
+    let dbus = match connect_dbus(&db) {
+        Ok(dbus) => Some(dbus),
+        Err(err) => {
+            hw_msg::warningln!("Unable to connect to the DBus session bus, so the tray icon won't be available: '{err}'.");
+            hw_msg::warningln!("Send SIGTERM to quit Celeste, or SIGUSR1 to open its window.");
+
+            glib::unix_signal_add_local(Signal::SIGUSR1 as i32, || {
+                *(*OPEN_REQUEST).lock().unwrap() = true;
+                glib::Continue(true)
+            });
+
+            None
+        }
+    };
+
+    // Also accept commands over our own socket, for when DBus isn't usable. It gets
+    // removed once we quit.
+    let _socket = ipc::serve();
+
+    if let Some(port) = options.metrics_port {
+        metrics::serve(port);
+    }
+
+    // Get our remotes.
+    let get_remotes = || {
+        db::expect(|| {
+            RemotesEntity::find()
+                .order_by_asc(RemotesColumn::Position)
+                .order_by_asc(RemotesColumn::Id)
+                .all(&db)
+        })
+    };
+    let mut remotes = get_remotes();
+
+    if remotes.is_empty() {
+        if login::login(app, &db).is_none() {
+            return;
+        }
+
+        remotes = get_remotes();
+    }
+
+    // Create the main UI.
+    let window = ApplicationWindow::builder()
+        .application(app)
+        .title(&libceleste::get_title!("Servers"))
+        .build();
+    window.add_css_class("celeste-global-padding");
+    let stack_sidebar = StackSidebar::builder()
+        .width_request(150)
+        .height_request(500)
+        .vexpand_set(true)
+        .vexpand(true)
+        .build();
+    let stack = Stack::new();
+    stack_sidebar.set_stack(&stack);
+
+    // Let remotes be reordered by dragging them around the sidebar.
+    let sidebar_drag_source = DragSource::builder().actions(DragAction::MOVE).build();
+    sidebar_drag_source.connect_prepare(glib::clone!(@weak stack_sidebar => @default-return None, move |_, x, y| {
+        sidebar_remote_at(&stack_sidebar, x, y).map(|name| ContentProvider::for_value(&name.to_value()))
+    }));
+    stack_sidebar.add_controller(&sidebar_drag_source);
+    let sidebar_drop_target = DropTarget::new(glib::Type::STRING, DragAction::MOVE);
+    sidebar_drop_target.connect_drop(glib::clone!(@weak stack_sidebar, @weak stack, @strong db => @default-return false, move |_, value, x, y| {
+        let Ok(dragged_name) = value.get::<String>() else { return false };
+        let mut names: Vec<String> = (0..stack.pages().n_items())
+            .filter_map(|index| stack.pages().item(index)?.downcast::<StackPage>().ok()?.name())
+            .map(|name| name.to_string())
+            .collect();
+        let Some(from) = names.iter().position(|name| name == &dragged_name) else { return false };
+        // Dropping below the last remote moves the dragged one to the end.
+        let to = sidebar_remote_at(&stack_sidebar, x, y)
+            .and_then(|target_name| names.iter().position(|name| name == &target_name))
+            .unwrap_or(names.len() - 1);
+
+        let name = names.remove(from);
+        names.insert(to, name);
+        reorder_remotes(&stack, &names, &db);
+        true
+    }));
+    stack_sidebar.add_controller(&sidebar_drop_target);
+
+    let directory_map: DirectoryMap = Rc::new(RefCell::new(IndexMap::new()));
+
+    // Store any remote deletions (values of the remote names) in a queue so they
+    // can be processed when syncing is at a good point of stopping.
+    let remote_deletion_queue: RemoteDeletionQueue = Rc::new(RefCell::new(vec![]));
+
+    // Store any sync deletions (the remote + local directory + remote directory) in
+    // a queue so they can be processed when syncing is at a good point of stopping.
+    let sync_dir_deletion_queue: SyncDirDeletionQueue = Rc::new(RefCell::new(vec![]));
+
+    // Store any requests to undo a directory's last sync, so they can be processed
+    // before the directory is next synced.
+    let sync_dir_undo_queue: SyncDirUndoQueue = Rc::new(RefCell::new(vec![]));
+
+    // The remotes that are currently mounted.
+    let mount_map: MountMap = Rc::new(RefCell::new(HashMap::new()));
+    let storage_banners: StorageBannerMap = Rc::new(RefCell::new(HashMap::new()));
+    let dir_adders: DirAdderMap = Rc::new(RefCell::new(HashMap::new()));
+
+    // Add servers.
+    let gen_remote_window = glib::clone!(@strong window, @strong remote_deletion_queue, @strong sync_dir_deletion_queue, @strong sync_dir_undo_queue, @strong directory_map, @strong mount_map, @strong storage_banners, @strong dir_adders, @strong db => move |remote: RemotesModel| {
         let remote_name = remote.name;
 
         // The stack containing the window of sync status', as well as extra information for each sync pair.
@@ -314,8 +4761,60 @@ pub fn launch(app: &Application, background: bool) {
             .css_classes(vec!["boxed-list".to_string()])
             .build();
 
+        // Let directories be reordered by dragging their rows around the list.
+        let sync_dirs_drop_target = DropTarget::new(glib::Type::STRING, DragAction::MOVE);
+        sync_dirs_drop_target.connect_drop(glib::clone!(@weak sync_dirs, @strong remote_name, @strong directory_map, @strong db => @default-return false, move |_, value, _, y| {
+            let Ok(dragged_name) = value.get::<String>() else { return false };
+            let dmap = directory_map.get_ref();
+            let Some(dirs) = dmap.get(&remote_name) else { return false };
+            // Rows from other remotes' lists can't be dropped here.
+            let Some(dragged_row) = dirs
+                .iter()
+                .find(|((local_path, remote_path), _)| format!("{local_path}/{remote_path}") == dragged_name)
+                .map(|(_, dir)| dir.container.clone())
+            else { return false };
+
+            // Dropping below the last directory moves the dragged one to the end.
+            let to = sync_dirs.row_at_y(y as i32).map_or(-1, |row| row.index());
+            sync_dirs.remove(&dragged_row);
+            sync_dirs.insert(&dragged_row, to);
+
+            let rows: Vec<ListBoxRow> = (0..).map_while(|index| sync_dirs.row_at_index(index)).collect();
+            db::expect(|| async {
+                for (index, row) in rows.iter().enumerate() {
+                    let Some(((local_path, remote_path), _)) = dirs.iter().find(|(_, dir)| &dir.container == row) else { continue };
+                    let Some(sync_dir) = SyncDirsEntity::find()
+                        .filter(SyncDirsColumn::LocalPath.eq(local_path.clone()))
+                        .filter(SyncDirsColumn::RemotePath.eq(remote_path.clone()))
+                        .one(&db)
+                        .await?
+                    else { continue };
+                    let mut active_model: SyncDirsActiveModel = sync_dir.into();
+                    active_model.position = ActiveValue::Set(reordered_position(index, rows.len()));
+                    active_model.update(&db).await?;
+                }
+
+                Ok::<_, DbErr>(())
+            });
+            true
+        }));
+        sync_dirs.add_controller(&sync_dirs_drop_target);
+
+        // Show a directory's more info page when its row gets activated.
+        sync_dirs.connect_row_activated(glib::clone!(@weak sections, @strong remote_name, @strong directory_map => move |_, row| {
+            let dmap = directory_map.get_ref();
+            let dir = dmap
+                .get(&remote_name)
+                .and_then(|dirs| dirs.iter().find(|(_, dir)| &dir.container == row));
+
+            if let Some(((local_path, remote_path), dir)) = dir {
+                (dir.update_error_ui)();
+                sections.set_visible_child_name(&format!("{local_path}/{remote_path}"));
+            }
+        }));
+
         // Add a directory to the stack.
-        let add_dir = glib::clone!(@weak window, @weak sections, @weak page, @weak sync_dirs, @strong remote_name, @strong directory_map, @strong sync_dir_deletion_queue => move |
+        let add_dir = glib::clone!(@weak window, @weak sections, @weak page, @weak sync_dirs, @strong remote_name, @strong directory_map, @strong sync_dir_deletion_queue, @strong sync_dir_undo_queue, @strong db => move |
             server_name: String,
             local_path: String,
             remote_path: String,
@@ -332,16 +4831,18 @@ pub fn launch(app: &Application, background: bool) {
             row_sections.append(&status_container);
 
             let text_sections = Box::builder().orientation(Orientation::Vertical).valign(Align::Center).margin_start(10).margin_end(10).margin_top(5).margin_bottom(5).build();
-            let title = {
-                let sections = Box::builder().orientation(Orientation::Horizontal).build();
-                let local_label = Label::builder().label(&formatted_local_path).ellipsize(EllipsizeMode::Start).build();
-                let remote_label = Label::builder().label(&formatted_remote_path).ellipsize(EllipsizeMode::Start).build();
-                let arrow = Image::builder().icon_name("go-next-symbolic").build();
+            // Show the local and remote paths side by side when there's enough room for them, and stacked on top of each other otherwise (such as on phones).
+            let title = Squeezer::builder().xalign(0.0).build();
+            for (orientation, arrow_icon) in [(Orientation::Horizontal, "go-next-symbolic"), (Orientation::Vertical, "go-down-symbolic")] {
+                let sections = Box::builder().orientation(orientation).build();
+                let local_label = Label::builder().label(&formatted_local_path).halign(Align::Start).ellipsize(EllipsizeMode::Start).build();
+                let remote_label = Label::builder().label(&formatted_remote_path).halign(Align::Start).ellipsize(EllipsizeMode::Start).build();
+                let arrow = Image::builder().icon_name(arrow_icon).halign(Align::Start).build();
                 sections.append(&local_label);
                 sections.append(&arrow);
                 sections.append(&remote_label);
-                sections
-            };
+                title.add(&sections);
+            }
             let text_status_container = Box::builder().orientation(Orientation::Horizontal).build();
             let error_status = Label::builder()
                 .halign(Align::Start)
@@ -383,6 +4884,7 @@ pub fn launch(app: &Application, background: bool) {
                 .build();
 
             // The errors section.
+            let more_info_errors_header = Box::builder().orientation(Orientation::Horizontal).build();
             let more_info_errors_label = Label::builder()
             .label(&tr::tr!("Sync Errors"))
             .halign(Align::Start)
@@ -393,8 +4895,157 @@ pub fn launch(app: &Application, background: bool) {
             .margin_bottom(10)
             .css_classes(vec!["heading".to_string()])
             .build();
+            let more_info_resolve_conflicts_button = Button::builder()
+                .label(&tr::tr!("Resolve All"))
+                .tooltip_text(&tr::tr!("Resolve all conflicts in this directory"))
+                .halign(Align::End)
+                .hexpand(true)
+                .margin_end(5)
+                .visible(false)
+                .build();
+            more_info_resolve_conflicts_button.connect_clicked(glib::clone!(@strong db, @strong directory_map, @strong server_name, @strong local_path, @strong remote_path => move |button| {
+                let path_pair = (local_path.clone(), remote_path.clone());
+                let (conflicts, remove_error) = {
+                    let ptr = directory_map.get_ref();
+                    let item = &ptr[&server_name][&path_pair];
+                    (item.conflicts(), item.remove_error.clone())
+                };
+                let Some(remove_error) = remove_error else { return };
+                let Some(choice) = ask_conflict_choice(conflicts.len()) else { return };
+                let Some(sync_dir) = db::expect(|| {
+                    SyncDirsEntity::find()
+                        .filter(SyncDirsColumn::LocalPath.eq(local_path.clone()))
+                        .filter(SyncDirsColumn::RemotePath.eq(remote_path.clone()))
+                        .one(&db)
+                }) else { return };
+                let remote = db::expect(|| RemotesEntity::find_by_id(sync_dir.remote_id).one(&db)).unwrap();
+
+                button.set_sensitive(false);
+                let mut failures = vec![];
+                for error in &conflicts {
+                    let SyncError::BothMoreCurrent(local_item, remote_item) = error else { unreachable!() };
+                    match resolve_conflict(&db, &sync_dir, &remote, local_item, remote_item, choice) {
+                        Ok(()) => remove_error(error),
+                        Err(err) => failures.push(format!("{}: {err}", libceleste::fmt_home(local_item))),
+                    }
+                }
+                button.set_sensitive(true);
+
+                if !failures.is_empty() {
+                    gtk_util::show_error(
+                        &tr::tr!("Unable to resolve {n} conflict." | "Unable to resolve {n} conflicts." % failures.len()),
+                        Some(&failures.join("\n")),
+                    );
+                }
+            }));
+            more_info_errors_header.append(&more_info_errors_label);
+            more_info_errors_header.append(&more_info_resolve_conflicts_button);
             let more_info_errors_list = ListBox::builder().selection_mode(SelectionMode::None).css_classes(vec!["boxed-list".to_string()]).margin_top(5).margin_end(5).margin_bottom(5).margin_start(5).build();
             let more_info_errors_list_scrolled = ScrolledWindow::builder().child(&more_info_errors_list).valign(Align::Start).visible(false).build();
+            let more_info_hidden_errors_button = Button::builder().halign(Align::Start).visible(false).margin_start(5).margin_bottom(5).css_classes(vec!["flat".to_string()]).build();
+            // Add the next errors that didn't fit in the error list to it. This is kept
+            // on the directory, so the directory map is only held weakly.
+            more_info_hidden_errors_button.connect_clicked(glib::clone!(@weak directory_map, @strong server_name, @strong local_path, @strong remote_path => move |_| {
+                let path_pair = (local_path.clone(), remote_path.clone());
+                let (errors, show_error_row) = {
+                    let mut ptr = directory_map.get_mut_ref();
+                    let item = ptr.get_mut(&server_name).unwrap().get_mut(&path_pair).unwrap();
+                    let num_shown = item.hidden_errors.len().min(MAX_SHOWN_ERRORS);
+                    (item.hidden_errors.drain(..num_shown).collect::<Vec<_>>(), item.show_error_row.clone())
+                };
+                let Some(show_error_row) = show_error_row else { return };
+                for error in errors {
+                    show_error_row(error);
+                }
+                directory_map.get_ref()[&server_name][&path_pair].update_hidden_errors_button();
+            }));
+
+            // The sync options.
+            let more_info_options_label = Label::builder()
+                .label(&tr::tr!("Options"))
+                .halign(Align::Start)
+                .margin_top(20)
+                .margin_bottom(10)
+                .css_classes(vec!["heading".to_string()])
+                .build();
+            let more_info_options_list = ListBox::builder().selection_mode(SelectionMode::None).css_classes(vec!["boxed-list".to_string()]).margin_top(5).margin_end(5).margin_bottom(5).margin_start(5).build();
+            let get_db_sync_dir = glib::clone!(@strong db, @strong local_path, @strong remote_path => move || {
+                db::expect(|| {
+                    SyncDirsEntity::find()
+                        .filter(SyncDirsColumn::LocalPath.eq(local_path.clone()))
+                        .filter(SyncDirsColumn::RemotePath.eq(remote_path.clone()))
+                        .one(&db)
+                })
+            });
+            let update_db_sync_dir = glib::clone!(@strong db, @strong get_db_sync_dir => move |update: &dyn Fn(&mut SyncDirsActiveModel)| {
+                if let Some(sync_dir) = get_db_sync_dir() {
+                    let mut active_model: SyncDirsActiveModel = sync_dir.into();
+                    update(&mut active_model);
+                    db::expect(|| active_model.clone().update(&db));
+                }
+            });
+
+            let remove_empty_dirs_switch = Switch::builder()
+                .valign(Align::Center)
+                .active(get_db_sync_dir().map(|sync_dir| sync_dir.remove_empty_dirs).unwrap_or(false))
+                .build();
+            remove_empty_dirs_switch.connect_state_set(glib::clone!(@strong update_db_sync_dir => move |_, state| {
+                update_db_sync_dir(&|active_model: &mut SyncDirsActiveModel| active_model.remove_empty_dirs = ActiveValue::Set(state));
+                Inhibit(false)
+            }));
+            let remove_empty_dirs_row = ActionRow::builder()
+                .title(&tr::tr!("Remove empty folders after sync"))
+                .subtitle(&tr::tr!("Empty folders will be removed both locally and on the remote."))
+                .activatable_widget(&remove_empty_dirs_switch)
+                .build();
+            remove_empty_dirs_row.add_suffix(&remove_empty_dirs_switch);
+            more_info_options_list.append(&remove_empty_dirs_row);
+
+            let high_priority_switch = Switch::builder()
+                .valign(Align::Center)
+                .active(get_db_sync_dir().map(|sync_dir| sync_dir.high_priority).unwrap_or(false))
+                .build();
+            high_priority_switch.connect_state_set(glib::clone!(@strong update_db_sync_dir => move |_, state| {
+                update_db_sync_dir(&|active_model: &mut SyncDirsActiveModel| active_model.high_priority = ActiveValue::Set(state));
+                Inhibit(false)
+            }));
+            let high_priority_row = ActionRow::builder()
+                .title(&tr::tr!("High priority"))
+                .subtitle(&tr::tr!("This folder is synced before any others, and is checked again every 30 seconds while they're syncing."))
+                .activatable_widget(&high_priority_switch)
+                .build();
+            high_priority_row.add_suffix(&high_priority_switch);
+            more_info_options_list.append(&high_priority_row);
+
+            // A command that can skip syncs, such as while something is using the synced files.
+            let pre_sync_command_row = EntryRow::builder()
+                .title(&tr::tr!("Command to run before syncing"))
+                .show_apply_button(true)
+                .build();
+            pre_sync_command_row.set_text(&get_db_sync_dir().map(|sync_dir| sync_dir.pre_sync_command).unwrap_or_default());
+            pre_sync_command_row.set_tooltip_text(Some(&tr::tr!(
+                "Run through the shell in this folder before each sync. The sync is skipped if it fails, with the last line it printed shown as the reason."
+            )));
+            pre_sync_command_row.connect_apply(glib::clone!(@strong update_db_sync_dir => move |row| {
+                let command = row.text().to_string();
+                update_db_sync_dir(&|active_model: &mut SyncDirsActiveModel| active_model.pre_sync_command = ActiveValue::Set(command.clone()));
+            }));
+            more_info_options_list.append(&pre_sync_command_row);
+
+            // A command to run after each sync, such as to rebuild something from the synced files.
+            let post_sync_command_row = EntryRow::builder()
+                .title(&tr::tr!("Command to run after syncing changes"))
+                .show_apply_button(true)
+                .build();
+            post_sync_command_row.set_text(&get_db_sync_dir().map(|sync_dir| sync_dir.post_sync_command).unwrap_or_default());
+            post_sync_command_row.set_tooltip_text(Some(&tr::tr!(
+                "Run through the shell in this folder whenever a sync transfers files or runs into errors, with the results in CELESTE_FILES_UPLOADED, CELESTE_FILES_DOWNLOADED, CELESTE_ERRORS and CELESTE_STATUS."
+            )));
+            post_sync_command_row.connect_apply(glib::clone!(@strong update_db_sync_dir => move |row| {
+                let command = row.text().to_string();
+                update_db_sync_dir(&|active_model: &mut SyncDirsActiveModel| active_model.post_sync_command = ActiveValue::Set(command.clone()));
+            }));
+            more_info_options_list.append(&post_sync_command_row);
 
             // The exclusion list.
             let more_info_exclusions_header = Box::builder().orientation(Orientation::Horizontal).margin_top(20).margin_bottom(10).build();
@@ -410,38 +5061,139 @@ pub fn launch(app: &Application, background: bool) {
                 .icon_name("list-add-symbolic")
                 .halign(Align::End)
                 .build();
+            gtk_util::set_accessible_label(&more_info_exclusions_add_button, &tr::tr!("Add an exclusion rule"));
             more_info_exclusions_header.append(&more_info_exclusions_label);
             more_info_exclusions_header.append(&more_info_exclusions_add_button);
             let more_info_exclusions_list = ListBox::builder().selection_mode(SelectionMode::None).css_classes(vec!["boxed-list".to_string()]).valign(Align::Start).margin_top(5).margin_end(5).margin_bottom(5).margin_start(5).build();
             let more_info_exclusions_list_scrolled = ScrolledWindow::builder().child(&more_info_exclusions_list).vexpand_set(true).vexpand(true).build();
 
-            // Read the ignore file to see if anything exists in it so far.
-            let file_ignore_path_string = format!("{local_path}/{FILE_IGNORE_NAME}");
-            let get_lock = glib::clone!(@strong file_ignore_path_string => move || {
-                // This will return an [`Err`] if the parent folder doesn't exist, so handle that case instead of `.unwrap`ing it.
-                FileLock::lock(&file_ignore_path_string, true, FileOptions::new().create(true).read(true).write(true).append(false))
-            });
-
-            let file_ignore_content = if get_lock().is_ok() {
-                Some(fs::read_to_string(&file_ignore_path_string).unwrap())
-            } else {
-                None
-            };
+            // Read the exclusion rules to see if any exist so far.
+            let file_ignore_content = get_db_sync_dir().map(|sync_dir| read_exclusions(&sync_dir, &db));
 
             let ignore_rules: Rc<RefCell<IndexMap<EntryRow, String>>> = Rc::new(RefCell::new(IndexMap::new()));
-            let write_file = glib::clone!(@strong file_ignore_path_string, @strong ignore_rules, @strong get_lock => move || {
+            let write_file = glib::clone!(@strong db, @strong ignore_rules, @strong get_db_sync_dir => move || {
                 let ptr = ignore_rules.get_ref();
                 let strings: Vec<String> = ptr.values().map(|item| item.to_owned()).collect();
 
-                // First truncate the file.
-                OpenOptions::new().write(true).truncate(true).open(&file_ignore_path_string).unwrap();
+                if let Some(sync_dir) = get_db_sync_dir() && let Err(err) = write_exclusions(&sync_dir, &db, &strings.join("\n")) {
+                    hw_msg::warningln!("Unable to save exclusion rules for '{}': '{err}'.", sync_dir.local_path);
+                }
+            });
 
-                // And then write to it.
-                if let Ok(mut lock) = get_lock() {
-                    lock.file.write_all(strings.join("\n").as_bytes()).unwrap()
+            // Where the exclusion rules are stored.
+            let exclusions_in_db = get_db_sync_dir().map(|sync_dir| sync_dir.exclusions_in_db).unwrap_or(false);
+            let ignore_file_name_row = EntryRow::builder()
+                .title(&tr::tr!("Exclusion file name"))
+                .sensitive(!exclusions_in_db)
+                .build();
+            ignore_file_name_row.set_text(&get_db_sync_dir().map(|sync_dir| sync_dir.ignore_file_name).unwrap_or_else(|| exclusions::DEFAULT_IGNORE_FILE_NAME.to_owned()));
+            ignore_file_name_row.connect_changed(|row| {
+                // The file has to go directly in the sync directory, and can't be our lock file.
+                let text = row.text();
+                if text.is_empty() || text.contains('/') || text.as_str() == exclusions::SYNC_LOCK_NAME {
+                    row.set_show_apply_button(false);
+                    row.add_css_class("error");
+                } else {
+                    row.remove_css_class("error");
+                    row.set_show_apply_button(true);
+                }
+            });
+            ignore_file_name_row.connect_apply(glib::clone!(@strong db, @strong get_db_sync_dir => move |row| {
+                let sync_dir = match get_db_sync_dir() {
+                    Some(sync_dir) => sync_dir,
+                    None => return,
                 };
+                let new_name = row.text().to_string();
+
+                // Move the existing rules over, so the old file doesn't start getting synced.
+                let old_path = sync_dir.ignore_file_path();
+                let new_path = Path::new(&sync_dir.local_path).join(&new_name);
+                if old_path.exists() && let Err(err) = fs::rename(&old_path, &new_path) {
+                    gtk_util::show_error(&tr::tr!("Unable to rename the exclusion file [{}].", err), None);
+                    row.set_text(&sync_dir.ignore_file_name);
+                    return;
+                }
+
+                let mut active_model: SyncDirsActiveModel = sync_dir.into();
+                active_model.ignore_file_name = ActiveValue::Set(new_name);
+                db::expect(|| active_model.clone().update(&db));
+            }));
+            more_info_options_list.append(&ignore_file_name_row);
+
+            let exclusions_in_db_switch = Switch::builder()
+                .valign(Align::Center)
+                .active(exclusions_in_db)
+                .build();
+            exclusions_in_db_switch.connect_state_set(glib::clone!(@strong update_db_sync_dir, @strong get_db_sync_dir, @strong write_file, @weak ignore_file_name_row => @default-return Inhibit(false), move |_, state| {
+                update_db_sync_dir(&|active_model: &mut SyncDirsActiveModel| active_model.exclusions_in_db = ActiveValue::Set(state));
+                ignore_file_name_row.set_sensitive(!state);
+
+                // Move the current rules over to their new location.
+                write_file();
+                if state && let Some(sync_dir) = get_db_sync_dir() {
+                    let ignore_file_path = sync_dir.ignore_file_path();
+                    if ignore_file_path.exists() && let Err(err) = fs::remove_file(&ignore_file_path) {
+                        hw_msg::warningln!("Unable to remove ignore file '{}': '{err}'.", ignore_file_path.display());
+                    }
+                }
+
+                Inhibit(false)
+            }));
+            let exclusions_in_db_row = ActionRow::builder()
+                .title(&tr::tr!("Keep exclusions out of the synced folder"))
+                .subtitle(&tr::tr!("Exclusion rules will be stored by Celeste instead of in a file in the folder."))
+                .activatable_widget(&exclusions_in_db_switch)
+                .build();
+            exclusions_in_db_row.add_suffix(&exclusions_in_db_switch);
+            more_info_options_list.append(&exclusions_in_db_row);
+
+            // The patterns of the only files to sync.
+            let inclusions_row = EntryRow::builder()
+                .title(&tr::tr!("Only sync files matching (such as *.jpg;*.raw)"))
+                .build();
+            inclusions_row.set_text(&get_db_sync_dir().map(|sync_dir| sync_dir.inclusions).unwrap_or_default());
+            inclusions_row.connect_changed(|row| {
+                if let Err(err) = exclusions::validate_inclusions(row.text().as_str()) {
+                    row.set_show_apply_button(false);
+                    row.add_css_class("error");
+                    row.set_tooltip_text(Some(&err.to_string()));
+                } else {
+                    row.remove_css_class("error");
+                    row.set_tooltip_text(None);
+                    row.set_show_apply_button(true);
+                }
             });
-            let gen_ignore_row = glib::clone!(@strong get_lock, @strong write_file, @strong ignore_rules, @strong more_info_exclusions_list => move |content: Option<String>| {
+            inclusions_row.connect_apply(glib::clone!(@strong update_db_sync_dir => move |row| {
+                let inclusions = row.text().to_string();
+                update_db_sync_dir(&|active_model: &mut SyncDirsActiveModel| active_model.inclusions = ActiveValue::Set(inclusions.clone()));
+            }));
+            more_info_options_list.append(&inclusions_row);
+
+            // How recently files have to have been modified to get synced.
+            let max_file_age_button = SpinButton::builder()
+                .valign(Align::Center)
+                .adjustment(&Adjustment::new(
+                    get_db_sync_dir().map(|sync_dir| sync_dir.max_file_age_days).unwrap_or(0).into(),
+                    0.0,
+                    36500.0,
+                    1.0,
+                    30.0,
+                    0.0,
+                ))
+                .build();
+            max_file_age_button.connect_value_changed(glib::clone!(@strong update_db_sync_dir => move |button| {
+                let days = button.value_as_int();
+                update_db_sync_dir(&|active_model: &mut SyncDirsActiveModel| active_model.max_file_age_days = ActiveValue::Set(days));
+            }));
+            let max_file_age_row = ActionRow::builder()
+                .title(&tr::tr!("Only sync files modified in the last (days)"))
+                .subtitle(&tr::tr!("Older files are left alone on both sides, such as in large archives where only recent work matters. Set to 0 for no limit."))
+                .activatable_widget(&max_file_age_button)
+                .build();
+            max_file_age_row.add_suffix(&max_file_age_button);
+            more_info_options_list.append(&max_file_age_row);
+
+            let gen_ignore_row = glib::clone!(@strong write_file, @strong ignore_rules, @strong more_info_exclusions_list => move |content: Option<String>| {
                 let row = EntryRow::builder().css_classes(vec!["celeste-no-title".to_string()]).build();
                 if let Some(text) = content {
                     row.set_text(&text);
@@ -449,7 +5201,8 @@ pub fn launch(app: &Application, background: bool) {
                     row.set_show_apply_button(true);
                 }
                 let remove_button = Button::builder().icon_name("list-remove-symbolic").valign(Align::Center).css_classes(vec!["flat".to_string()]).build();
-                row.connect_apply(glib::clone!(@strong get_lock, @strong write_file, @strong ignore_rules => move |row| {
+                gtk_util::set_accessible_label(&remove_button, &tr::tr!("Remove this exclusion rule"));
+                row.connect_apply(glib::clone!(@strong write_file, @strong ignore_rules => move |row| {
                     // Make sure our ignore rules has the latest string for this item.
                     let mut ptr = ignore_rules.get_mut_ref();
                     ptr.insert(row.clone(), row.text().to_string());
@@ -458,7 +5211,7 @@ pub fn launch(app: &Application, background: bool) {
                     // Write out all the current ignore rules to the file.
                     write_file();
                 }));
-                remove_button.connect_clicked(glib::clone!(@strong get_lock, @strong write_file, @strong ignore_rules, @weak row, @weak more_info_exclusions_list => move |_| {
+                remove_button.connect_clicked(glib::clone!(@strong write_file, @strong ignore_rules, @weak row, @weak more_info_exclusions_list => move |_| {
                     row.set_sensitive(false);
                     more_info_exclusions_list.remove(&row);
 
@@ -501,6 +5254,14 @@ pub fn launch(app: &Application, background: bool) {
                 }
             }
 
+            // Add a new rule to the exclusion list, such as when excluding an item from the error list.
+            let add_exclusion = glib::clone!(@strong gen_ignore_row, @strong write_file, @strong ignore_rules, @weak more_info_exclusions_list => move |rule: String| {
+                let row = gen_ignore_row(Some(rule.clone()));
+                more_info_exclusions_list.append(&row);
+                ignore_rules.get_mut_ref().insert(row, rule);
+                write_file();
+            });
+
             // The back button to go back to the main page.
             let more_info_back_button = Button::builder()
                 .icon_name("go-previous-symbolic")
@@ -508,6 +5269,7 @@ pub fn launch(app: &Application, background: bool) {
                 .hexpand_set(true)
                 .hexpand(true)
                 .build();
+            gtk_util::set_accessible_label(&more_info_back_button, &tr::tr!("Back"));
             more_info_back_button.connect_clicked(glib::clone!(@weak sections => move |_| {
                 // Temporarily reverse the transition direction so it looks like we're going back a page.
                 let previous_transition_type = sections.transition_type();
@@ -515,20 +5277,76 @@ pub fn launch(app: &Application, background: bool) {
                 sections.set_visible_child_name("main");
                 sections.set_transition_type(previous_transition_type);
             }));
-            let more_info_delete_button = Button::builder()
-                .icon_name("user-trash-symbolic")
-                .has_tooltip(true)
-                .tooltip_text(&tr::tr!("Stop syncing this directory"))
-                .halign(Align::End)
-                .build();
+            let more_info_delete_button = Button::builder()
+                .icon_name("user-trash-symbolic")
+                .halign(Align::End)
+                .build();
+            gtk_util::set_accessible_label(&more_info_delete_button, &tr::tr!("Stop syncing this directory"));
+
+            // The button to copy a share link for the remote directory, which is disabled if the remote doesn't support such.
+            let more_info_share_button = Button::builder()
+                .icon_name("emblem-shared-symbolic")
+                .halign(Align::End)
+                .margin_end(10)
+                .build();
+            if !supports_links(&db, &server_name) {
+                more_info_share_button.set_sensitive(false);
+                more_info_share_button.set_tooltip_text(Some(&tr::tr!("This remote doesn't support share links")));
+            }
+            gtk_util::set_accessible_label(&more_info_share_button, &tr::tr!("Copy share link"));
+            more_info_share_button.connect_clicked(glib::clone!(@strong server_name, @strong remote_path => move |more_info_share_button| {
+                copy_share_link(more_info_share_button, &server_name, &remote_path);
+            }));
+
+            // The button to undo the directory's last sync.
+            let more_info_undo_button = Button::builder()
+                .icon_name("edit-undo-symbolic")
+                .halign(Align::End)
+                .margin_end(10)
+                .build();
+            gtk_util::set_accessible_label(&more_info_undo_button, &tr::tr!("Undo the last sync"));
+            more_info_undo_button.connect_clicked(glib::clone!(@strong db, @strong sync_dir_undo_queue, @strong server_name, @strong local_path, @strong remote_path, @strong formatted_local_path => move |_| {
+                let sync_dir = db::expect(|| {
+                    SyncDirsEntity::find()
+                        .filter(SyncDirsColumn::LocalPath.eq(local_path.clone()))
+                        .filter(SyncDirsColumn::RemotePath.eq(remote_path.clone()))
+                        .one(&db)
+                });
+                let Some(last_pass) = sync_dir.and_then(|sync_dir| audit::last_pass(&db, sync_dir.id, i64::MAX)) else {
+                    gtk_util::show_error(&tr::tr!("There's no sync of '{}' to undo.", formatted_local_path), Some(&tr::tr!("Nothing has been deleted or replaced while syncing it.")));
+                    return;
+                };
+
+                let dialog = MessageDialog::builder()
+                    .text(&tr::tr!("Undo the sync of '{}' from {}?", formatted_local_path, audit::format_timestamp(last_pass)))
+                    .secondary_text(&tr::tr!("Items it deleted or replaced will be restored where copies of them were kept. Replaced items will then show up as conflicts, so you can pick which copy to keep."))
+                    .buttons(ButtonsType::YesNo)
+                    .build();
+                dialog.connect_response(glib::clone!(@strong sync_dir_undo_queue, @strong server_name, @strong local_path, @strong remote_path => move |dialog, resp| {
+                    match resp {
+                        ResponseType::Yes => {
+                            sync_dir_undo_queue.get_mut_ref().push((server_name.clone(), local_path.clone(), remote_path.clone()));
+                            AppAction::SyncNow.run();
+                        },
+                        ResponseType::No => (),
+                        _ => return,
+                    }
+
+                    dialog.close();
+                }));
+                dialog.show();
+            }));
 
             // Store the pages element's in a vector. When the delete button is pressed and we confirm a deletion, we want the entire page to not be sensitive except for the back button, and we do that by only making the back button sensitive.
             let more_info_widgets: Vec<Widget> = vec![
-                more_info_errors_label.clone().into(),
+                more_info_errors_header.clone().into(),
                 more_info_errors_list_scrolled.clone().into(),
+                more_info_options_list.clone().into(),
                 more_info_exclusions_header.clone().into(),
                 more_info_exclusions_list_scrolled.clone().into(),
                 more_info_back_button.clone().into(),
+                more_info_share_button.clone().into(),
+                more_info_undo_button.clone().into(),
                 more_info_delete_button.clone().into(),
             ];
             more_info_delete_button.connect_clicked(glib::clone!(@strong sync_dir_deletion_queue, @strong server_name, @strong local_path, @strong remote_path, @strong formatted_local_path, @strong formatted_remote_path, @weak sections, @weak more_info_back_button, @weak more_info_delete_button, @strong more_info_widgets => move |_| {
@@ -559,19 +5377,22 @@ pub fn launch(app: &Application, background: bool) {
                 dialog.show();
             }));
             more_info_header_buttons.append(&more_info_back_button);
+            more_info_header_buttons.append(&more_info_share_button);
+            more_info_header_buttons.append(&more_info_undo_button);
             more_info_header_buttons.append(&more_info_delete_button);
             more_info_page.append(&more_info_header_buttons);
-            more_info_page.append(&more_info_errors_label);
+            more_info_page.append(&more_info_errors_header);
             more_info_page.append(&more_info_errors_list_scrolled);
+            more_info_page.append(&more_info_hidden_errors_button);
+            more_info_page.append(&more_info_options_label);
+            more_info_page.append(&more_info_options_list);
             more_info_page.append(&more_info_exclusions_header);
             more_info_page.append(&more_info_exclusions_list_scrolled);
 
-            // Show the window upon click.
             let stack_child_name = format!("{local_path}/{remote_path}");
-            let gesture = GestureClick::new();
-            let update_error_list = glib::clone!(@weak error_status, @weak more_info_errors_list_scrolled => move || {
+            let update_error_list = glib::clone!(@weak error_status, @weak more_info_errors_list, @weak more_info_errors_list_scrolled => move || {
                 // Ensure the errors section is set up correctly.
-                let num_errors = error_status.text().as_str().split_whitespace().next().unwrap_or("0").parse::<i32>().unwrap();
+                let num_errors = more_info_errors_list.observe_children().n_items();
 
                 // Hide the section if we have no errors.
                 if num_errors == 0 {
@@ -590,14 +5411,17 @@ pub fn launch(app: &Application, background: bool) {
                 }
             });
 
-            gesture.connect_released(glib::clone!(@weak sections, @strong stack_child_name, @strong update_error_list  => move |_, _, _, _| {
-                update_error_list();
-                sections.set_visible_child_name(&stack_child_name);
-            }));
-            sync_status_sections.add_controller(&gesture);
-
             // Add the items to the directory map.
             let sync_status_sections_container = ListBoxRow::builder().child(&sync_status_sections).build();
+            let drag_source = DragSource::builder().actions(DragAction::MOVE).build();
+            drag_source.connect_prepare(glib::clone!(@strong stack_child_name => move |_, _, _| {
+                Some(ContentProvider::for_value(&stack_child_name.to_value()))
+            }));
+            drag_source.connect_drag_begin(glib::clone!(@weak sync_status_sections_container => move |drag_source, _| {
+                let icon = WidgetPaintable::new(Some(&sync_status_sections_container));
+                drag_source.set_icon(Some(&icon), 0, 0);
+            }));
+            sync_status_sections_container.add_controller(&drag_source);
             let mut dmap = directory_map.borrow_mut();
 
             if !dmap.contains_key(&server_name_owned) {
@@ -615,7 +5439,13 @@ pub fn launch(app: &Application, background: bool) {
                     error_label: more_info_errors_label,
                     error_list: more_info_errors_list,
                     error_items: HashMap::new(),
-                    update_error_ui: boxed::Box::new(update_error_list)
+                    hidden_errors: IndexSet::new(),
+                    hidden_errors_button: more_info_hidden_errors_button,
+                    show_error_row: None,
+                    remove_error: None,
+                    resolve_conflicts_button: more_info_resolve_conflicts_button,
+                    update_error_ui: boxed::Box::new(update_error_list),
+                    add_exclusion: boxed::Box::new(add_exclusion)
                 }
             );
 
@@ -624,12 +5454,17 @@ pub fn launch(app: &Application, background: bool) {
         });
 
         // Create the remote in the database if it doesn't current exist.
-        let db_remote = libceleste::await_future(
-                RemotesEntity::find()
-                    .filter(RemotesColumn::Name.eq(remote_name.clone()))
-                    .one(&db),
-            )
-            .unwrap().unwrap();
+        let db_remote = db::expect(|| {
+            RemotesEntity::find()
+                .filter(RemotesColumn::Name.eq(remote_name.clone()))
+                .one(&db)
+        })
+        .unwrap();
+
+        // Use the remote's extra Rclone options for everything done with it.
+        if let Err(err) = apply_remote_options(&db_remote) {
+            hw_msg::warningln!("Unable to use the Rclone options for '{remote_name}', so ignoring them: '{err}'.");
+        }
 
         // The directory header, directory addition button, and remote deletion button.
         {
@@ -648,7 +5483,9 @@ pub fn launch(app: &Application, background: bool) {
                 .halign(Align::End)
                 .valign(Align::Start)
                 .build();
-            new_folder_button.connect_clicked(glib::clone!(@weak window, @weak sections, @weak page, @strong remote_name, @strong sync_dirs, @strong db, @strong directory_map, @strong db_remote, @strong add_dir => @default-panic, move |_| {
+            gtk_util::set_accessible_label(&new_folder_button, &tr::tr!("Sync a new directory"));
+            // Show the dialog for syncing a new directory, with `local_path` filled in if given.
+            let new_folder_dialog = glib::clone!(@weak window, @weak sections, @weak page, @strong remote_name, @strong sync_dirs, @strong db, @strong directory_map, @strong db_remote, @strong add_dir => @default-panic, move |local_path: Option<String>| {
                 window.set_sensitive(false);
                 let folder_window = ApplicationWindow::builder()
                     .title(&libceleste::get_title!("Remote Folder Picker"))
@@ -663,6 +5500,8 @@ pub fn launch(app: &Application, background: bool) {
                     .secondary_icon_activatable(true)
                     .secondary_icon_name("folder-symbolic")
                     .secondary_icon_sensitive(true)
+                    .secondary_icon_tooltip_text(&tr::tr!("Choose a folder (Ctrl+O)"))
+                    .activates_default(true)
                     .build();
                 local_entry.connect_icon_press(glib::clone!(@weak folder_window, @weak local_label => move |local_entry, _| {
                     folder_window.set_sensitive(false);
@@ -686,12 +5525,30 @@ pub fn launch(app: &Application, background: bool) {
                         dialog.close();
                     }));
                     ok_button.connect_clicked(glib::clone!(@weak folder_window, @weak local_entry, @weak dialog => move |_| {
-                        local_entry.set_text(&dialog.file().unwrap().path().unwrap().into_os_string().into_string().unwrap());
+                        // Folders that aren't on this computer (such as ones opened through GVfs) don't have a path.
+                        let path = dialog.file().and_then(|file| file.path());
+                        match path.as_deref().map(Path::to_str) {
+                            Some(Some(path)) => local_entry.set_text(path),
+                            Some(None) => gtk_util::show_error(&tr::tr!("The selected folder's path isn't valid UTF-8, so it can't be synced."), None),
+                            None => (),
+                        }
                         dialog.close();
                     }));
                     dialog.show();
                 }));
 
+                // The folder icon can't be focused, so let the folder picker be opened from the keyboard too.
+                let local_entry_shortcut = Shortcut::new(
+                    ShortcutTrigger::parse_string("<Control>o").as_ref(),
+                    Some(&CallbackAction::new(|local_entry, _| {
+                        local_entry.emit_by_name::<()>("icon-press", &[&EntryIconPosition::Secondary]);
+                        true
+                    })),
+                );
+                let local_entry_shortcut_controller = ShortcutController::new();
+                local_entry_shortcut_controller.add_shortcut(&local_entry_shortcut);
+                local_entry.add_controller(&local_entry_shortcut_controller);
+
                 // Get the remote folder to sync with, and add it.
                 // The entry completion code is largely inspired by https://github.com/gtk-rs/gtk4-rs/blob/master/examples/entry_completion/main.rs. I honestly have no clue what half the code for that is doing, I just know the current code is working well enough, and it can be fixed later if it breaks.
                 let remote_label = Label::builder().label(&tr::tr!("Remote folder:")).halign(Align::Start).css_classes(vec!["heading".to_string()]).build();
@@ -710,7 +5567,7 @@ pub fn launch(app: &Application, background: bool) {
                 entry_completion.set_text_column(0);
                 entry_completion.set_popup_completion(true);
                 entry_completion.set_model(Some(&store));
-                let remote_entry = Entry::builder().completion(&entry_completion).build();
+                let remote_entry = Entry::builder().completion(&entry_completion).activates_default(true).build();
                 remote_entry.insert_text("/", &mut -1);
 
                 // Get the current path, up to the last '/'.
@@ -728,7 +5585,7 @@ pub fn launch(app: &Application, background: bool) {
 
                     // Get the current specified directory.
                     let current_item_text = remote_entry.text();
-                    let current_item = Path::new(current_item_text.as_str()).file_name().map(|path| path.to_str().unwrap()).unwrap_or("");
+                    let current_item = Path::new(current_item_text.as_str()).file_name().map(|path| path.to_string_lossy()).unwrap_or_default();
 
                     // Clear the current list of completions.
                     store.clear();
@@ -736,7 +5593,7 @@ pub fn launch(app: &Application, background: bool) {
                     // See if any of the currently stored matches start with the same characters as
                     // our path, and if they do, append them to the valid completions list.
                     for item in &store_path.get_ref().1 {
-                        if item.starts_with(current_item) {
+                        if item.starts_with(&*current_item) {
                             store.set(&store.append(), &[(0, item)]);
                         }
                     }
@@ -754,8 +5611,8 @@ pub fn launch(app: &Application, background: bool) {
 
                     // The last component of the directory specified by the user.
                     let remote_entry_text = remote_entry.text().to_string();
-                    let entry_final_path_item = Path::new(&remote_entry_text).file_name().map(|path| path.to_str().unwrap()).unwrap_or("");
-                    text_value.starts_with(entry_final_path_item)
+                    let entry_final_path_item = Path::new(&remote_entry_text).file_name().map(|path| path.to_string_lossy()).unwrap_or_default();
+                    text_value.starts_with(&*entry_final_path_item)
                 }));
 
                 entry_completion.connect_match_selected(glib::clone!(@weak remote_entry => @default-panic, move |_, model, iter| {
@@ -791,7 +5648,7 @@ pub fn launch(app: &Application, background: bool) {
                 // Update the stored list of autocompletions to the parent of those of the currently typed in directory.
                 let update_options = glib::clone!(@strong remote_name, @strong store_path, @weak remote_entry, @strong update_completions, @strong get_current_path => move || {
                     let current_path = get_current_path();
-                    let current_path_string = current_path.as_os_str().to_owned().into_string().unwrap();
+                    let current_path_string = current_path.to_string_lossy().into_owned();
 
                     let items = if let Ok(items) = rclone::sync::list(&remote_name, &current_path_string, false, RcloneListFilter::Dirs) {
                         items.into_iter().map(|item| item.name).collect()
@@ -830,8 +5687,50 @@ pub fn launch(app: &Application, background: bool) {
                     }
                 }));
 
+                // Google Drive remotes can use a shared drive or the files shared with the user instead, as long as nothing on them is being synced yet.
+                if let Some(rclone::Remote::GDrive(_)) = rclone::get_remote(&remote_name) {
+                    let (target_row, targets) = login::login_util::gdrive_target_row(&remote_name);
+                    let has_sync_dirs = db::expect(|| SyncDirsEntity::find().filter(SyncDirsColumn::RemoteId.eq(db_remote.id)).one(&db)).is_some();
+                    if has_sync_dirs {
+                        target_row.set_sensitive(false);
+                        target_row.set_subtitle(&tr::tr!("This can't be changed while directories on this remote are being synced."));
+                    }
+                    target_row.connect_selected_notify(glib::clone!(@strong remote_name => move |target_row| {
+                        rclone::set_gdrive_target(&remote_name, &targets[target_row.selected() as usize]);
+                    }));
+                    let target_list = ListBox::builder()
+                        .selection_mode(SelectionMode::None)
+                        .css_classes(vec!["boxed-list".to_string()])
+                        .build();
+                    target_list.append(&target_row);
+                    folder_sections.append(&target_list);
+                    folder_sections.append(&Separator::builder().orientation(Orientation::Vertical).css_classes(vec!["spacer".to_string()]).build());
+                }
+
+                // Buttons for quickly syncing the user's common folders, which sync them to a folder of the same name on the remote.
+                let common_dirs_box = Box::builder().orientation(Orientation::Horizontal).spacing(6).margin_top(6).build();
+                let home_dir = glib::home_dir();
+                for user_dir in [UserDirectory::Documents, UserDirectory::Pictures, UserDirectory::Music] {
+                    // Unset user directories fall back to the home directory, which isn't what these are for.
+                    let Some(path) = glib::user_special_dir(user_dir).filter(|path| path != &home_dir && path.is_dir()) else { continue };
+                    let (Some(path), Some(name)) = (path.to_str().map(str::to_owned), path.file_name().and_then(|name| name.to_str()).map(str::to_owned)) else { continue };
+                    let button = Button::builder()
+                        .label(&name)
+                        .tooltip_text(&libceleste::fmt_home(&path))
+                        .css_classes(vec!["pill".to_string()])
+                        .build();
+                    button.connect_clicked(glib::clone!(@weak local_entry, @weak remote_entry, @strong path, @strong name => move |_| {
+                        local_entry.set_text(&path);
+                        remote_entry.set_text(&format!("/{name}"));
+                    }));
+                    common_dirs_box.append(&button);
+                }
+
                 folder_sections.append(&local_label);
                 folder_sections.append(&local_entry);
+                if common_dirs_box.first_child().is_some() {
+                    folder_sections.append(&common_dirs_box);
+                }
                 folder_sections.append(&Separator::builder().orientation(Orientation::Vertical).css_classes(vec!["spacer".to_string()]).build());
                 folder_sections.append(&remote_label);
                 folder_sections.append(&remote_entry);
@@ -847,20 +5746,66 @@ pub fn launch(app: &Application, background: bool) {
                 // Also initialize the button as non-clickable.
                 ok_button.set_sensitive(false);
 
-                local_entry.connect_changed(glib::clone!(@weak ok_button, @weak remote_entry => move |local_entry| {
-                    if local_entry.to_string().is_empty() || remote_entry.to_string().is_empty() {
-                        ok_button.set_sensitive(false);
-                    } else {
-                        ok_button.set_sensitive(true);
-                    }
-                }));
-                remote_entry.connect_changed(glib::clone!(@weak ok_button, @weak local_entry => move |remote_entry| {
-                    if local_entry.to_string().is_empty() || remote_entry.to_string().is_empty() {
-                        ok_button.set_sensitive(false);
-                    } else {
-                        ok_button.set_sensitive(true);
-                    }
+                // Let the dialog be confirmed with Enter, and cancelled with Escape.
+                folder_window.set_default_widget(Some(&ok_button));
+                let escape_shortcut = Shortcut::new(
+                    ShortcutTrigger::parse_string("Escape").as_ref(),
+                    Some(&CallbackAction::new(glib::clone!(@weak cancel_button => @default-return false, move |_, _| {
+                        cancel_button.emit_clicked();
+                        true
+                    }))),
+                );
+                let escape_shortcut_controller = ShortcutController::new();
+                escape_shortcut_controller.add_shortcut(&escape_shortcut);
+                folder_window.add_controller(&escape_shortcut_controller);
+
+                // Whether the remote directory exists, which gets checked as it's typed in. This is
+                // [`None`] while it's being checked, or if it couldn't be, and the dialog can't be
+                // confirmed until it's known.
+                let remote_exists: Rc<Cell<Option<bool>>> = Rc::new(Cell::new(None));
+                // Incremented on every change to the remote directory, so that checks of what was
+                // typed in before can be told apart.
+                let remote_check_id = Rc::new(Cell::new(0u32));
+
+                let update_ok_button = glib::clone!(@weak ok_button, @weak local_entry, @weak remote_entry, @strong remote_exists => move || {
+                    ok_button.set_sensitive(!local_entry.text().is_empty() && !remote_entry.text().is_empty() && remote_exists.get().is_some());
+                });
+                local_entry.connect_changed(glib::clone!(@strong update_ok_button => move |_| update_ok_button()));
+                remote_entry.connect_changed(glib::clone!(@strong remote_name, @strong remote_exists, @strong remote_check_id, @strong update_ok_button => move |remote_entry| {
+                    remote_exists.set(None);
+                    remote_entry.set_secondary_icon_name(None);
+                    update_ok_button();
+                    let check_id = remote_check_id.get().wrapping_add(1);
+                    remote_check_id.set(check_id);
+
+                    glib::timeout_add_local_once(REMOTE_PATH_CHECK_DELAY, glib::clone!(@weak remote_entry, @strong remote_name, @strong remote_exists, @strong remote_check_id, @strong update_ok_button => move || {
+                        // The remote directory has been changed again since.
+                        if remote_check_id.get() != check_id {
+                            return;
+                        }
+
+                        let remote_path = libceleste::strip_slashes(remote_entry.text().as_str());
+                        MainContext::default().spawn_local(async move {
+                            let result = blocking::unblock(move || rclone::sync::stat(&remote_name, &remote_path)).await;
+                            if remote_check_id.get() != check_id {
+                                return;
+                            }
+
+                            let (exists, icon, tooltip) = match result {
+                                Ok(Some(item)) if item.is_dir => (Some(true), "emblem-ok-symbolic", tr::tr!("The remote directory exists")),
+                                Ok(Some(_)) => (None, "dialog-error-symbolic", tr::tr!("The specified remote path isn't a directory")),
+                                Ok(None) => (Some(false), "dialog-warning-symbolic", tr::tr!("The remote directory doesn't exist, and will be created")),
+                                Err(err) => (None, "dialog-error-symbolic", format!("{}\n{}", tr::tr!("Failed to check if the specified remote directory exists"), err.message())),
+                            };
+                            remote_entry.set_secondary_icon_name(Some(icon));
+                            remote_entry.set_secondary_icon_tooltip_text(Some(&tooltip));
+                            remote_exists.set(exists);
+                            update_ok_button();
+                        });
+                    }));
                 }));
+                // The initial remote directory needs checking too.
+                remote_entry.emit_by_name::<()>("changed", &[]);
 
                 folder_window.connect_close_request(glib::clone!(@strong window => move |_| {
                     window.set_sensitive(true);
@@ -870,38 +5815,42 @@ pub fn launch(app: &Application, background: bool) {
                     folder_window.close();
                     window.set_sensitive(true);
                 }));
-                ok_button.connect_clicked(glib::clone!(@strong window, @weak sections, @weak folder_window, @weak sync_dirs, @weak local_entry, @weak remote_entry, @strong db_remote, @strong db, @weak directory_map, @strong remote_name, @strong add_dir => move |_| {
+                ok_button.connect_clicked(glib::clone!(@strong window, @weak sections, @weak folder_window, @weak sync_dirs, @weak local_entry, @weak remote_entry, @strong remote_exists, @strong db_remote, @strong db, @weak directory_map, @strong remote_name, @strong add_dir => move |_| {
                     folder_window.set_sensitive(false);
 
-                    // The local path needs to start with a slash, but not end with one. The remote
-                    // needs to not start or end with a slash.
-                    let local_text = "/".to_string() + &libceleste::strip_slashes(local_entry.text().as_str());
+                    // The local path can't end with a separator, and the remote needs to not start
+                    // or end with a slash.
+                    let local_text = paths::local_path_from_input(local_entry.text().as_str());
                     let remote_text = libceleste::strip_slashes(remote_entry.text().as_str());
                     let local_path = Path::new(&local_text);
-                    match rclone::sync::stat(&remote_name, &remote_text) {
-                        Ok(path) => {
-                            if path.is_none() {
-                                gtk_util::show_error(&tr::tr!("The specified remote directory doesn't exist"), None);
-                                folder_window.set_sensitive(true);
-                                return;
-                            } else {
-                                path
-                            }
-                        },
-                        Err(err) => {
-                            gtk_util::show_error(&tr::tr!("Failed to check if the specified remote directory exists"), Some(&err.error));
+                    // Whether the remote directory exists was already checked as it was typed in.
+                    if remote_exists.get() == Some(false) {
+                        if !ask_create_remote_dir(&remote_text) {
                             folder_window.set_sensitive(true);
                             return;
                         }
-                    };
 
-                    let sync_dir = libceleste::await_future(
-                        SyncDirsEntity::find().filter(SyncDirsColumn::LocalPath.eq(local_text.clone())).filter(SyncDirsColumn::RemotePath.eq(remote_text.clone())).one(&db)
-                    ).unwrap();
+                        // Rclone creates any missing parent directories along with it.
+                        if let Err(err) = rclone::sync::mkdir(&remote_name, &remote_text) {
+                            gtk_util::show_error(&tr::tr!("Failed to create the specified remote directory"), Some(err.message()));
+                            folder_window.set_sensitive(true);
+                            return;
+                        }
+                    }
+
+                    let sync_dir = db::expect(|| SyncDirsEntity::find().filter(SyncDirsColumn::LocalPath.eq(local_text.clone())).filter(SyncDirsColumn::RemotePath.eq(remote_text.clone())).one(&db));
+                    // Two directories synced with the same or nested remote folders would fight over the items in them.
+                    let overlapping_dir = db::expect(|| SyncDirsEntity::find().filter(SyncDirsColumn::RemoteId.eq(db_remote.id)).all(&db)).into_iter().find(|other| remote_paths_overlap(&other.remote_path, &remote_text));
 
                     if sync_dir.is_some() {
                         gtk_util::show_error(&tr::tr!("The specified directory pair is already being synced"), None);
                         folder_window.set_sensitive(true);
+                    } else if let Some(overlapping_dir) = overlapping_dir {
+                        gtk_util::show_error(
+                            &tr::tr!("The specified remote directory overlaps one that's already being synced"),
+                            Some(&tr::tr!("'{}' is synced with '{}' on this remote. Syncing the same remote items from two local directories makes them conflict with each other.", overlapping_dir.local_path, overlapping_dir.remote_path)),
+                        );
+                        folder_window.set_sensitive(true);
                     } else if !local_path.exists() {
                         gtk_util::show_error(&tr::tr!("The specified local directory doesn't exist"), None);
                         folder_window.set_sensitive(true);
@@ -911,29 +5860,84 @@ pub fn launch(app: &Application, background: bool) {
                     } else if !local_path.is_absolute() {
                         gtk_util::show_error(&tr::tr!("The specified local directory needs to be an absolute path"), None);
                         folder_window.set_sensitive(true);
+                    } else if local_path_has_config(local_path) {
+                        gtk_util::show_error(
+                            &tr::tr!("The specified local directory contains Celeste's configuration"),
+                            Some(&tr::tr!("Syncing '{}' would upload Celeste's database and Rclone config, which breaks Celeste on other devices that sync them.", libceleste::get_config_dir().display())),
+                        );
+                        folder_window.set_sensitive(true);
                     } else {
-                        libceleste::await_future(
+                        let sync_dir = db::expect(|| {
                             SyncDirsActiveModel {
                                 remote_id: ActiveValue::Set(db_remote.id),
                                 local_path: ActiveValue::Set(local_text.clone()),
                                 remote_path: ActiveValue::Set(remote_text.clone()),
                                 ..Default::default()
                             }.insert(&db)
-                        ).unwrap();
+                        });
+
+                        // If both sides already have files that differ, let the user choose which side wins the first sync.
+                        // Otherwise just let them know how much the first sync will transfer.
+                        let filter = exclusions::SyncFilter::new(&sync_dir, &read_exclusions(&sync_dir, &db))
+                            .with_default_exclusions(SettingsModel::get(&db).exclude_temp_files);
+                        let timestamps = TimestampComparison::new(&db_remote, &db);
+                        let initial_sync = match SyncPreview::generate(&RcloneBackend::new(&remote_name).with_capabilities(RemoteCapabilities::get(&db, &db_remote)), &sync_dir, &filter, timestamps) {
+                            Ok(preview) if preview.has_differences() => ask_initial_sync(&sync_dir, &preview),
+                            Ok(preview) => confirm_first_sync(&sync_dir, &preview.transfers(InitialSync::Merge)).then_some(InitialSync::Merge),
+                            Err(err) => {
+                                hw_msg::warningln!("Unable to check the contents of '{}' before its first sync: '{err}'.", sync_dir.local_path);
+                                Some(InitialSync::Merge)
+                            }
+                        };
+
+                        match initial_sync {
+                            Some(initial_sync) => if let Some(name) = initial_sync.setting_name() {
+                                let mut active_model: SyncDirsActiveModel = sync_dir.clone().into();
+                                active_model.initial_sync = ActiveValue::Set(Some(name.to_owned()));
+                                db::expect(|| active_model.clone().update(&db));
+                            },
+                            None => {
+                                db::expect(|| health::delete_sync_dir(&db, sync_dir.clone()));
+                                folder_window.set_sensitive(true);
+                                return;
+                            }
+                        }
+
                         add_dir(remote_name.clone(), local_text, remote_text);
                         folder_window.close();
                     }
                 }));
 
+                if let Some(local_path) = local_path {
+                    local_entry.set_text(&local_path);
+                }
+
                 folder_window.set_content(Some(&folder_sections));
                 folder_window.show();
+            });
+            new_folder_button.connect_clicked(glib::clone!(@strong new_folder_dialog => move |_| new_folder_dialog(None)));
+
+            // Folders dropped onto the page from a file manager get synced through the same dialog.
+            let folder_drop_target = DropTarget::new(FileList::static_type(), DragAction::COPY);
+            folder_drop_target.connect_drop(glib::clone!(@strong new_folder_dialog => @default-return false, move |_, value, _, _| {
+                let Ok(file_list) = value.get::<FileList>() else { return false };
+                let Some(path) = file_list.files().into_iter().filter_map(|file| file.path()).find(|path| path.is_dir()) else { return false };
+
+                match path.to_str() {
+                    Some(path) => new_folder_dialog(Some(path.to_owned())),
+                    None => gtk_util::show_error(&tr::tr!("The selected folder's path isn't valid UTF-8, so it can't be synced."), None),
+                }
+                true
             }));
+            page.add_controller(&folder_drop_target);
+
             let delete_remote_button = Button::builder()
                 .icon_name("user-trash-symbolic")
                 .halign(Align::End)
                 .valign(Align::Start)
                 .margin_start(10)
                 .build();
+            gtk_util::set_accessible_label(&delete_remote_button, &tr::tr!("Delete this remote"));
             delete_remote_button.connect_clicked(glib::clone!(@strong remote_deletion_queue, @strong page, @strong remote_name => move |delete_remote_button| {
                 page.set_sensitive(false);
                 let dialog = MessageDialog::builder()
@@ -956,21 +5960,290 @@ pub fn launch(app: &Application, background: bool) {
                 }));
                 dialog.show();
             }));
+            // The button for editing the extra Rclone flags used for the remote.
+            let flags_button = Button::builder()
+                .icon_name("preferences-other-symbolic")
+                .halign(Align::End)
+                .valign(Align::Start)
+                .margin_start(10)
+                .build();
+            gtk_util::set_accessible_label(&flags_button, &tr::tr!("Advanced remote options"));
+            flags_button.connect_clicked(glib::clone!(@weak window, @strong db, @strong db_remote, @strong remote_name => move |_| {
+                window.set_sensitive(false);
+                let flags_window = ApplicationWindow::builder()
+                    .title(&libceleste::get_title!("Advanced Options"))
+                    .build();
+                flags_window.add_css_class("celeste-global-padding");
+                let flags_sections = Box::builder().orientation(Orientation::Vertical).build();
+                let flags_label = Label::builder()
+                    .label(&tr::tr!("Extra Rclone flags to use for everything done with this remote, such as '--drive-export-formats=docx'. Only flags for this type of remote can be used."))
+                    .halign(Align::Start)
+                    .wrap(true)
+                    .max_width_chars(50)
+                    .build();
+
+                // The options might have been changed since this page was created, so get them from the database.
+                let current_remote = db::expect(|| RemotesEntity::find_by_id(db_remote.id).one(&db)).unwrap();
+
+                // How to sync Google Docs, for Google Drive remotes.
+                let google_docs_choices = google_docs_choices();
+                let google_docs_model = StringList::new(&google_docs_choices.iter().map(|(label, _)| label.as_str()).collect::<Vec<_>>());
+                let google_docs_row = ComboRow::builder()
+                    .title(&tr::tr!("Google Docs, Sheets, and Slides"))
+                    .subtitle(&tr::tr!("These can only be downloaded as exported copies, and changes to those copies aren't uploaded."))
+                    .model(&google_docs_model)
+                    .build();
+                let current_index = google_docs_choices
+                    .iter()
+                    .position(|(_, formats)| *formats == current_remote.google_docs_formats)
+                    .unwrap_or(1);
+                google_docs_row.set_selected(current_index as u32);
+
+                // Whether deletions skip the trash, for remotes where that can be chosen.
+                let permanent_delete_switch = Switch::builder()
+                    .valign(Align::Center)
+                    .active(current_remote.permanent_delete)
+                    .build();
+                let permanent_delete_row = ActionRow::builder()
+                    .title(&tr::tr!("Delete permanently"))
+                    .subtitle(&tr::tr!("Items deleted while syncing skip the remote's trash, so they can't be recovered from it."))
+                    .activatable_widget(&permanent_delete_switch)
+                    .build();
+                permanent_delete_row.add_suffix(&permanent_delete_switch);
+
+                // Whether copies of removed and replaced items are kept on the remote, and for how long copies are kept on both sides.
+                let keep_remote_copies_switch = Switch::builder()
+                    .valign(Align::Center)
+                    .active(current_remote.keep_remote_copies)
+                    .build();
+                let keep_remote_copies_row = ActionRow::builder()
+                    .title(&tr::tr!("Keep copies on the remote"))
+                    .subtitle(&tr::tr!("Copies of items removed or replaced while syncing are kept on the remote so that syncs can be undone, which uses up its storage. Removed items aren't kept when deleting permanently."))
+                    .activatable_widget(&keep_remote_copies_switch)
+                    .build();
+                keep_remote_copies_row.add_suffix(&keep_remote_copies_switch);
+                let keep_copies_days_button = SpinButton::builder()
+                    .valign(Align::Center)
+                    .adjustment(&Adjustment::new(current_remote.keep_copies_days.into(), 1.0, 3650.0, 1.0, 30.0, 0.0))
+                    .build();
+                let keep_copies_days_row = ActionRow::builder()
+                    .title(&tr::tr!("Keep copies for (days)"))
+                    .subtitle(&tr::tr!("Copies of removed and replaced items older than this are deleted, both locally and on the remote."))
+                    .activatable_widget(&keep_copies_days_button)
+                    .build();
+                keep_copies_days_row.add_suffix(&keep_copies_days_button);
+
+                // The folder on the remote that Celeste is restricted to. Sync directories are relative to it, so it can't be changed once any are set up.
+                let root_folder_row = EntryRow::builder()
+                    .title(&tr::tr!("Root folder (empty to use the whole remote)"))
+                    .text(&current_remote.root_folder)
+                    .build();
+                let has_sync_dirs = db::expect(|| SyncDirsEntity::find().filter(SyncDirsColumn::RemoteId.eq(db_remote.id)).one(&db)).is_some();
+                if has_sync_dirs {
+                    root_folder_row.set_sensitive(false);
+                    root_folder_row.set_tooltip_text(Some(&tr::tr!("This can't be changed while directories on this remote are being synced.")));
+                }
+
+                let options_list = ListBox::builder()
+                    .selection_mode(SelectionMode::None)
+                    .css_classes(vec!["boxed-list".to_string()])
+                    .margin_bottom(10)
+                    .build();
+                options_list.append(&root_folder_row);
+                if let Some(rclone::Remote::GDrive(_)) = rclone::get_remote(&remote_name) {
+                    options_list.append(&google_docs_row);
+                    options_list.append(&permanent_delete_row);
+                }
+                options_list.append(&keep_remote_copies_row);
+                options_list.append(&keep_copies_days_row);
+                flags_sections.append(&options_list);
+
+                let flags_buffer = TextBuffer::builder().text(&current_remote.rclone_flags).build();
+                let flags_view = TextView::builder()
+                    .buffer(&flags_buffer)
+                    .monospace(true)
+                    .wrap_mode(WrapMode::WordChar)
+                    .build();
+                let flags_scrolled = ScrolledWindow::builder()
+                    .child(&flags_view)
+                    .min_content_height(100)
+                    .vexpand(true)
+                    .margin_top(10)
+                    .build();
+
+                flags_sections.append(&flags_label);
+                flags_sections.append(&flags_scrolled);
+                let confirm_box = Box::builder().orientation(Orientation::Horizontal).spacing(10).halign(Align::End).build();
+                let cancel_button = Button::with_label(&tr::tr!("Cancel"));
+                let ok_button = Button::with_label(&tr::tr!("Ok"));
+                confirm_box.append(&cancel_button);
+                confirm_box.append(&ok_button);
+                flags_sections.append(&Separator::builder().orientation(Orientation::Vertical).css_classes(vec!["spacer".to_string()]).build());
+                flags_sections.append(&confirm_box);
+
+                flags_window.connect_close_request(glib::clone!(@strong window => move |_| {
+                    window.set_sensitive(true);
+                    Inhibit(false)
+                }));
+                cancel_button.connect_clicked(glib::clone!(@weak flags_window => move |_| {
+                    flags_window.close();
+                }));
+                ok_button.connect_clicked(glib::clone!(@weak flags_window, @weak flags_buffer, @weak google_docs_row, @weak permanent_delete_switch, @weak keep_remote_copies_switch, @weak keep_copies_days_button, @weak root_folder_row, @strong db, @strong current_remote => move |_| {
+                    let mut new_remote = current_remote.clone();
+                    new_remote.rclone_flags = flags_buffer.text(&flags_buffer.start_iter(), &flags_buffer.end_iter(), false).to_string();
+                    new_remote.google_docs_formats = google_docs_choices()[google_docs_row.selected() as usize].1.to_owned();
+                    new_remote.root_folder = libceleste::strip_slashes(&root_folder_row.text());
+                    new_remote.permanent_delete = permanent_delete_switch.is_active();
+                    new_remote.keep_remote_copies = keep_remote_copies_switch.is_active();
+                    new_remote.keep_copies_days = keep_copies_days_button.value_as_int();
+
+                    if let Err(err) = apply_remote_options(&new_remote) {
+                        gtk_util::show_error(&tr::tr!("The Rclone flags for this remote aren't valid."), Some(&err));
+                        return;
+                    }
+
+                    // Make sure a new root folder exists, so that it doesn't get created by accident from a typo.
+                    if new_remote.root_folder != current_remote.root_folder
+                        && !new_remote.root_folder.is_empty()
+                        && let Err(err) = rclone::sync::list(&new_remote.name, "", false, RcloneListFilter::Dirs)
+                    {
+                        apply_remote_options(&current_remote).unwrap_or(());
+                        let err_msg = match err {
+                            rclone::RcloneError::NotFound(_) => tr::tr!("The folder doesn't exist on the remote."),
+                            err => err.to_string(),
+                        };
+                        gtk_util::show_error(&tr::tr!("Unable to use '{}' as the root folder.", new_remote.root_folder), Some(&err_msg));
+                        return;
+                    }
+
+                    let mut active_model: RemotesActiveModel = current_remote.clone().into();
+                    active_model.rclone_flags = ActiveValue::Set(new_remote.rclone_flags);
+                    active_model.google_docs_formats = ActiveValue::Set(new_remote.google_docs_formats);
+                    active_model.root_folder = ActiveValue::Set(new_remote.root_folder);
+                    active_model.permanent_delete = ActiveValue::Set(new_remote.permanent_delete);
+                    active_model.keep_remote_copies = ActiveValue::Set(new_remote.keep_remote_copies);
+                    active_model.keep_copies_days = ActiveValue::Set(new_remote.keep_copies_days);
+                    db::expect(|| active_model.clone().update(&db));
+                    flags_window.close();
+                }));
+
+                flags_window.set_content(Some(&flags_sections));
+                flags_window.show();
+            }));
+
+            // The button for mounting the remote, and the label showing the mount's status.
+            let mount_button = ToggleButton::builder()
+                .icon_name("drive-harddisk-symbolic")
+                .halign(Align::End)
+                .valign(Align::Start)
+                .margin_start(10)
+                .build();
+            gtk_util::set_accessible_label(&mount_button, &tr::tr!("Mount remote"));
+            let mount_status = Label::builder()
+                .halign(Align::Start)
+                .visible(false)
+                .css_classes(vec!["caption".to_string(), "dim-label".to_string()])
+                .ellipsize(EllipsizeMode::End)
+                .build();
+
+            // Record the mount point in the database, so the remote can be mounted again on the next launch.
+            let set_mount_point = glib::clone!(@strong db, @strong db_remote => move |mount_point: Option<String>| {
+                let mut active_model: RemotesActiveModel = db_remote.clone().into();
+                active_model.mount_point = ActiveValue::Set(mount_point);
+                db::expect(|| active_model.clone().update(&db));
+            });
+            let start_mount = glib::clone!(@strong mount_map, @strong remote_name, @strong set_mount_point, @weak mount_button, @weak mount_status => move |mount_point: PathBuf| {
+                match Mount::start(&remote_name, &mount_point) {
+                    Ok(mount) => {
+                        let mount_point_string = mount_point.display().to_string();
+                        mount_status.set_label(&tr::tr!("Mounted at '{}'.", libceleste::fmt_home(&mount_point_string)));
+                        mount_status.set_visible(true);
+                        mount_button.set_active(true);
+                        gtk_util::set_accessible_label(&mount_button, &tr::tr!("Unmount remote"));
+                        set_mount_point(Some(mount_point_string));
+                        mount_map.get_mut_ref().insert(remote_name.clone(), RemoteMount {
+                            mount,
+                            button: mount_button.clone(),
+                            status_text: mount_status.clone()
+                        });
+                    },
+                    Err(err) => {
+                        mount_button.set_active(false);
+                        gtk_util::show_error(&tr::tr!("Unable to mount '{}'.", remote_name), Some(&err));
+                    }
+                }
+            });
+            mount_button.connect_clicked(glib::clone!(@strong mount_map, @strong remote_name, @strong set_mount_point, @strong start_mount, @weak mount_status => move |mount_button| {
+                // The button has already been toggled by the time we get here, so an inactive button means we need to unmount.
+                if !mount_button.is_active() {
+                    // Dropping the mount unmounts it.
+                    mount_map.get_mut_ref().remove(&remote_name);
+                    mount_status.set_visible(false);
+                    gtk_util::set_accessible_label(&mount_button, &tr::tr!("Mount remote"));
+                    set_mount_point(None);
+                    return;
+                }
+
+                let filter = FileFilter::new();
+                filter.add_mime_type("inode/directory");
+                let dialog = FileChooserDialog::builder()
+                    .title(&libceleste::get_title!("Mount Point Picker"))
+                    .action(FileChooserAction::SelectFolder)
+                    .select_multiple(false)
+                    .create_folders(true)
+                    .filter(&filter)
+                    .build();
+                let cancel_button = Button::with_label(&tr::tr!("Cancel"));
+                let ok_button = Button::with_label(&tr::tr!("Ok"));
+                dialog.add_action_widget(&cancel_button, ResponseType::Cancel);
+                dialog.add_action_widget(&ok_button, ResponseType::Ok);
+                // If the dialog got closed without a mount being started, reset the button.
+                dialog.connect_close_request(glib::clone!(@strong mount_map, @strong remote_name, @weak mount_button => @default-return Inhibit(false), move |_| {
+                    if !mount_map.get_ref().contains_key(&remote_name) {
+                        mount_button.set_active(false);
+                    }
+                    Inhibit(false)
+                }));
+                cancel_button.connect_clicked(glib::clone!(@weak dialog => move |_| {
+                    dialog.close();
+                }));
+                ok_button.connect_clicked(glib::clone!(@weak dialog, @strong start_mount => move |_| {
+                    let mount_point = dialog.file().unwrap().path().unwrap();
+                    dialog.close();
+                    start_mount(mount_point);
+                }));
+                dialog.show();
+            }));
+
+            // Mount the remote if it was mounted when we last ran.
+            if let Some(mount_point) = &db_remote.mount_point {
+                start_mount(PathBuf::from(mount_point));
+            }
+
             section.append(&label);
             section.append(&new_folder_button);
+            section.append(&mount_button);
+            section.append(&flags_button);
             section.append(&delete_remote_button);
             page.append(&section);
+            page.append(&mount_status);
+
+            let storage_banner = StorageBanner::new();
+            page.append(&storage_banner.container);
+            storage_banners.get_mut_ref().insert(remote_name.clone(), storage_banner);
+            dir_adders.get_mut_ref().insert(remote_name.clone(), Rc::new(add_dir.clone()));
         }
 
         // The directory listing.
         {
             // Get the currently present directories.
-            let dirs = libceleste::await_future(
+            let dirs = db::expect(|| {
                 SyncDirsEntity::find()
                     .filter(SyncDirsColumn::RemoteId.eq(db_remote.id))
-                    .all(&db),
-            )
-            .unwrap();
+                    .order_by_asc(SyncDirsColumn::Position)
+                    .order_by_asc(SyncDirsColumn::Id)
+                    .all(&db)
+            });
             // Create the entry for each directory.
             for dir in dirs {
                 add_dir(
@@ -1004,50 +6277,111 @@ pub fn launch(app: &Application, background: bool) {
         .css_classes(vec!["sidebar".to_string()])
         .build();
     let sidebar_header = HeaderBar::builder().decoration_layout("").build();
-    let sidebar_add_server_button = Button::from_icon_name("list-add-symbolic");
-    sidebar_add_server_button.connect_clicked(
-        glib::clone!(@weak app, @weak window, @weak stack, @strong gen_remote_window, @strong db => move |_| {
+    // Set up the application's actions and their keyboard shortcuts.
+    let add_remote_action = SimpleAction::new("add-remote", None);
+    add_remote_action.connect_activate(
+        glib::clone!(@weak app, @weak window, @weak stack, @strong gen_remote_window, @strong db => move |action, _| {
             window.set_sensitive(false);
+            action.set_enabled(false);
 
             if let Some(remote) = login::login(&app, &db) {
                 let window = gen_remote_window(remote.clone());
                 stack.add_titled(&window, Some(&remote.name), &remote.name);
             }
 
+            action.set_enabled(true);
             window.set_sensitive(true);
         }),
     );
+    let sync_now_action = SimpleAction::new(AppAction::SyncNow.action_name(), None);
+    sync_now_action.connect_activate(|_, _| AppAction::SyncNow.run());
+    let pause_syncing_action = SimpleAction::new(AppAction::PauseSyncing.action_name(), None);
+    pause_syncing_action.connect_activate(|_, _| AppAction::PauseSyncing.run());
+    let about_action = SimpleAction::new("about", None);
+    about_action.connect_activate(glib::clone!(@weak app => move |_, _| {
+        crate::about::about_window(&app);
+    }));
+    let preferences_action = SimpleAction::new("preferences", None);
+    preferences_action.connect_activate(glib::clone!(@weak app, @strong db => move |_, _| {
+        crate::preferences::preferences_window(&app, &db);
+    }));
+    let audit_log_action = SimpleAction::new("audit-log", None);
+    audit_log_action.connect_activate(glib::clone!(@weak app, @strong db => move |_, _| {
+        crate::audit::audit_log_window(&app, &db);
+    }));
+    let statistics_action = SimpleAction::new("statistics", None);
+    statistics_action.connect_activate(glib::clone!(@weak app, @strong db => move |_, _| {
+        crate::stats::stats_window(&app, &db);
+    }));
+    let shortcuts_action = SimpleAction::new("shortcuts", None);
+    shortcuts_action.connect_activate(glib::clone!(@weak app => move |_, _| {
+        crate::shortcuts::shortcuts_window(&app);
+    }));
+    let open_action = SimpleAction::new("open", None);
+    open_action.connect_activate(glib::clone!(@weak app, @weak window => move |_, _| {
+        app.withdraw_notification("running");
+        window.present();
+    }));
+    let quit_action = SimpleAction::new("quit", None);
+    quit_action.connect_activate(|_, _| {
+        *(*CLOSE_REQUEST).lock().unwrap() = true;
+    });
+    for action in [
+        &add_remote_action,
+        &sync_now_action,
+        &pause_syncing_action,
+        &about_action,
+        &preferences_action,
+        &audit_log_action,
+        &statistics_action,
+        &shortcuts_action,
+        &open_action,
+        &quit_action,
+    ] {
+        app.add_action(action);
+    }
+    for (action, accel, _) in crate::shortcuts::shortcuts() {
+        app.set_accels_for_action(action, &[accel]);
+    }
+
+    let sidebar_add_server_button = Button::builder()
+        .icon_name("list-add-symbolic")
+        .action_name("app.add-remote")
+        .build();
+    gtk_util::set_accessible_label(&sidebar_add_server_button, &tr::tr!("Add a server"));
     let sidebar_menu_button = Button::from_icon_name("open-menu-symbolic");
+    gtk_util::set_accessible_label(&sidebar_menu_button, &tr::tr!("Main menu"));
     let sidebar_menu_popover_sections = Box::new(Orientation::Vertical, 5);
     let sidebar_menu_popover = Popover::builder()
         .child(&sidebar_menu_popover_sections)
         .position(PositionType::Bottom)
         .build();
-    let sidebar_menu_about_button = Button::builder()
-        .label("About")
-        .css_classes(vec!["flat".to_string()])
-        .build();
-    sidebar_menu_about_button.connect_clicked(
-        glib::clone!(@weak app, @weak sidebar_menu_popover => move |_| {
+    for (label, action_name) in [
+        (tr::tr!("Sync Now"), "app.sync-now"),
+        (tr::tr!("Pause Syncing"), "app.pause-syncing"),
+        (tr::tr!("Preferences"), "app.preferences"),
+        (tr::tr!("Audit Log"), "app.audit-log"),
+        (tr::tr!("Statistics"), "app.statistics"),
+        (tr::tr!("Keyboard Shortcuts"), "app.shortcuts"),
+        (tr::tr!("About"), "app.about"),
+        (tr::tr!("Quit"), "app.quit"),
+    ] {
+        let button = Button::builder()
+            .label(&label)
+            .action_name(action_name)
+            .css_classes(vec!["flat".to_string()])
+            .build();
+        button.connect_clicked(glib::clone!(@weak sidebar_menu_popover => move |_| {
             sidebar_menu_popover.popdown();
-            crate::about::about_window(&app);
-        }),
-    );
-    let sidebar_menu_quit_button = Button::builder()
-        .label("Quit")
-        .css_classes(vec!["flat".to_string()])
-        .build();
-    sidebar_menu_quit_button.connect_clicked(glib::clone!(@weak sidebar_menu_popover => move |_| {
-        sidebar_menu_popover.popdown();
-        *(*CLOSE_REQUEST).lock().unwrap() = true;
-    }));
-    sidebar_menu_popover_sections.append(&sidebar_menu_about_button);
-    sidebar_menu_popover_sections.append(&sidebar_menu_quit_button);
+        }));
+        sidebar_menu_popover_sections.append(&button);
+    }
     sidebar_menu_popover.set_parent(&sidebar_menu_button);
     sidebar_menu_button.connect_clicked(glib::clone!(@weak sidebar_menu_popover => move |_| {
         sidebar_menu_popover.popup();
     }));
     let sidebar_nav_right_button = Button::from_icon_name("go-next-symbolic");
+    gtk_util::set_accessible_label(&sidebar_nav_right_button, &tr::tr!("Back to the server"));
     sidebar_header.pack_start(&sidebar_add_server_button);
     sidebar_header.pack_end(&sidebar_menu_button);
     sidebar_box.append(&sidebar_header);
@@ -1071,6 +6405,7 @@ pub fn launch(app: &Application, background: bool) {
         .title_widget(&stack_window_title)
         .build();
     let stack_nav_left_button = Button::from_icon_name("go-previous-symbolic");
+    gtk_util::set_accessible_label(&stack_nav_left_button, &tr::tr!("Show the server list"));
     stack_box.append(&stack_header);
     stack_box.append(&stack);
 
@@ -1111,89 +6446,373 @@ pub fn launch(app: &Application, background: bool) {
     window.set_content(Some(&sections));
 
     // We have to manually close the window when the close button is clicked for some reason. See https://matrix.to/#/!CxdTjqASmMdXwTeLsR:matrix.org/$16724077630uSZSF:hunterwittenborn.com?via=gnome.org&via=matrix.org&via=tchncs.de.
-    window.connect_close_request(|window| {
-        window.hide();
+    // Whether to use the tray icon.
+    let use_tray = match &dbus {
+        Some(dbus) if !options.no_tray => {
+            let supported = tray_supported(dbus);
+            if !supported {
+                hw_msg::warningln!("No tray icon host is running on this Wayland session, so the tray icon won't be available.");
+            }
+            supported
+        }
+        _ => false,
+    };
+
+    // Without a tray icon there'd be no way to get back to a hidden window, so
+    // minimize it instead when the user wants to keep Celeste running.
+    let background_action = if !use_tray {
+        CloseAction::Minimize
+    } else {
+        CloseAction::Hide
+    };
+    // Closing the window of the service only hides it, so that syncing carries on
+    // for frontends connected to it.
+    let cli_close_action = options
+        .close_action
+        .or(options.service.then_some(CloseAction::Hide));
+    window.connect_close_request(glib::clone!(@strong db => move |window| {
+        let close_action = cli_close_action.or_else(|| {
+            SettingsModel::get(&db)
+                .close_action
+                .as_deref()
+                .and_then(CloseAction::from_setting)
+        });
+
+        match close_action {
+            Some(close_action) => close_action.run(window),
+            None => ask_close_action(window, &db, background_action),
+        }
         Inhibit(true)
+    }));
+
+    // Show the window, start up the tray, and start syncing.
+    if !options.background {
+        window.show();
+    }
+
+    // The DBus connection to the tray icon, if it's being used.
+    let tray_dbus = dbus.as_ref().filter(|_| use_tray);
+    let mut tray_app = tray_dbus.is_some().then(TrayApp::start);
+
+    // The last status message and icon sent to the tray icon, so they can be sent
+    // again if it gets restarted.
+    let tray_status = RefCell::new(tr::tr!("Awaiting sync checks..."));
+    let tray_icon: RefCell<Option<String>> = RefCell::new(None);
+
+    let send_dbus_msg_checked = |msg: &str| match tray_dbus {
+        Some(dbus) => dbus
+            .call_method(
+                Some(libceleste::TRAY_ID),
+                libceleste::DBUS_TRAY_OBJECT,
+                Some(libceleste::TRAY_ID),
+                "UpdateStatus",
+                &(msg),
+            )
+            .map(|_| ()),
+        None => Ok(()),
+    };
+    let send_dbus_msg = |msg: &str| {
+        *tray_status.borrow_mut() = msg.to_owned();
+        ENGINE_STATUS.lock().unwrap().message = msg.to_owned();
+
+        // Without a tray icon, show the status in the window and the desktop's list of
+        // background apps instead.
+        if tray_dbus.is_none() {
+            stack_window_title.set_subtitle(msg);
+
+            if let Some(dbus) = &dbus {
+                set_background_status(dbus, msg);
+            }
+        }
+
+        if let Err(err) = send_dbus_msg_checked(msg) {
+            hw_msg::warningln!("Got error while sending message to tray icon: '{err}'.");
+        }
+    };
+    let send_dbus_fn = |func: &str| {
+        *tray_icon.borrow_mut() = Some(func.to_owned());
+
+        if let Some(dbus) = tray_dbus
+            && let Err(err) = dbus.call_method(
+                Some(libceleste::TRAY_ID),
+                libceleste::DBUS_TRAY_OBJECT,
+                Some(libceleste::TRAY_ID),
+                func,
+                &(),
+            )
+        {
+            hw_msg::warningln!("Got error while sending message to tray icon: '{err}'.");
+        }
+    };
+    let sync_errors_count = glib::clone!(@strong directory_map => move || {
+        let dmap = directory_map.get_ref();
+        let mut error_count = 0;
+
+        for remote_dirs in dmap.values() {
+            for dir in remote_dirs.values() {
+                if !dir.error_label.text().is_empty() {
+                    error_count += 1;
+                }
+            }
+        }
+
+        error_count
     });
 
-    // Show the window, start up the tray, and start syncing.
-    if !background {
-        window.show();
+    // Wait until we can successfully send the current status to the tray icon,
+    // giving up after `TRAY_STARTUP_TIMEOUT`.
+    let wait_for_tray = || {
+        let start = Instant::now();
+
+        while send_dbus_msg_checked(&tray_status.borrow()).is_err() {
+            if start.elapsed() >= TRAY_STARTUP_TIMEOUT {
+                hw_msg::warningln!("Timed out while waiting for the tray icon to start up.");
+                return;
+            }
+
+            libceleste::run_in_background(|| thread::sleep(Duration::from_millis(100)));
+        }
+
+        let func = tray_icon.borrow().clone();
+        if let Some(func) = func {
+            send_dbus_fn(&func);
+        }
+    };
+    wait_for_tray();
+
+    // Without a tray icon, let the user know Celeste is still running when it's
+    // started in the background.
+    if tray_dbus.is_none() && options.background {
+        let notification = Notification::new(&tr::tr!("Celeste is running in the background"));
+        notification.set_body(Some(&tr::tr!(
+            "Your files will keep syncing. Open Celeste to see their status."
+        )));
+        notification.set_priority(NotificationPriority::Low);
+        notification.set_default_action("app.open");
+        app.send_notification(Some("running"), &notification);
+    }
+
+    // The sync loop only checks for close requests between items, so if one comes
+    // in partway through a transfer, let the user know we're finishing it up. If
+    // it takes too long, quit anyway - the interrupted transfer was recorded, and
+    // will be redone on the next start.
+    let close_requested_at: Cell<Option<Instant>> = Cell::new(None);
+    let finishing_tray_dbus = tray_dbus.cloned();
+    glib::timeout_add_local(
+        Duration::from_millis(250),
+        glib::clone!(@weak stack_window_title => @default-return glib::Continue(false), move || {
+            if !*(*CLOSE_REQUEST).lock().unwrap() || !*(*TRANSFER_IN_PROGRESS).lock().unwrap() {
+                return glib::Continue(true);
+            }
+
+            let requested_at = match close_requested_at.get() {
+                Some(requested_at) => requested_at,
+                None => {
+                    let msg = tr::tr!("Finishing current transfer...");
+                    match &finishing_tray_dbus {
+                        Some(dbus) => {
+                            if let Err(err) = dbus.call_method(
+                                Some(libceleste::TRAY_ID),
+                                libceleste::DBUS_TRAY_OBJECT,
+                                Some(libceleste::TRAY_ID),
+                                "UpdateStatus",
+                                &(&msg),
+                            ) {
+                                hw_msg::warningln!("Got error while sending message to tray icon: '{err}'.");
+                            }
+                        }
+                        None => stack_window_title.set_subtitle(&msg),
+                    }
+
+                    let now = Instant::now();
+                    close_requested_at.set(Some(now));
+                    now
+                }
+            };
+
+            if requested_at.elapsed() >= CLOSE_TIMEOUT {
+                hw_msg::warningln!("The current transfer didn't finish in time, so quitting without it.");
+                if let Some(dbus) = &finishing_tray_dbus {
+                    close_tray(dbus);
+                }
+                process::exit(0);
+            }
+
+            glib::Continue(true)
+        }),
+    );
+
+    // The sync loop pings systemd's watchdog between items, but a single transfer
+    // can take longer than the watchdog's timeout. The transfer runs in the
+    // background while the main loop keeps going though, so ping it from here
+    // while one's in progress.
+    if options.service && sd_notify::watchdog_enabled(false, &mut 0) {
+        glib::timeout_add_local(Duration::from_secs(5), || {
+            if *(*TRANSFER_IN_PROGRESS).lock().unwrap() {
+                ping_watchdog();
+            }
+            glib::Continue(true)
+        });
     }
 
-    let tray_app = TrayApp::start();
-
-    let send_dbus_msg_checked = |msg: &str| {
-        dbus.call_method(
-            Some(libceleste::TRAY_ID),
-            libceleste::DBUS_TRAY_OBJECT,
-            Some(libceleste::TRAY_ID),
-            "UpdateStatus",
-            &(msg),
-        )
-    };
-    let send_dbus_msg = |msg: &str| {
-        if let Err(err) = send_dbus_msg_checked(msg) {
-            hw_msg::warningln!("Got error while sending message to tray icon: '{err}'.");
-        }
-    };
-    let send_dbus_fn = |func: &str| {
-        if let Err(err) = dbus.call_method(
-            Some(libceleste::TRAY_ID),
-            libceleste::DBUS_TRAY_OBJECT,
-            Some(libceleste::TRAY_ID),
-            func,
-            &(),
-        ) {
-            hw_msg::warningln!("Got error while sending message to tray icon: '{err}'.");
-        }
-    };
-    let sync_errors_count = glib::clone!(@strong directory_map => move || {
-        let dmap = directory_map.get_ref();
-        let mut error_count = 0;
+    // Everything's set up, so let systemd know we've started.
+    if options.service
+        && let Err(err) = sd_notify::notify(false, &[NotifyState::Ready])
+    {
+        hw_msg::warningln!("Unable to notify systemd that Celeste has started: '{err}'.");
+    }
 
-        for remote_dirs in dmap.values() {
-            for dir in remote_dirs.values() {
-                if !dir.error_label.text().is_empty() {
-                    error_count += 1;
-                }
+    // The number of sync errors the user was last notified about.
+    let mut notified_error_count = 0;
+    let service = options.service;
+
+    // When Rclone's config was last checked for remotes added or removed from
+    // outside of Celeste, the remotes that have gone missing from it, and the new
+    // ones the user has been offered to add.
+    let mut rclone_config_checked: Option<SystemTime> = None;
+    let mut missing_remotes: HashSet<String> = HashSet::new();
+    // The IDs of the directories that had nothing left to sync after their last
+    // pass.
+    let mut settled_dirs: HashSet<i32> = HashSet::new();
+    // Remotes that came up in the health check have already been asked about.
+    let mut offered_remotes: HashSet<String> = health_issues
+        .iter()
+        .filter_map(HealthIssue::remote_name)
+        .map(str::to_owned)
+        .collect();
+
+    // Remotes whose storage quota has been used up, along with when to try
+    // uploading to them again.
+    let mut full_remotes: HashMap<String, Instant> = HashMap::new();
+
+    // Decides the order sync directories get synced in.
+    let mut scheduler = Scheduler::default();
+
+    // When the copies kept by each directory's sync passes were last pruned.
+    let mut last_pruned: HashMap<i32, Instant> = HashMap::new();
+
+    // The IDs of the directories whose local directory or remote folder went
+    // missing and the user chose to leave alone. They aren't asked about again
+    // until the directory is back or the user chooses to sync now.
+    let mut skipped_missing_local: HashSet<i32> = HashSet::new();
+    let mut skipped_missing_remote: HashSet<i32> = HashSet::new();
+
+    // What the pre-sync command of each directory it's skipping printed.
+    let mut pre_sync_outputs: HashMap<i32, String> = HashMap::new();
+
+    // Ask the user whether to start using a remote that was added to Rclone's
+    // config from outside of Celeste.
+    let offer_remote = glib::clone!(@weak stack, @strong gen_remote_window, @strong db => move |remote_name: String| {
+        let dialog = MessageDialog::builder()
+            .text(&tr::tr!("Add the remote '{}'?", remote_name))
+            .secondary_text(&tr::tr!("It was added to Celeste's Rclone config from outside of Celeste. Adding it lets you sync directories with it."))
+            .buttons(ButtonsType::YesNo)
+            .build();
+        dialog.connect_response(glib::clone!(@weak stack, @strong gen_remote_window, @strong db, @strong remote_name => move |dialog, resp| {
+            match resp {
+                ResponseType::Yes => {
+                    let remote = db::expect(|| {
+                        RemotesActiveModel {
+                            name: ActiveValue::Set(remote_name.clone()),
+                            ..Default::default()
+                        }
+                        .insert(&db)
+                    });
+                    let window = gen_remote_window(remote.clone());
+                    stack.add_titled(&window, Some(&remote.name), &remote.name);
+                },
+                ResponseType::No => (),
+                _ => return,
             }
-        }
 
-        error_count
+            dialog.close();
+        }));
+        dialog.show();
     });
 
-    // Wait until we can successfully send a message to the tray icon.
-    while send_dbus_msg_checked(&tr::tr!("Awaiting sync checks...")).is_err() {}
-
     'main: loop {
         // If the user requested to quit the application, then close the tray icon and
         // break the loop.
         if *(*CLOSE_REQUEST).lock().unwrap() {
-            // I'm not sure when this can fail, so output an error if one is received.
-            if let Err(err) = dbus.call_method(
-                Some(libceleste::TRAY_ID),
-                libceleste::DBUS_TRAY_OBJECT,
-                Some(libceleste::TRAY_ID),
-                "Close",
-                &(),
-            ) {
-                hw_msg::warningln!("Got error while sending close request to tray icon: '{err}'.");
+            if service && let Err(err) = sd_notify::notify(false, &[NotifyState::Stopping]) {
+                hw_msg::warningln!("Unable to notify systemd that Celeste is stopping: '{err}'.");
+            }
+            if let Some(dbus) = tray_dbus {
+                close_tray(dbus);
             }
 
             break 'main;
         }
 
-        // If the user requested to open the application, then open it up.
+        if service {
+            ping_watchdog();
+        }
+
+        // If the user requested to open the application, then open it up. This gets
+        // called between each synced item, so ping systemd's watchdog here too.
         let check_open_requests = glib::clone!(@weak window => move || {
+            if service {
+                ping_watchdog();
+            }
             if *(*OPEN_REQUEST).lock().unwrap() {
                 window.show();
                 *(*OPEN_REQUEST).lock().unwrap() = false;
             }
         });
 
+        // Restart the tray icon if it has stopped running.
+        if let Some(tray_app) = &mut tray_app
+            && tray_app.restart_if_stopped()
+        {
+            wait_for_tray();
+        }
+
+        // Report any mounts that have stopped since we last checked.
+        {
+            let mut mounts = mount_map.get_mut_ref();
+            let mut stopped_mounts = vec![];
+
+            for (remote_name, remote_mount) in mounts.iter_mut() {
+                if let Some(err) = remote_mount.mount.error() {
+                    remote_mount.button.set_active(false);
+                    gtk_util::set_accessible_label(&remote_mount.button, &tr::tr!("Mount remote"));
+                    remote_mount
+                        .status_text
+                        .set_label(&tr::tr!("The mount has stopped: {}", err));
+                    stopped_mounts.push(remote_name.clone());
+                }
+            }
+
+            for remote_name in stopped_mounts {
+                mounts.remove(&remote_name);
+            }
+        }
+
         // Continue with syncing.
-        let remotes = libceleste::await_future(RemotesEntity::find().all(&db)).unwrap();
+        let remotes = db::expect(|| RemotesEntity::find().all(&db));
+
+        // Check for remotes added to or removed from Rclone's config from outside of
+        // Celeste, such as by running `rclone config` on it.
+        let config_modified = rclone_config_modified();
+        if config_modified != rclone_config_checked {
+            rclone_config_checked = config_modified;
+            let config_remotes = rclone::get_remote_names();
+            missing_remotes = remotes
+                .iter()
+                .filter(|remote| !config_remotes.contains(&remote.name))
+                .map(|remote| remote.name.clone())
+                .collect();
+
+            // Only offer remotes of types we support, and only once each.
+            for remote_name in config_remotes {
+                if !remotes.iter().any(|remote| remote.name == remote_name)
+                    && rclone::get_remote(&remote_name).is_some()
+                    && offered_remotes.insert(remote_name.clone())
+                {
+                    offer_remote(remote_name);
+                }
+            }
+        }
 
         // If no remotes are present we need to close the window and ask the user to log
         // in again.
@@ -1210,1259 +6829,1341 @@ pub fn launch(app: &Application, background: bool) {
             }
         }
 
-        libceleste::run_in_background(|| thread::sleep(Duration::from_millis(500)));
+        // Wait a bit before the next sync pass, unless the user asked to sync now.
+        if wait_for_next_pass() {
+            skipped_missing_local.clear();
+            skipped_missing_remote.clear();
+        }
+
+        // Let frontends know how each directory is doing as of the last sync check.
+        ENGINE_STATUS.lock().unwrap().dirs = directory_map
+            .get_ref()
+            .iter()
+            .flat_map(|(remote_name, dirs)| {
+                dirs.iter()
+                    .map(move |((local_path, remote_path), dir)| DirStatus {
+                        remote_name: remote_name.clone(),
+                        local_path: local_path.clone(),
+                        remote_path: remote_path.clone(),
+                        status: dir.status_text.text().to_string(),
+                        errors: dir.error_items.len(),
+                    })
+            })
+            .collect();
+
+        // Don't sync anything while syncing is paused.
+        if *(*SYNC_PAUSED).lock().unwrap() {
+            let paused_msg = tr::tr!("Syncing is paused.");
+            if *tray_status.borrow() != paused_msg {
+                send_dbus_msg(&paused_msg);
+            }
+
+            check_open_requests();
+            continue;
+        }
 
         if sync_errors_count() == 0 {
             send_dbus_fn("SetSyncingIcon");
         }
 
-        for remote in remotes {
-            // Process any remote deletion requests.
-            {
-                let mut remote_queue = remote_deletion_queue.get_mut_ref();
+        // Process any remote deletion requests.
+        {
+            let mut remote_queue = remote_deletion_queue.get_mut_ref();
 
-                while !remote_queue.is_empty() {
-                    let remote_name = remote_queue.remove(0);
+            while !remote_queue.is_empty() {
+                let remote_name = remote_queue.remove(0);
 
-                    // Remove the item from the UI.
-                    let child = stack.child_by_name(&remote_name).unwrap();
-                    stack.remove(&child);
+                // Unmount the remote if it's mounted.
+                mount_map.get_mut_ref().remove(&remote_name);
 
-                    // Delete all related database entries.
-                    libceleste::await_future(async {
-                        let db_remote = RemotesEntity::find()
-                            .filter(RemotesColumn::Name.eq(remote_name.clone()))
-                            .one(&db)
-                            .await
-                            .unwrap()
-                            .unwrap();
-                        let sync_dirs = SyncDirsEntity::find()
-                            .filter(SyncDirsColumn::RemoteId.eq(db_remote.id))
-                            .all(&db)
-                            .await
-                            .unwrap();
+                // Remove the item from the UI.
+                let child = stack.child_by_name(&remote_name).unwrap();
+                stack.remove(&child);
 
-                        for sync_dir in sync_dirs {
-                            SyncItemsEntity::delete_many()
-                                .filter(SyncItemsColumn::SyncDirId.eq(sync_dir.id))
-                                .exec(&db)
-                                .await
-                                .unwrap();
-                            sync_dir.delete(&db).await.unwrap();
-                        }
+                // Delete all related database entries.
+                db::expect(|| async {
+                    let db_remote = RemotesEntity::find()
+                        .filter(RemotesColumn::Name.eq(remote_name.clone()))
+                        .one(&db)
+                        .await?
+                        .unwrap();
+                    health::delete_remote(&db, db_remote).await
+                });
 
-                        db_remote.delete(&db).await.unwrap();
-                    });
+                // Delete the Rclone config.
+                rclone::sync::delete_config(&remote_name).unwrap();
+            }
+        }
 
-                    // Delete the Rclone config.
-                    rclone::sync::delete_config(&remote_name).unwrap();
+        // Gather every directory to sync, so that the scheduler can pick the order
+        // they get synced in.
+        let mut pairs = vec![];
+        for remote in remotes {
+            // Everything done with a remote that's missing from Rclone's config fails, so
+            // don't try syncing it until it's back.
+            if missing_remotes.contains(&remote.name) {
+                let missing_msg = tr::tr!(
+                    "This remote is missing from Celeste's Rclone config, so it can't be synced."
+                );
+                for item in directory_map.get_ref()[&remote.name].values() {
+                    item.status_icon
+                        .set_child(Some(&get_image("dialog-warning-symbolic")));
+                    item.status_text.set_label(&missing_msg);
                 }
+                continue;
+            }
+
+            pairs.extend(remote_sync_pairs(&db, &remote));
+        }
+        let pair_ids: Vec<i32> = pairs.iter().map(|pair| pair.sync_dir.id).collect();
+        let mut queue = scheduler.queue(pairs);
+
+        while let Some(SyncPair {
+            remote,
+            sync_dir,
+            overlapping_dir,
+        }) = scheduler.next(&mut queue)
+        {
+            // This directory might have been removed while syncing the ones before it.
+            if !sync_dir.exists(&db) {
+                continue;
             }
 
             // Notify the tray app that we're syncing this remote now.
             let status_string = tr::tr!("Syncing '{}'...", remote.name);
             send_dbus_msg(&status_string);
 
-            let sync_dirs = libceleste::await_future(
-                SyncDirsEntity::find()
-                    .filter(SyncDirsColumn::RemoteId.eq(remote.id))
-                    .all(&db),
-            )
-            .unwrap();
-
-            for sync_dir in sync_dirs {
-                let item_ptr = directory_map.get_ref();
-                let item = item_ptr
-                    .get(&remote.name)
-                    .unwrap()
-                    .get(&(sync_dir.local_path.clone(), sync_dir.remote_path.clone()))
-                    .unwrap();
+            let item_ptr = directory_map.get_ref();
+            let item = item_ptr
+                .get(&remote.name)
+                .unwrap()
+                .get(&(sync_dir.local_path.clone(), sync_dir.remote_path.clone()))
+                .unwrap();
 
-                // If we have pending errors that need resolved, don't sync this directory.
-                if item.error_status_text.text().len() != 0 {
-                    continue;
-                }
+            // If we have pending errors that need resolved, don't sync this directory.
+            if item.error_status_text.text().len() != 0 {
+                continue;
+            }
 
-                // Set up the UI for notifying the user that this directory is being synced.
-                // The width/height and margins for this are based on those from `get_image()`
-                // at the top of this file, as they're placed at the same place in the UI.
-                let spinner = Spinner::builder()
-                    .spinning(true)
-                    .width_request(4)
-                    .height_request(4)
-                    .margin_start(3)
-                    .margin_end(3)
-                    .build();
-                item.status_icon.set_child(Some(&spinner));
+            // Directories that keep failing are retried less often, instead of running
+            // into the same errors every pass.
+            if let Some(retry_at) = scheduler.backing_off(&sync_dir) {
+                item.status_icon
+                    .set_child(Some(&get_image("dialog-warning-symbolic")));
+                item.status_text.set_label(&tr::tr!(
+                    "Syncing keeps failing. Will retry at {}.",
+                    format_instant(retry_at)
+                ));
+                settled_dirs.remove(&sync_dir.id);
+                continue;
+            }
+            drop(item_ptr);
+
+            // Let the directory's pre-sync command skip this pass, such as while something
+            // is using the files in it.
+            let pre_sync_result =
+                run_pre_sync_command(&refresh_sync_dir(&sync_dir, &db), &remote.name);
+            log_pre_sync_result(&sync_dir, &pre_sync_result, &mut pre_sync_outputs);
+            let item_ptr = directory_map.get_ref();
+            let item = item_ptr
+                .get(&remote.name)
+                .unwrap()
+                .get(&(sync_dir.local_path.clone(), sync_dir.remote_path.clone()))
+                .unwrap();
+            if let Err(skip) = pre_sync_result {
+                item.status_icon
+                    .set_child(Some(&get_image("action-unavailable-symbolic")));
+                item.status_text.set_label(&skip.msg);
                 item.status_text
-                    .set_label(&tr::tr!("Checking for changes..."));
-                // Dropping this is important, otherwise the pointer borrow might last a lot
-                // longer and other parts of the code won't be able to get a pointer to the
-                // directory indexmap.
-                drop(item_ptr);
-
-                // Add an error for reporting in the UI.
-                let please_resolve_msg_tr = tr::tr!("Please resolve the reported syncing issues.");
-                let please_resolve_msg = " ".to_owned() + &please_resolve_msg_tr;
-                let add_error = glib::clone!(@strong db, @strong directory_map, @strong remote, @strong sync_dir, @strong sync_errors_count, @strong please_resolve_msg => move |error: SyncError| {
-                    let path_pair = (sync_dir.local_path.clone(), sync_dir.remote_path.clone());
-                    let ui_item = error.generate_ui();
-                    let ui_item_listbox = ListBoxRow::builder().child(&ui_item).build();
-
-                    // Generate the callback.
-                    let gesture = GestureClick::new();
-                    gesture.connect_released(glib::clone!(@strong directory_map, @strong remote, @strong path_pair, @strong db, @strong error, @weak ui_item, @weak ui_item_listbox, @strong please_resolve_msg => move |_, _, _, _| {
-                        ui_item.set_sensitive(false);
-                        let remove_ui_item = glib::clone!(@strong directory_map, @strong remote, @strong path_pair, @strong error, @weak ui_item_listbox, @strong please_resolve_msg => move || {
-                            let mut ptr = directory_map.get_mut_ref();
-                            let item = ptr.get_mut(&remote.name).unwrap().get_mut(&path_pair).unwrap();
-
-                            // Update the error brief on the main page.
-                            let error_text = item.error_status_text.text().to_string();
-                            let new_num_errors = error_text.split_whitespace().next().unwrap_or("0").parse::<i32>().unwrap() - 1;
-                            if new_num_errors == 0 {
-                                item.error_status_text.set_label("");
-                                let label_text = match item.status_text.text().as_str().strip_suffix(&please_resolve_msg) {
-                                    Some(text) => text.to_string(),
-                                    None => item.status_text.text().to_string()
-                                };
-                                item.status_text.set_label(&label_text);
-
-                            } else {
-                                let error_string = tr::tr!("{} errors found. ", new_num_errors);
-                                item.error_status_text.set_label(&error_string);
-                            }
-
-                            (item.update_error_ui)();
-
-                            // Update the sync dir's page and our code.
-                            item.error_items.remove(&error).unwrap();
-                            item.error_list.remove(&ui_item_listbox);
-                        });
-
-                        match &error {
-                            SyncError::General(_, _) => {
-                                let dialog = MessageDialog::builder()
-                                    .text(&tr::tr!("Would you like to dismiss this error?"))
-                                    .buttons(ButtonsType::YesNo)
-                                    .build();
-                                dialog.connect_close_request(glib::clone!(@strong ui_item => move |_| {
-                                    ui_item.set_sensitive(true);
-                                    Inhibit(false)
-                                }));
-                                dialog.connect_response(glib::clone!(@strong directory_map, @strong remote, @strong path_pair, @weak ui_item, @strong error, @strong remove_ui_item => move |dialog, resp| {
-                                    match resp {
-                                        ResponseType::Yes => {
-                                            remove_ui_item();
-                                        },
-                                        ResponseType::No => {
-                                            ui_item.set_sensitive(true);
-                                        },
-                                        _ => return,
-                                    }
-
-                                    dialog.close();
-                                }));
-                                dialog.show();
-                            },
-                            SyncError::BothMoreCurrent(local_item, remote_item) => {
-                                let local_item_formatted = libceleste::fmt_home(local_item);
-                                let local_path = Path::new(&local_item);
-                                let sync_local_to_remote = glib::clone!(@strong remote, @strong local_item_formatted, @strong local_item, @strong remote_item => move || {
-                                    if let Err(err) = rclone::sync::copy_to_remote(&local_item, &remote.name, &remote_item) {
-                                        gtk_util::show_error(&tr::tr!("Failed to sync '{}' to '{}' on remote.", local_item_formatted, remote_item), Some(&err.error));
-                                        Err(())
-                                    } else {
-                                        Ok(())
-                                    }
-                                });
-                                let sync_remote_to_local = glib::clone!(@strong remote, @strong local_item_formatted, @strong local_item, @strong remote_item => move || {
-                                    if let Err(err) = rclone::sync::copy_to_local(&local_item, &remote.name, &remote_item) {
-                                        gtk_util::show_error(&tr::tr!("Failed to sync '{}' on remote to '{}'.", remote_item, local_item_formatted), Some(&err.error));
-                                        Err(())
-                                    } else {
-                                        Ok(())
-                                    }
-                                });
-                                let local_item = local_item.clone();
-                                let update_db_item = glib::clone!(@strong db, @strong remote, @strong local_item, @strong remote_item => move || {
-                                    let local_timestamp = Path::new(&local_item).metadata().unwrap().modified().unwrap().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
-                                    let remote_timestamp = rclone::sync::stat(&remote.name, &remote_item).unwrap().unwrap().mod_time.unix_timestamp();
-                                    let mut active_model: SyncItemsActiveModel = libceleste::await_future(SyncItemsEntity::find()
-                                        .filter(SyncItemsColumn::LocalPath.eq(local_item.clone()))
-                                        .filter(SyncItemsColumn::RemotePath.eq(remote_item.clone()))
-                                        .one(&db)
-                                    ).unwrap()
-                                    .unwrap()
-                                    .into();
-                                    active_model.last_local_timestamp = ActiveValue::set(local_timestamp.try_into().unwrap());
-                                    active_model.last_remote_timestamp = ActiveValue::Set(remote_timestamp.try_into().unwrap());
-                                    libceleste::await_future(active_model.update(&db)).unwrap();
-                                });
-                                let rclone_remote_item = match rclone::sync::stat(&remote.name, remote_item) {
-                                    Ok(item) => item,
-                                    Err(err) => {
-                                        gtk_util::show_error(
-                                            &tr::tr!("Unable to fetch data for '{}' from the remote.", remote_item),
-                                            Some(&err.error)
-                                        );
-                                        return;
-                                    }
-                                };
-
-                                // If neither the local item or the remote item exist anymore, this error is no longer relevant.
-                                if !local_path.exists() && rclone_remote_item.is_none() {
-                                    gtk_util::show_error(&tr::tr!("File Update"), Some(&tr::tr!("Neither the local item or remote item exists anymore. This error will now be removed.")));
-                                    remove_ui_item();
-                                    return;
-                                // Otherwise if only the local exists, use that.
-                                } else if local_path.exists() && rclone_remote_item.is_none() {
-                                    gtk_util::show_error(&tr::tr!("File Update"), Some(&tr::tr!("Only the local item exists now, so it will be synced to the remote.")));
-                                    if sync_local_to_remote().is_ok() {
-                                        update_db_item();
-                                        remove_ui_item();
-                                        return;
-                                    }
-                                // Otherwise if only the remote exists, use that.
-                                } else if !local_path.exists() && rclone_remote_item.is_some() {
-                                    gtk_util::show_error(&tr::tr!("File Update"), Some(&tr::tr!("Only the remote item exists now, so it will be synced to the local machine.")));
-                                    if sync_remote_to_local().is_ok() {
-                                        update_db_item();
-                                        remove_ui_item();
-                                        return;
-                                    }
-                                }
-
-                                let dialog = MessageDialog::builder()
-                                    .text(
-                                        &tr::tr!("Both the local item '{}' and remote item '{}' have been updated since the last sync.", local_item_formatted, remote_item)
-                                    )
-                                    .secondary_text(&tr::tr!("Which item would you like to keep?"))
-                                    .build();
-                                dialog.add_button(&tr::tr!("Local"), ResponseType::Other(0));
-                                dialog.add_button(&tr::tr!("Remote"), ResponseType::Other(1));
-                                dialog.connect_close_request(glib::clone!(@strong ui_item => move |_| {
-                                    ui_item.set_sensitive(true);
-                                    Inhibit(false)
-                                }));
-                                dialog.connect_response(glib::clone!(@strong directory_map, @strong remote, @strong path_pair, @weak ui_item, @strong error, @strong local_item, @strong remote_item, @strong local_path, @strong rclone_remote_item, @strong sync_local_to_remote, @strong sync_remote_to_local => move |dialog, resp| {
-                                    match resp {
-                                        ResponseType::Other(0) => {
-                                            if sync_local_to_remote().is_ok() {
-                                                update_db_item();
-                                                remove_ui_item();
-                                            }
-                                        },
-                                        ResponseType::Other(1) => {
-                                            if sync_remote_to_local().is_ok() {
-                                                update_db_item();
-                                                remove_ui_item();
-                                            }
-                                        },
-                                        ResponseType::Other(_) => unreachable!(),
-                                        _ => return
-                                    }
-
-                                    dialog.close();
-                                }));
-
-                                dialog.show();
-                            }
-                        }
-                    }));
-                    ui_item.add_controller(&gesture);
-
-                    // If we have zero errors now, remove the warning icon.
-                    if sync_errors_count() == 0 {
-                        send_dbus_fn("SetSyncingIcon");
-                    }
-
-                    // Report the brief on the number of errors.
-                    let mut ptr = directory_map.get_mut_ref();
-                    let item = ptr
-                        .get_mut(&remote.name)
-                        .unwrap()
-                        .get_mut(&path_pair)
-                        .unwrap();
-
-                    let error_text = item.error_status_text.text().to_string();
-                    let new_num_errors = error_text.split_whitespace().next().unwrap_or("0").parse::<i32>().unwrap() + 1;
-
-                    let error_string = if new_num_errors == 1 {
-                        tr::tr!("1 error found.")
-                    } else {
-                        tr::tr!("{} errors found.", new_num_errors)
-                    };
-                    item.error_status_text.set_label(&(error_string + " "));
-
-                    // Add the error to the UI.
-                    item.error_list.append(&ui_item_listbox);
-                    item.error_items.insert(error, ui_item);
-                    (item.update_error_ui)();
-
-                    // Set the tray icon to show the warning icon.
-                    send_dbus_fn("SetWarningIcon");
-                });
-
-                // A vector of local/remote sync item pairs to make sure we don't sync anything
-                // twice between 'sync_local_directory' and 'sync_remote_directory' below. It
-                // also prevents errors from showing up twice when they occur. We have to wrap
-                // this in a [`RefCell`] to avoid some borrow checker issues with multiple
-                // mutable closures needing access to this.
-                let synced_items: RefCell<Vec<(String, String)>> = RefCell::new(vec![]);
-
-                // Get any pending deletion requests and process them.
-                let process_deletion_requests = glib::clone!(@strong db, @weak stack, @strong directory_map, @strong remote_deletion_queue, @strong sync_dir_deletion_queue => move || {
-                    let mut dmap = directory_map.get_mut_ref();
-                    let mut remote_queue = remote_deletion_queue.get_mut_ref();
-                    let mut dir_queue = sync_dir_deletion_queue.get_mut_ref();
-
-                    // Process directory deletions.
-                    while !dir_queue.is_empty() {
-                        let queue_item = dir_queue.remove(0);
-                        let dir_pair = (queue_item.1.clone(), queue_item.2.clone());
-                        let ui_item = dmap.get(&queue_item.0).unwrap().get(&dir_pair).unwrap();
-
-                        // Remove the item from the UI.
-                        ui_item.parent_list.remove(&ui_item.container);
-
-                        // Remove the item from the directory map.
-                        dmap.get_mut(&queue_item.0).unwrap().remove(&dir_pair).unwrap();
-
-                        // Remove the item from the database.
-                        libceleste::await_future(async {
-                            let sync_dir = SyncDirsEntity::find()
-                                .filter(SyncDirsColumn::LocalPath.eq(queue_item.1.clone()))
-                                .filter(SyncDirsColumn::RemotePath.eq(queue_item.2.clone()))
-                                .one(&db)
-                                .await
-                                .unwrap()
-                                .unwrap();
-
-                            SyncItemsEntity::delete_many()
-                                .filter(SyncItemsColumn::SyncDirId.eq(sync_dir.id))
-                                .exec(&db)
-                                .await
-                                .unwrap();
-                            sync_dir.delete(&db).await.unwrap();
-                        });
+                    .set_tooltip_text((!skip.output.is_empty()).then_some(skip.output.as_str()));
+                settled_dirs.remove(&sync_dir.id);
+                continue;
+            }
+            item.status_text.set_tooltip_text(None);
+
+            // Set up the UI for notifying the user that this directory is being synced.
+            // The width/height and margins for this are based on those from `get_image()`
+            // at the top of this file, as they're placed at the same place in the UI.
+            let spinner = Spinner::builder()
+                .spinning(true)
+                .width_request(4)
+                .height_request(4)
+                .margin_start(3)
+                .margin_end(3)
+                .build();
+            item.status_icon.set_child(Some(&spinner));
+            item.status_text
+                .set_label(&tr::tr!("Checking for changes..."));
+            // Dropping this is important, otherwise the pointer borrow might last a lot
+            // longer and other parts of the code won't be able to get a pointer to the
+            // directory indexmap.
+            drop(item_ptr);
+
+            // Whether to record the sync status of items in their extended attributes.
+            let write_sync_status = SettingsModel::get(&db).sync_status_xattrs
+                && xattrs_supported(Path::new(&sync_dir.local_path));
+            let timestamps = TimestampComparison::new(&remote, &db);
+
+            // Add an error for reporting in the UI.
+            let please_resolve_msg_tr = tr::tr!("Please resolve the reported syncing issues.");
+            let please_resolve_msg = " ".to_owned() + &please_resolve_msg_tr;
+            let stats = Rc::new(PassStats::with_transfer_limit(
+                SettingsModel::get(&db).transfer_limit,
+            ));
+            // Remove an error from the directory's error list, or from the errors waiting
+            // to be shown in it. This and the closures for the error rows are kept on the
+            // directory, so they only hold the directory map weakly.
+            let remove_error = glib::clone!(@weak directory_map, @strong remote, @strong sync_dir, @strong please_resolve_msg => move |error: &SyncError| {
+                let path_pair = (sync_dir.local_path.clone(), sync_dir.remote_path.clone());
+                let mut ptr = directory_map.get_mut_ref();
+                let item = ptr.get_mut(&remote.name).unwrap().get_mut(&path_pair).unwrap();
+                path_status::clear_error(error.path());
+
+                // Update the sync dir's page and our code, showing the next error that didn't
+                // fit in the error list in this one's place.
+                let next_error = match item.error_items.remove(error) {
+                    Some(ui_item) => {
+                        item.error_list.remove(&ui_item.parent().unwrap());
+                        item.hidden_errors.shift_remove_index(0)
                     }
-
-                    // Process remote deletions.
-                    while !remote_queue.is_empty() {
-                        let remote_name = remote_queue.remove(0);
-
-                        // Remove the item from the UI.
-                        let child = stack.child_by_name(&remote_name).unwrap();
-                        stack.remove(&child);
-
-                        // Delete all related database entries.
-                        libceleste::await_future(async {
-                            let db_remote = RemotesEntity::find()
-                                .filter(RemotesColumn::Name.eq(remote_name.clone()))
-                                .one(&db)
-                                .await
-                                .unwrap()
-                                .unwrap();
-                            let sync_dirs = SyncDirsEntity::find()
-                                .filter(SyncDirsColumn::RemoteId.eq(db_remote.id))
-                                .all(&db)
-                                .await
-                                .unwrap();
-
-                            for sync_dir in sync_dirs {
-                                SyncItemsEntity::delete_many()
-                                    .filter(SyncItemsColumn::SyncDirId.eq(sync_dir.id))
-                                    .exec(&db)
-                                    .await
-                                    .unwrap();
-                                sync_dir.delete(&db).await.unwrap();
-                            }
-
-                            db_remote.delete(&db).await.unwrap();
-                        });
-
-                        // Delete the Rclone config.
-                        rclone::sync::delete_config(&remote_name).unwrap();
+                    None => {
+                        item.hidden_errors.shift_remove(error);
+                        None
                     }
-                });
-
-                // Sync a local directory. This is implemented as a function instead of a
-                // closure so that it can be called recursively.
-                //
-                // Returning an [`Err<()>`] means we this directory has to stop being synced
-                // because it was in the deletion queue. Any other error should return an
-                // [`Ok<()>`].
-                #[allow(clippy::too_many_arguments)]
-                fn sync_local_directory<
-                    F1: Fn(SyncError) + Clone,
-                    F2: Fn() + Clone,
-                    F3: Fn() + Clone,
-                >(
-                    local_dir: &Path,
-                    remote: &RemotesModel,
-                    sync_dir: &SyncDirsModel,
-                    db: &DatabaseConnection,
-                    directory_map: &DirectoryMap,
-                    synced_items: &RefCell<Vec<(String, String)>>,
-                    add_error: F1,
-                    check_open_requests: F2,
-                    process_deletion_requests: F3,
-                ) {
-                    process_deletion_requests();
-
-                    let dir_string = local_dir.to_str().unwrap().to_owned();
-                    let update_ui_progress = |dir: &str| {
-                        // If this directory no longer exists in the database (i.e. from being
-                        // deleted from the `sync_dir_deletion_queue`), then do nothing.
-                        if !sync_dir.exists(db) {
-                            return;
-                        }
-
-                        let ptr = directory_map.get_ref();
-                        let dir_pair = (sync_dir.local_path.clone(), sync_dir.remote_path.clone());
-                        let item = ptr.get(&remote.name).unwrap().get(&dir_pair).unwrap();
-                        let status_string =
-                            tr::tr!("Checking '{}' for changes...", libceleste::fmt_home(dir));
-                        item.status_text.set_label(&status_string);
-                    };
-                    update_ui_progress(&dir_string);
-                    let directory = match fs::read_dir(local_dir) {
-                        Ok(ok_dir) => ok_dir,
-                        Err(err) => {
-                            add_error(SyncError::General(dir_string, err.to_string()));
-                            return;
-                        }
-                    };
-
-                    // Get the list of ignore globs.
-                    let ignore_file_string =
-                        format!("{}/{}", sync_dir.local_path, FILE_IGNORE_NAME);
-                    let ignore_file_path = Path::new(&ignore_file_string);
-                    let ignore_globs = if ignore_file_path.exists() {
-                        let _lock = FileLock::lock(
-                            &ignore_file_string,
-                            true,
-                            FileOptions::new().write(true).read(true),
-                        )
-                        .unwrap();
-                        let file_content = fs::read_to_string(ignore_file_path).unwrap();
-                        let mut globs = vec![];
-
-                        for line in file_content.lines() {
-                            if let Ok(pattern) = glob::Pattern::new(line) {
-                                globs.push(pattern);
-                            }
-                        }
-
-                        globs
-                    } else {
-                        vec![]
+                };
+                let show_error_row = item.show_error_row.clone();
+                drop(ptr);
+                if let Some(next_error) = next_error && let Some(show_error_row) = show_error_row {
+                    show_error_row(next_error);
+                }
+                let mut ptr = directory_map.get_mut_ref();
+                let item = ptr.get_mut(&remote.name).unwrap().get_mut(&path_pair).unwrap();
+
+                // Update the error brief on the main page.
+                let new_num_errors = item.error_count();
+                if new_num_errors == 0 {
+                    item.error_status_text.set_label("");
+                    let label_text = match item.status_text.text().as_str().strip_suffix(&please_resolve_msg) {
+                        Some(text) => text.to_string(),
+                        None => item.status_text.text().to_string()
                     };
+                    item.status_text.set_label(&label_text);
 
-                    for item in directory {
-                        // If a close request was sent in, stop syncing this remote so we can quit
-                        // the application in the 'main loop.
-                        if *(*CLOSE_REQUEST).lock().unwrap() {
-                            break;
-                        }
-
-                        // Check for open requests.
-                        check_open_requests();
-
-                        // If this directory no longer exists in the database (i.e. from being
-                        // deleted from the `sync_dir_deletion_queue`), stop processing and return.
-                        if !sync_dir.exists(db) {
-                            break;
-                        }
-
-                        if let Err(err) = item {
-                            add_error(SyncError::General(dir_string.clone(), err.to_string()));
-                            continue;
-                        }
-                        let item = item.unwrap();
-                        let local_path = item.path().to_str().unwrap().to_owned();
-
-                        // The path from the root of the remote.
-                        let remote_path = {
-                            let local_path_stripped = local_path
-                                .strip_prefix(&format!("{}/", sync_dir.local_path))
-                                .unwrap();
-                            let stripped_path = match local_path_stripped.strip_suffix('/') {
-                                Some(string) => string,
-                                None => local_path_stripped,
-                            };
-
-                            if sync_dir.remote_path.is_empty() {
-                                stripped_path.to_owned()
-                            } else {
-                                sync_dir.remote_path.clone() + "/" + stripped_path
-                            }
-                        };
-                        // The above path, with `sync_dir.remote_path` stripped from it.
-                        let stripped_remote_path =
-                            if remote_path.contains('/') && sync_dir.remote_path.contains('/') {
-                                remote_path
-                                    .strip_prefix(&format!("{}/", sync_dir.remote_path))
-                                    .unwrap()
-                                    .to_owned()
-                            } else {
-                                remote_path.clone()
-                            };
-
-                        update_ui_progress(&local_path);
-                        // If this item matches the ignore list, don't sync it.
-                        if ignore_globs
-                            .iter()
-                            .filter(|pattern| pattern.matches(&stripped_remote_path))
-                            .count()
-                            > 0
-                        {
-                            continue;
-                        }
-
-                        synced_items
-                            .borrow_mut()
-                            .push((local_path.clone(), remote_path.clone()));
-
-                        let get_local_file_timestamp = || {
-                            item.metadata()
-                                .unwrap()
-                                .modified()
-                                .unwrap()
-                                .duration_since(SystemTime::UNIX_EPOCH)
-                                .unwrap()
-                                .as_secs()
-                        };
-                        let local_utc_timestamp = get_local_file_timestamp();
-                        let remote_item = match rclone::sync::stat(&remote.name, &remote_path) {
-                            Ok(item) => item,
-                            Err(err) => {
-                                add_error(SyncError::General(remote_path.clone(), err.error));
-                                continue;
-                            }
-                        };
-                        let remote_utc_timestamp = remote_item
-                            .as_ref()
-                            .map(|item| item.mod_time.unix_timestamp());
-                        let db_item = libceleste::await_future(
-                            SyncItemsEntity::find()
-                                .filter(SyncItemsColumn::LocalPath.eq(local_path.clone()))
-                                .filter(SyncItemsColumn::RemotePath.eq(remote_path.clone()))
-                                .one(db),
-                        )
-                        .unwrap();
-
-                        // Push the item to the remote. Returns the
-                        // [`crate::rclone::sync::RcloneRemoteItem`] of the item on the remote, or
-                        // an [`Err<()>`] if an issue occurred (all errors are automatically added
-                        // via `add_errors`).
-                        let push_local_to_remote = || -> Result<rclone::RcloneRemoteItem, ()> {
-                            let file_type = item.file_type().unwrap();
-
-                            if let Some(rclone_item) = &remote_item {
-                                let same_type = file_type.is_dir() && rclone_item.is_dir;
-
-                                if !same_type {
-                                    if let Err(err) =
-                                        rclone::sync::purge(&remote.name, &remote_path)
-                                    {
-                                        add_error(SyncError::General(
-                                            remote_path.clone(),
-                                            err.error,
-                                        ));
-                                        return Err(());
-                                    }
-                                }
-                            }
-
-                            if file_type.is_dir() {
-                                if let Err(err) = rclone::sync::mkdir(&remote.name, &remote_path) {
-                                    add_error(SyncError::General(remote_path.clone(), err.error));
-                                    return Err(());
-                                }
-                                sync_local_directory(
-                                    &item.path(),
-                                    remote,
-                                    sync_dir,
-                                    db,
-                                    directory_map,
-                                    synced_items,
-                                    add_error.clone(),
-                                    check_open_requests.clone(),
-                                    process_deletion_requests.clone(),
-                                );
-                                update_ui_progress(&local_path);
-                            } else if let Err(err) = rclone::sync::copy_to_remote(
-                                &local_path,
-                                &remote.name,
-                                &remote_path,
-                            ) {
-                                add_error(SyncError::General(local_path.clone(), err.error));
-                                return Err(());
-                            }
+                } else {
+                    let error_string = tr::tr!("{n} error found. " | "{n} errors found. " % new_num_errors);
+                    item.error_status_text.set_label(&error_string);
+                }
 
-                            Ok(rclone::sync::stat(&remote.name, &remote_path)
-                                .unwrap()
-                                .unwrap())
-                        };
-                        // Pull the item from the remote.
-                        let pull_remote_to_local = || -> Result<(), ()> {
-                            let file_type = item.file_type().unwrap();
-                            let same_type =
-                                file_type.is_dir() && remote_item.as_ref().unwrap().is_dir;
-
-                            if !same_type {
-                                if file_type.is_dir() && let Err(err) = fs::remove_dir_all(item.path()) {
-                                    add_error(SyncError::General(local_path.clone(), err.to_string()));
-                                    return Err(());
-                                } else if let Err(err) = fs::remove_file(item.path()) {
-                                    add_error(SyncError::General(local_path.clone(), err.to_string()));
-                                    return Err(());
+                item.update_hidden_errors_button();
+                item.update_conflicts_button();
+                (item.update_error_ui)();
+            });
+            directory_map
+                .get_mut_ref()
+                .get_mut(&remote.name)
+                .unwrap()
+                .get_mut(&(sync_dir.local_path.clone(), sync_dir.remote_path.clone()))
+                .unwrap()
+                .remove_error = Some(Rc::new(remove_error.clone()));
+
+            // Add an error's row to the directory's error list. This is also kept on the
+            // directory, so that errors that didn't fit in the list can be shown later.
+            let show_error_row = glib::clone!(@strong db, @weak directory_map, @strong remote, @strong sync_dir, @strong please_resolve_msg, @strong remove_error => move |error: SyncError| {
+                let path_pair = (sync_dir.local_path.clone(), sync_dir.remote_path.clone());
+                let ui_item = error.generate_ui();
+                let ui_item_listbox = ListBoxRow::builder().child(&ui_item).build();
+
+                // Remove this error from the UI.
+                let remove_ui_item = glib::clone!(@strong error, @strong remove_error => move || remove_error(&error));
+
+                // Generate the callback.
+                let activate_error = glib::clone!(@strong remote, @strong sync_dir, @strong path_pair, @strong db, @strong error, @weak ui_item, @weak ui_item_listbox, @strong please_resolve_msg, @strong remove_ui_item => move || {
+                    ui_item.set_sensitive(false);
+
+                    match &error {
+                        SyncError::General(_, _) | SyncError::ChecksumMismatch(_, _) => {
+                            let dialog = MessageDialog::builder()
+                                .text(&tr::tr!("Would you like to dismiss this error?"))
+                                .buttons(ButtonsType::YesNo)
+                                .build();
+                            dialog.connect_close_request(glib::clone!(@strong ui_item => move |_| {
+                                ui_item.set_sensitive(true);
+                                Inhibit(false)
+                            }));
+                            dialog.connect_response(glib::clone!(@strong remote, @strong path_pair, @weak ui_item, @strong error, @strong remove_ui_item => move |dialog, resp| {
+                                match resp {
+                                    ResponseType::Yes => {
+                                        remove_ui_item();
+                                    },
+                                    ResponseType::No => {
+                                        ui_item.set_sensitive(true);
+                                    },
+                                    _ => return,
                                 }
-                            }
-
-                            if file_type.is_dir() {
-                                sync_local_directory(
-                                    &item.path(),
-                                    remote,
-                                    sync_dir,
-                                    db,
-                                    directory_map,
-                                    synced_items,
-                                    add_error.clone(),
-                                    check_open_requests.clone(),
-                                    process_deletion_requests.clone(),
-                                );
-                                update_ui_progress(&local_path);
-                            } else if let Err(err) =
-                                rclone::sync::copy_to_local(&local_path, &remote.name, &remote_path)
-                            {
-                                add_error(SyncError::General(remote_path.clone(), err.error));
-                                return Err(());
-                            }
-
-                            Ok(())
-                        };
-                        // Delete this item from the database.
-                        let delete_db_entry = || {
-                            libceleste::await_future(async {
-                                SyncItemsEntity::find()
-                                    .filter(SyncItemsColumn::SyncDirId.eq(sync_dir.id))
-                                    .filter(SyncItemsColumn::LocalPath.eq(local_path.clone()))
-                                    .filter(SyncItemsColumn::RemotePath.eq(remote_path.clone()))
-                                    .one(db)
-                                    .await
-                                    .unwrap()
-                                    .unwrap()
-                                    .delete(db)
-                                    .await
-                                    .unwrap()
-                            })
-                        };
-
-                        // If we have a record of the last sync, use that to aid in timestamp
-                        // checks.
-                        if let Some(db_model) = db_item {
-                            let update_db_item = |local_timestamp, remote_timestamp| {
-                                let mut active_model: SyncItemsActiveModel =
-                                    db_model.clone().into();
-                                active_model.last_local_timestamp =
-                                    ActiveValue::Set(local_timestamp);
-                                active_model.last_remote_timestamp =
-                                    ActiveValue::Set(remote_timestamp);
-                                libceleste::await_future(active_model.update(db)).unwrap();
-                            };
 
-                            // Both items are more current than at the last transaction - we need to
-                            // let the user decide which to keep.
-                            // Since `db_model.last_sync_timestamp` is an `i32`, we should be able
-                            // to safely convert it to an `i64` and `u64`.
-                            if local_utc_timestamp > db_model.last_local_timestamp as u64 && let Some(remote_timestamp) = remote_utc_timestamp && remote_timestamp > db_model.last_remote_timestamp as i64 {
-                                // Only add the error if one of the items is not a directory - there's no point in saying both directories are more current, and it's probably because one of the items in the directory got updated anyway.
-                                if let Some(r_item) = remote_item && (!item.path().is_dir() || !r_item.is_dir) {
-                                    add_error(SyncError::BothMoreCurrent(local_path.clone(), remote_path.clone()));
-                                }
-                            // The local item is more recent.
-                            } else if local_utc_timestamp > db_model.last_local_timestamp as u64 {
-                                if let Ok(rclone_item) = push_local_to_remote() {
-                                    update_db_item(get_local_file_timestamp().try_into().unwrap(), rclone_item.mod_time.unix_timestamp().try_into().unwrap());
-                                    continue;
+                                dialog.close();
+                            }));
+                            dialog.show();
+                        },
+                        SyncError::BothMoreCurrent(local_item, remote_item) => {
+                            let local_item_formatted = libceleste::fmt_home(local_item);
+                            let local_path = Path::new(&local_item);
+                            let sync_local_to_remote = glib::clone!(@strong db, @strong sync_dir, @strong remote, @strong local_item_formatted, @strong local_item, @strong remote_item => move |reason: AuditReason| {
+                                if let Err(err) = run_transfer(&db, &sync_dir, &local_item, &remote_item, TransferDirection::Upload, || rclone::sync::copy_to_remote(&local_item, &remote.name, &remote_item)) {
+                                    gtk_util::show_error(&tr::tr!("Failed to sync '{}' to '{}' on remote.", local_item_formatted, remote_item), Some(err.message()));
+                                    Err(())
+                                } else if verify_transfer(&RcloneBackend::new(&remote.name).with_capabilities(RemoteCapabilities::get(&db, &remote)), &sync_dir, &db, &local_item, &remote_item, TransferDirection::Upload).is_err() {
+                                    gtk_util::show_error(&tr::tr!("'{}' didn't match '{}' on remote after syncing it. Please try again.", local_item_formatted, remote_item), None);
+                                    Err(())
                                 } else {
-                                    continue;
+                                    AuditLog::start().record(&db, &sync_dir, AuditAction::OverwriteRemote, reason, &local_item, &remote_item);
+                                    Ok(())
                                 }
-                            // The remote item is more recent.
-                            } else if let Some(remote_timestamp) = remote_utc_timestamp && remote_timestamp > db_model.last_remote_timestamp as i64 {
-                                if pull_remote_to_local().is_err() {
-                                    continue;
+                            });
+                            let sync_remote_to_local = glib::clone!(@strong db, @strong sync_dir, @strong remote, @strong local_item_formatted, @strong local_item, @strong remote_item => move || {
+                                if let Err(err) = run_transfer(&db, &sync_dir, &local_item, &remote_item, TransferDirection::Download, || rclone::sync::copy_to_local(&local_item, &remote.name, &remote_item)) {
+                                    gtk_util::show_error(&tr::tr!("Failed to sync '{}' on remote to '{}'.", remote_item, local_item_formatted), Some(err.message()));
+                                    Err(())
+                                } else if verify_transfer(&RcloneBackend::new(&remote.name).with_capabilities(RemoteCapabilities::get(&db, &remote)), &sync_dir, &db, &local_item, &remote_item, TransferDirection::Download).is_err() {
+                                    gtk_util::show_error(&tr::tr!("'{}' on remote didn't match '{}' after syncing it. Please try again.", remote_item, local_item_formatted), None);
+                                    Err(())
                                 } else {
-                                    update_db_item(get_local_file_timestamp().try_into().unwrap(), remote_timestamp.try_into().unwrap());
+                                    AuditLog::start().record(&db, &sync_dir, AuditAction::OverwriteLocal, AuditReason::KeptRemote, &local_item, &remote_item);
+                                    Ok(())
                                 }
-                            // The item is missing from the remote, but the last recorded timestamp for the local item is still the same. This means the item got deleted on the server, and we need to reflect such locally.
-                            } else if remote_item.is_none() && local_utc_timestamp == db_model.last_local_timestamp as u64 {
-                                if item.path().is_dir() {
-                                    if let Err(err) = fs::remove_dir_all(&local_path) {
-                                        add_error(SyncError::General(local_path.clone(), err.to_string()));
-                                        continue;
-                                    }
-                                } else if let Err(err) = fs::remove_file(&local_path) {
-                                    add_error(SyncError::General(local_path.clone(), err.to_string()));
-                                    continue;
+                            });
+                            let local_item = local_item.clone();
+                            let update_db_item = glib::clone!(@strong db, @strong remote, @strong local_item, @strong remote_item => move || {
+                                mark_conflict_resolved(&db, &remote.name, &local_item, &remote_item);
+                            });
+                            let rclone_remote_item = match rclone::sync::stat_with_metadata(&remote.name, remote_item) {
+                                Ok(item) => item,
+                                Err(err) => {
+                                    gtk_util::show_error(
+                                        &tr::tr!("Unable to fetch data for '{}' from the remote.", remote_item),
+                                        Some(err.message())
+                                    );
+                                    return;
                                 }
+                            };
 
-                                delete_db_entry();
-                                continue;
-                            // Both the local and remote item remain unchanged - do nothing.
-                            } else if local_utc_timestamp == db_model.last_local_timestamp as u64 && let Some(remote_timestamp) = remote_utc_timestamp && remote_timestamp == db_model.last_remote_timestamp as i64 {
-                                continue;
-                            // Every possible scenario should have been covered above, so panic if not.
-                            } else {
-                                unreachable!();
-                            }
-                        // Otherwise just check the local timestamps against
-                        // those on the remote, and record our new transaction
-                        // in the database.
-                        } else {
-                            // If the timestamp exists, then the remote item did, so check
-                            // timestamps.
-                            if let Some(remote_timestamp) = remote_utc_timestamp {
-                                if local_utc_timestamp > remote_timestamp as u64 {
-                                    if push_local_to_remote().is_err() {
-                                        continue;
-                                    }
-                                } else if pull_remote_to_local().is_err() {
-                                    continue;
+                            // If neither the local item or the remote item exist anymore, this error is no longer relevant.
+                            if !local_path.exists() && rclone_remote_item.is_none() {
+                                gtk_util::show_error(&tr::tr!("File Update"), Some(&tr::tr!("Neither the local item or remote item exists anymore. This error will now be removed.")));
+                                remove_ui_item();
+                                return;
+                            // Otherwise if only the local exists, use that.
+                            } else if local_path.exists() && rclone_remote_item.is_none() {
+                                gtk_util::show_error(&tr::tr!("File Update"), Some(&tr::tr!("Only the local item exists now, so it will be synced to the remote.")));
+                                if sync_local_to_remote(AuditReason::KeptLocal).is_ok() {
+                                    update_db_item();
+                                    remove_ui_item();
+                                    return;
+                                }
+                            // Otherwise if only the remote exists, use that.
+                            } else if !local_path.exists() && rclone_remote_item.is_some() {
+                                gtk_util::show_error(&tr::tr!("File Update"), Some(&tr::tr!("Only the remote item exists now, so it will be synced to the local machine.")));
+                                if sync_remote_to_local().is_ok() {
+                                    update_db_item();
+                                    remove_ui_item();
+                                    return;
                                 }
-                            // Otherwise the remote item didn't exist, so just
-                            // sync our local copy.
-                            } else if push_local_to_remote().is_err() {
-                                continue;
                             }
 
-                            // The remote item is now guaranteed to exist, so fetch it.
-                            let remote_item_safe =
-                                match rclone::sync::stat(&remote.name, &remote_path) {
-                                    Ok(item) => item.unwrap(),
-                                    Err(err) => {
-                                        add_error(SyncError::General(
-                                            remote_path.clone(),
-                                            err.error,
-                                        ));
-                                        continue;
-                                    }
-                                };
-                            match rclone::sync::stat(&remote.name, &remote_path) {
-                                Ok(item) => item.unwrap(),
-                                Err(err) => {
-                                    add_error(SyncError::General(remote_path.clone(), err.error));
-                                    continue;
-                                }
-                            };
+                            let dialog = MessageDialog::builder()
+                                .text(
+                                    &tr::tr!("Both the local item '{}' and remote item '{}' have been updated since the last sync.", local_item_formatted, remote_item)
+                                )
+                                .secondary_text(&conflict_details(local_path, rclone_remote_item.as_ref()))
+                                .build();
+                            dialog.add_button(&tr::tr!("Local"), ResponseType::Other(0));
+                            dialog.add_button(&tr::tr!("Remote"), ResponseType::Other(1));
+                            if rclone_remote_item.as_ref().map_or(false, |item| conflicts::can_compare(local_path, item)) {
+                                dialog.add_button(&tr::tr!("Compare"), ResponseType::Other(2));
+                                dialog.add_button(&tr::tr!("Merge"), ResponseType::Other(3));
+                            }
+                            dialog.connect_close_request(glib::clone!(@strong ui_item => move |_| {
+                                ui_item.set_sensitive(true);
+                                Inhibit(false)
+                            }));
+                            dialog.connect_response(glib::clone!(@strong db, @strong sync_dir, @strong remote, @strong path_pair, @weak ui_item, @strong error, @strong local_item, @strong remote_item, @strong local_path, @strong rclone_remote_item, @strong sync_local_to_remote, @strong sync_remote_to_local, @strong remove_ui_item => move |dialog, resp| {
+                                match resp {
+                                    ResponseType::Other(0) => {
+                                        if sync_local_to_remote(AuditReason::KeptLocal).is_ok() {
+                                            update_db_item();
+                                            remove_ui_item();
+                                        }
+                                    },
+                                    ResponseType::Other(1) => {
+                                        if sync_remote_to_local().is_ok() {
+                                            update_db_item();
+                                            remove_ui_item();
+                                        }
+                                    },
+                                    // Comparing the copies leaves the choice of which to keep open.
+                                    ResponseType::Other(2) => {
+                                        conflicts::compare_window(dialog, &local_item, &remote.name, &remote_item);
+                                        return;
+                                    },
+                                    // The merged file replaces the local copy, and then gets synced to the remote.
+                                    ResponseType::Other(3) => {
+                                        conflicts::merge_window(dialog, &local_item, &remote.name, &remote_item, glib::clone!(@weak dialog, @strong db, @strong sync_dir, @strong local_item, @strong remote_item, @strong sync_local_to_remote, @strong update_db_item, @strong remove_ui_item => @default-return false, move |merged| {
+                                            if let Err(err) = fs::write(&local_item, merged) {
+                                                gtk_util::show_error(&tr::tr!("Unable to save the merged file to '{}'.", libceleste::fmt_home(&local_item)), Some(&err.to_string()));
+                                                return false;
+                                            }
+                                            AuditLog::start().record(&db, &sync_dir, AuditAction::OverwriteLocal, AuditReason::Merged, &local_item, &remote_item);
+                                            if sync_local_to_remote(AuditReason::Merged).is_err() {
+                                                return false;
+                                            }
 
-                            // Record the current transaction's timestamps in the database.
-                            libceleste::await_future(
-                                SyncItemsActiveModel {
-                                    sync_dir_id: ActiveValue::Set(sync_dir.id),
-                                    local_path: ActiveValue::Set(local_path.clone()),
-                                    remote_path: ActiveValue::Set(remote_path.clone()),
-                                    last_local_timestamp: ActiveValue::Set(
-                                        local_utc_timestamp.try_into().unwrap(),
-                                    ),
-                                    last_remote_timestamp: ActiveValue::Set(
-                                        remote_item_safe
-                                            .mod_time
-                                            .unix_timestamp()
-                                            .try_into()
-                                            .unwrap(),
-                                    ),
-                                    ..Default::default()
+                                            update_db_item();
+                                            remove_ui_item();
+                                            dialog.close();
+                                            true
+                                        }));
+                                        return;
+                                    },
+                                    ResponseType::Other(_) => unreachable!(),
+                                    _ => return
                                 }
-                                .insert(db),
-                            )
-                            .unwrap();
+
+                                dialog.close();
+                            }));
+
+                            dialog.show();
                         }
                     }
-                }
+                });
+                let gesture = GestureClick::new();
+                gesture.connect_released(glib::clone!(@strong activate_error => move |_, _, _, _| activate_error()));
+                ui_item.add_controller(&gesture);
+                // Clicks are handled above, so this only gets emitted from the keyboard.
+                ui_item_listbox.connect_activate(move |_| activate_error());
+
+                // The context menu for the error, containing actions for the erroring item.
+                let show_context_menu = glib::clone!(@strong db, @weak directory_map, @strong remote, @strong path_pair, @strong sync_dir, @strong error, @weak ui_item, @strong remove_ui_item => move || {
+                    // The path of the item relative to the sync directory.
+                    let relative_path = get_relative_path(&sync_dir, error.path());
+                    let local_path = Path::new(&sync_dir.local_path).join(&relative_path).to_string_lossy().into_owned();
+                    let remote_path = paths::join_remote(&sync_dir.remote_path, &relative_path);
+
+                    let popover_sections = Box::new(Orientation::Vertical, 5);
+                    let popover = Popover::builder()
+                        .child(&popover_sections)
+                        .position(PositionType::Bottom)
+                        .build();
+                    popover.set_parent(&ui_item);
+                    popover.connect_closed(|popover| popover.unparent());
 
-                // Sync a remote directory. It's implemented as a function because of the same
-                // logic for `fn sync_local_directory` above.
-                // - NOTE: `remote_dir` should be: 1. the path with any `/` prefix/suffix
-                //   removed 2. the full path from the root of the remote server.
-                #[allow(clippy::too_many_arguments)]
-                fn sync_remote_directory<
-                    F1: Fn(SyncError) + Clone,
-                    F2: Fn() + Clone,
-                    F3: Fn() + Clone,
-                >(
-                    remote_dir: &str,
-                    remote: &RemotesModel,
-                    sync_dir: &SyncDirsModel,
-                    db: &DatabaseConnection,
-                    directory_map: &DirectoryMap,
-                    synced_items: &RefCell<Vec<(String, String)>>,
-                    add_error: F1,
-                    check_open_requests: F2,
-                    process_deletion_requests: F3,
-                ) {
-                    process_deletion_requests();
-
-                    let ignore_file_string =
-                        format!("{}/{}", sync_dir.local_path, FILE_IGNORE_NAME);
-                    let ignore_file_path = Path::new(&ignore_file_string);
-                    let ignore_globs = if ignore_file_path.exists() {
-                        let _lock = FileLock::lock(
-                            ignore_file_path,
-                            true,
-                            FileOptions::new().write(true).read(true),
-                        )
-                        .unwrap();
-                        let file_content = fs::read_to_string(ignore_file_path).unwrap();
-                        let mut globs = vec![];
+                    // Open the folder containing the item in the file manager.
+                    let open_folder_button = Button::builder()
+                        .label(&tr::tr!("Open containing folder"))
+                        .css_classes(vec!["flat".to_string()])
+                        .build();
+                    open_folder_button.connect_clicked(glib::clone!(@strong local_path, @weak popover => move |_| {
+                        popover.popdown();
+                        let folder = Path::new(&local_path).parent().unwrap().to_owned();
 
-                        for line in file_content.lines() {
-                            if let Ok(pattern) = glob::Pattern::new(line) {
-                                globs.push(pattern);
-                            }
+                        if !folder.exists() {
+                            gtk_util::show_error(&tr::tr!("The folder '{}' doesn't exist anymore.", libceleste::fmt_home(&folder.display().to_string())), None);
+                            return;
                         }
 
-                        globs
-                    } else {
-                        vec![]
-                    };
-                    let update_ui_progress = |dir: &str| {
-                        // If this directory no longer exists in the database (i.e. from being
-                        // deleted from the `sync_dir_deletion_queue`, do nothing).
-                        if !sync_dir.exists(db) {
-                            return;
+                        gtk_util::open_uri(&folder.display().to_string());
+                    }));
+                    popover_sections.append(&open_folder_button);
+
+                    // Open the item on the remote's website, which is disabled if the remote's website can't be linked to items.
+                    let rclone_remote = rclone::get_remote(&remote.name);
+                    let open_remote_button = Button::builder()
+                        .label(&tr::tr!("Open on remote"))
+                        .css_classes(vec!["flat".to_string()])
+                        .sensitive(rclone_remote.as_ref().map_or(false, |rclone_remote| rclone_remote.supports_web_urls()))
+                        .build();
+                    open_remote_button.connect_clicked(glib::clone!(@strong remote_path, @weak popover => move |_| {
+                        popover.popdown();
+
+                        match rclone_remote.as_ref().map(|rclone_remote| rclone_remote.web_url(&remote_path)) {
+                            Some(Ok(Some(url))) => gtk_util::open_uri(&url),
+                            Some(Err(err)) => gtk_util::show_error(&tr::tr!("Unable to get a link to '{}' on the remote.", remote_path), Some(err.message())),
+                            Some(Ok(None)) | None => gtk_util::show_error(&tr::tr!("Unable to get a link to '{}' on the remote.", remote_path), None),
                         }
+                    }));
+                    popover_sections.append(&open_remote_button);
+
+                    // Copying a share link is what makes the item public, so it's only done when asked for.
+                    let links_supported = supports_links(&db, &remote.name);
+                    let share_link_button = Button::builder()
+                        .label(&tr::tr!("Copy share link"))
+                        .css_classes(vec!["flat".to_string()])
+                        .sensitive(links_supported)
+                        .build();
+                    share_link_button.connect_clicked(glib::clone!(@strong remote, @strong remote_path, @weak popover, @weak ui_item => move |_| {
+                        popover.popdown();
+                        copy_share_link(&ui_item, &remote.name, &remote_path);
+                    }));
+                    popover_sections.append(&share_link_button);
+
+                    // Exclude the item from syncing.
+                    let exclude_button = Button::builder()
+                        .label(&tr::tr!("Exclude from sync"))
+                        .css_classes(vec!["flat".to_string()])
+                        .build();
+                    exclude_button.connect_clicked(glib::clone!(@weak directory_map, @strong remote, @strong path_pair, @strong relative_path, @weak popover, @strong remove_ui_item => move |_| {
+                        popover.popdown();
 
                         let ptr = directory_map.get_ref();
-                        let dir_pair = (sync_dir.local_path.clone(), sync_dir.remote_path.clone());
-                        let item = ptr.get(&remote.name).unwrap().get(&dir_pair).unwrap();
-                        let status_string = tr::tr!("Checking '{}' on remote for changes...", dir);
-                        item.status_text.set_label(&status_string);
-                    };
-                    update_ui_progress(remote_dir);
-                    let items = match rclone::sync::list(
-                        &remote.name,
-                        remote_dir,
-                        false,
-                        RcloneListFilter::All,
-                    ) {
-                        Ok(ok_items) => ok_items,
-                        Err(err) => {
-                            add_error(SyncError::General(remote_dir.to_owned(), err.error));
-                            return;
-                        }
-                    };
+                        let item = ptr.get(&remote.name).unwrap().get(&path_pair).unwrap();
+                        (item.add_exclusion)(glob::Pattern::escape(&relative_path));
+                        // Drop the pointer so `remove_ui_item` can get its own.
+                        drop(ptr);
 
-                    for item in items {
-                        // If a close request was sent in, stop syncing this remote so we can quit
-                        // the application in the 'main loop.
-                        if *(*CLOSE_REQUEST).lock().unwrap() {
-                            break;
-                        }
+                        remove_ui_item();
+                    }));
+                    popover_sections.append(&exclude_button);
+
+                    // Stop reporting the error for a while.
+                    for (label, duration) in snooze_durations() {
+                        let snooze_button = Button::builder()
+                            .label(&label)
+                            .css_classes(vec!["flat".to_string()])
+                            .build();
+                        snooze_button.connect_clicked(glib::clone!(@strong db, @strong sync_dir, @strong error, @weak popover, @strong remove_ui_item => move |_| {
+                            popover.popdown();
+                            snooze_error(&db, &sync_dir, &error, duration);
+                            remove_ui_item();
+                        }));
+                        popover_sections.append(&snooze_button);
+                    }
 
-                        // Check for open requests.
-                        check_open_requests();
+                    popover.popup();
+                });
+                let context_gesture = GestureClick::new();
+                // The secondary (right) mouse button.
+                context_gesture.set_button(3);
+                context_gesture.connect_pressed(glib::clone!(@strong show_context_menu => move |_, _, _, _| show_context_menu()));
+                ui_item.add_controller(&context_gesture);
+                // And the usual keyboard shortcuts for opening a context menu.
+                let context_shortcut = Shortcut::new(
+                    ShortcutTrigger::parse_string("<Shift>F10|Menu").as_ref(),
+                    Some(&CallbackAction::new(move |_, _| {
+                        show_context_menu();
+                        true
+                    })),
+                );
+                let context_shortcut_controller = ShortcutController::new();
+                context_shortcut_controller.add_shortcut(&context_shortcut);
+                ui_item_listbox.add_controller(&context_shortcut_controller);
+
+                // Add the error to the UI.
+                let mut ptr = directory_map.get_mut_ref();
+                let item = ptr
+                    .get_mut(&remote.name)
+                    .unwrap()
+                    .get_mut(&path_pair)
+                    .unwrap();
+                item.error_list.append(&ui_item_listbox);
+                item.error_items.insert(error, ui_item);
+            });
+            directory_map
+                .get_mut_ref()
+                .get_mut(&remote.name)
+                .unwrap()
+                .get_mut(&(sync_dir.local_path.clone(), sync_dir.remote_path.clone()))
+                .unwrap()
+                .show_error_row = Some(Rc::new(show_error_row.clone()));
+
+            let add_error = glib::clone!(@strong db, @strong directory_map, @strong remote, @strong sync_dir, @strong sync_errors_count, @strong stats, @strong show_error_row => move |error: SyncError| {
+                // Snoozed errors aren't reported until their snooze runs out.
+                if is_snoozed(&db, &sync_dir, &error) {
+                    stats.record_snoozed_error();
+                    return;
+                }
 
-                        // If this directory no longer exists in the database (i.e. from being
-                        // deleted from the `sync_dir_deletion_queue`), stop processing and return.
-                        if !sync_dir.exists(db) {
-                            break;
-                        }
+                stats.record_error();
+                let path_pair = (sync_dir.local_path.clone(), sync_dir.remote_path.clone());
 
-                        // If this item matches the ignore filter, don't sync it.
-                        if ignore_globs
-                            .iter()
-                            .filter(|pattern| pattern.matches(&item.path))
-                            .count()
-                            > 0
-                        {
-                            continue;
-                        }
+                // Errors that haven't been resolved get found again by each pass, so only
+                // report the ones that are new.
+                let known = {
+                    let ptr = directory_map.get_ref();
+                    let item = &ptr[&remote.name][&path_pair];
+                    item.error_items.contains_key(&error) || item.hidden_errors.contains(&error)
+                };
+                if let SyncError::BothMoreCurrent(local_item, remote_item) = &error && !known {
+                    let event = SyncEvent::ConflictDetected {
+                        local_item: local_item.clone(),
+                        remote_item: remote_item.clone(),
+                    };
+                    webhooks::send(&db, &sync_dir, &remote.name, event);
+                }
 
-                        let remote_path_string = item.path.clone();
-                        let local_path_string = format!(
-                            "{}/{}",
-                            sync_dir.local_path,
-                            item.path.strip_prefix(&sync_dir.remote_path).unwrap()
-                        );
-                        update_ui_progress(&remote_path_string);
-                        // If we've already synced this directory from `fn sync_local_directory`
-                        // above, don't sync it again.
-                        if synced_items
-                            .borrow()
-                            .contains(&(local_path_string.clone(), remote_path_string.clone()))
-                        {
-                            continue;
-                        }
+                // Record the error on the local item, if the error is for one.
+                let error_path = error.path();
+                path_status::record_error(error_path);
+                if write_sync_status && Path::new(error_path).starts_with(&sync_dir.local_path) {
+                    set_sync_status(Path::new(error_path), "error");
+                }
 
-                        let local_path = Path::new(&local_path_string);
-                        let remote_timestamp = item.mod_time.unix_timestamp();
-                        let get_local_file_timestamp = || {
-                            local_path.metadata().ok().map(|metadata| {
-                                metadata
-                                    .modified()
-                                    .unwrap()
-                                    .duration_since(SystemTime::UNIX_EPOCH)
-                                    .unwrap()
-                                    .as_secs()
-                            })
-                        };
-                        let local_timestamp = get_local_file_timestamp();
-                        let db_item = libceleste::await_future(
-                            SyncItemsEntity::find()
-                                .filter(SyncItemsColumn::LocalPath.eq(local_path_string.clone()))
-                                .filter(SyncItemsColumn::RemotePath.eq(remote_path_string.clone()))
-                                .one(db),
-                        )
-                        .unwrap();
+                // If we have zero errors now, remove the warning icon.
+                if sync_errors_count() == 0 {
+                    send_dbus_fn("SetSyncingIcon");
+                }
 
-                        // Push the item from the local machine to the remote machine. Returns the
-                        // timestamp of the new file on the remote. Returns the
-                        // [`crate::rclone::sync::RcloneRemoteItem`] of the item on the remote, or
-                        // an [`Err<()>`] if an issue occurred (all errors are automatically added
-                        // via `add_errors`).
-                        let push_local_to_remote = || {
-                            if local_path.is_dir() {
-                                if !item.is_dir {
-                                    if let Err(err) =
-                                        rclone::sync::delete(&remote.name, &remote_path_string)
-                                    {
-                                        add_error(SyncError::General(
-                                            remote_path_string.clone(),
-                                            err.error,
-                                        ));
-                                        return Err(());
-                                    }
-
-                                    if let Err(err) =
-                                        rclone::sync::mkdir(&remote.name, &remote_path_string)
-                                    {
-                                        add_error(SyncError::General(
-                                            remote_path_string.clone(),
-                                            err.error,
-                                        ));
-                                        return Err(());
-                                    }
-                                }
+                // Add the error to the UI, or keep it until there's room for it if the error
+                // list is full.
+                let has_room = directory_map.get_ref()[&remote.name][&path_pair].error_items.len() < MAX_SHOWN_ERRORS;
+                if known {
+                    // It's still listed from when it was first found.
+                } else if has_room {
+                    show_error_row(error);
+                } else {
+                    directory_map
+                        .get_mut_ref()
+                        .get_mut(&remote.name)
+                        .unwrap()
+                        .get_mut(&path_pair)
+                        .unwrap()
+                        .hidden_errors
+                        .insert(error);
+                }
 
-                                sync_remote_directory(
-                                    &item.path,
-                                    remote,
-                                    sync_dir,
-                                    db,
-                                    directory_map,
-                                    synced_items,
-                                    add_error.clone(),
-                                    check_open_requests.clone(),
-                                    process_deletion_requests.clone(),
-                                );
-                                update_ui_progress(&remote_path_string);
-                            } else {
-                                if item.is_dir {
-                                    if let Err(err) =
-                                        rclone::sync::purge(&remote.name, &remote_path_string)
-                                    {
-                                        add_error(SyncError::General(
-                                            remote_path_string.clone(),
-                                            err.error,
-                                        ));
-                                        return Err(());
-                                    }
-                                }
+                // Report the brief on the number of errors.
+                let ptr = directory_map.get_ref();
+                let item = &ptr[&remote.name][&path_pair];
+                let new_num_errors = item.error_count();
+                let error_string = tr::tr!("{n} error found. " | "{n} errors found. " % new_num_errors);
+                item.error_status_text.set_label(&error_string);
+                item.update_hidden_errors_button();
+                item.update_conflicts_button();
+                (item.update_error_ui)();
+
+                // Set the tray icon to show the warning icon.
+                send_dbus_fn("SetWarningIcon");
+            });
 
-                                if let Err(err) = rclone::sync::copy_to_remote(
-                                    &local_path_string,
-                                    &remote.name,
-                                    &remote_path_string,
-                                ) {
-                                    add_error(SyncError::General(
-                                        remote_path_string.clone(),
-                                        err.error,
-                                    ));
-                                    return Err(());
-                                }
-                            }
+            // Get any pending deletion requests and process them.
+            let process_deletion_requests = glib::clone!(@strong db, @weak stack, @strong directory_map, @strong mount_map, @strong remote_deletion_queue, @strong sync_dir_deletion_queue => move || {
+                let mut dmap = directory_map.get_mut_ref();
+                let mut remote_queue = remote_deletion_queue.get_mut_ref();
+                let mut dir_queue = sync_dir_deletion_queue.get_mut_ref();
 
-                            Ok(rclone::sync::stat(&remote.name, &remote_path_string)
-                                .unwrap()
-                                .unwrap())
-                        };
+                // Process directory deletions.
+                while !dir_queue.is_empty() {
+                    let queue_item = dir_queue.remove(0);
+                    let dir_pair = (queue_item.1.clone(), queue_item.2.clone());
+                    let ui_item = dmap.get(&queue_item.0).unwrap().get(&dir_pair).unwrap();
 
-                        // Pull the item from the remote to the local machine.
-                        let pull_remote_to_local = || {
-                            // Make sure file types match up.
-                            if local_path.exists() {
-                                if local_path.is_dir() && !item.is_dir {
-                                    if let Err(err) = fs::remove_dir_all(local_path) {
-                                        add_error(SyncError::General(
-                                            local_path_string.clone(),
-                                            err.to_string(),
-                                        ));
-                                        return Err(());
-                                    }
-                                } else if !local_path.is_dir() && item.is_dir {
-                                    if let Err(err) = fs::remove_file(local_path) {
-                                        add_error(SyncError::General(
-                                            local_path_string.clone(),
-                                            err.to_string(),
-                                        ));
-                                        return Err(());
-                                    }
-
-                                    if let Err(err) = fs::create_dir(local_path) {
-                                        add_error(SyncError::General(
-                                            local_path_string.clone(),
-                                            err.to_string(),
-                                        ));
-                                        return Err(());
-                                    }
-                                }
-                            }
+                    // Remove the item from the UI.
+                    ui_item.parent_list.remove(&ui_item.container);
 
-                            if item.is_dir {
-                                if !local_path.exists() && let Err(err) = fs::create_dir(local_path) {
-                                    add_error(SyncError::General(local_path_string.clone(), err.to_string()));
-                                    return Err(());
-                                }
+                    // Remove the item from the directory map.
+                    dmap.get_mut(&queue_item.0).unwrap().remove(&dir_pair).unwrap();
 
-                                sync_remote_directory(
-                                    &item.path,
-                                    remote,
-                                    sync_dir,
-                                    db,
-                                    directory_map,
-                                    synced_items,
-                                    add_error.clone(),
-                                    check_open_requests.clone(),
-                                    process_deletion_requests.clone(),
-                                );
-                                update_ui_progress(&remote_path_string);
-                            } else if let Err(err) = rclone::sync::copy_to_local(
-                                &local_path_string,
-                                &remote.name,
-                                &remote_path_string,
-                            ) {
-                                add_error(SyncError::General(
-                                    remote_path_string.clone(),
-                                    err.error,
-                                ));
-                                return Err(());
-                            }
+                    // Remove the item from the database.
+                    db::expect(|| async {
+                        let sync_dir = SyncDirsEntity::find()
+                            .filter(SyncDirsColumn::LocalPath.eq(queue_item.1.clone()))
+                            .filter(SyncDirsColumn::RemotePath.eq(queue_item.2.clone()))
+                            .one(&db)
+                            .await?
+                            .unwrap();
+                        health::delete_sync_dir(&db, sync_dir).await
+                    });
+                }
 
-                            Ok(())
-                        };
-                        // Delete this item from the database.
-                        let delete_db_entry = || {
-                            libceleste::await_future(async {
-                                SyncItemsEntity::find()
-                                    .filter(SyncItemsColumn::SyncDirId.eq(sync_dir.id))
-                                    .filter(
-                                        SyncItemsColumn::LocalPath.eq(local_path_string.clone()),
-                                    )
-                                    .filter(
-                                        SyncItemsColumn::RemotePath.eq(remote_path_string.clone()),
-                                    )
-                                    .one(db)
-                                    .await
-                                    .unwrap()
-                                    .unwrap()
-                                    .delete(db)
-                                    .await
-                                    .unwrap()
-                            })
-                        };
+                // Process remote deletions.
+                while !remote_queue.is_empty() {
+                    let remote_name = remote_queue.remove(0);
 
-                        // If we have a database record, use that in checks.
-                        if let Some(db_model) = db_item {
-                            let update_db_item = |local_timestamp, remote_timestamp| {
-                                let mut active_model: SyncItemsActiveModel =
-                                    db_model.clone().into();
-                                active_model.last_local_timestamp =
-                                    ActiveValue::Set(local_timestamp);
-                                active_model.last_remote_timestamp =
-                                    ActiveValue::Set(remote_timestamp);
-                                libceleste::await_future(active_model.update(db)).unwrap();
-                            };
+                    // Unmount the remote if it's mounted.
+                    mount_map.get_mut_ref().remove(&remote_name);
 
-                            // Both items are more recent.
-                            if let Some(l_timestamp) = local_timestamp && l_timestamp > db_model.last_local_timestamp as u64 && remote_timestamp > db_model.last_remote_timestamp as i64 {
-                                // Only add the error if one of the items is not a directory - there's no point in saying both directories are more current, and it's probably because one of the items in the directory got updated anyway.
-                                if !local_path.is_dir() || !item.is_dir {
-                                    add_error(SyncError::BothMoreCurrent(local_path_string.clone(), remote_path_string.clone()));
-                                }
-                                continue;
-                            // The local item is more recent.
-                            } else if let Some(l_timestamp) = local_timestamp && l_timestamp > db_model.last_local_timestamp as u64 {
-                                if let Ok(rclone_item) = push_local_to_remote() {
-                                    update_db_item(get_local_file_timestamp().unwrap().try_into().unwrap(), rclone_item.mod_time.unix_timestamp().try_into().unwrap());
-                                    continue;
-                                } else {
-                                    continue;
-                                }
+                    // Remove the item from the UI.
+                    let child = stack.child_by_name(&remote_name).unwrap();
+                    stack.remove(&child);
 
-                            // The remote item is more recent.
-                            } else if remote_timestamp > db_model.last_remote_timestamp as i64 {
-                                if pull_remote_to_local().is_err() {
-                                    continue;
-                                } else {
-                                    update_db_item(get_local_file_timestamp().unwrap().try_into().unwrap(), remote_timestamp.try_into().unwrap());
-                                }
+                    // Delete all related database entries.
+                    db::expect(|| async {
+                        let db_remote = RemotesEntity::find()
+                            .filter(RemotesColumn::Name.eq(remote_name.clone()))
+                            .one(&db)
+                            .await?
+                            .unwrap();
+                        health::delete_remote(&db, db_remote).await
+                    });
 
-                            // The item is missing locally, but the last recorded timestamp for the remote item is still the same. This means the item got deleted locally, and we need to reflect such on the server.
-                            } else if !local_path.exists() && remote_timestamp == db_model.last_remote_timestamp as i64 {
-                                if let Err(err) = rclone::sync::purge(&remote.name, &remote_path_string) {
-                                    add_error(SyncError::General(remote_path_string.clone(), err.error));
-                                    delete_db_entry();
-                                    continue;
-                                } else {
-                                    continue;
-                                }
+                    // Delete the Rclone config.
+                    rclone::sync::delete_config(&remote_name).unwrap();
+                }
+            });
+            // Don't sync this directory if it overlaps one set up before it on the remote.
+            if let Some(overlapping_dir) = &overlapping_dir {
+                add_error(SyncError::General(
+                    sync_dir.local_path.clone(),
+                    tr::tr!(
+                        "This directory's remote folder overlaps the one '{}' is synced with, so it can't be synced until one of them is removed.",
+                        overlapping_dir
+                    ),
+                ));
+                scheduler.finished(&sync_dir, false, true);
+                continue;
+            }
 
-                            // Both the local and remote item remain unchanged - do nothing.
-                            } else if let Some(l_timestamp) = local_timestamp && l_timestamp == db_model.last_local_timestamp as u64 && remote_timestamp == db_model.last_remote_timestamp as i64 {
-                                continue;
+            // A local directory that's gone missing since it was last synced was most
+            // likely moved or renamed, so let the user sync it from where it is now instead
+            // of treating every item in it as deleted.
+            let local_exists = Path::new(&sync_dir.local_path).exists();
+            if local_exists {
+                skipped_missing_local.remove(&sync_dir.id);
+            } else if skipped_missing_local.contains(&sync_dir.id) {
+                show_missing_dir(
+                    &directory_map,
+                    &remote.name,
+                    &sync_dir,
+                    &tr::tr!("The local folder doesn't exist anymore. Sync now to choose what to do about it."),
+                );
+                scheduler.finished(&sync_dir, false, true);
+                continue;
+            }
+            let local_items = if local_exists {
+                vec![]
+            } else {
+                db::expect(|| {
+                    SyncItemsEntity::find()
+                        .filter(SyncItemsColumn::SyncDirId.eq(sync_dir.id))
+                        .all(&db)
+                })
+            };
+            if !local_items.is_empty() {
+                let found = find_moved_local_dir(&sync_dir, &local_items);
+                let mut error = None;
+                let action = loop {
+                    let action =
+                        ask_missing_local_action(&sync_dir, found.as_deref(), error.as_deref());
+                    let local_path = match action {
+                        MissingLocalAction::UseFound => found.clone().unwrap(),
+                        MissingLocalAction::Locate => match choose_moved_local_dir() {
+                            Some(local_path) => local_path,
+                            None => continue,
+                        },
+                        _ => break Err(action),
+                    };
+                    match check_relink_local_path(&db, &sync_dir, &local_items, &local_path) {
+                        Ok(local_path) => break Ok(local_path),
+                        Err(err) => error = Some(err),
+                    }
+                };
+                let path_pair = (sync_dir.local_path.clone(), sync_dir.remote_path.clone());
+
+                match action {
+                    Ok(local_path) => {
+                        let sync_dir =
+                            relink_sync_dir(&db, &sync_dir, &local_path, &sync_dir.remote_path);
+                        relink_dir_ui(
+                            &directory_map,
+                            &dir_adders,
+                            &remote.name,
+                            &path_pair,
+                            (sync_dir.local_path, sync_dir.remote_path),
+                        );
+                    }
+                    Err(MissingLocalAction::StopSyncing) => {
+                        sync_dir_deletion_queue.get_mut_ref().push((
+                            remote.name.clone(),
+                            path_pair.0,
+                            path_pair.1,
+                        ));
+                        process_deletion_requests();
+                    }
+                    Err(_) => {
+                        skipped_missing_local.insert(sync_dir.id);
+                        show_missing_dir(
+                            &directory_map,
+                            &remote.name,
+                            &sync_dir,
+                            &tr::tr!("The local folder doesn't exist anymore. Sync now to choose what to do about it."),
+                        );
+                        scheduler.finished(&sync_dir, false, true);
+                    }
+                }
+                continue;
+            }
 
-                            // Every possible scenario should have been covered above, so panic if not.
-                            } else {
-                                unreachable!();
-                            }
-                        // Otherwise just check the local timestamps against
-                        // those on th remote, and record our new transaction in
-                        // the database.
-                        } else {
-                            // If the local timestamp exists, then compare local and remote
-                            // timestamps.
-                            if let Some(l_timestamp) = local_timestamp {
-                                if l_timestamp > remote_timestamp as u64 {
-                                    if push_local_to_remote().is_err() {
-                                        continue;
-                                    }
-                                } else if pull_remote_to_local().is_err() {
-                                    continue;
-                                }
+            // Directories added before Celeste refused them might still contain its config.
+            if local_path_has_config(Path::new(&sync_dir.local_path)) {
+                add_error(SyncError::General(
+                    sync_dir.local_path.clone(),
+                    tr::tr!(
+                        "This directory contains Celeste's configuration at '{}', so it can't be synced until it's removed.",
+                        libceleste::get_config_dir().display()
+                    ),
+                ));
+                scheduler.finished(&sync_dir, false, true);
+                continue;
+            }
 
-                            // Otherwise the local item didn't exist, so just
-                            // sync it from the remote.
-                            } else if pull_remote_to_local().is_err() {
-                                continue;
-                            }
-                        }
+            // Lock the directory while we sync it, so another instance of Celeste
+            // doesn't sync it at the same time. The lock gets released when it's dropped at
+            // the end of this iteration.
+            let _lock = match lock_sync_dir(&sync_dir) {
+                Ok(lock) => lock,
+                Err(msg) => {
+                    add_error(SyncError::General(sync_dir.local_path.clone(), msg));
+                    continue;
+                }
+            };
 
-                        // The local item is now guaranteed to exist. Also fetch the remote's
-                        // timestamp in case it got updated above.
-                        let l_timestamp = get_local_file_timestamp().unwrap();
-                        let r_timestamp =
-                            match rclone::sync::stat(&remote.name, &remote_path_string) {
-                                Ok(item) => item.unwrap().mod_time.unix_timestamp(),
-                                Err(err) => {
-                                    add_error(SyncError::General(
-                                        remote_path_string.clone(),
-                                        err.error,
-                                    ));
-                                    continue;
-                                }
-                            };
+            let backend = RcloneBackend::new(&remote.name)
+                .with_capabilities(RemoteCapabilities::get(&db, &remote));
+            let remote_full = full_remotes
+                .get(&remote.name)
+                .map_or(false, |retry_at| Instant::now() < *retry_at);
+            let space = SpaceCheck::for_remote(remote_full);
+            let uploaded = UploadedFiles::default();
+            let audit = AuditLog::start()
+                .with_system_trash(SettingsModel::get(&db).system_trash)
+                .with_remote_options(&remote);
+            let started = system_time_to_timestamp(SystemTime::now());
+            let start = Instant::now();
+
+            // A directory without any sync records hasn't been synced on this device
+            // before, so use the remote folder's manifest to find the files that are
+            // already in sync, instead of comparing every one of them.
+            let has_records = db::expect(|| {
+                SyncItemsEntity::find()
+                    .filter(SyncItemsColumn::SyncDirId.eq(sync_dir.id))
+                    .one(&db)
+            })
+            .is_some();
+
+            // A remote folder that's gone missing since the directory was last synced was
+            // deleted or moved, so let the user choose what to do about it instead of
+            // reporting every item as missing from the remote.
+            let remote_missing = has_records
+                && !sync_dir.remote_path.is_empty()
+                && matches!(backend.stat(&sync_dir.remote_path), Ok(None));
+            if !remote_missing {
+                skipped_missing_remote.remove(&sync_dir.id);
+            } else if skipped_missing_remote.contains(&sync_dir.id) {
+                show_missing_dir(
+                    &directory_map,
+                    &remote.name,
+                    &sync_dir,
+                    &tr::tr!("The remote folder doesn't exist anymore. Sync now to choose what to do about it."),
+                );
+                scheduler.finished(&sync_dir, false, true);
+                continue;
+            } else {
+                let mut error = None;
+                let action = loop {
+                    let action =
+                        ask_missing_remote_action(&sync_dir, &remote.name, error.as_deref());
+                    let MissingRemoteAction::Relink(remote_path) = &action else {
+                        break action;
+                    };
+                    match check_relink_remote_path(&db, &remote, &sync_dir, remote_path) {
+                        Ok(()) => break action,
+                        Err(err) => error = Some(err),
+                    }
+                };
+                let path_pair = (sync_dir.local_path.clone(), sync_dir.remote_path.clone());
+
+                match action {
+                    MissingRemoteAction::Recreate => {
+                        if let Err(err) = backend.mkdir(&sync_dir.remote_path) {
+                            add_error(SyncError::General(
+                                sync_dir.local_path.clone(),
+                                tr::tr!("Unable to create the remote folder again [{}].", err),
+                            ));
+                            continue;
+                        }
 
-                        // Record the current transaction's timestamps in the database.
-                        libceleste::await_future(
-                            SyncItemsActiveModel {
-                                sync_dir_id: ActiveValue::Set(sync_dir.id),
-                                local_path: ActiveValue::Set(local_path_string.clone()),
-                                remote_path: ActiveValue::Set(remote_path_string.clone()),
-                                last_local_timestamp: ActiveValue::Set(
-                                    l_timestamp.try_into().unwrap(),
-                                ),
-                                last_remote_timestamp: ActiveValue::Set(
-                                    r_timestamp.try_into().unwrap(),
-                                ),
-                                ..Default::default()
-                            }
-                            .insert(db),
-                        )
-                        .unwrap();
+                        // Without any records, every local item gets uploaded like on a first sync.
+                        db::expect(|| {
+                            SyncItemsEntity::delete_many()
+                                .filter(SyncItemsColumn::SyncDirId.eq(sync_dir.id))
+                                .exec(&db)
+                        });
+                    }
+                    MissingRemoteAction::StopSyncing => {
+                        sync_dir_deletion_queue.get_mut_ref().push((
+                            remote.name.clone(),
+                            path_pair.0,
+                            path_pair.1,
+                        ));
+                        process_deletion_requests();
+                        continue;
+                    }
+                    MissingRemoteAction::Relink(remote_path) => {
+                        let sync_dir =
+                            relink_sync_dir(&db, &sync_dir, &sync_dir.local_path, &remote_path);
+                        relink_dir_ui(
+                            &directory_map,
+                            &dir_adders,
+                            &remote.name,
+                            &path_pair,
+                            (sync_dir.local_path, sync_dir.remote_path),
+                        );
+                        continue;
+                    }
+                    MissingRemoteAction::Skip => {
+                        skipped_missing_remote.insert(sync_dir.id);
+                        show_missing_dir(
+                            &directory_map,
+                            &remote.name,
+                            &sync_dir,
+                            &tr::tr!("The remote folder doesn't exist anymore. Sync now to choose what to do about it."),
+                        );
+                        scheduler.finished(&sync_dir, false, true);
+                        continue;
                     }
                 }
+            }
 
-                sync_local_directory(
-                    Path::new(&sync_dir.local_path),
-                    &remote,
-                    &sync_dir,
-                    &db,
-                    &directory_map,
-                    &synced_items,
-                    &add_error,
-                    &check_open_requests,
-                    &process_deletion_requests,
+            if !has_records {
+                let path_pair = (sync_dir.local_path.clone(), sync_dir.remote_path.clone());
+                directory_map.get_ref()[&remote.name][&path_pair]
+                    .status_text
+                    .set_label(&tr::tr!("Checking which files are already synced..."));
+                match Manifest::load(&backend, &sync_dir) {
+                    Ok(Some(manifest)) => seed_sync_records(&manifest, &sync_dir, &db, timestamps),
+                    Ok(None) => (),
+                    Err(err) => hw_msg::warningln!(
+                        "Unable to load the manifest for '{}': '{err}'.",
+                        sync_dir.local_path
+                    ),
+                }
+            }
+
+            // Catch any panics while syncing, so that an unexpected issue with this
+            // directory only stops it from syncing, instead of the whole application.
+            let sync_result = catch_panic(|| {
+                // Undo the directory's last sync first if the user asked for such, so that
+                // this sync picks up the restored items.
+                let undo_request = (
+                    remote.name.clone(),
+                    sync_dir.local_path.clone(),
+                    sync_dir.remote_path.clone(),
                 );
-                sync_remote_directory(
-                    &sync_dir.remote_path,
-                    &remote,
+                let undo_requested = {
+                    let mut undo_queue = sync_dir_undo_queue.get_mut_ref();
+                    let requested = undo_queue.contains(&undo_request);
+                    undo_queue.retain(|request| *request != undo_request);
+                    requested
+                };
+                if undo_requested {
+                    for (path, msg) in audit.undo_previous_pass(&backend, &sync_dir, &db) {
+                        add_error(SyncError::General(path, msg));
+                    }
+                }
+
+                recover_interrupted_transfers(&backend, &sync_dir, &db, &add_error);
+
+                // Show which item is being checked as the directory's status.
+                let show_progress = |status: &str| {
+                    let path_pair = (sync_dir.local_path.clone(), sync_dir.remote_path.clone());
+                    directory_map.get_ref()[&remote.name][&path_pair]
+                        .status_text
+                        .set_label(status);
+                };
+                sync_directory(
+                    &backend,
                     &sync_dir,
                     &db,
-                    &directory_map,
-                    &synced_items,
+                    &space,
+                    &uploaded,
+                    &audit,
+                    &stats,
+                    write_sync_status,
+                    timestamps,
                     &add_error,
                     &check_open_requests,
                     &process_deletion_requests,
+                    &show_progress,
                 );
+            });
+            if let Err(msg) = sync_result {
+                add_error(SyncError::General(
+                    sync_dir.local_path.clone(),
+                    tr::tr!("An unexpected error occurred while syncing [{}].", msg),
+                ));
+            }
 
-                // If a close request was sent in, quit.
-                if *(*CLOSE_REQUEST).lock().unwrap() {
-                    continue 'main;
-                }
+            // If a close request was sent in, quit.
+            if *(*CLOSE_REQUEST).lock().unwrap() {
+                continue 'main;
+            }
+
+            // If this sync directory doesn't exist anymore (from being deleted during
+            // `process_deletion_requests` calls in the about two functions), go to the next
+            // sync directory.
+            if !sync_dir.exists(&db) {
+                continue 'main;
+            }
+
+            let settled = finish_sync_pass(
+                &backend,
+                &remote,
+                &sync_dir,
+                &db,
+                &space,
+                &audit,
+                &stats,
+                started,
+                start.elapsed(),
+                &mut scheduler,
+                &mut last_pruned,
+                &add_error,
+            );
+            if settled {
+                settled_dirs.insert(sync_dir.id);
+            } else {
+                settled_dirs.remove(&sync_dir.id);
+            }
 
-                // If this sync directory doesn't exist anymore (from being deleted during
-                // `process_deletion_requests` calls in the about two functions), go to the next
-                // sync directory.
-                if !sync_dir.exists(&db) {
-                    continue 'main;
+            // Pause uploads to the remote for a while if its storage got full, and resume
+            // them once they work again.
+            if space.remote_full() {
+                if !remote_full {
+                    full_remotes.insert(remote.name.clone(), Instant::now() + QUOTA_RETRY_INTERVAL);
+                    if let Some(banner) = storage_banners.get_ref().get(&remote.name) {
+                        banner.show(&remote.name);
+                    }
                 }
+            } else if full_remotes.remove(&remote.name).is_some()
+                && let Some(banner) = storage_banners.get_ref().get(&remote.name)
+            {
+                banner.hide();
+            }
 
-                // Set up the UI for notifying the user that this directory has been synced.
-                let item_ptr = directory_map.get_ref();
-                let item = item_ptr
-                    .get(&remote.name)
-                    .unwrap()
-                    .get(&(sync_dir.local_path.clone(), sync_dir.remote_path.clone()))
-                    .unwrap();
+            // Set up the UI for notifying the user that this directory has been synced.
+            let item_ptr = directory_map.get_ref();
+            let item = item_ptr
+                .get(&remote.name)
+                .unwrap()
+                .get(&(sync_dir.local_path.clone(), sync_dir.remote_path.clone()))
+                .unwrap();
+            item.status_icon
+                .set_child(Some(&get_image("object-select-symbolic")));
+            let mut finished_text = pass_status(&space, &stats);
+            if item.error_status_text.text().len() != 0 {
+                finished_text += &please_resolve_msg;
+                item.status_icon
+                    .set_child(Some(&get_image("dialog-warning-symbolic")));
+            } else {
                 item.status_icon
                     .set_child(Some(&get_image("object-select-symbolic")));
-                let mut finished_text = tr::tr!("Directory has finished sync checks.");
-                if item.error_status_text.text().len() != 0 {
-                    finished_text += &please_resolve_msg;
-                    item.status_icon
-                        .set_child(Some(&get_image("dialog-warning-symbolic")));
-                } else {
-                    item.status_icon
-                        .set_child(Some(&get_image("object-select-symbolic")));
-                }
-                item.status_text.set_label(&finished_text);
-                drop(item_ptr);
             }
+            item.status_text.set_label(&finished_text);
+            drop(item_ptr);
         }
 
         // Notify that we've finished checking all remotes for changes.
         let error_count = sync_errors_count();
 
         if error_count != 0 {
-            let error_msg = if error_count == 1 {
-                "Finished sync checks with 1 error.".to_string()
-            } else {
-                tr::tr!("Finished sync checks with {} errors.", error_count)
-            };
+            let error_msg = tr::tr!(
+                "Finished sync checks with {n} error."
+                    | "Finished sync checks with {n} errors." % error_count
+            );
             send_dbus_msg(&error_msg);
+
+            // Without a tray icon, let the user know about new errors through a desktop
+            // notification instead.
+            if tray_dbus.is_none() && error_count != notified_error_count {
+                let notification = Notification::new(&tr::tr!("Sync errors"));
+                notification.set_body(Some(&error_msg));
+                app.send_notification(Some("sync-errors"), &notification);
+            }
         } else {
-            send_dbus_msg("Finished sync checks.");
+            send_dbus_msg(&tr::tr!("Finished sync checks."));
             send_dbus_fn("SetDoneIcon");
+
+            if tray_dbus.is_none() && notified_error_count != 0 {
+                app.withdraw_notification("sync-errors");
+            }
         }
+        notified_error_count = error_count;
+
+        // Let anything waiting on everything to be synced know whether it has been.
+        record_sync_check(
+            error_count == 0
+                && missing_remotes.is_empty()
+                && pair_ids.iter().all(|id| settled_dirs.contains(id)),
+        );
     }
 
-    // We broke out of the loop because of a close request, so stop the tray app,
-    // and then close and destroy the window.
+    // We broke out of the loop because of a close request, so unmount any mounted
+    // remotes, stop the tray app, and then close and destroy the window.
+    mount_map.get_mut_ref().clear();
     drop(tray_app);
     window.close();
     window.destroy();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync_backend::MemoryBackend;
+    use sea_orm::Database;
+    use std::time::Duration;
+    use tempfile::TempDir;
+    use time::OffsetDateTime;
+
+    /// The remote directory that gets synced with.
+    const REMOTE_DIR: &str = "Documents";
+
+    /// When the items written by the tests were modified, which is far enough
+    /// in the past that they get uploaded without waiting for them to settle.
+    const MOD_TIME: i64 = 1_700_000_000;
+
+    /// A sync directory that syncs with a [`MemoryBackend`], using a database
+    /// of its own.
+    struct TestSync {
+        db: DatabaseConnection,
+        sync_dir: SyncDirsModel,
+        backend: MemoryBackend,
+        // Declared last so that it's removed after the database is closed.
+        _dir: TempDir,
+    }
+
+    impl TestSync {
+        fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let local_path = dir.path().join("local");
+            fs::create_dir(&local_path).unwrap();
+            let db_path = dir.path().join("celeste.db");
+            fs::File::create(&db_path).unwrap();
+            let db = libceleste::await_future(Database::connect(format!(
+                "sqlite://{}",
+                db_path.display()
+            )))
+            .unwrap();
+            libceleste::await_future(Migrator::up(&db, None)).unwrap();
+
+            let remote = db::expect(|| {
+                RemotesActiveModel {
+                    name: ActiveValue::Set("memory".to_owned()),
+                    ..Default::default()
+                }
+                .insert(&db)
+            });
+            let sync_dir = db::expect(|| {
+                SyncDirsActiveModel {
+                    remote_id: ActiveValue::Set(remote.id),
+                    local_path: ActiveValue::Set(local_path.to_str().unwrap().to_owned()),
+                    remote_path: ActiveValue::Set(REMOTE_DIR.to_owned()),
+                    remove_empty_dirs: ActiveValue::Set(false),
+                    ..Default::default()
+                }
+                .insert(&db)
+            });
+            let backend = MemoryBackend::new();
+            backend.mkdir(REMOTE_DIR).unwrap();
+
+            Self {
+                db,
+                sync_dir,
+                backend,
+                _dir: dir,
+            }
+        }
+
+        fn local_path(&self, path: &str) -> PathBuf {
+            Path::new(&self.sync_dir.local_path).join(path)
+        }
+
+        fn remote_path(&self, path: &str) -> String {
+            paths::join_remote(REMOTE_DIR, path)
+        }
+
+        /// Write a local file, modified at `mod_time`.
+        fn write_local(&self, path: &str, contents: &str, mod_time: i64) {
+            let path = self.local_path(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, contents).unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(mod_time as u64))
+                .unwrap();
+        }
+
+        /// Write a remote file, modified at `mod_time`.
+        fn write_remote(&self, path: &str, contents: &str, mod_time: i64) {
+            self.backend.insert_file(
+                &self.remote_path(path),
+                contents.as_bytes(),
+                OffsetDateTime::from_unix_timestamp(mod_time).unwrap(),
+            );
+        }
+
+        fn read_local(&self, path: &str) -> Option<String> {
+            fs::read_to_string(self.local_path(path)).ok()
+        }
+
+        fn read_remote(&self, path: &str) -> Option<String> {
+            self.backend
+                .contents(&self.remote_path(path))
+                .map(|contents| String::from_utf8(contents).unwrap())
+        }
+
+        /// Run a sync pass, returning the errors it ran into.
+        fn sync(&self) -> Vec<SyncError> {
+            let errors = RefCell::new(vec![]);
+            sync_directory(
+                &self.backend,
+                &self.sync_dir,
+                &self.db,
+                &SpaceCheck::default(),
+                &UploadedFiles::default(),
+                &AuditLog::start(),
+                &PassStats::default(),
+                false,
+                TimestampComparison::with_tolerance(0, 1),
+                |err| errors.borrow_mut().push(err),
+                || (),
+                || (),
+                |_: &str| (),
+            );
+            errors.into_inner()
+        }
+    }
+
+    // Files of the same size get hashed to check if they're duplicates, which
+    // goes through Rclone, so every file in these tests has a different size.
+
+    #[test]
+    fn sync_fresh_directory() {
+        let test = TestSync::new();
+        test.write_local("notes.txt", "local notes", MOD_TIME);
+        test.write_local("photos/cat.txt", "a cat", MOD_TIME);
+        test.write_remote("todo.txt", "remote todo list", MOD_TIME);
+
+        assert_eq!(test.sync(), vec![]);
+        assert_eq!(
+            test.read_remote("notes.txt").as_deref(),
+            Some("local notes")
+        );
+        assert_eq!(test.read_remote("photos/cat.txt").as_deref(), Some("a cat"));
+        assert_eq!(
+            test.read_local("todo.txt").as_deref(),
+            Some("remote todo list")
+        );
+
+        // Everything is in sync now, so another pass has nothing to do.
+        assert_eq!(test.sync(), vec![]);
+        assert_eq!(
+            test.read_remote("notes.txt").as_deref(),
+            Some("local notes")
+        );
+        assert_eq!(
+            test.read_local("todo.txt").as_deref(),
+            Some("remote todo list")
+        );
+    }
+
+    #[test]
+    fn sync_conflict() {
+        let test = TestSync::new();
+        test.write_local("report.txt", "draft", MOD_TIME);
+        assert_eq!(test.sync(), vec![]);
+
+        test.write_local("report.txt", "local edit", MOD_TIME + 60);
+        test.write_remote("report.txt", "remote edit!", MOD_TIME + 120);
+        assert_eq!(
+            test.sync(),
+            vec![SyncError::BothMoreCurrent(
+                test.local_path("report.txt").to_str().unwrap().to_owned(),
+                test.remote_path("report.txt"),
+            )]
+        );
+
+        // Neither side gets overwritten until the user picks which to keep.
+        assert_eq!(test.read_local("report.txt").as_deref(), Some("local edit"));
+        assert_eq!(
+            test.read_remote("report.txt").as_deref(),
+            Some("remote edit!")
+        );
+    }
+
+    #[test]
+    fn sync_deletions() {
+        let test = TestSync::new();
+        test.write_local("local.txt", "deleted locally", MOD_TIME);
+        test.write_remote("remote.txt", "deleted on the remote", MOD_TIME);
+        assert_eq!(test.sync(), vec![]);
+
+        fs::remove_file(test.local_path("local.txt")).unwrap();
+        test.backend
+            .delete(&test.remote_path("remote.txt"))
+            .unwrap();
+        assert_eq!(test.sync(), vec![]);
+        assert_eq!(test.read_remote("local.txt"), None);
+        assert_eq!(test.read_local("remote.txt"), None);
+
+        // The deletions stay put on the next pass.
+        assert_eq!(test.sync(), vec![]);
+        assert_eq!(test.read_remote("local.txt"), None);
+        assert_eq!(test.read_local("remote.txt"), None);
+    }
+
+    #[test]
+    fn sync_exclusions() {
+        let test = TestSync::new();
+        db::expect(|| {
+            let mut sync_dir: SyncDirsActiveModel = test.sync_dir.clone().into();
+            sync_dir.exclusions_in_db = ActiveValue::Set(true);
+            sync_dir.exclusions = ActiveValue::Set("*.log".to_owned());
+            sync_dir.update(&test.db)
+        });
+        test.write_local("debug.log", "local log", MOD_TIME);
+        test.write_local("notes.txt", "local notes!", MOD_TIME);
+        test.write_remote("server.log", "remote log file", MOD_TIME);
+
+        assert_eq!(test.sync(), vec![]);
+        assert_eq!(test.read_remote("debug.log"), None);
+        assert_eq!(test.read_local("server.log"), None);
+        assert_eq!(
+            test.read_remote("notes.txt").as_deref(),
+            Some("local notes!")
+        );
+    }
+
+    #[test]
+    fn timestamps_of_system_times() {
+        let cases = [
+            (SystemTime::UNIX_EPOCH, 0),
+            (
+                SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+                1_700_000_000,
+            ),
+            // Partial seconds are rounded down, so that times on either side of the
+            // epoch line up.
+            (SystemTime::UNIX_EPOCH + Duration::from_millis(1_900), 1),
+            (SystemTime::UNIX_EPOCH + Duration::from_nanos(1), 0),
+            (SystemTime::UNIX_EPOCH - Duration::from_secs(1), -1),
+            (SystemTime::UNIX_EPOCH - Duration::from_millis(100), -1),
+            (SystemTime::UNIX_EPOCH - Duration::from_millis(1_500), -2),
+            (
+                SystemTime::UNIX_EPOCH - Duration::from_secs(86_400 * 365),
+                -86_400 * 365,
+            ),
+            (
+                SystemTime::UNIX_EPOCH + Duration::from_secs(i64::MAX as u64),
+                i64::MAX,
+            ),
+            (
+                SystemTime::UNIX_EPOCH - Duration::from_secs(i64::MAX as u64),
+                -i64::MAX,
+            ),
+        ];
+
+        for (time, expected) in cases {
+            assert_eq!(system_time_to_timestamp(time), expected, "{time:?}");
+        }
+    }
+}
@@ -3,9 +3,15 @@ use super::ServerType;
 use crate::{
     gtk_util,
     login::{dropbox, login_util, pcloud},
-    mpsc::Sender,
+    mpsc::{self, Sender},
+    rclone::{self, GDriveTarget},
+};
+use adw::{
+    glib,
+    gtk::{Button, ListBox, SelectionMode},
+    prelude::*,
+    ApplicationWindow, EntryRow, MessageDialog,
 };
-use adw::{glib, gtk::Button, prelude::*, ApplicationWindow, EntryRow, MessageDialog};
 use libceleste::traits::prelude::*;
 use nix::{
     sys::signal::{self, Signal},
@@ -140,7 +146,7 @@ impl GDriveConfig {
                 args.push("--auth-no-open-browser");
             }
 
-            let mut process = Command::new("rclone")
+            let mut process = rclone::command()
                 .args(&args)
                 .stdout(Stdio::piped())
                 .stderr(Stdio::piped())
@@ -279,3 +285,40 @@ impl GDriveConfig {
         (sections, submit_button)
     }
 }
+
+/// Ask the user which part of their Google Drive account the newly created
+/// remote `remote_name` should use.
+pub fn choose_target(window: &ApplicationWindow, remote_name: &str) {
+    let (target_row, targets) = login_util::gdrive_target_row(remote_name);
+    let target_list = ListBox::builder()
+        .selection_mode(SelectionMode::None)
+        .css_classes(vec!["boxed-list".to_string()])
+        .build();
+    target_list.append(&target_row);
+
+    let (sender, mut receiver) = mpsc::channel::<()>();
+    let dialog = MessageDialog::builder()
+        .transient_for(window)
+        .modal(true)
+        .heading(&tr::tr!("Choose a Drive"))
+        .body(&tr::tr!(
+            "Choose which files on your Google Drive account this remote should use."
+        ))
+        .extra_child(&target_list)
+        .build();
+    dialog.add_response("ok", &tr::tr!("Ok"));
+    dialog.connect_response(
+        None,
+        glib::clone!(@strong sender => move |dialog, _| {
+            dialog.close();
+            sender.send(());
+        }),
+    );
+    dialog.show();
+    receiver.recv();
+
+    let target = &targets[target_row.selected() as usize];
+    if *target != GDriveTarget::MyDrive {
+        rclone::set_gdrive_target(remote_name, target);
+    }
+}
@@ -1,9 +1,9 @@
 //! A collection of helper functions for generating login UIs.
-use crate::rclone::{self};
+use crate::rclone::{self, GDriveTarget};
 use adw::{
-    gtk::{Align, Button, Label},
+    gtk::{Align, Button, Label, StringList},
     prelude::*,
-    EntryRow, PasswordEntryRow,
+    ComboRow, EntryRow, PasswordEntryRow,
 };
 
 use regex::Regex;
@@ -124,3 +124,25 @@ pub fn check_responses(responses: &[&EntryRow], submit_button: &Button) {
 
     submit_button.set_sensitive(no_errors);
 }
+
+/// Get a row for choosing the part of a Google Drive account that the Google
+/// Drive remote `remote_name` uses, with its current one selected. The
+/// returned targets are in the same order as the row's items.
+pub fn gdrive_target_row(remote_name: &str) -> (ComboRow, Vec<GDriveTarget>) {
+    let (targets, labels): (Vec<_>, Vec<_>) = rclone::sync::gdrive_targets(remote_name)
+        .into_iter()
+        .unzip();
+    let model = StringList::new(&labels.iter().map(String::as_str).collect::<Vec<_>>());
+    let row = ComboRow::builder()
+        .title(&tr::tr!("Drive"))
+        .model(&model)
+        .build();
+
+    if let Some(rclone::Remote::GDrive(remote)) = rclone::get_remote(remote_name)
+        && let Some(index) = targets.iter().position(|target| *target == remote.target)
+    {
+        row.set_selected(index as u32);
+    }
+
+    (row, targets)
+}
@@ -1,9 +1,10 @@
 //! Functions and libcelesteities for logging in to a server.
 use crate::{
+    db,
     entities::{RemotesActiveModel, RemotesModel},
     gtk_util,
     mpsc::{self, Sender},
-    rclone,
+    rclone::{self, RcloneError},
 };
 use libceleste::traits::prelude::*;
 mod dropbox;
@@ -67,7 +68,7 @@ impl ToString for ServerType {
 // Verify if a specific config can log in to a server.
 pub fn can_login(_app: &Application, config_name: &str) -> bool {
     if let Err(err) = rclone::sync::stat(config_name, "/") {
-        let err_msg = if err.error.contains("Temporary failure in name resolution") {
+        let err_msg = if let RcloneError::Network(_) = err {
             tr::tr!(
                 "Unable to connect to the server. Check your internet connection and try again."
             )
@@ -96,7 +97,7 @@ pub fn login(app: &Application, db: &DatabaseConnection) -> Option<RemotesModel>
     let window = ApplicationWindow::builder()
         .application(app)
         .title(&libceleste::get_title!("Log in"))
-        .width_request(400)
+        .default_width(400)
         .build();
     window.add_css_class("celeste-global-padding");
     window.connect_close_request(glib::clone!(@strong sender => move |_| {
@@ -305,14 +306,19 @@ pub fn login(app: &Application, db: &DatabaseConnection) -> Option<RemotesModel>
         // We've passed validation otherwise, so add the remote to the db, close
         // the window and return the config.
         } else {
-            let model = libceleste::await_future(
+            // Google Drive remotes can use a shared drive, or the files shared with the
+            // user, instead of their own files.
+            if let ServerType::GDrive(_) = server {
+                gdrive::choose_target(&window, &config_name);
+            }
+
+            let model = db::expect(|| {
                 RemotesActiveModel {
-                    name: ActiveValue::Set(config_name),
+                    name: ActiveValue::Set(config_name.clone()),
                     ..Default::default()
                 }
-                .insert(db),
-            )
-            .unwrap();
+                .insert(db)
+            });
 
             window.close();
             return Some(model);
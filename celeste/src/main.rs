@@ -6,25 +6,62 @@
 #![feature(exit_status_error)]
 
 pub mod about;
+pub mod audit;
+pub mod backup;
+pub mod bench;
+pub mod capabilities;
+pub mod conflicts;
+pub mod db;
+pub mod encryption;
+pub mod engine;
 pub mod entities;
+pub mod exclusions;
+pub mod file_blocks;
+pub mod frontend;
 pub mod gtk_util;
+pub mod health;
+pub mod ipc;
 pub mod launch;
 pub mod login;
+pub mod metrics;
 pub mod migrations;
+pub mod mount;
 pub mod mpsc;
+pub mod path_status;
+pub mod paths;
+pub mod platform;
+pub mod preferences;
 pub mod rclone;
+pub mod remote_state;
+pub mod scheduler;
+pub mod shortcuts;
+pub mod stats;
+pub mod sync_backend;
+pub mod sync_decision;
+pub mod sync_preview;
+pub mod webhooks;
 
 use adw::{
     gtk::{self, gdk::Display, Align, Box, CssProvider, Label, Orientation, StyleContext},
     prelude::*,
     Application, ApplicationWindow, HeaderBar,
 };
-use clap::{Parser, Subcommand};
+use bench::BenchOptions;
+use clap::{Parser, Subcommand, ValueEnum};
+use engine::EngineOptions;
+use ipc::IpcCommand;
+use launch::{AppAction, CloseAction, LaunchOptions};
+use nix::{
+    libc::c_int,
+    sys::signal::{self, SigHandler, Signal},
+    unistd::Pid,
+};
 use serde_json::json;
 use std::{
     env,
     io::{BufRead, BufReader},
-    process::{Command, Stdio},
+    process::{self, Command, Stdio},
+    sync::atomic::{AtomicI32, Ordering},
     thread,
 };
 
@@ -37,6 +74,30 @@ struct Cli {
     /// Whether to start in the background.
     #[arg(long)]
     background: bool,
+
+    /// Whether to run without a tray icon, for desktops that don't support one.
+    #[arg(long)]
+    no_tray: bool,
+
+    /// What to do when the window is closed. Overrides the preference set in
+    /// Celeste's preferences window.
+    #[arg(long, value_enum)]
+    close_action: Option<CloseAction>,
+
+    /// An action to send to the running instance of Celeste. Celeste gets
+    /// started in the background if it isn't already running.
+    #[arg(long, value_enum)]
+    action: Option<AppAction>,
+
+    /// Run as a systemd user service. This starts in the background, and
+    /// reports readiness and watchdog pings to systemd.
+    #[arg(long)]
+    service: bool,
+
+    /// Serve Prometheus metrics about syncing at `/metrics` on this port on
+    /// localhost, while running as a service.
+    #[arg(long, requires = "service")]
+    metrics_port: Option<u16>,
 }
 
 #[derive(Subcommand)]
@@ -45,18 +106,134 @@ enum Commands {
         /// Whether to start in the background.
         #[arg(long)]
         background: bool,
+
+        /// Whether to run without a tray icon.
+        #[arg(long)]
+        no_tray: bool,
+
+        /// What to do when the window is closed.
+        #[arg(long, value_enum)]
+        close_action: Option<CloseAction>,
+
+        /// An action to send to the running instance.
+        #[arg(long, value_enum)]
+        action: Option<AppAction>,
+
+        /// Whether to run as a systemd user service.
+        #[arg(long)]
+        service: bool,
+
+        /// The port to serve Prometheus metrics on.
+        #[arg(long, requires = "service")]
+        metrics_port: Option<u16>,
+    },
+
+    /// Benchmark the backend and database work of syncing a generated tree of
+    /// files.
+    #[command(hide = true)]
+    Bench(BenchOptions),
+
+    /// Run the sync engine without any windows, so that it keeps syncing
+    /// regardless of whether a frontend is open.
+    Engine {
+        /// Whether to run as a systemd user service.
+        #[arg(long)]
+        service: bool,
+
+        /// The port to serve Prometheus metrics on.
+        #[arg(long, requires = "service")]
+        metrics_port: Option<u16>,
+    },
+
+    /// Show what the running sync engine is syncing from a separate process,
+    /// starting the engine in the background if it isn't running. Closing
+    /// the window leaves it syncing.
+    Frontend,
+
+    /// Wait until the running instance of Celeste has synced every directory
+    /// without any errors, such as before shutting down. Exits with an error if
+    /// Celeste isn't running or the timeout is reached first.
+    Wait {
+        /// The most seconds to wait for.
+        #[arg(long)]
+        timeout: Option<u64>,
     },
 }
 
+/// The process ID of the GUI subprocess.
+static GUI_PID: AtomicI32 = AtomicI32::new(0);
+
+/// Forward a signal to the GUI subprocess.
+extern "C" fn forward_signal(signal: c_int) {
+    if let Ok(signal) = Signal::try_from(signal) {
+        signal::kill(Pid::from_raw(GUI_PID.load(Ordering::SeqCst)), signal).unwrap_or(());
+    }
+}
+
 fn main() {
-    // Initialize GTK.
-    gtk::init().unwrap();
+    let cli = Cli::parse();
+
+    // Taking the socket passed in through systemd's socket activation removes its
+    // environment variables, which can only be done safely before any threads are
+    // started. The process that relaunches Celeste leaves them for its subprocess.
+    if let Some(Commands::RunGui { .. } | Commands::Engine { .. }) = &cli.command {
+        ipc::take_activated_listener();
+    }
+
+    // Benchmarks don't use the GUI, so run them before anything else gets set up.
+    if let Some(Commands::Bench(options)) = &cli.command {
+        bench::run(options);
+        return;
+    }
+
+    // Waiting is done by the running instance, so nothing needs setting up for it
+    // either.
+    if let Some(Commands::Wait { timeout }) = &cli.command {
+        let command = IpcCommand::Wait {
+            timeout_secs: *timeout,
+        };
+        if let Err(err) = ipc::send(command) {
+            hw_msg::errorln!("Unable to wait for syncing to finish: '{err}'.");
+            process::exit(1);
+        }
+        return;
+    }
+
+    // Set up translations.
+    tr::tr_init!(libceleste::get_locale_dir());
+    libceleste::init_translations();
 
     // Configure Rclone.
-    let mut config = libceleste::get_config_dir();
-    config.push("rclone.conf");
     librclone::initialize();
-    librclone::rpc("config/setpath", json!({ "path": config }).to_string()).unwrap();
+    librclone::rpc(
+        "config/setpath",
+        json!({ "path": rclone::config_path() }).to_string(),
+    )
+    .unwrap();
+    // There's no terminal to ask for the config's password on, so Celeste asks for
+    // it itself if it's encrypted.
+    librclone::rpc(
+        "options/set",
+        json!({ "main": { "AskPassword": false } }).to_string(),
+    )
+    .unwrap();
+
+    // The engine doesn't have any windows, so it doesn't need GTK, and can run
+    // without a display.
+    if let Some(Commands::Engine {
+        service,
+        metrics_port,
+    }) = &cli.command
+    {
+        engine::run(&EngineOptions {
+            service: *service,
+            metrics_port: *metrics_port,
+        });
+        return;
+    }
+
+    // Initialize GTK.
+    gtk::init().unwrap();
 
     // Load our CSS.
     let provider = CssProvider::new();
@@ -78,10 +255,59 @@ fn main() {
     // observed). Panics would like to be captured when they're encountered though,
     // so we relaunch this program in a subprocess and capture any errors from
     // there.
-    let cli = Cli::parse();
     if let Some(cmd) = cli.command {
         match cmd {
-            Commands::RunGui { background } => {
+            Commands::RunGui {
+                background,
+                no_tray,
+                close_action,
+                action,
+                service,
+                metrics_port,
+            } => {
+                // If Celeste is already running and listening on its socket, send the
+                // action (or a request to open its window) over to it. This works even
+                // when the DBus session bus doesn't.
+                let command = match action {
+                    Some(action) => Some(IpcCommand::from(action)),
+                    None if !background && !service => Some(IpcCommand::Open),
+                    None => None,
+                };
+                if let Some(command) = command
+                    && ipc::send(command).is_ok()
+                {
+                    return;
+                }
+
+                // If an action was requested and Celeste is already running, send the
+                // action over to the running instance.
+                if let Some(action) = action {
+                    app.register(gtk::gio::Cancellable::NONE).unwrap();
+
+                    if app.is_remote() {
+                        app.activate_action(action.action_name(), None);
+
+                        if let Some(dbus) = app.dbus_connection()
+                            && let Err(err) = dbus.flush_sync(gtk::gio::Cancellable::NONE)
+                        {
+                            hw_msg::warningln!(
+                                "Unable to send action to the running instance: '{err}'."
+                            );
+                        }
+                        return;
+                    }
+
+                    action.run();
+                }
+
+                let options = LaunchOptions {
+                    background: background || action.is_some() || service,
+                    no_tray,
+                    close_action,
+                    service,
+                    metrics_port,
+                };
+
                 // Start up the application.
                 app.connect_activate(move |app| {
                     if app.is_remote() {
@@ -91,7 +317,7 @@ fn main() {
 
                     let windows = app.windows();
                     if windows.is_empty() {
-                        launch::launch(app, background);
+                        launch::launch(app, &options);
                     } else {
                         windows.iter().for_each(|window| window.show());
                     }
@@ -99,6 +325,10 @@ fn main() {
 
                 app.run_with_args::<&str>(&[]);
             }
+            Commands::Frontend => frontend::run(),
+            Commands::Bench(_) | Commands::Engine { .. } | Commands::Wait { .. } => {
+                unreachable!()
+            }
         }
     } else {
         // Set `RUST_BACKTRACE` so we get a better backtrace for reporting.
@@ -109,6 +339,29 @@ fn main() {
         if cli.background {
             args.push("--background");
         }
+        if cli.no_tray {
+            args.push("--no-tray");
+        }
+        let close_action = cli
+            .close_action
+            .and_then(|close_action| close_action.to_possible_value());
+        if let Some(close_action) = &close_action {
+            args.push("--close-action");
+            args.push(close_action.get_name());
+        }
+        let action = cli.action.and_then(|action| action.to_possible_value());
+        if let Some(action) = &action {
+            args.push("--action");
+            args.push(action.get_name());
+        }
+        if cli.service {
+            args.push("--service");
+        }
+        let metrics_port = cli.metrics_port.map(|port| port.to_string());
+        if let Some(metrics_port) = &metrics_port {
+            args.push("--metrics-port");
+            args.push(metrics_port);
+        }
 
         let mut command = Command::new(env::args().next().unwrap())
             .args(args)
@@ -116,10 +369,20 @@ fn main() {
             .stderr(Stdio::piped())
             .spawn()
             .unwrap();
+
+        // The GUI handles SIGTERM and SIGINT by finishing up and quitting, so pass them
+        // on to it and keep running until it does, instead of quitting here and
+        // leaving it without anywhere to send its output.
+        GUI_PID.store(command.id() as i32, Ordering::SeqCst);
+        for signal in [Signal::SIGTERM, Signal::SIGINT] {
+            unsafe { signal::signal(signal, SigHandler::Handler(forward_signal)) }.unwrap();
+        }
+
+        let stdout_handle = command.stdout.take().unwrap();
+        let stderr_handle = command.stderr.take().unwrap();
         let stdout_thread = thread::spawn(move || {
             let mut stdout = String::new();
-            let mut stdout_handle = command.stdout.as_mut().unwrap();
-            let stdout_reader = BufReader::new(&mut stdout_handle);
+            let stdout_reader = BufReader::new(stdout_handle);
 
             for line in stdout_reader.lines() {
                 let unwrapped_line = line.unwrap();
@@ -132,8 +395,7 @@ fn main() {
         });
         let stderr_thread = thread::spawn(move || {
             let mut stderr = String::new();
-            let mut stderr_handle = command.stderr.as_mut().unwrap();
-            let stderr_reader = BufReader::new(&mut stderr_handle);
+            let stderr_reader = BufReader::new(stderr_handle);
 
             for line in stderr_reader.lines() {
                 let unwrapped_line = line.unwrap();
@@ -146,6 +408,7 @@ fn main() {
         });
         let _stdout = stdout_thread.join().unwrap();
         let stderr = stderr_thread.join().unwrap();
+        let status = command.wait().unwrap();
 
         let backtrace = {
             let mut backtrace = String::new();
@@ -171,8 +434,10 @@ fn main() {
             }
         };
 
-        // Show the backtrace in the GUI if one was found.
-        if backtrace.is_some() {
+        // Show the backtrace in the GUI if one was found. Panics that were recovered
+        // from (such as those while syncing a directory) still print a backtrace, so
+        // only show it if the program actually crashed.
+        if backtrace.is_some() && !status.success() {
             app.connect_activate(move |app| {
                 let window = ApplicationWindow::builder()
                     .application(app)
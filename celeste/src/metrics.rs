@@ -0,0 +1,187 @@
+//! Prometheus metrics about syncing, which can be served over HTTP on
+//! localhost when running as a service, so that they can be scraped and
+//! graphed (such as in Grafana).
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+
+lazy_static::lazy_static! {
+    // The metrics recorded since Celeste started.
+    static ref METRICS: Mutex<Metrics> = Mutex::new(Metrics::default());
+}
+
+/// The totals of the sync passes over a sync directory.
+#[derive(Default)]
+struct DirMetrics {
+    passes: u64,
+    duration: Duration,
+    files_uploaded: u64,
+    bytes_uploaded: u64,
+    files_downloaded: u64,
+    bytes_downloaded: u64,
+    errors: u64,
+}
+
+/// Everything recorded for the metrics.
+#[derive(Default)]
+struct Metrics {
+    /// The totals for each sync directory, by its local path.
+    dirs: BTreeMap<String, DirMetrics>,
+    /// The number of calls made to Rclone, by the method called.
+    rclone_calls: BTreeMap<String, u64>,
+}
+
+/// The counters in [`DirMetrics`], as their name, help text, and a way to get
+/// their value.
+type DirCounter = (&'static str, &'static str, fn(&DirMetrics) -> String);
+
+const DIR_COUNTERS: &[DirCounter] = &[
+    (
+        "celeste_sync_passes_total",
+        "Sync passes finished.",
+        |dir| dir.passes.to_string(),
+    ),
+    (
+        "celeste_sync_pass_duration_seconds_total",
+        "Time spent in sync passes.",
+        |dir| dir.duration.as_secs_f64().to_string(),
+    ),
+    (
+        "celeste_files_uploaded_total",
+        "Files uploaded to the remote.",
+        |dir| dir.files_uploaded.to_string(),
+    ),
+    (
+        "celeste_bytes_uploaded_total",
+        "Bytes uploaded to the remote.",
+        |dir| dir.bytes_uploaded.to_string(),
+    ),
+    (
+        "celeste_files_downloaded_total",
+        "Files downloaded from the remote.",
+        |dir| dir.files_downloaded.to_string(),
+    ),
+    (
+        "celeste_bytes_downloaded_total",
+        "Bytes downloaded from the remote.",
+        |dir| dir.bytes_downloaded.to_string(),
+    ),
+    (
+        "celeste_sync_errors_total",
+        "Errors run into while syncing.",
+        |dir| dir.errors.to_string(),
+    ),
+];
+
+/// Record a finished sync pass over the directory at `local_path`.
+pub fn record_pass(
+    local_path: &str,
+    duration: Duration,
+    files_uploaded: i32,
+    bytes_uploaded: i64,
+    files_downloaded: i32,
+    bytes_downloaded: i64,
+    errors: i32,
+) {
+    let mut metrics = METRICS.lock().unwrap();
+    let dir = metrics.dirs.entry(local_path.to_owned()).or_default();
+    dir.passes += 1;
+    dir.duration += duration;
+    dir.files_uploaded += files_uploaded.max(0) as u64;
+    dir.bytes_uploaded += bytes_uploaded.max(0) as u64;
+    dir.files_downloaded += files_downloaded.max(0) as u64;
+    dir.bytes_downloaded += bytes_downloaded.max(0) as u64;
+    dir.errors += errors.max(0) as u64;
+}
+
+/// Record a call to Rclone's `method`.
+pub fn record_rclone_call(method: &str) {
+    *METRICS
+        .lock()
+        .unwrap()
+        .rclone_calls
+        .entry(method.to_owned())
+        .or_default() += 1;
+}
+
+/// Escape `value` for use as a label's value.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Render the metrics in Prometheus' text format.
+fn render() -> String {
+    let metrics = METRICS.lock().unwrap();
+    let mut text = String::new();
+
+    for (name, help, value) in DIR_COUNTERS {
+        writeln!(text, "# HELP {name} {help}\n# TYPE {name} counter").unwrap();
+        for (local_path, dir) in &metrics.dirs {
+            let directory = escape_label(local_path);
+            writeln!(text, "{name}{{directory=\"{directory}\"}} {}", value(dir)).unwrap();
+        }
+    }
+
+    let name = "celeste_rclone_calls_total";
+    writeln!(
+        text,
+        "# HELP {name} Calls made to Rclone.\n# TYPE {name} counter"
+    )
+    .unwrap();
+    for (method, calls) in &metrics.rclone_calls {
+        let method = escape_label(method);
+        writeln!(text, "{name}{{method=\"{method}\"}} {calls}").unwrap();
+    }
+
+    text
+}
+
+/// Respond to a client's request for the metrics.
+fn handle_client(mut stream: TcpStream) -> io::Result<()> {
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+
+    let (status, body) = match path {
+        "/metrics" => ("200 OK", render()),
+        _ => ("404 Not Found", String::new()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Start serving the metrics at `/metrics` on `port` in the background. Only
+/// connections from this computer are accepted. The metrics are only an
+/// addition to running as a service, so any issues setting this up are just
+/// warned about.
+pub fn serve(port: u16) {
+    let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            hw_msg::warningln!("Unable to serve metrics on port {port}: '{err}'.");
+            return;
+        }
+    };
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            thread::spawn(move || {
+                if let Err(err) = handle_client(stream) {
+                    hw_msg::warningln!("Got error while serving metrics: '{err}'.");
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,38 @@
+use sea_orm::{ConnectionTrait, Statement};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let backend = manager.get_database_backend();
+
+        for sql in [
+            "ALTER TABLE sync_items ADD COLUMN local_inode BIGINT;",
+            "ALTER TABLE sync_items ADD COLUMN local_size BIGINT;",
+        ] {
+            db.execute(Statement::from_string(backend, sql.to_owned()))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let backend = manager.get_database_backend();
+
+        for sql in [
+            "ALTER TABLE sync_items DROP COLUMN local_inode;",
+            "ALTER TABLE sync_items DROP COLUMN local_size;",
+        ] {
+            db.execute(Statement::from_string(backend, sql.to_owned()))
+                .await?;
+        }
+
+        Ok(())
+    }
+}
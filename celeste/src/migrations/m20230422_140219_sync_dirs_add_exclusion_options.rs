@@ -0,0 +1,40 @@
+use sea_orm::{ConnectionTrait, Statement};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let backend = manager.get_database_backend();
+
+        for sql in [
+            "ALTER TABLE sync_dirs ADD COLUMN ignore_file_name TEXT NOT NULL DEFAULT '.sync-exclude.lst';",
+            "ALTER TABLE sync_dirs ADD COLUMN exclusions_in_db BOOLEAN NOT NULL DEFAULT 0;",
+            "ALTER TABLE sync_dirs ADD COLUMN exclusions TEXT NOT NULL DEFAULT '';",
+        ] {
+            db.execute(Statement::from_string(backend, sql.to_owned()))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let backend = manager.get_database_backend();
+
+        for sql in [
+            "ALTER TABLE sync_dirs DROP COLUMN exclusions;",
+            "ALTER TABLE sync_dirs DROP COLUMN exclusions_in_db;",
+            "ALTER TABLE sync_dirs DROP COLUMN ignore_file_name;",
+        ] {
+            db.execute(Statement::from_string(backend, sql.to_owned()))
+                .await?;
+        }
+
+        Ok(())
+    }
+}
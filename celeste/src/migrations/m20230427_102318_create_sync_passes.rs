@@ -0,0 +1,35 @@
+use sea_orm::{ConnectionTrait, Statement};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Passes are kept after their sync directory or remote is removed, so that
+        // the totals for all remotes don't change, so there's no foreign keys.
+        let sql = r#"
+            CREATE TABLE sync_passes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+                remote_id INTEGER NOT NULL,
+                sync_dir_id INTEGER NOT NULL,
+                started BIGINT NOT NULL,
+                duration_ms BIGINT NOT NULL,
+                files_uploaded INTEGER NOT NULL,
+                bytes_uploaded BIGINT NOT NULL,
+                files_downloaded INTEGER NOT NULL,
+                bytes_downloaded BIGINT NOT NULL,
+                errors INTEGER NOT NULL
+            );
+        "#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        manager.get_connection().execute(stmt).await.map(|_| ())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let sql = "DROP TABLE `sync_passes`;";
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        manager.get_connection().execute(stmt).await.map(|_| ())
+    }
+}
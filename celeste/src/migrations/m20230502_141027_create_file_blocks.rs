@@ -0,0 +1,30 @@
+use sea_orm::{ConnectionTrait, Statement};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let sql = r#"
+            CREATE TABLE file_blocks (
+                id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+                sync_item_id INTEGER NOT NULL,
+                block_index INTEGER NOT NULL,
+                hash TEXT NOT NULL,
+                FOREIGN KEY(sync_item_id) REFERENCES sync_items(id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX file_blocks_sync_item_id ON file_blocks (sync_item_id);
+        "#;
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        manager.get_connection().execute(stmt).await.map(|_| ())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let sql = "DROP TABLE `file_blocks`;";
+        let stmt = Statement::from_string(manager.get_database_backend(), sql.to_owned());
+        manager.get_connection().execute(stmt).await.map(|_| ())
+    }
+}
@@ -0,0 +1,38 @@
+use sea_orm::{ConnectionTrait, Statement};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let backend = manager.get_database_backend();
+
+        for sql in [
+            "ALTER TABLE remotes ADD COLUMN keep_remote_copies BOOLEAN NOT NULL DEFAULT 1;",
+            "ALTER TABLE remotes ADD COLUMN keep_copies_days INTEGER NOT NULL DEFAULT 30;",
+        ] {
+            db.execute(Statement::from_string(backend, sql.to_owned()))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let backend = manager.get_database_backend();
+
+        for sql in [
+            "ALTER TABLE remotes DROP COLUMN keep_copies_days;",
+            "ALTER TABLE remotes DROP COLUMN keep_remote_copies;",
+        ] {
+            db.execute(Statement::from_string(backend, sql.to_owned()))
+                .await?;
+        }
+
+        Ok(())
+    }
+}
@@ -3,6 +3,41 @@ pub use sea_orm_migration::prelude::*;
 mod m20220101_000001_create_table;
 mod m20230207_204909_sync_dirs_remove_slash_suffix;
 mod m20230220_215840_remote_sync_items_fix;
+mod m20230410_183012_remotes_add_mount_point;
+mod m20230412_201544_sync_items_add_inode;
+mod m20230414_172230_sync_dirs_add_remove_empty_dirs;
+mod m20230416_190412_create_settings;
+mod m20230418_153607_settings_add_sync_status_xattrs;
+mod m20230419_201148_create_pending_transfers;
+mod m20230420_164523_settings_add_timestamp_tolerance;
+mod m20230422_140219_sync_dirs_add_exclusion_options;
+mod m20230423_112607_remotes_add_rclone_flags;
+mod m20230424_091344_remotes_add_google_docs_formats;
+mod m20230425_154702_remotes_add_root_folder;
+mod m20230426_083915_create_audit_log;
+mod m20230427_102318_create_sync_passes;
+mod m20230428_141905_sync_dirs_add_inclusions;
+mod m20230429_110432_settings_add_system_trash;
+mod m20230430_162251_remotes_add_permanent_delete;
+mod m20230501_093512_settings_add_device_id;
+mod m20230502_141027_create_file_blocks;
+mod m20230502_141544_settings_add_block_hashing;
+mod m20230503_110218_settings_add_transfer_limit;
+mod m20230504_085931_sync_dirs_add_high_priority;
+mod m20230505_102344_sync_dirs_add_initial_sync;
+mod m20230506_143120_settings_add_encrypt_database;
+mod m20230507_091245_remotes_add_capabilities;
+mod m20230508_104512_settings_add_exclude_temp_files;
+mod m20230509_083624_settings_add_inhibit_suspend;
+mod m20230510_142207_sync_dirs_add_post_sync_command;
+mod m20230511_091538_sync_dirs_add_pre_sync_command;
+mod m20230512_160415_settings_add_webhook_url;
+mod m20230513_103752_settings_add_ntfy_topic_url;
+mod m20230514_081204_remotes_add_position;
+mod m20230514_081219_sync_dirs_add_position;
+mod m20230515_093402_create_snoozed_errors;
+mod m20230516_110824_sync_dirs_add_max_file_age;
+mod m20230517_102814_remotes_add_kept_copies;
 
 pub struct Migrator;
 
@@ -13,6 +48,41 @@ impl MigratorTrait for Migrator {
             Box::new(m20220101_000001_create_table::Migration),
             Box::new(m20230207_204909_sync_dirs_remove_slash_suffix::Migration),
             Box::new(m20230220_215840_remote_sync_items_fix::Migration),
+            Box::new(m20230410_183012_remotes_add_mount_point::Migration),
+            Box::new(m20230412_201544_sync_items_add_inode::Migration),
+            Box::new(m20230414_172230_sync_dirs_add_remove_empty_dirs::Migration),
+            Box::new(m20230416_190412_create_settings::Migration),
+            Box::new(m20230418_153607_settings_add_sync_status_xattrs::Migration),
+            Box::new(m20230419_201148_create_pending_transfers::Migration),
+            Box::new(m20230420_164523_settings_add_timestamp_tolerance::Migration),
+            Box::new(m20230422_140219_sync_dirs_add_exclusion_options::Migration),
+            Box::new(m20230423_112607_remotes_add_rclone_flags::Migration),
+            Box::new(m20230424_091344_remotes_add_google_docs_formats::Migration),
+            Box::new(m20230425_154702_remotes_add_root_folder::Migration),
+            Box::new(m20230426_083915_create_audit_log::Migration),
+            Box::new(m20230427_102318_create_sync_passes::Migration),
+            Box::new(m20230428_141905_sync_dirs_add_inclusions::Migration),
+            Box::new(m20230429_110432_settings_add_system_trash::Migration),
+            Box::new(m20230430_162251_remotes_add_permanent_delete::Migration),
+            Box::new(m20230501_093512_settings_add_device_id::Migration),
+            Box::new(m20230502_141027_create_file_blocks::Migration),
+            Box::new(m20230502_141544_settings_add_block_hashing::Migration),
+            Box::new(m20230503_110218_settings_add_transfer_limit::Migration),
+            Box::new(m20230504_085931_sync_dirs_add_high_priority::Migration),
+            Box::new(m20230505_102344_sync_dirs_add_initial_sync::Migration),
+            Box::new(m20230506_143120_settings_add_encrypt_database::Migration),
+            Box::new(m20230507_091245_remotes_add_capabilities::Migration),
+            Box::new(m20230508_104512_settings_add_exclude_temp_files::Migration),
+            Box::new(m20230509_083624_settings_add_inhibit_suspend::Migration),
+            Box::new(m20230510_142207_sync_dirs_add_post_sync_command::Migration),
+            Box::new(m20230511_091538_sync_dirs_add_pre_sync_command::Migration),
+            Box::new(m20230512_160415_settings_add_webhook_url::Migration),
+            Box::new(m20230513_103752_settings_add_ntfy_topic_url::Migration),
+            Box::new(m20230514_081204_remotes_add_position::Migration),
+            Box::new(m20230514_081219_sync_dirs_add_position::Migration),
+            Box::new(m20230515_093402_create_snoozed_errors::Migration),
+            Box::new(m20230516_110824_sync_dirs_add_max_file_age::Migration),
+            Box::new(m20230517_102814_remotes_add_kept_copies::Migration),
         ]
     }
 }
@@ -0,0 +1,126 @@
+//! Functions for mounting remotes via `rclone mount`, so they can be browsed
+//! without being synced.
+use crate::rclone;
+use nix::{
+    sys::signal::{self, Signal},
+    unistd::Pid,
+};
+use std::{
+    fs,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    process::{Child, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// A remote mounted via `rclone mount`. The remote gets unmounted when this is
+/// dropped.
+pub struct Mount {
+    /// The directory the remote is mounted at.
+    pub mount_point: PathBuf,
+    /// The `rclone mount` process.
+    process: Child,
+    /// The stderr of the `rclone mount` process.
+    stderr: Arc<Mutex<String>>,
+}
+
+impl Mount {
+    /// Mount `remote_name` at `mount_point`. Returns an [`Err`] containing the
+    /// error message if the remote couldn't be mounted.
+    pub fn start(remote_name: &str, mount_point: &Path) -> Result<Self, String> {
+        if !mount_point.is_dir() {
+            return Err(tr::tr!(
+                "The mount point '{}' isn't a directory.",
+                mount_point.display()
+            ));
+        }
+
+        match fs::read_dir(mount_point) {
+            Ok(mut items) => {
+                if items.next().is_some() {
+                    return Err(tr::tr!(
+                        "The mount point '{}' isn't empty.",
+                        mount_point.display()
+                    ));
+                }
+            }
+            Err(err) => return Err(err.to_string()),
+        }
+
+        let mut process = rclone::command()
+            .arg("mount")
+            .arg("--config")
+            .arg(rclone::config_path())
+            .args(["--vfs-cache-mode", "writes"])
+            .arg(rclone::sync::get_remote_name(remote_name))
+            .arg(mount_point)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| err.to_string())?;
+
+        // Read stderr in the background so the process never blocks on a full pipe.
+        let stderr = Arc::new(Mutex::new(String::new()));
+        let stderr_handle = process.stderr.take().unwrap();
+        thread::spawn({
+            let stderr = stderr.clone();
+            move || {
+                let reader = BufReader::new(stderr_handle);
+                for line in reader.lines().map_while(Result::ok) {
+                    let mut stderr = stderr.lock().unwrap();
+                    stderr.push_str(&line);
+                    stderr.push('\n');
+                }
+            }
+        });
+
+        // Give rclone a moment to fail on things like invalid configs before we report
+        // the mount as successful.
+        libceleste::run_in_background(|| thread::sleep(Duration::from_secs(1)));
+
+        let mut mount = Self {
+            mount_point: mount_point.to_owned(),
+            process,
+            stderr,
+        };
+
+        match mount.error() {
+            Some(err) => Err(err),
+            None => Ok(mount),
+        }
+    }
+
+    /// Check if the mount has stopped, returning the error it stopped with if
+    /// so.
+    pub fn error(&mut self) -> Option<String> {
+        match self.process.try_wait() {
+            Ok(None) => None,
+            Ok(Some(status)) => {
+                let stderr = self.stderr.lock().unwrap().trim().to_owned();
+
+                if stderr.is_empty() {
+                    Some(status.to_string())
+                } else {
+                    Some(stderr)
+                }
+            }
+            Err(err) => Some(err.to_string()),
+        }
+    }
+}
+
+impl Drop for Mount {
+    fn drop(&mut self) {
+        // Rclone unmounts the remote by itself when it receives a SIGTERM.
+        if let Ok(None) = self.process.try_wait() {
+            signal::kill(
+                Pid::from_raw(self.process.id().try_into().unwrap()),
+                Signal::SIGTERM,
+            )
+            .unwrap_or(());
+            self.process.wait().ok();
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! The sync status of individual local paths, for scripts and shell prompts to
+//! check through DBus (such as whether a file has been uploaded before shutting
+//! down). The status comes from what's recorded in the database, along with
+//! the errors the running syncs have reported.
+use crate::{
+    db,
+    entities::{
+        PendingTransfersColumn, PendingTransfersEntity, RemotesEntity, SettingsModel,
+        SyncDirsEntity, SyncItemsColumn, SyncItemsEntity, SyncPassesColumn, SyncPassesEntity,
+    },
+    exclusions::{self, SyncFilter},
+    launch,
+};
+use sea_orm::{entity::prelude::*, DatabaseConnection, DbErr, QueryOrder};
+use std::{
+    collections::HashSet,
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+lazy_static::lazy_static! {
+    // The local paths that syncing has reported errors for, which haven't been
+    // resolved yet.
+    static ref ERROR_PATHS: Arc<Mutex<HashSet<String>>> = Arc::new(Mutex::new(HashSet::new()));
+}
+
+/// Record that syncing ran into an error for the local item at `path`.
+pub fn record_error(path: &str) {
+    ERROR_PATHS.lock().unwrap().insert(path.to_owned());
+}
+
+/// Record that the error for the local item at `path` has been resolved.
+pub fn clear_error(path: &str) {
+    ERROR_PATHS.lock().unwrap().remove(path);
+}
+
+/// How far along syncing a local path is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathState {
+    /// The path isn't in any synced directory.
+    NotSynced,
+    /// The path is in a synced directory, but never gets synced. This includes
+    /// the conflict copies and previous versions Celeste keeps, so that they
+    /// don't look like changes waiting to be synced.
+    Excluded,
+    /// The path is being transferred right now.
+    Syncing,
+    /// Syncing the path ran into an error that hasn't been resolved.
+    Error,
+    /// The path has changed since it was last synced, or hasn't been synced
+    /// yet.
+    Pending,
+    /// The path is the same as when it was last synced.
+    Synced,
+}
+
+impl PathState {
+    /// Get the name of the state, as given over DBus.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::NotSynced => "not-synced",
+            Self::Excluded => "excluded",
+            Self::Syncing => "syncing",
+            Self::Error => "error",
+            Self::Pending => "pending",
+            Self::Synced => "synced",
+        }
+    }
+}
+
+/// The sync status of a local path.
+#[derive(Clone, Debug)]
+pub struct PathStatus {
+    pub state: PathState,
+    /// When the directory the path is in last finished syncing, as a UNIX
+    /// timestamp, if it ever has.
+    pub last_synced: Option<i64>,
+    /// The remote item the path is synced with, as `remote:path`, if it's in a
+    /// synced directory.
+    pub remote: Option<String>,
+}
+
+impl PathStatus {
+    /// Get the status of the local item at `path`, which has to be absolute.
+    pub fn get(db: &DatabaseConnection, path: &Path) -> Result<Self, DbErr> {
+        let path = path.to_string_lossy().trim_end_matches('/').to_owned();
+        let sync_dirs = db::run(|| SyncDirsEntity::find().all(db))?;
+        let Some(sync_dir) = sync_dirs
+            .into_iter()
+            .filter(|sync_dir| Path::new(&path).starts_with(&sync_dir.local_path))
+            .max_by_key(|sync_dir| sync_dir.local_path.len())
+        else {
+            return Ok(Self {
+                state: PathState::NotSynced,
+                last_synced: None,
+                remote: None,
+            });
+        };
+
+        let remote_name = db::run(|| RemotesEntity::find_by_id(sync_dir.remote_id).one(db))?
+            .map(|remote| remote.name)
+            .unwrap_or_default();
+        let relative_path = path
+            .strip_prefix(&sync_dir.local_path)
+            .unwrap()
+            .trim_start_matches('/');
+        let remote_path = [sync_dir.remote_path.as_str(), relative_path]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("/");
+        let last_synced = db::run(|| {
+            SyncPassesEntity::find()
+                .filter(SyncPassesColumn::SyncDirId.eq(sync_dir.id))
+                .order_by_desc(SyncPassesColumn::Started)
+                .one(db)
+        })?
+        .map(|pass| pass.started + pass.duration_ms / 1000);
+
+        let transferring = db::run(|| {
+            PendingTransfersEntity::find()
+                .filter(PendingTransfersColumn::SyncDirId.eq(sync_dir.id))
+                .filter(PendingTransfersColumn::LocalPath.eq(path.clone()))
+                .one(db)
+        })?
+        .is_some();
+        let metadata = fs::metadata(&path).ok();
+        let is_dir = metadata
+            .as_ref()
+            .map_or(false, |metadata| metadata.is_dir());
+        let filter = SyncFilter::new(&sync_dir, &launch::read_exclusions(&sync_dir, db))
+            .with_default_exclusions(SettingsModel::get(db).exclude_temp_files);
+        let too_old = !is_dir
+            && metadata
+                .and_then(|metadata| metadata.modified().ok())
+                .map_or(false, |modified| {
+                    !filter.includes_modified(launch::system_time_to_timestamp(modified))
+                });
+        let excluded = !relative_path.is_empty()
+            && (exclusions::is_builtin_exclusion(&sync_dir, relative_path)
+                || !filter.includes(relative_path, is_dir)
+                || too_old);
+
+        let state = if excluded {
+            PathState::Excluded
+        } else if transferring {
+            PathState::Syncing
+        } else if ERROR_PATHS.lock().unwrap().contains(&path) {
+            PathState::Error
+        } else {
+            let sync_item = db::run(|| {
+                SyncItemsEntity::find()
+                    .filter(SyncItemsColumn::SyncDirId.eq(sync_dir.id))
+                    .filter(SyncItemsColumn::LocalPath.eq(path.clone()))
+                    .one(db)
+            })?;
+            // The root of the directory doesn't get an item of its own.
+            let unchanged = (relative_path.is_empty() && last_synced.is_some())
+                || match (sync_item, fs::metadata(&path)) {
+                    (Some(_), Ok(metadata)) if metadata.is_dir() => true,
+                    (Some(sync_item), Ok(metadata)) => {
+                        metadata.modified().map_or(false, |modified| {
+                            launch::system_time_to_timestamp(modified)
+                                == sync_item.last_local_timestamp
+                        })
+                    }
+                    _ => false,
+                };
+
+            if unchanged {
+                PathState::Synced
+            } else {
+                PathState::Pending
+            }
+        };
+
+        Ok(Self {
+            state,
+            last_synced,
+            remote: Some(format!("{remote_name}:{remote_path}")),
+        })
+    }
+}
@@ -0,0 +1,250 @@
+//! Conversions between local paths and the paths of items on a remote.
+//!
+//! Remote paths are always relative to the root of the remote, and never have a
+//! leading or trailing `/`. Paths are only ever split and joined on `/`, so any
+//! other characters in a name (such as `#`, `%`, newlines, emoji, or trailing
+//! spaces) are passed through to Rclone untouched. Rclone then takes care of
+//! encoding any characters that a remote doesn't support, using each backend's
+//! default encoding rules.
+use crate::entities::SyncDirsModel;
+use std::path::{Path, PathBuf};
+
+/// Join a name onto a remote directory. An empty `dir` refers to the root of
+/// the remote.
+pub fn join_remote(dir: &str, name: &str) -> String {
+    let dir = dir.trim_end_matches('/');
+    let name = name.trim_matches('/');
+
+    if dir.is_empty() {
+        name.to_owned()
+    } else if name.is_empty() {
+        dir.to_owned()
+    } else {
+        format!("{dir}/{name}")
+    }
+}
+
+/// Turn a local directory typed in by the user into the form sync directories
+/// are stored in, without a separator at the end. On Unix-like systems the
+/// path always starts at the root, even if the user left off the leading `/`.
+pub fn local_path_from_input(text: &str) -> String {
+    let path = text.trim_end_matches(std::path::is_separator);
+
+    if cfg!(unix) {
+        format!("/{}", path.trim_start_matches('/'))
+    } else {
+        path.to_owned()
+    }
+}
+
+/// Get the path of a local item relative to the local directory of
+/// `sync_dir`, or [`None`] if it isn't inside of it.
+pub fn relative_local_path<'a>(sync_dir: &SyncDirsModel, local_path: &'a Path) -> Option<&'a Path> {
+    local_path.strip_prefix(&sync_dir.local_path).ok()
+}
+
+/// Get the path of a remote item relative to the remote directory of
+/// `sync_dir`, or [`None`] if it isn't inside of it.
+pub fn relative_remote_path<'a>(sync_dir: &SyncDirsModel, remote_path: &'a str) -> Option<&'a str> {
+    // A leading `/` would make the path absolute once it's joined onto a local
+    // one, taking it outside of the local directory.
+    let remote_path = remote_path.trim_start_matches('/');
+
+    if sync_dir.remote_path.is_empty() {
+        Some(remote_path)
+    } else {
+        remote_path
+            .strip_prefix(&sync_dir.remote_path)?
+            .strip_prefix('/')
+    }
+}
+
+/// Get the remote path that a local item in `sync_dir` gets synced to. Returns
+/// [`None`] if the item isn't inside of `sync_dir`, or if its path isn't valid
+/// UTF-8 (which Rclone requires for remote paths).
+pub fn local_to_remote(sync_dir: &SyncDirsModel, local_path: &Path) -> Option<String> {
+    let relative_path = relative_local_path(sync_dir, local_path)?.to_str()?;
+    Some(join_remote(&sync_dir.remote_path, relative_path))
+}
+
+/// Get the local path that a remote item in `sync_dir` gets synced to. Returns
+/// [`None`] if the item isn't inside of `sync_dir`.
+pub fn remote_to_local(sync_dir: &SyncDirsModel, remote_path: &str) -> Option<PathBuf> {
+    let relative_path = relative_remote_path(sync_dir, remote_path)?;
+    Some(Path::new(&sync_dir.local_path).join(relative_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_sync_dir(local_path: &str, remote_path: &str) -> SyncDirsModel {
+        SyncDirsModel {
+            local_path: local_path.to_owned(),
+            remote_path: remote_path.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn join_remote_paths() {
+        assert_eq!(join_remote("", "file"), "file");
+        assert_eq!(join_remote("/", "file"), "file");
+        assert_eq!(join_remote("dir", "file"), "dir/file");
+        assert_eq!(join_remote("dir/", "/file/"), "dir/file");
+        // Local paths (such as the roots of local remotes) keep their leading `/`.
+        assert_eq!(join_remote("/tmp/dir", "file"), "/tmp/dir/file");
+        assert_eq!(join_remote("dir", ""), "dir");
+        assert_eq!(join_remote("", ""), "");
+        assert_eq!(join_remote("dir", "sub dir/file #1"), "dir/sub dir/file #1");
+    }
+
+    #[test]
+    fn local_paths_from_input() {
+        assert_eq!(local_path_from_input("/home/user/"), "/home/user");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn local_paths_from_input_start_at_root() {
+        assert_eq!(local_path_from_input("home/user"), "/home/user");
+        assert_eq!(local_path_from_input("/"), "/");
+    }
+
+    #[test]
+    fn relative_local_paths() {
+        let sync_dir = new_sync_dir("/home/user/Sync", "Sync");
+        assert_eq!(
+            relative_local_path(&sync_dir, Path::new("/home/user/Sync/dir/file")),
+            Some(Path::new("dir/file"))
+        );
+        assert_eq!(
+            relative_local_path(&sync_dir, Path::new("/home/user/Sync")),
+            Some(Path::new(""))
+        );
+        assert_eq!(
+            relative_local_path(&sync_dir, Path::new("/home/user/Synced/file")),
+            None
+        );
+        assert_eq!(
+            relative_local_path(&sync_dir, Path::new("/home/user/file")),
+            None
+        );
+    }
+
+    #[test]
+    fn relative_remote_paths() {
+        let sync_dir = new_sync_dir("/home/user/Sync", "Sync");
+        assert_eq!(
+            relative_remote_path(&sync_dir, "Sync/dir/file"),
+            Some("dir/file")
+        );
+        assert_eq!(relative_remote_path(&sync_dir, "/Sync/file"), Some("file"));
+        assert_eq!(relative_remote_path(&sync_dir, "Sync"), None);
+        assert_eq!(relative_remote_path(&sync_dir, "Synced/file"), None);
+        assert_eq!(relative_remote_path(&sync_dir, "Other/file"), None);
+
+        // The root of the remote contains everything on it.
+        let root_dir = new_sync_dir("/home/user/Sync", "");
+        assert_eq!(
+            relative_remote_path(&root_dir, "dir/file"),
+            Some("dir/file")
+        );
+        assert_eq!(
+            relative_remote_path(&root_dir, "/dir/file"),
+            Some("dir/file")
+        );
+    }
+
+    #[test]
+    fn local_to_remote_paths() {
+        let sync_dir = new_sync_dir("/home/user/Sync", "Sync");
+        assert_eq!(
+            local_to_remote(&sync_dir, Path::new("/home/user/Sync/dir/file")),
+            Some("Sync/dir/file".to_owned())
+        );
+        assert_eq!(
+            local_to_remote(&sync_dir, Path::new("/home/user/Sync")),
+            Some("Sync".to_owned())
+        );
+        assert_eq!(
+            local_to_remote(&sync_dir, Path::new("/home/user/Other/file")),
+            None
+        );
+
+        let root_dir = new_sync_dir("/home/user/Sync", "");
+        assert_eq!(
+            local_to_remote(&root_dir, Path::new("/home/user/Sync/dir/file")),
+            Some("dir/file".to_owned())
+        );
+        assert_eq!(
+            local_to_remote(&root_dir, Path::new("/home/user/Sync")),
+            Some(String::new())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_local_paths() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        // Rclone needs remote paths to be valid UTF-8.
+        let sync_dir = new_sync_dir("/home/user/Sync", "Sync");
+        let non_utf8 = Path::new("/home/user/Sync").join(OsStr::from_bytes(b"caf\xe9"));
+        assert_eq!(local_to_remote(&sync_dir, &non_utf8), None);
+    }
+
+    #[test]
+    fn special_characters_round_trip() {
+        let names = [
+            "file #1.txt",
+            "100% done.txt",
+            "%20 isn't a space.txt",
+            "line\nbreak.txt",
+            "party 🎉.png",
+            "trailing space ",
+            "trailing space /file.txt",
+            "  leading spaces",
+            "dots...",
+        ];
+
+        for (local_dir, remote_dir) in [("/home/user/Sync", "Sync"), ("/home/user/Sync", "")] {
+            let sync_dir = new_sync_dir(local_dir, remote_dir);
+            for name in names {
+                let local_path = Path::new(local_dir).join(name);
+                let remote_path = local_to_remote(&sync_dir, &local_path).unwrap();
+                assert_eq!(remote_path, join_remote(remote_dir, name), "{name:?}");
+                assert_eq!(
+                    remote_to_local(&sync_dir, &remote_path),
+                    Some(local_path),
+                    "{name:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn remote_to_local_paths() {
+        let sync_dir = new_sync_dir("/home/user/Sync", "Sync");
+        assert_eq!(
+            remote_to_local(&sync_dir, "Sync/dir/file"),
+            Some(PathBuf::from("/home/user/Sync/dir/file"))
+        );
+        assert_eq!(
+            remote_to_local(&sync_dir, "Sync/dir/"),
+            Some(PathBuf::from("/home/user/Sync/dir"))
+        );
+        assert_eq!(remote_to_local(&sync_dir, "Other/file"), None);
+
+        // A leading `/` mustn't escape the local directory.
+        let root_dir = new_sync_dir("/home/user/Sync", "");
+        assert_eq!(
+            remote_to_local(&root_dir, "/etc/passwd"),
+            Some(PathBuf::from("/home/user/Sync/etc/passwd"))
+        );
+        assert_eq!(
+            remote_to_local(&root_dir, "dir/file"),
+            Some(PathBuf::from("/home/user/Sync/dir/file"))
+        );
+    }
+}
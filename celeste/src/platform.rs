@@ -0,0 +1,229 @@
+//! The parts of interacting with the local system that differ between
+//! platforms. Anything that only works on Unix-like systems should go through
+//! here, with a fallback for the platforms it doesn't work on, so that the
+//! rest of the sync engine doesn't need to know about them.
+use std::{
+    fs::{File, Metadata},
+    io,
+    path::Path,
+    process::{Child, Command},
+};
+
+#[cfg(target_os = "linux")]
+use std::sync::Mutex;
+
+#[cfg(unix)]
+use nix::{
+    sys::{
+        signal::{self, Signal},
+        statvfs,
+    },
+    unistd::Pid,
+};
+#[cfg(unix)]
+use std::os::unix::{
+    fs::{MetadataExt, PermissionsExt},
+    process::CommandExt,
+};
+
+/// Get the ID of a local file that stays the same when it gets renamed, if the
+/// platform has such. On Unix-like systems, this is its inode.
+#[cfg(unix)]
+pub fn file_id(metadata: &Metadata) -> Option<u64> {
+    Some(metadata.ino())
+}
+
+/// Get the ID of a local file that stays the same when it gets renamed, if the
+/// platform has such. On Unix-like systems, this is its inode.
+#[cfg(not(unix))]
+pub fn file_id(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+/// Get an ID for a local file that's unique across every file system, such as
+/// for telling whether two paths are the same file.
+#[cfg(unix)]
+pub fn unique_file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    Some((metadata.dev(), metadata.ino()))
+}
+
+/// Get an ID for a local file that's unique across every file system, such as
+/// for telling whether two paths are the same file.
+#[cfg(not(unix))]
+pub fn unique_file_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Get the number of bytes available to us on the file system that `dir` is
+/// on. Returns [`None`] if the platform can't tell us.
+#[cfg(unix)]
+pub fn available_space(dir: &Path) -> io::Result<Option<u64>> {
+    let stat = statvfs::statvfs(dir)?;
+    let available = stat.blocks_available() as u64 * stat.fragment_size() as u64;
+    Ok(Some(available))
+}
+
+/// Get the number of bytes available to us on the file system that `dir` is
+/// on. Returns [`None`] if the platform can't tell us.
+#[cfg(not(unix))]
+pub fn available_space(_dir: &Path) -> io::Result<Option<u64>> {
+    Ok(None)
+}
+
+/// Let `file` be run as a program.
+#[cfg(unix)]
+pub fn make_executable(file: &File) -> io::Result<()> {
+    let mut perms = file.metadata()?.permissions();
+    perms.set_mode(0o755);
+    file.set_permissions(perms)
+}
+
+/// Let `file` be run as a program.
+#[cfg(not(unix))]
+pub fn make_executable(_file: &File) -> io::Result<()> {
+    Ok(())
+}
+
+/// Run `command` in its own process group, so that signals sent to ours (such
+/// as from pressing Ctrl+C in a terminal) don't reach it.
+#[cfg(unix)]
+pub fn separate_process_group(command: &mut Command) -> &mut Command {
+    command.process_group(0)
+}
+
+/// Run `command` in its own process group, so that signals sent to ours (such
+/// as from pressing Ctrl+C in a terminal) don't reach it.
+#[cfg(not(unix))]
+pub fn separate_process_group(command: &mut Command) -> &mut Command {
+    command
+}
+
+/// Kill `child`, along with everything it started if it was run in its own
+/// process group (see [`separate_process_group`]).
+#[cfg(unix)]
+pub fn kill_process_group(child: &mut Child) -> io::Result<()> {
+    let pid = Pid::from_raw(child.id().try_into().unwrap());
+    signal::killpg(pid, Signal::SIGKILL)?;
+    Ok(())
+}
+
+/// Kill `child`, along with everything it started if it was run in its own
+/// process group (see [`separate_process_group`]).
+#[cfg(not(unix))]
+pub fn kill_process_group(child: &mut Child) -> io::Result<()> {
+    child.kill()
+}
+
+#[cfg(target_os = "linux")]
+lazy_static::lazy_static! {
+    // The logind inhibitor lock keeping the computer from suspending, while one is
+    // held. The lock is released when it's closed.
+    static ref SUSPEND_INHIBITOR: Mutex<Option<zbus::zvariant::OwnedFd>> = Mutex::new(None);
+}
+
+/// Keep the computer from suspending, including from its lid being closed,
+/// until [`allow_suspend`] is called. This goes through logind, so it only
+/// does anything on Linux.
+#[cfg(target_os = "linux")]
+pub fn inhibit_suspend() -> zbus::Result<()> {
+    let mut inhibitor = SUSPEND_INHIBITOR.lock().unwrap();
+    if inhibitor.is_some() {
+        return Ok(());
+    }
+
+    let reason = tr::tr!("Files are being transferred");
+    let reply = zbus::blocking::Connection::system()?.call_method(
+        Some("org.freedesktop.login1"),
+        "/org/freedesktop/login1",
+        Some("org.freedesktop.login1.Manager"),
+        "Inhibit",
+        &(
+            "sleep:handle-lid-switch",
+            "Celeste",
+            reason.as_str(),
+            "block",
+        ),
+    )?;
+    *inhibitor = Some(reply.body()?);
+    Ok(())
+}
+
+/// Keep the computer from suspending until [`allow_suspend`] is called. This
+/// goes through logind, so it only does anything on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn inhibit_suspend() -> zbus::Result<()> {
+    Ok(())
+}
+
+/// Let the computer suspend again after [`inhibit_suspend`].
+#[cfg(target_os = "linux")]
+pub fn allow_suspend() {
+    SUSPEND_INHIBITOR.lock().unwrap().take();
+}
+
+/// Let the computer suspend again after [`inhibit_suspend`].
+#[cfg(not(target_os = "linux"))]
+pub fn allow_suspend() {}
+
+/// Starting Celeste when the user logs in on macOS, through a launchd agent.
+/// Other platforms start it through their own service managers instead (such
+/// as the systemd user service on Linux).
+#[cfg(target_os = "macos")]
+pub mod launch_agent {
+    use std::{env, fs, io, path::PathBuf};
+
+    /// Get the path of the agent's property list.
+    fn plist_path() -> PathBuf {
+        adw::glib::home_dir()
+            .join("Library/LaunchAgents")
+            .join(format!("{}.plist", libceleste::APP_ID))
+    }
+
+    /// Escape `text` for use in the property list.
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+
+    /// Whether Celeste is set to start when the user logs in.
+    pub fn is_enabled() -> bool {
+        plist_path().exists()
+    }
+
+    /// Set whether Celeste starts in the background when the user logs in. This
+    /// takes effect the next time they log in.
+    pub fn set_enabled(enabled: bool) -> io::Result<()> {
+        let path = plist_path();
+        if !enabled {
+            return match fs::remove_file(path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            };
+        }
+
+        let exe = env::current_exe()?;
+        let plist = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+        <string>--background</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+</dict>
+</plist>
+"#,
+            libceleste::APP_ID,
+            escape(&exe.to_string_lossy())
+        );
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, plist)
+    }
+}
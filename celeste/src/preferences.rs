@@ -0,0 +1,324 @@
+use crate::{entities::SettingsModel, exclusions, launch::CloseAction};
+use adw::{
+    glib,
+    gtk::{Adjustment, Align, Inhibit, SpinButton, StringList, Switch},
+    prelude::*,
+    ActionRow, Application, ComboRow, EntryRow, PreferencesGroup, PreferencesPage,
+    PreferencesWindow,
+};
+use sea_orm::{ActiveValue, DatabaseConnection};
+use url::Url;
+
+/// Only let the URL in `row` be applied if it's valid, or empty to clear it.
+fn check_url(row: &EntryRow) {
+    let url = row.text();
+    if url.is_empty() || Url::parse(url.trim()).is_ok() {
+        row.remove_css_class("error");
+        row.set_show_apply_button(true);
+    } else {
+        row.set_show_apply_button(false);
+        row.add_css_class("error");
+    }
+}
+
+pub fn preferences_window(app: &Application, db: &DatabaseConnection) {
+    let settings = SettingsModel::get(db);
+
+    // What to do when the main window is closed. [`None`] means to ask the user.
+    let close_actions = [
+        None,
+        Some(CloseAction::Hide),
+        Some(CloseAction::Minimize),
+        Some(CloseAction::Quit),
+    ];
+    let close_action_labels = [
+        tr::tr!("Ask every time"),
+        tr::tr!("Keep running in the background"),
+        tr::tr!("Minimize the window"),
+        tr::tr!("Quit Celeste"),
+    ];
+    let close_action_model = StringList::new(
+        &close_action_labels
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>(),
+    );
+    let close_action_row = ComboRow::builder()
+        .title(&tr::tr!("When Closing the Window"))
+        .model(&close_action_model)
+        .build();
+    let current_close_action = settings
+        .close_action
+        .as_deref()
+        .and_then(CloseAction::from_setting);
+    let current_index = close_actions
+        .iter()
+        .position(|close_action| *close_action == current_close_action)
+        .unwrap_or(0);
+    close_action_row.set_selected(current_index as u32);
+    close_action_row.connect_selected_notify(glib::clone!(@strong db => move |row| {
+        let close_action = close_actions[row.selected() as usize];
+        SettingsModel::update(&db, |settings| {
+            settings.close_action = ActiveValue::Set(
+                close_action.map(|close_action| close_action.setting_name().to_owned()),
+            );
+        });
+    }));
+
+    let sync_status_xattrs_switch = Switch::builder()
+        .valign(Align::Center)
+        .active(settings.sync_status_xattrs)
+        .build();
+    sync_status_xattrs_switch.connect_state_set(glib::clone!(@strong db => move |_, state| {
+        SettingsModel::update(&db, |settings| {
+            settings.sync_status_xattrs = ActiveValue::Set(state);
+        });
+        Inhibit(false)
+    }));
+    let sync_status_xattrs_row = ActionRow::builder()
+        .title(&tr::tr!("Record sync status in file attributes"))
+        .subtitle(&tr::tr!(
+            "Lets other apps see whether a file is synced, on file systems that support it."
+        ))
+        .activatable_widget(&sync_status_xattrs_switch)
+        .build();
+    sync_status_xattrs_row.add_suffix(&sync_status_xattrs_switch);
+
+    let timestamp_tolerance_button = SpinButton::builder()
+        .valign(Align::Center)
+        .adjustment(&Adjustment::new(
+            settings.timestamp_tolerance.into(),
+            0.0,
+            3600.0,
+            1.0,
+            10.0,
+            0.0,
+        ))
+        .build();
+    timestamp_tolerance_button.connect_value_changed(glib::clone!(@strong db => move |button| {
+        SettingsModel::update(&db, |settings| {
+            settings.timestamp_tolerance = ActiveValue::Set(button.value_as_int());
+        });
+    }));
+    let timestamp_tolerance_row = ActionRow::builder()
+        .title(&tr::tr!("Timestamp tolerance"))
+        .subtitle(&tr::tr!(
+            "How many seconds apart local and remote modification times can be while still counting as the same."
+        ))
+        .activatable_widget(&timestamp_tolerance_button)
+        .build();
+    timestamp_tolerance_row.add_suffix(&timestamp_tolerance_button);
+
+    let transfer_limit_button = SpinButton::builder()
+        .valign(Align::Center)
+        .adjustment(&Adjustment::new(
+            settings.transfer_limit.into(),
+            0.0,
+            100000.0,
+            1.0,
+            100.0,
+            0.0,
+        ))
+        .build();
+    transfer_limit_button.connect_value_changed(glib::clone!(@strong db => move |button| {
+        SettingsModel::update(&db, |settings| {
+            settings.transfer_limit = ActiveValue::Set(button.value_as_int());
+        });
+    }));
+    let transfer_limit_row = ActionRow::builder()
+        .title(&tr::tr!("Transfers per sync"))
+        .subtitle(&tr::tr!(
+            "The most files to transfer for a directory before moving on to the others, with the rest being transferred on the next sync. Set to 0 for no limit."
+        ))
+        .activatable_widget(&transfer_limit_button)
+        .build();
+    transfer_limit_row.add_suffix(&transfer_limit_button);
+
+    let system_trash_switch = Switch::builder()
+        .valign(Align::Center)
+        .active(settings.system_trash)
+        .build();
+    system_trash_switch.connect_state_set(glib::clone!(@strong db => move |_, state| {
+        SettingsModel::update(&db, |settings| {
+            settings.system_trash = ActiveValue::Set(state);
+        });
+        Inhibit(false)
+    }));
+    let system_trash_row = ActionRow::builder()
+        .title(&tr::tr!("Move deleted files to the trash"))
+        .subtitle(&tr::tr!(
+            "Files deleted on the remote are moved to the trash when they're deleted locally, where they can be restored from instead of by undoing the sync."
+        ))
+        .activatable_widget(&system_trash_switch)
+        .build();
+    system_trash_row.add_suffix(&system_trash_switch);
+
+    let block_hashing_switch = Switch::builder()
+        .valign(Align::Center)
+        .active(settings.block_hashing)
+        .build();
+    block_hashing_switch.connect_state_set(glib::clone!(@strong db => move |_, state| {
+        SettingsModel::update(&db, |settings| {
+            settings.block_hashing = ActiveValue::Set(state);
+        });
+        Inhibit(false)
+    }));
+    let block_hashing_row = ActionRow::builder()
+        .title(&tr::tr!("Check large files for changes by their contents"))
+        .subtitle(&tr::tr!(
+            "Files over 1 GB are hashed after being synced, so they aren't uploaded again when only their modification time changes. This takes extra time after each transfer."
+        ))
+        .activatable_widget(&block_hashing_switch)
+        .build();
+    block_hashing_row.add_suffix(&block_hashing_switch);
+
+    let exclude_temp_files_switch = Switch::builder()
+        .valign(Align::Center)
+        .active(settings.exclude_temp_files)
+        .build();
+    exclude_temp_files_switch.connect_state_set(glib::clone!(@strong db => move |_, state| {
+        SettingsModel::update(&db, |settings| {
+            settings.exclude_temp_files = ActiveValue::Set(state);
+        });
+        Inhibit(false)
+    }));
+    let exclude_temp_files_row = ActionRow::builder()
+        .title(&tr::tr!("Skip temporary files"))
+        .subtitle(&tr::tr!(
+            "Files that editors and browsers only keep while working (such as {}) aren't synced.",
+            exclusions::DEFAULT_EXCLUSIONS.join(", ")
+        ))
+        .activatable_widget(&exclude_temp_files_switch)
+        .build();
+    exclude_temp_files_row.add_suffix(&exclude_temp_files_switch);
+
+    let inhibit_suspend_switch = Switch::builder()
+        .valign(Align::Center)
+        .active(settings.inhibit_suspend)
+        .build();
+    inhibit_suspend_switch.connect_state_set(glib::clone!(@strong db => move |_, state| {
+        SettingsModel::update(&db, |settings| {
+            settings.inhibit_suspend = ActiveValue::Set(state);
+        });
+        Inhibit(false)
+    }));
+    let inhibit_suspend_row = ActionRow::builder()
+        .title(&tr::tr!("Prevent suspending during transfers"))
+        .subtitle(&tr::tr!(
+            "Keeps the computer awake, even with its lid closed, until the files being synced have finished transferring."
+        ))
+        .activatable_widget(&inhibit_suspend_switch)
+        .build();
+    inhibit_suspend_row.add_suffix(&inhibit_suspend_switch);
+
+    let encrypt_database_switch = Switch::builder()
+        .valign(Align::Center)
+        .active(settings.encrypt_database)
+        .build();
+    encrypt_database_switch.connect_state_set(glib::clone!(@strong db => move |_, state| {
+        SettingsModel::update(&db, |settings| {
+            settings.encrypt_database = ActiveValue::Set(state);
+        });
+        Inhibit(false)
+    }));
+    let encrypt_database_row = ActionRow::builder()
+        .title(&tr::tr!("Encrypt Celeste's data"))
+        .subtitle(&tr::tr!(
+            "Keeps the names of synced files from being read by other users of this computer, with a key saved in your keyring. This takes effect the next time Celeste starts, and removes existing backups of the data."
+        ))
+        .activatable_widget(&encrypt_database_switch)
+        .build();
+    encrypt_database_row.add_suffix(&encrypt_database_switch);
+
+    // macOS doesn't have a user service to start Celeste with, so it gets
+    // started through a launch agent that the user can turn on here instead.
+    #[cfg(target_os = "macos")]
+    let launch_agent_row = {
+        let launch_agent_switch = Switch::builder()
+            .valign(Align::Center)
+            .active(crate::platform::launch_agent::is_enabled())
+            .build();
+        launch_agent_switch.connect_state_set(|_, state| {
+            if let Err(err) = crate::platform::launch_agent::set_enabled(state) {
+                crate::gtk_util::show_error(
+                    &tr::tr!("Unable to update the login item"),
+                    Some(&err.to_string()),
+                );
+                return Inhibit(true);
+            }
+            Inhibit(false)
+        });
+        let launch_agent_row = ActionRow::builder()
+            .title(&tr::tr!("Start at login"))
+            .subtitle(&tr::tr!("Start Celeste in the menu bar when you log in."))
+            .activatable_widget(&launch_agent_switch)
+            .build();
+        launch_agent_row.add_suffix(&launch_agent_switch);
+        launch_agent_row
+    };
+
+    let general_group = PreferencesGroup::builder()
+        .title(&tr::tr!("General"))
+        .build();
+    general_group.add(&close_action_row);
+    #[cfg(target_os = "macos")]
+    general_group.add(&launch_agent_row);
+    general_group.add(&sync_status_xattrs_row);
+    general_group.add(&timestamp_tolerance_row);
+    general_group.add(&transfer_limit_row);
+    general_group.add(&system_trash_row);
+    general_group.add(&block_hashing_row);
+    general_group.add(&exclude_temp_files_row);
+    general_group.add(&inhibit_suspend_row);
+    general_group.add(&encrypt_database_row);
+
+    let webhook_url_row = EntryRow::builder()
+        .title(&tr::tr!("URL to send sync events to"))
+        .build();
+    webhook_url_row.set_text(&settings.webhook_url);
+    webhook_url_row.connect_changed(check_url);
+    webhook_url_row.connect_apply(glib::clone!(@strong db => move |row| {
+        let url = row.text().trim().to_owned();
+        SettingsModel::update(&db, |settings| {
+            settings.webhook_url = ActiveValue::Set(url);
+        });
+    }));
+    let webhooks_group = PreferencesGroup::builder()
+        .title(&tr::tr!("Webhooks"))
+        .description(&tr::tr!(
+            "Finished syncs, errors and conflicts are sent to this URL as JSON, such as to pass them on to a notification service."
+        ))
+        .build();
+    webhooks_group.add(&webhook_url_row);
+
+    let ntfy_topic_url_row = EntryRow::builder()
+        .title(&tr::tr!("ntfy topic URL (such as https://ntfy.sh/topic)"))
+        .build();
+    ntfy_topic_url_row.set_text(&settings.ntfy_topic_url);
+    ntfy_topic_url_row.connect_changed(check_url);
+    ntfy_topic_url_row.connect_apply(glib::clone!(@strong db => move |row| {
+        let url = row.text().trim().to_owned();
+        SettingsModel::update(&db, |settings| {
+            settings.ntfy_topic_url = ActiveValue::Set(url);
+        });
+    }));
+    let push_notifications_group = PreferencesGroup::builder()
+        .title(&tr::tr!("Push Notifications"))
+        .description(&tr::tr!(
+            "Sync errors and conflicts that need you to do something are sent to this ntfy topic, so that they show up on your phone."
+        ))
+        .build();
+    push_notifications_group.add(&ntfy_topic_url_row);
+
+    let page = PreferencesPage::new();
+    page.add(&general_group);
+    page.add(&webhooks_group);
+    page.add(&push_notifications_group);
+
+    let window = PreferencesWindow::builder()
+        .application(app)
+        .title(&libceleste::get_title!("Preferences"))
+        .build();
+    window.add(&page);
+    window.show();
+}
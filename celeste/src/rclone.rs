@@ -1,11 +1,139 @@
 //! Structs and functions for use with Rclone RPC calls.
 use adw::glib;
+use lazy_static::lazy_static;
 use serde::Deserialize;
 use serde_json::json;
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{self, BufReader, Lines},
+    path::PathBuf,
+    process::{Child, ChildStdout, Command},
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+    time::Duration,
+};
 use time::OffsetDateTime;
+use url::Url;
 
-/// Get a remote from the config file.
+/// The minimum version of the Rclone binary that Celeste supports, as a
+/// `(major, minor, patch)` tuple.
+pub static MIN_RCLONE_VERSION: (u32, u32, u32) = (1, 58, 0);
+
+lazy_static! {
+    /// The extra backend options to use for each remote, from the flags the
+    /// user set for it. See [`set_remote_options`].
+    static ref REMOTE_OPTIONS: Mutex<HashMap<String, Vec<(String, String)>>> =
+        Mutex::new(HashMap::new());
+    /// The folder on each remote that everything done with it is restricted
+    /// to. See [`set_remote_root`].
+    static ref REMOTE_ROOTS: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
+    /// The password the config file was unlocked with, if it's encrypted. See
+    /// [`sync::unlock_config`].
+    static ref CONFIG_PASSWORD: Mutex<Option<String>> = Mutex::new(None);
+}
+
+/// The ways the Rclone binary can be unusable.
+#[derive(Clone, Debug)]
+pub enum RcloneBinaryError {
+    /// The binary couldn't be found in the user's `PATH`.
+    NotFound,
+    /// The binary was found, but its version couldn't be determined. Contains
+    /// the error that occurred.
+    UnknownVersion(String),
+    /// The binary is older than [`MIN_RCLONE_VERSION`]. Contains the version
+    /// that was found.
+    TooOld(String),
+}
+
+/// Check that the Rclone binary is installed, and that it's at least
+/// [`MIN_RCLONE_VERSION`]. The binary is needed for things like authorizing
+/// remotes, which can't be done through Rclone's RPC API.
+pub fn check_binary() -> Result<(), RcloneBinaryError> {
+    let output = libceleste::run_in_background(|| command().arg("version").output());
+
+    let output = match output {
+        Ok(output) => output,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Err(RcloneBinaryError::NotFound)
+        }
+        Err(err) => return Err(RcloneBinaryError::UnknownVersion(err.to_string())),
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    // The first line is in the format of `rclone v1.62.2`, possibly with a suffix
+    // such as `-beta.1234` or `-DEV`.
+    let version_string = match stdout
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("rclone v"))
+    {
+        Some(version) => version.to_owned(),
+        None => {
+            return Err(RcloneBinaryError::UnknownVersion(
+                String::from_utf8_lossy(&output.stderr).to_string(),
+            ))
+        }
+    };
+    let mut version = version_string
+        .split(|char: char| !char.is_ascii_digit())
+        .map(|component| component.parse::<u32>().ok());
+    let version = match (version.next(), version.next(), version.next()) {
+        (Some(Some(major)), Some(Some(minor)), Some(Some(patch))) => (major, minor, patch),
+        _ => return Err(RcloneBinaryError::UnknownVersion(version_string)),
+    };
+
+    if version < MIN_RCLONE_VERSION {
+        Err(RcloneBinaryError::TooOld(version_string))
+    } else {
+        Ok(())
+    }
+}
+
+/// The line that the contents of an encrypted config file start with.
+static ENCRYPTED_CONFIG_MARKER: &str = "RCLONE_ENCRYPT_V0:";
+
+/// The environment variable Rclone reads the config file's password from.
+pub static CONFIG_PASSWORD_VAR: &str = "RCLONE_CONFIG_PASS";
+
+/// Get a command that runs the Rclone binary. If the config file has been
+/// unlocked, the password is passed to just this command through its
+/// environment, instead of being set in Celeste's own environment where every
+/// other process it starts would get it too.
+pub fn command() -> Command {
+    let mut command = Command::new("rclone");
+    if let Some(password) = &*CONFIG_PASSWORD.lock().unwrap() {
+        command.env(CONFIG_PASSWORD_VAR, password);
+    }
+    command
+}
+
+/// Get the path of the config file Celeste keeps its remotes in.
+pub fn config_path() -> PathBuf {
+    libceleste::get_config_dir().join("rclone.conf")
+}
+
+/// Whether the config file is encrypted with a password, which needs to be
+/// given to [`sync::unlock_config`] before any remotes can be used.
+pub fn config_is_encrypted() -> bool {
+    fs::read_to_string(config_path()).map_or(false, |config| {
+        config
+            .lines()
+            .any(|line| line.trim() == ENCRYPTED_CONFIG_MARKER)
+    })
+}
+
+/// Whether the remotes in the config file can be listed, which needs the file
+/// to be readable and, if it's encrypted, to have been unlocked. When they
+/// can't be, [`get_remote_names`] gives no remotes at all.
+pub fn config_is_readable() -> bool {
+    fs::read_to_string(config_path()).is_ok()
+        && (!config_is_encrypted() || CONFIG_PASSWORD.lock().unwrap().is_some())
+}
+
+/// Get a remote from the config file. Remotes of types Celeste doesn't support
+/// (such as ones added to the file from outside of Celeste), and ones that
+/// aren't in the file, give [`None`].
 pub fn get_remote<T: ToString>(remote: T) -> Option<Remote> {
     let remote = remote.to_string();
 
@@ -16,35 +144,46 @@ pub fn get_remote<T: ToString>(remote: T) -> Option<Remote> {
     );
     let config: HashMap<String, String> = serde_json::from_str(&config_str).unwrap();
 
-    match config["type"].as_str() {
-        "dropbox" => Some(Remote::Dropbox(DropboxRemote {
-            remote_name: remote,
-            client_id: config["client_id"].clone(),
-            client_secret: config["client_secret"].clone(),
-        })),
-        "drive" => Some(Remote::GDrive(GDriveRemote {
+    match config.get("type").map(String::as_str) {
+        Some("dropbox") => Some(Remote::Dropbox(DropboxRemote {
             remote_name: remote,
-            client_id: config["client_id"].clone(),
-            client_secret: config["client_secret"].clone(),
+            client_id: config.get("client_id").cloned().unwrap_or_default(),
+            client_secret: config.get("client_secret").cloned().unwrap_or_default(),
         })),
-        "pcloud" => Some(Remote::PCloud(PCloudRemote {
+        Some("drive") => {
+            let target = match config.get("team_drive").filter(|id| !id.is_empty()) {
+                Some(id) => GDriveTarget::SharedDrive(id.clone()),
+                None if config.get("shared_with_me").map(String::as_str) == Some("true") => {
+                    GDriveTarget::SharedWithMe
+                }
+                None => GDriveTarget::MyDrive,
+            };
+
+            Some(Remote::GDrive(GDriveRemote {
+                remote_name: remote,
+                client_id: config.get("client_id").cloned().unwrap_or_default(),
+                client_secret: config.get("client_secret").cloned().unwrap_or_default(),
+                target,
+            }))
+        }
+        Some("pcloud") => Some(Remote::PCloud(PCloudRemote {
             remote_name: remote,
-            client_id: config["client_id"].clone(),
-            client_secret: config["client_secret"].clone(),
+            client_id: config.get("client_id").cloned().unwrap_or_default(),
+            client_secret: config.get("client_secret").cloned().unwrap_or_default(),
         })),
-        "webdav" => {
-            let vendor = match config["vendor"].as_str() {
-                "nextcloud" => WebDavVendors::Nextcloud,
-                "owncloud" => WebDavVendors::Owncloud,
-                "webdav" => WebDavVendors::WebDav,
-                _ => unreachable!(),
+        Some("webdav") => {
+            let vendor = match config.get("vendor").map(String::as_str) {
+                Some("nextcloud") => WebDavVendors::Nextcloud,
+                Some("owncloud") => WebDavVendors::Owncloud,
+                Some("webdav") => WebDavVendors::WebDav,
+                _ => return None,
             };
 
             Some(Remote::WebDav(WebDavRemote {
                 remote_name: remote,
-                user: config["user"].clone(),
-                pass: config["pass"].clone(),
-                url: config["user"].clone(),
+                user: config.get("user").cloned().unwrap_or_default(),
+                pass: config.get("pass").cloned().unwrap_or_default(),
+                url: config.get("user").cloned().unwrap_or_default(),
                 vendor,
             }))
         }
@@ -52,23 +191,90 @@ pub fn get_remote<T: ToString>(remote: T) -> Option<Remote> {
     }
 }
 
-/// Get all the remotes from the config file.
+/// Get all the remotes of types Celeste supports from the config file.
 pub fn get_remotes() -> Vec<Remote> {
+    get_remote_names().iter().filter_map(get_remote).collect()
+}
+
+/// Get the names of all the remotes in the config file. Rclone reloads the file
+/// when it changes, so this includes remotes added to it from outside of
+/// Celeste.
+pub fn get_remote_names() -> Vec<String> {
     let configs_str = libceleste::run_in_background(move || {
         librclone::rpc("config/listremotes", json!({}).to_string())
             .unwrap_or_else(|_| unreachable!())
     });
-    let configs = {
-        let config: HashMap<String, Vec<String>> = serde_json::from_str(&configs_str).unwrap();
-        config.get(&"remotes".to_string()).unwrap().to_owned()
-    };
-    let mut celeste_configs = vec![];
+    let config: HashMap<String, Vec<String>> = serde_json::from_str(&configs_str).unwrap();
+    config.get("remotes").cloned().unwrap_or_default()
+}
+
+/// Parse extra Rclone flags for a remote of the type `backend_type` (such as
+/// `drive`), as entered by the user. Only flags for that type's backend (such
+/// as `--drive-export-formats=docx` or `--drive-chunk-size 64M`) are allowed.
+/// Flags without a value are set to `true`. Returns the backend options the
+/// flags set, or an [`Err`] describing the first invalid flag.
+pub fn parse_flags(backend_type: &str, flags: &str) -> Result<Vec<(String, String)>, String> {
+    let prefix = format!("--{backend_type}-");
+    let mut options = vec![];
+    let mut words = flags.split_whitespace().peekable();
 
-    for config in configs {
-        celeste_configs.push(get_remote(&config).unwrap());
+    while let Some(word) = words.next() {
+        let Some(flag) = word.strip_prefix(&prefix) else {
+            return Err(tr::tr!(
+                "'{}' isn't a flag for this remote. Only flags starting with '{}' can be used.",
+                word,
+                prefix
+            ));
+        };
+        let (name, value) = match flag.split_once('=') {
+            Some((name, value)) => (name, value.to_owned()),
+            None => match words.next_if(|next_word| !next_word.starts_with("--")) {
+                Some(value) => (flag, value.to_owned()),
+                None => (flag, "true".to_owned()),
+            },
+        };
+
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|char| char.is_ascii_lowercase() || char.is_ascii_digit() || char == '-')
+        {
+            return Err(tr::tr!("'{}' isn't a valid flag.", word));
+        }
+        options.push((name.replace('-', "_"), value));
     }
 
-    celeste_configs
+    Ok(options)
+}
+
+/// Set the extra backend options (from [`parse_flags`]) to use for every
+/// operation on `remote_name`.
+pub fn set_remote_options(remote_name: &str, options: Vec<(String, String)>) {
+    REMOTE_OPTIONS
+        .lock()
+        .unwrap()
+        .insert(remote_name.to_owned(), options);
+}
+
+/// Restrict every operation on `remote_name` to the folder `root`, so that
+/// paths on the remote are relative to it and nothing outside of it can be
+/// seen. An empty `root` uses the whole remote.
+pub fn set_remote_root(remote_name: &str, root: &str) {
+    REMOTE_ROOTS
+        .lock()
+        .unwrap()
+        .insert(remote_name.to_owned(), libceleste::strip_slashes(root));
+}
+
+/// Get the folder that operations on `remote_name` are restricted to, as set
+/// by [`set_remote_root`]. This is empty if it uses the whole remote.
+fn remote_root(remote_name: &str) -> String {
+    REMOTE_ROOTS
+        .lock()
+        .unwrap()
+        .get(remote_name)
+        .cloned()
+        .unwrap_or_default()
 }
 
 /// The types of remotes in the config.
@@ -89,6 +295,82 @@ impl Remote {
             Remote::WebDav(remote) => remote.remote_name.clone(),
         }
     }
+
+    /// The name of the Rclone backend this remote uses, as used in its flags.
+    pub fn backend_type(&self) -> &'static str {
+        match self {
+            Remote::Dropbox(_) => "dropbox",
+            Remote::GDrive(_) => "drive",
+            Remote::PCloud(_) => "pcloud",
+            Remote::WebDav(_) => "webdav",
+        }
+    }
+
+    /// The name of the service this remote is on, such as "Google Drive".
+    pub fn provider_name(&self) -> String {
+        match self {
+            Remote::Dropbox(_) => "Dropbox".to_owned(),
+            Remote::GDrive(_) => "Google Drive".to_owned(),
+            Remote::PCloud(_) => "pCloud".to_owned(),
+            Remote::WebDav(remote) => remote.vendor.to_string(),
+        }
+    }
+
+    /// Whether this remote supports generating links to items via
+    /// [`sync::public_link`].
+    pub fn supports_links(&self) -> bool {
+        match self {
+            Remote::Dropbox(_) | Remote::GDrive(_) | Remote::PCloud(_) => true,
+            Remote::WebDav(_) => false,
+        }
+    }
+
+    /// Whether this remote's website can be opened to an item via
+    /// [`Remote::web_url`].
+    pub fn supports_web_urls(&self) -> bool {
+        match self {
+            Remote::Dropbox(_) | Remote::GDrive(_) => true,
+            Remote::PCloud(_) | Remote::WebDav(_) => false,
+        }
+    }
+
+    /// Get the URL of the page for the item at `path` on this remote's website.
+    /// Unlike [`sync::public_link`], this doesn't share the item with anyone,
+    /// so the page only works when signed in to the account. Returns
+    /// [`None`] if the remote's website can't be linked to the item.
+    pub fn web_url(&self, path: &str) -> Result<Option<String>, RcloneError> {
+        let remote_name = self.remote_name();
+
+        match self {
+            // Dropbox's website shows files in the folder they're in, with the file
+            // previewed.
+            Remote::Dropbox(_) => {
+                let path = crate::paths::join_remote(&remote_root(&remote_name), path);
+                let (folder, name) = path.rsplit_once('/').unwrap_or(("", path.as_str()));
+                let mut url = Url::parse("https://www.dropbox.com/home").unwrap();
+                url.path_segments_mut()
+                    .unwrap()
+                    .extend(folder.split('/').filter(|segment| !segment.is_empty()));
+                if !name.is_empty() {
+                    url.query_pairs_mut().append_pair("preview", name);
+                }
+                Ok(Some(url.to_string()))
+            }
+            // Google Drive's website only links to items by their IDs.
+            Remote::GDrive(_) => Ok(sync::stat(&remote_name, path)?
+                .and_then(|item| item.id)
+                .map(|id| format!("https://drive.google.com/open?id={id}"))),
+            Remote::PCloud(_) | Remote::WebDav(_) => Ok(None),
+        }
+    }
+
+    /// The precision that this remote stores modification times with.
+    pub fn timestamp_precision(&self) -> Duration {
+        match self {
+            Remote::GDrive(_) => Duration::from_millis(1),
+            Remote::Dropbox(_) | Remote::PCloud(_) | Remote::WebDav(_) => Duration::from_secs(1),
+        }
+    }
 }
 
 // The Dropbox remote type.
@@ -111,6 +393,43 @@ pub struct GDriveRemote {
     pub client_id: String,
     /// The client secret.
     pub client_secret: String,
+    /// The part of the Google Drive account the remote uses.
+    pub target: GDriveTarget,
+}
+
+/// The parts of a Google Drive account that a remote can use.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GDriveTarget {
+    /// The user's own files.
+    MyDrive,
+    /// The files other users have shared with the user.
+    SharedWithMe,
+    /// A shared drive (formerly called a Team Drive), by its ID.
+    SharedDrive(String),
+}
+
+/// Set the part of a Google Drive account that the Google Drive remote
+/// `remote_name` uses.
+pub fn set_gdrive_target(remote_name: &str, target: &GDriveTarget) {
+    let (team_drive, shared_with_me) = match target {
+        GDriveTarget::MyDrive => ("", false),
+        GDriveTarget::SharedWithMe => ("", true),
+        GDriveTarget::SharedDrive(id) => (id.as_str(), false),
+    };
+    let query = json!({
+        "name": remote_name,
+        "parameters": {
+            "team_drive": team_drive,
+            "shared_with_me": shared_with_me
+        }
+    });
+
+    libceleste::run_in_background(move || {
+        librclone::rpc("config/update", query.to_string()).unwrap();
+        // Rclone caches remotes after they're first used, so make sure it picks up the
+        // new config.
+        librclone::rpc("fscache/clear", "{}").unwrap_or_default();
+    });
 }
 
 // The pCloud remote type.
@@ -162,10 +481,151 @@ impl ToString for WebDavVendors {
     }
 }
 
-/// Error returned from Rclone.
+/// The raw error output of a failed Rclone RPC call.
 #[derive(Clone, Deserialize, Debug)]
-pub struct RcloneError {
-    pub error: String,
+struct RcloneRpcError {
+    error: String,
+    #[serde(default)]
+    status: u16,
+}
+
+/// Error returned from Rclone, categorized so callers can react to specific
+/// kinds of failures. Each variant contains the error message reported by
+/// Rclone.
+#[derive(Clone, Debug)]
+pub enum RcloneError {
+    /// The requested item (or remote) doesn't exist.
+    NotFound(String),
+    /// The remote's login has expired or been revoked, and needs to be
+    /// authenticated again.
+    AuthExpired(String),
+    /// The remote is rate limiting us.
+    RateLimited(String),
+    /// The remote couldn't be reached.
+    Network(String),
+    /// The remote has run out of storage space.
+    QuotaExceeded(String),
+    /// Any other error.
+    Unknown(String),
+}
+
+impl RcloneError {
+    /// Parse the error from the JSON output of a failed Rclone RPC call.
+    fn from_json(json_str: &str) -> Self {
+        let rpc_error = match serde_json::from_str::<RcloneRpcError>(json_str) {
+            Ok(rpc_error) => rpc_error,
+            Err(_) => return Self::Unknown(json_str.to_owned()),
+        };
+
+        Self::categorize(rpc_error.status, rpc_error.error)
+    }
+
+    /// Remove the quoted parts of an error message from Rclone, which are the
+    /// paths and names of the items it was about. Rclone quotes them with Go's
+    /// `%q`, so quotes inside of them are escaped with a backslash.
+    fn strip_quoted(error: &str) -> String {
+        let mut stripped = String::with_capacity(error.len());
+        let mut quoted = false;
+        let mut escaped = false;
+
+        for char in error.chars() {
+            if quoted {
+                match char {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => quoted = false,
+                    _ => (),
+                }
+            } else if char == '"' {
+                quoted = true;
+            } else {
+                stripped.push(char);
+            }
+        }
+
+        stripped
+    }
+
+    /// Categorize an error message from Rclone. `status` is the HTTP status
+    /// code Rclone reported alongside it, or `0` if there wasn't one. The
+    /// status is used when there is one, with the message only being checked
+    /// once the paths in it have been removed, so that an item's name can't
+    /// change what kind of error it got.
+    fn categorize(status: u16, error: String) -> Self {
+        match status {
+            401 => return Self::AuthExpired(error),
+            404 => return Self::NotFound(error),
+            429 => return Self::RateLimited(error),
+            507 => return Self::QuotaExceeded(error),
+            _ => (),
+        }
+
+        let lowercase_error = Self::strip_quoted(&error).to_lowercase();
+        let contains_any = |needles: &[&str]| {
+            needles
+                .iter()
+                .any(|needle| lowercase_error.contains(needle))
+        };
+
+        if contains_any(&["not found", "didn't find section in config file"]) {
+            Self::NotFound(error)
+        } else if contains_any(&[
+            "invalid_grant",
+            "token expired",
+            "expired_access_token",
+            "couldn't fetch token",
+            "401 unauthorized",
+        ]) {
+            Self::AuthExpired(error)
+        } else if contains_any(&[
+            "ratelimitexceeded",
+            "too_many_requests",
+            "too many requests",
+            "error 429",
+            "status 429",
+            "status code 429",
+        ]) {
+            Self::RateLimited(error)
+        } else if contains_any(&[
+            "storagequotaexceeded",
+            "quotaexceeded",
+            "insufficient_space",
+            "insufficient storage",
+            "quota exceeded",
+        ]) {
+            Self::QuotaExceeded(error)
+        } else if contains_any(&[
+            "temporary failure in name resolution",
+            "no such host",
+            "connection refused",
+            "connection reset",
+            "network is unreachable",
+            "i/o timeout",
+            "tls handshake timeout",
+        ]) {
+            Self::Network(error)
+        } else {
+            Self::Unknown(error)
+        }
+    }
+
+    /// The error message reported by Rclone.
+    pub fn message(&self) -> &str {
+        match self {
+            Self::NotFound(error)
+            | Self::AuthExpired(error)
+            | Self::RateLimited(error)
+            | Self::Network(error)
+            | Self::QuotaExceeded(error)
+            | Self::Unknown(error) => error,
+        }
+    }
+}
+
+impl fmt::Display for RcloneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
 }
 
 /// The output of an `operations/stat` command.
@@ -174,6 +634,54 @@ pub struct RcloneStat {
     item: Option<RcloneRemoteItem>,
 }
 
+/// The output of an `operations/publiclink` command.
+#[derive(Clone, Deserialize, Debug)]
+pub struct RclonePublicLink {
+    url: String,
+}
+
+/// The output of an `operations/about` command. Remotes only report the
+/// fields they know about.
+#[derive(Clone, Deserialize, Debug)]
+pub struct RcloneAbout {
+    /// The free space left on the remote, in bytes.
+    pub free: Option<i64>,
+    /// The space used on the remote, in bytes.
+    pub used: Option<i64>,
+    /// The total space on the remote, in bytes.
+    pub total: Option<i64>,
+}
+
+/// A shared drive on Google Drive, from the output of the `drives` backend
+/// command.
+#[derive(Clone, Deserialize, Debug)]
+pub struct RcloneSharedDrive {
+    pub id: String,
+    pub name: String,
+}
+
+/// The output of the `drives` backend command.
+#[derive(Clone, Deserialize, Debug)]
+pub struct RcloneSharedDrives {
+    result: Vec<RcloneSharedDrive>,
+}
+
+/// The output of an `operations/fsinfo` command.
+#[derive(Clone, Deserialize, Debug)]
+pub struct RcloneFsInfo {
+    /// The optional features the remote supports, such as `Copy` for
+    /// server-side copies.
+    #[serde(rename = "Features")]
+    pub features: HashMap<String, bool>,
+    /// The types of hashes the remote has for its files.
+    #[serde(rename = "Hashes")]
+    pub hashes: Option<Vec<String>>,
+    /// The precision the remote stores modification times with, in
+    /// nanoseconds.
+    #[serde(rename = "Precision")]
+    pub precision: i64,
+}
+
 /// The output of an `operations/list` command.
 #[derive(Clone, Deserialize, Debug)]
 pub struct RcloneList {
@@ -191,8 +699,134 @@ pub struct RcloneRemoteItem {
     pub path: String,
     #[serde(rename = "Name")]
     pub name: String,
+    #[serde(rename = "Size")]
+    pub size: i64,
     #[serde(rename = "ModTime", with = "time::serde::rfc3339")]
     pub mod_time: OffsetDateTime,
+    /// The remote's ID for the item, for remotes that have such.
+    #[serde(rename = "ID", default)]
+    pub id: Option<String>,
+    /// The item's hashes, keyed by hash type. This is only filled in by
+    /// [`sync::stat_with_hashes`] and [`sync::list_files_with_hashes`].
+    #[serde(rename = "Hashes", default)]
+    pub hashes: HashMap<String, String>,
+    /// The item's metadata from the remote, keyed by name. This is only filled
+    /// in by [`sync::stat_with_metadata`], and only for remotes that have any.
+    #[serde(rename = "Metadata", default)]
+    pub metadata: HashMap<String, String>,
+}
+
+/// The metadata keys remotes give the name of the user who last modified an
+/// item under.
+const MODIFIED_BY_METADATA_KEYS: &[&str] = &["last-modified-by-display-name"];
+
+impl RcloneRemoteItem {
+    /// Get the name of the user who last modified the item, if the remote
+    /// reports it in the item's metadata.
+    pub fn modified_by(&self) -> Option<&str> {
+        MODIFIED_BY_METADATA_KEYS
+            .iter()
+            .find_map(|key| self.metadata.get(*key))
+            .map(String::as_str)
+            .filter(|name| !name.is_empty())
+    }
+}
+
+/// A listing of a folder that's read from `rclone lsjson --stream` as Rclone
+/// sends it, instead of being loaded all at once like
+/// [`sync::list`]. This keeps memory usage down on folders with a large number
+/// of items. The `rclone lsjson` process gets killed when this is dropped.
+pub struct RcloneListStream {
+    /// The folder being listed, relative to the root of the remote.
+    dir: String,
+    /// The `rclone lsjson` process.
+    process: Child,
+    /// The lines of the process's stdout.
+    lines: Arc<Mutex<Lines<BufReader<ChildStdout>>>>,
+    /// The thread reading the process's stderr, which returns everything it
+    /// read once the process exits.
+    stderr: Option<JoinHandle<String>>,
+    /// Whether the listing has finished.
+    finished: bool,
+}
+
+impl RcloneListStream {
+    /// Wait for the process to exit once its stdout has been read, returning
+    /// the error it failed with if it didn't succeed.
+    fn finish(&mut self) -> Option<RcloneError> {
+        self.finished = true;
+
+        match self.process.wait() {
+            Ok(status) if status.success() => None,
+            Ok(status) => {
+                let stderr = self
+                    .stderr
+                    .take()
+                    .and_then(|handle| handle.join().ok())
+                    .unwrap_or_default()
+                    .trim()
+                    .to_owned();
+
+                if stderr.is_empty() {
+                    Some(RcloneError::Unknown(status.to_string()))
+                } else {
+                    Some(RcloneError::categorize(0, stderr))
+                }
+            }
+            Err(err) => Some(RcloneError::Unknown(err.to_string())),
+        }
+    }
+}
+
+impl Iterator for RcloneListStream {
+    type Item = Result<RcloneRemoteItem, RcloneError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        loop {
+            let lines = self.lines.clone();
+            let line = match libceleste::run_in_background(move || lines.lock().unwrap().next()) {
+                Some(Ok(line)) => line,
+                Some(Err(err)) => {
+                    self.finished = true;
+                    return Some(Err(RcloneError::Unknown(err.to_string())));
+                }
+                None => return self.finish().map(Err),
+            };
+
+            // Each item is on its own line, between the opening and closing brackets of
+            // the JSON array.
+            let line = line.trim().trim_end_matches(',');
+            if line.is_empty() || line == "[" || line == "]" {
+                continue;
+            }
+
+            return match serde_json::from_str::<RcloneRemoteItem>(line) {
+                // Like `operations/list`, make the path relative to the root of the remote
+                // instead of to the listed folder.
+                Ok(mut item) => {
+                    item.path = crate::paths::join_remote(&self.dir, &item.path);
+                    Some(Ok(item))
+                }
+                Err(err) => {
+                    self.finished = true;
+                    Some(Err(RcloneError::Unknown(err.to_string())))
+                }
+            };
+        }
+    }
+}
+
+impl Drop for RcloneListStream {
+    fn drop(&mut self) {
+        if let Ok(None) = self.process.try_wait() {
+            self.process.kill().unwrap_or(());
+            self.process.wait().ok();
+        }
+    }
 }
 
 /// The types of items to show in an `operations/list` command.
@@ -203,7 +837,6 @@ pub enum RcloneListFilter {
     /// Only return directories.
     Dirs,
     /// Only return files.
-    #[allow(dead_code)]
     Files,
 }
 
@@ -212,22 +845,77 @@ pub enum RcloneListFilter {
 /// [`libceleste::run_in_background`], so they don't need to be wrapped around
 /// such to be ran during UI execution.
 pub mod sync {
-    use super::{RcloneError, RcloneList, RcloneListFilter, RcloneRemoteItem, RcloneStat};
+    use super::{
+        command, GDriveTarget, RcloneAbout, RcloneError, RcloneFsInfo, RcloneList,
+        RcloneListFilter, RcloneListStream, RclonePublicLink, RcloneRemoteItem, RcloneSharedDrives,
+        RcloneStat, CONFIG_PASSWORD, REMOTE_OPTIONS, REMOTE_ROOTS,
+    };
+    use crate::metrics;
     use serde_json::json;
+    use std::{
+        io::{BufRead, BufReader, Read},
+        process::Stdio,
+        sync::{Arc, Mutex},
+        thread,
+        time::Duration,
+    };
 
-    /// Get a remote name.
-    fn get_remote_name(remote: &str) -> String {
+    /// Get a remote name, including any extra options and root folder set for
+    /// the remote with [`set_remote_options`] and [`set_remote_root`]. Names
+    /// starting with a `/` are local directories, which Rclone accepts as-is.
+    pub fn get_remote_name(remote: &str) -> String {
+        if remote.starts_with('/') {
+            return remote.to_owned();
+        }
         if remote.ends_with(':') {
             panic!("Remote '{remote}' is not allowed to end with a ':'. Please omit it.",);
         }
-        format!("{remote}:")
+
+        // Options get passed through Rclone's connection string syntax
+        // (`remote,name="value":`), with any quotes in the value doubled.
+        let options: String = REMOTE_OPTIONS
+            .lock()
+            .unwrap()
+            .get(remote)
+            .into_iter()
+            .flatten()
+            .map(|(name, value)| format!(",{name}=\"{}\"", value.replace('"', "\"\"")))
+            .collect();
+        let root = REMOTE_ROOTS
+            .lock()
+            .unwrap()
+            .get(remote)
+            .cloned()
+            .unwrap_or_default();
+        format!("{remote}{options}:{root}")
     }
 
-    /// Run an Rclone command without blocking the GUI.
-    fn run<T: ToString>(method: T, input: T) -> Result<String, String> {
+    /// The number of times to retry a command when the remote is rate limiting
+    /// us.
+    static RATE_LIMIT_RETRIES: u32 = 3;
+
+    /// Run an Rclone command without blocking the GUI. Commands that fail due
+    /// to rate limiting are retried with an exponential backoff.
+    fn run<T: ToString>(method: T, input: T) -> Result<String, RcloneError> {
         let method = method.to_string();
         let input = input.to_string();
-        libceleste::run_in_background(|| librclone::rpc(method, input))
+        let mut attempt = 0;
+
+        loop {
+            metrics::record_rclone_call(&method);
+            let (method, input) = (method.clone(), input.clone());
+            let resp = libceleste::run_in_background(|| librclone::rpc(method, input))
+                .map_err(|json_str| RcloneError::from_json(&json_str));
+
+            match resp {
+                Err(RcloneError::RateLimited(_)) if attempt < RATE_LIMIT_RETRIES => {
+                    let backoff = Duration::from_secs(2u64.pow(attempt));
+                    libceleste::run_in_background(move || thread::sleep(backoff));
+                    attempt += 1;
+                }
+                resp => return resp,
+            }
+        }
     }
 
     /// Common function for some of the below command.
@@ -241,20 +929,25 @@ pub mod sync {
             .to_string(),
         );
 
-        match resp {
-            Ok(_) => Ok(()),
-            Err(json_str) => Err(serde_json::from_str(&json_str).unwrap()),
-        }
+        resp.map(|_| ())
+    }
+
+    /// Unlock an encrypted config file with `password`. The `rclone` processes
+    /// we start get the password from [`super::command`].
+    pub fn unlock_config(password: &str) -> Result<(), RcloneError> {
+        run(
+            "config/unlock",
+            &json!({ "config_password": password }).to_string(),
+        )?;
+        *CONFIG_PASSWORD.lock().unwrap() = Some(password.to_owned());
+        Ok(())
     }
 
     /// Delete a config.
     pub fn delete_config(remote_name: &str) -> Result<(), RcloneError> {
         let resp = run("config/delete", &json!({ "name": remote_name }).to_string());
 
-        match resp {
-            Ok(_) => Ok(()),
-            Err(json_str) => Err(serde_json::from_str(&json_str).unwrap()),
-        }
+        resp.map(|_| ())
     }
 
     /// Get statistics about a file or folder.
@@ -268,10 +961,54 @@ pub mod sync {
             .to_string(),
         );
 
-        match resp {
-            Ok(json_str) => Ok(serde_json::from_str::<RcloneStat>(&json_str).unwrap().item),
-            Err(json_str) => Err(serde_json::from_str(&json_str).unwrap()),
-        }
+        resp.map(|json_str| serde_json::from_str::<RcloneStat>(&json_str).unwrap().item)
+    }
+
+    /// Get statistics about a file, along with its hashes of the types in
+    /// `hash_types`. If `hash_types` is empty, every hash type the remote
+    /// supports is returned. Remotes that store hashes can return them
+    /// directly, but others (such as local files) have to read the whole file
+    /// for each type.
+    pub fn stat_with_hashes(
+        remote_name: &str,
+        path: &str,
+        hash_types: &[&str],
+    ) -> Result<Option<RcloneRemoteItem>, RcloneError> {
+        let resp = run(
+            "operations/stat",
+            &json!({
+                "fs": get_remote_name(remote_name),
+                "remote": libceleste::strip_slashes(path),
+                "opt": {
+                    "showHash": true,
+                    "hashTypes": hash_types
+                }
+            })
+            .to_string(),
+        );
+
+        resp.map(|json_str| serde_json::from_str::<RcloneStat>(&json_str).unwrap().item)
+    }
+
+    /// Get statistics about a file or folder, along with its metadata. See
+    /// [`RcloneRemoteItem::metadata`].
+    pub fn stat_with_metadata(
+        remote_name: &str,
+        path: &str,
+    ) -> Result<Option<RcloneRemoteItem>, RcloneError> {
+        let resp = run(
+            "operations/stat",
+            &json!({
+                "fs": get_remote_name(remote_name),
+                "remote": libceleste::strip_slashes(path),
+                "opt": {
+                    "metadata": true
+                }
+            })
+            .to_string(),
+        );
+
+        resp.map(|json_str| serde_json::from_str::<RcloneStat>(&json_str).unwrap().item)
     }
 
     /// List the files/folders in a path.
@@ -297,10 +1034,143 @@ pub mod sync {
             .to_string(),
         );
 
+        resp.map(|json_str| serde_json::from_str::<RcloneList>(&json_str).unwrap().list)
+    }
+
+    /// List the files in a path and all of its subfolders, along with every
+    /// type of hash the remote has for them.
+    pub fn list_files_with_hashes(
+        remote_name: &str,
+        path: &str,
+    ) -> Result<Vec<RcloneRemoteItem>, RcloneError> {
+        let resp = run(
+            "operations/list",
+            &json!({
+                "fs": get_remote_name(remote_name),
+                "remote": libceleste::strip_slashes(path),
+                "opt": {
+                    "filesOnly": true,
+                    "recurse": true,
+                    "showHash": true
+                }
+            })
+            .to_string(),
+        );
+
+        resp.map(|json_str| serde_json::from_str::<RcloneList>(&json_str).unwrap().list)
+    }
+
+    /// List the files/folders directly in a path, reading them in as Rclone
+    /// finds them. See [`RcloneListStream`].
+    pub fn list_stream(remote_name: &str, path: &str) -> Result<RcloneListStream, RcloneError> {
+        let dir = libceleste::strip_slashes(path);
+        let fs = get_remote_name(remote_name);
+        let location = if fs.ends_with(':') {
+            format!("{fs}{dir}")
+        } else {
+            crate::paths::join_remote(&fs, &dir)
+        };
+
+        let mut process = command()
+            .arg("lsjson")
+            .arg("--stream")
+            .arg("--config")
+            .arg(config_path())
+            .arg(location)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| RcloneError::Unknown(err.to_string()))?;
+
+        // Read stderr in the background so the process never blocks on a full pipe.
+        let mut stderr_handle = process.stderr.take().unwrap();
+        let stderr = thread::spawn(move || {
+            let mut stderr = String::new();
+            stderr_handle.read_to_string(&mut stderr).ok();
+            stderr
+        });
+        let lines = BufReader::new(process.stdout.take().unwrap()).lines();
+
+        Ok(RcloneListStream {
+            dir,
+            process,
+            lines: Arc::new(Mutex::new(lines)),
+            stderr: Some(stderr),
+            finished: false,
+        })
+    }
+
+    /// Get a link to a file or folder on the remote.
+    pub fn public_link(remote_name: &str, path: &str) -> Result<String, RcloneError> {
+        let resp = run(
+            "operations/publiclink",
+            &json!({
+                "fs": get_remote_name(remote_name),
+                "remote": libceleste::strip_slashes(path)
+            })
+            .to_string(),
+        );
+
+        resp.map(|json_str| {
+            serde_json::from_str::<RclonePublicLink>(&json_str)
+                .unwrap()
+                .url
+        })
+    }
+
+    /// Get information about the remote's storage, such as how much space is
+    /// free.
+    pub fn about(remote_name: &str) -> Result<RcloneAbout, RcloneError> {
+        let resp = run(
+            "operations/about",
+            &json!({ "fs": get_remote_name(remote_name) }).to_string(),
+        );
+
+        resp.map(|json_str| serde_json::from_str::<RcloneAbout>(&json_str).unwrap())
+    }
+
+    /// Get the parts of the Google Drive account of the Google Drive remote
+    /// `remote_name` that it can use, along with their names. The user's own
+    /// files and the ones shared with them are always included, but shared
+    /// drives are left out if they can't be listed.
+    pub fn gdrive_targets(remote_name: &str) -> Vec<(GDriveTarget, String)> {
+        let mut targets = vec![
+            (GDriveTarget::MyDrive, tr::tr!("My Drive")),
+            (GDriveTarget::SharedWithMe, tr::tr!("Shared with me")),
+        ];
+        let resp = run(
+            "backend/command",
+            &json!({
+                "command": "drives",
+                "fs": get_remote_name(remote_name)
+            })
+            .to_string(),
+        );
+
         match resp {
-            Ok(json_str) => Ok(serde_json::from_str::<RcloneList>(&json_str).unwrap().list),
-            Err(json_str) => Err(serde_json::from_str(&json_str).unwrap()),
+            Ok(json_str) => targets.extend(
+                serde_json::from_str::<RcloneSharedDrives>(&json_str)
+                    .unwrap()
+                    .result
+                    .into_iter()
+                    .map(|drive| (GDriveTarget::SharedDrive(drive.id), drive.name)),
+            ),
+            Err(err) => {
+                hw_msg::warningln!("Unable to list the shared drives on '{remote_name}': '{err}'.")
+            }
         }
+
+        targets
+    }
+
+    /// Get information about the remote, such as which features it supports.
+    pub fn fsinfo(remote_name: &str) -> Result<RcloneFsInfo, RcloneError> {
+        let resp = run(
+            "operations/fsinfo",
+            &json!({ "fs": get_remote_name(remote_name) }).to_string(),
+        );
+
+        resp.map(|json_str| serde_json::from_str::<RcloneFsInfo>(&json_str).unwrap())
     }
 
     /// make a directory on the remote.
@@ -312,6 +1182,12 @@ pub mod sync {
     pub fn delete(remote_name: &str, path: &str) -> Result<(), RcloneError> {
         common("operations/delete", remote_name, path)
     }
+
+    /// Remove an empty directory.
+    pub fn rmdir(remote_name: &str, path: &str) -> Result<(), RcloneError> {
+        common("operations/rmdir", remote_name, path)
+    }
+
     /// Remove a directory and all of its contents.
     pub fn purge(remote_name: &str, path: &str) -> Result<(), RcloneError> {
         common("operations/purge", remote_name, path)
@@ -335,10 +1211,34 @@ pub mod sync {
             .to_string(),
         );
 
-        match resp {
-            Ok(_) => Ok(()),
-            Err(json_str) => Err(serde_json::from_str(&json_str).unwrap()),
-        }
+        resp.map(|_| ())
+    }
+
+    /// Move a file to a new location on the same remote. This is done
+    /// server-side when the remote supports it, so the file's contents don't
+    /// have to be transferred again.
+    pub fn move_file(remote_name: &str, src: &str, dst: &str) -> Result<(), RcloneError> {
+        let remote = get_remote_name(remote_name);
+        let resp = run(
+            "operations/movefile",
+            &json!({
+                "srcFs": remote,
+                "srcRemote": libceleste::strip_slashes(src),
+                "dstFs": remote,
+                "dstRemote": libceleste::strip_slashes(dst)
+            })
+            .to_string(),
+        );
+
+        resp.map(|_| ())
+    }
+
+    /// Copy a file to a new location on the same remote. This is only done
+    /// server-side if the remote supports such (see [`fsinfo`]), otherwise
+    /// Rclone downloads and reuploads the file.
+    pub fn copy_file(remote_name: &str, src: &str, dst: &str) -> Result<(), RcloneError> {
+        let remote = get_remote_name(remote_name);
+        copy(&remote, src, &remote, dst)
     }
 
     /// Copy a file from the local machine to the remote.
@@ -369,3 +1269,96 @@ pub mod sync {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn categorize_rate_limits() {
+        for (status, error) in [
+            (429, "Forbidden"),
+            (
+                0,
+                "googleapi: Error 429: User Rate Limit Exceeded, rateLimitExceeded",
+            ),
+            (
+                0,
+                "HTTP error 429 (429 Too Many Requests) returned body: \"\"",
+            ),
+            (0, "too_many_requests/..."),
+        ] {
+            assert!(
+                matches!(
+                    RcloneError::categorize(status, error.to_owned()),
+                    RcloneError::RateLimited(_)
+                ),
+                "{status}: {error}"
+            );
+        }
+    }
+
+    #[test]
+    fn categorize_numbers_in_paths() {
+        assert!(matches!(
+            RcloneError::categorize(0, "can't open IMG_4290.jpg".to_owned()),
+            RcloneError::Unknown(_)
+        ));
+        assert!(matches!(
+            RcloneError::categorize(
+                0,
+                "can't list \"Error 429\": directory not found".to_owned()
+            ),
+            RcloneError::NotFound(_)
+        ));
+    }
+
+    #[test]
+    fn categorize_ignores_quoted_paths() {
+        for (error, expected) in [
+            (
+                "failed to open \"Songs/Not Found.mp3\": connection reset by peer",
+                "network",
+            ),
+            (
+                "failed to open \"Too Many Requests/notes.txt\": object not found",
+                "not found",
+            ),
+            (
+                "failed to copy \"Quota Exceeded.pdf\": unexpected EOF",
+                "unknown",
+            ),
+            // Escaped quotes don't end the path.
+            (
+                "failed to copy \"say \\\"not found\\\".txt\": unexpected EOF",
+                "unknown",
+            ),
+            ("didn't find section in config file", "not found"),
+        ] {
+            let kind = match RcloneError::categorize(0, error.to_owned()) {
+                RcloneError::NotFound(_) => "not found",
+                RcloneError::Network(_) => "network",
+                RcloneError::Unknown(_) => "unknown",
+                _ => "other",
+            };
+            assert_eq!(kind, expected, "{error}");
+        }
+    }
+
+    #[test]
+    fn categorize_status_codes() {
+        // The status wins over whatever the message says.
+        assert!(matches!(
+            RcloneError::categorize(404, "too many requests".to_owned()),
+            RcloneError::NotFound(_)
+        ));
+        assert!(matches!(
+            RcloneError::categorize(401, "\"not found\"".to_owned()),
+            RcloneError::AuthExpired(_)
+        ));
+        assert!(matches!(
+            RcloneError::categorize(507, "Insufficient Storage".to_owned()),
+            RcloneError::QuotaExceeded(_)
+        ));
+    }
+}
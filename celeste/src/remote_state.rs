@@ -0,0 +1,194 @@
+//! The files kept in the remote folder of each sync directory. The state file
+//! records which devices sync the folder and when each of them last did, so
+//! that the devices syncing the same remote folder can tell each other apart.
+//! The manifest lists the files in the folder as of the last sync, so that a
+//! device syncing the folder for the first time can tell which of its local
+//! files are already in sync.
+use crate::{
+    entities::SyncDirsModel, exclusions, launch::VERIFY_HASH_TYPES, paths, rclone::RcloneError,
+    sync_backend::SyncBackend,
+};
+use adw::glib;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{collections::BTreeMap, fs, io};
+use tempfile::NamedTempFile;
+
+/// A device that syncs a remote folder.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DeviceRecord {
+    /// The device's host name, for showing to the user.
+    pub name: String,
+    /// When the device last finished syncing the folder, as a Unix timestamp.
+    pub last_sync: i64,
+}
+
+/// The contents of a remote folder's state file.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct RemoteState {
+    /// The devices that sync the folder, keyed by their IDs.
+    #[serde(default)]
+    pub devices: BTreeMap<String, DeviceRecord>,
+    /// The ID of the device that synced the folder most recently.
+    #[serde(default)]
+    pub last_device: Option<String>,
+    /// Any fields written by newer versions of Celeste, which get written back
+    /// untouched.
+    #[serde(flatten)]
+    other: Map<String, Value>,
+}
+
+/// Convert an error from reading or writing the local copy of a file.
+fn io_error(err: io::Error) -> RcloneError {
+    RcloneError::Unknown(err.to_string())
+}
+
+/// Download and parse the file at `remote_path`. A missing file gives
+/// [`None`], and so does one that can't be parsed, as it'll just get replaced
+/// the next time it's saved.
+fn download<T: DeserializeOwned>(
+    backend: &dyn SyncBackend,
+    remote_path: &str,
+) -> Result<Option<T>, RcloneError> {
+    if backend.stat(remote_path)?.is_none() {
+        return Ok(None);
+    }
+
+    let file = NamedTempFile::new().map_err(io_error)?;
+    backend.copy_to_local(&file.path().to_string_lossy(), remote_path)?;
+    let contents = fs::read_to_string(file.path()).map_err(io_error)?;
+
+    match serde_json::from_str(&contents) {
+        Ok(value) => Ok(Some(value)),
+        Err(err) => {
+            hw_msg::warningln!("Ignoring the unreadable file at '{remote_path}': '{err}'.");
+            Ok(None)
+        }
+    }
+}
+
+/// Upload `value` as the file at `remote_path`.
+fn upload<T: Serialize>(
+    backend: &dyn SyncBackend,
+    remote_path: &str,
+    value: &T,
+) -> Result<(), RcloneError> {
+    let file = NamedTempFile::new().map_err(io_error)?;
+    serde_json::to_writer(&file, value).map_err(|err| RcloneError::Unknown(err.to_string()))?;
+    backend.copy_to_remote(&file.path().to_string_lossy(), remote_path)
+}
+
+impl RemoteState {
+    /// Get the remote path of the state file for `sync_dir`.
+    fn remote_path(sync_dir: &SyncDirsModel) -> String {
+        paths::join_remote(&sync_dir.remote_path, exclusions::REMOTE_STATE_NAME)
+    }
+
+    /// Load the state of `sync_dir`'s remote folder. A folder without a state
+    /// file has an empty state.
+    pub fn load(backend: &dyn SyncBackend, sync_dir: &SyncDirsModel) -> Result<Self, RcloneError> {
+        download(backend, &Self::remote_path(sync_dir)).map(Option::unwrap_or_default)
+    }
+
+    /// Save this as the state of `sync_dir`'s remote folder.
+    pub fn save(
+        &self,
+        backend: &dyn SyncBackend,
+        sync_dir: &SyncDirsModel,
+    ) -> Result<(), RcloneError> {
+        upload(backend, &Self::remote_path(sync_dir), self)
+    }
+
+    /// Record that the device with the ID of `device_id` (which is this one)
+    /// finished syncing the folder at `timestamp`.
+    pub fn record_sync(&mut self, device_id: &str, timestamp: i64) {
+        self.devices.insert(
+            device_id.to_owned(),
+            DeviceRecord {
+                name: glib::host_name().to_string(),
+                last_sync: timestamp,
+            },
+        );
+        self.last_device = Some(device_id.to_owned());
+    }
+}
+
+/// A file listed in a [`Manifest`].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ManifestEntry {
+    /// The size of the file in bytes, which is negative if the remote doesn't
+    /// know it.
+    #[serde(rename = "s")]
+    pub size: i64,
+    /// The modification time of the file on the remote, as a Unix timestamp.
+    #[serde(rename = "t")]
+    pub mod_time: i64,
+    /// The type and value of one of the file's hashes, if the remote has any.
+    #[serde(rename = "h", default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<(String, String)>,
+}
+
+/// The files in a remote folder as of the last time it was synced. The field
+/// names are kept short, as this gets uploaded after every sync.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Manifest {
+    /// The files, keyed by their paths relative to the remote folder.
+    pub files: BTreeMap<String, ManifestEntry>,
+}
+
+impl Manifest {
+    /// Get the remote path of the manifest for `sync_dir`.
+    fn remote_path(sync_dir: &SyncDirsModel) -> String {
+        paths::join_remote(&sync_dir.remote_path, exclusions::MANIFEST_NAME)
+    }
+
+    /// Generate the manifest of the files currently in `sync_dir`'s remote
+    /// folder. This is a single listing of the whole folder.
+    pub fn generate(
+        backend: &dyn SyncBackend,
+        sync_dir: &SyncDirsModel,
+    ) -> Result<Self, RcloneError> {
+        let files = backend
+            .list_files_with_hashes(&sync_dir.remote_path)?
+            .into_iter()
+            .filter_map(|item| {
+                let relative_path = paths::relative_remote_path(sync_dir, &item.path)?.to_owned();
+                if exclusions::is_builtin_exclusion(sync_dir, &relative_path) {
+                    return None;
+                }
+
+                let hash = VERIFY_HASH_TYPES.iter().find_map(|hash_type| {
+                    item.hashes
+                        .get(*hash_type)
+                        .filter(|hash| !hash.is_empty())
+                        .map(|hash| ((*hash_type).to_owned(), hash.clone()))
+                });
+                let entry = ManifestEntry {
+                    size: item.size,
+                    mod_time: item.mod_time.unix_timestamp(),
+                    hash,
+                };
+                Some((relative_path, entry))
+            })
+            .collect();
+
+        Ok(Self { files })
+    }
+
+    /// Load the manifest of `sync_dir`'s remote folder, if it has one.
+    pub fn load(
+        backend: &dyn SyncBackend,
+        sync_dir: &SyncDirsModel,
+    ) -> Result<Option<Self>, RcloneError> {
+        download(backend, &Self::remote_path(sync_dir))
+    }
+
+    /// Save this as the manifest of `sync_dir`'s remote folder.
+    pub fn save(
+        &self,
+        backend: &dyn SyncBackend,
+        sync_dir: &SyncDirsModel,
+    ) -> Result<(), RcloneError> {
+        upload(backend, &Self::remote_path(sync_dir), self)
+    }
+}
@@ -0,0 +1,231 @@
+//! Deciding the order sync directories get synced in. High priority
+//! directories go first, then ones with recent local changes, and then the
+//! rest in the order they were last synced. This way a large directory (which
+//! only gets through part of its changes each pass, see
+//! [`crate::entities::SettingsModel::transfer_limit`]) takes turns with the
+//! others instead of holding them up. Directories that keep failing get
+//! retried less and less often, instead of running into the same errors every
+//! pass.
+use crate::entities::{RemotesModel, SyncDirsModel};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::Path,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How often high priority directories get synced again while other
+/// directories are being synced.
+const HIGH_PRIORITY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How many passes in a row a directory has to fail in before it starts being
+/// retried less often.
+const FAILURES_BEFORE_BACKOFF: u32 = 2;
+
+/// How long to wait before retrying a directory that's started failing. This
+/// doubles after each pass it fails in.
+const MIN_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The longest to wait before retrying a directory that keeps failing.
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// A sync directory to be synced, along with its remote.
+#[derive(Clone)]
+pub struct SyncPair {
+    pub remote: RemotesModel,
+    pub sync_dir: SyncDirsModel,
+    /// The local path of a directory set up before this one whose remote
+    /// folder overlaps this one's, in which case this one can't be synced.
+    pub overlapping_dir: Option<String>,
+}
+
+/// The directories left to sync in a single pass over all of them.
+pub struct SyncQueue {
+    pairs: VecDeque<SyncPair>,
+    /// The high priority directories, which get synced again during the pass
+    /// once they're due.
+    high_priority: Vec<SyncPair>,
+}
+
+/// What's known about a sync directory from the last time it got synced.
+struct DirState {
+    /// When the directory finished syncing.
+    synced: Instant,
+    /// The latest modification time of anything in the local directory at
+    /// that point.
+    local_modified: Option<SystemTime>,
+    /// Whether the directory had local changes to upload, or changes that were
+    /// left for the next pass.
+    active: bool,
+    /// The number of passes in a row the directory ran into errors in.
+    failures: u32,
+}
+
+/// Get the latest modification time of the item at `path`, or of anything
+/// inside of it if it's a directory. Editing a file only changes its own
+/// modification time, and adding or removing one only changes its parent's, so
+/// the whole tree has to be checked. Symlinks aren't followed.
+fn latest_modified(path: &Path) -> Option<SystemTime> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    let mut latest = metadata.modified().ok();
+
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path).into_iter().flatten().flatten() {
+            latest = latest.max(latest_modified(&entry.path()));
+        }
+    }
+    latest
+}
+
+/// Get the latest modification time of anything in the local directory of
+/// `sync_dir`.
+fn local_modified(sync_dir: &SyncDirsModel) -> Option<SystemTime> {
+    latest_modified(Path::new(&sync_dir.local_path))
+}
+
+/// Keeps track of when each sync directory was synced, to decide which to sync
+/// next.
+#[derive(Default)]
+pub struct Scheduler {
+    dirs: HashMap<i32, DirState>,
+    /// When each directory last started syncing.
+    started: HashMap<i32, Instant>,
+}
+
+impl Scheduler {
+    /// Whether `sync_dir` has local changes, from anything in it having been
+    /// modified since it was last synced. Directories that had changes left for
+    /// the next pass are assumed to still have them.
+    fn has_local_changes(&self, sync_dir: &SyncDirsModel) -> bool {
+        self.dirs.get(&sync_dir.id).map_or(false, |state| {
+            state.active || local_modified(sync_dir) != state.local_modified
+        })
+    }
+
+    /// Whether the high priority `sync_dir` is due to be synced again.
+    fn is_due(&self, sync_dir: &SyncDirsModel) -> bool {
+        self.started
+            .get(&sync_dir.id)
+            .map_or(true, |started| started.elapsed() >= HIGH_PRIORITY_INTERVAL)
+    }
+
+    /// When `sync_dir` is due to be retried, if it's failed in enough passes in
+    /// a row to be retried less often.
+    fn retry_at(&self, sync_dir: &SyncDirsModel) -> Option<Instant> {
+        let state = self.dirs.get(&sync_dir.id)?;
+        let backoffs = state.failures.checked_sub(FAILURES_BEFORE_BACKOFF)?;
+        let interval = MIN_RETRY_INTERVAL
+            .saturating_mul(2u32.saturating_pow(backoffs))
+            .min(MAX_RETRY_INTERVAL);
+        Some(state.synced + interval)
+    }
+
+    /// Get when `sync_dir` will be retried, if it keeps failing and shouldn't
+    /// be synced until then.
+    pub fn backing_off(&self, sync_dir: &SyncDirsModel) -> Option<Instant> {
+        self.retry_at(sync_dir)
+            .filter(|retry_at| *retry_at > Instant::now())
+    }
+
+    /// Put `pairs` into the order they should be synced in. Directories that
+    /// haven't been synced yet go before the others with no local changes.
+    pub fn queue(&self, mut pairs: Vec<SyncPair>) -> SyncQueue {
+        pairs.sort_by_cached_key(|pair| {
+            (
+                !pair.sync_dir.high_priority,
+                !self.has_local_changes(&pair.sync_dir),
+                self.dirs.get(&pair.sync_dir.id).map(|state| state.synced),
+            )
+        });
+
+        SyncQueue {
+            high_priority: pairs
+                .iter()
+                .filter(|pair| pair.sync_dir.high_priority)
+                .cloned()
+                .collect(),
+            pairs: pairs.into(),
+        }
+    }
+
+    /// Get the next directory to sync from `queue`, or [`None`] once the pass
+    /// is over.
+    pub fn next(&mut self, queue: &mut SyncQueue) -> Option<SyncPair> {
+        // A high priority directory that's due goes before whatever's next, as long
+        // as there's anything left in the pass.
+        let pair = match queue
+            .high_priority
+            .iter()
+            .find(|pair| self.is_due(&pair.sync_dir))
+        {
+            Some(pair) if !queue.pairs.is_empty() => pair.clone(),
+            _ => loop {
+                let pair = queue.pairs.pop_front()?;
+
+                // High priority directories might have just been synced as being due.
+                if !pair.sync_dir.high_priority || self.is_due(&pair.sync_dir) {
+                    break pair;
+                }
+            },
+        };
+
+        self.started.insert(pair.sync_dir.id, Instant::now());
+        Some(pair)
+    }
+
+    /// Record that `sync_dir` just finished syncing. `active` is whether it
+    /// had local changes to upload, or changes that were left for the next
+    /// pass, and `failed` is whether it ran into any errors.
+    pub fn finished(&mut self, sync_dir: &SyncDirsModel, active: bool, failed: bool) {
+        let failures = match self.dirs.get(&sync_dir.id) {
+            Some(state) if failed => state.failures + 1,
+            None if failed => 1,
+            _ => 0,
+        };
+        self.dirs.insert(
+            sync_dir.id,
+            DirState {
+                synced: Instant::now(),
+                local_modified: local_modified(sync_dir),
+                active,
+                failures,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_modified(path: &Path, secs: u64) {
+        fs::File::open(path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    #[test]
+    fn latest_modified_in_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("a/b");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(nested.join("file.txt"), "contents").unwrap();
+        for (path, secs) in [
+            (nested.join("file.txt"), 100),
+            (nested.clone(), 100),
+            (dir.path().join("a"), 100),
+            (dir.path().to_owned(), 100),
+        ] {
+            set_modified(&path, secs);
+        }
+        let modified = |secs| Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        assert_eq!(latest_modified(dir.path()), modified(100));
+
+        // A file deep inside the tree changing is noticed, even though none of
+        // the directories above it change.
+        set_modified(&nested.join("file.txt"), 200);
+        assert_eq!(latest_modified(dir.path()), modified(200));
+        assert_eq!(latest_modified(&dir.path().join("missing")), None);
+    }
+}
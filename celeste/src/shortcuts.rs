@@ -0,0 +1,52 @@
+use adw::{
+    gtk::{Builder, ShortcutsWindow},
+    prelude::*,
+    Application,
+};
+
+/// The application actions that have keyboard shortcuts, along with their
+/// accelerators and the titles shown for them in the shortcuts window.
+pub fn shortcuts() -> Vec<(&'static str, &'static str, String)> {
+    vec![
+        ("app.add-remote", "<Control>n", tr::tr!("Add a server")),
+        ("app.sync-now", "<Control>r", tr::tr!("Sync now")),
+        ("app.preferences", "<Control>comma", tr::tr!("Preferences")),
+        ("app.shortcuts", "F1", tr::tr!("Keyboard shortcuts")),
+        ("app.quit", "<Control>q", tr::tr!("Quit")),
+    ]
+}
+
+/// Escape text for use inside of the shortcuts window's UI definition.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn shortcuts_window(app: &Application) {
+    // `ShortcutsWindow` can only be populated from a UI definition, so build one up
+    // from our list of shortcuts.
+    let shortcuts: String = shortcuts()
+        .iter()
+        .map(|(_, accel, title)| {
+            format!(
+                r#"<child><object class="GtkShortcutsShortcut"><property name="accelerator">{}</property><property name="title">{}</property></object></child>"#,
+                escape(accel),
+                escape(title)
+            )
+        })
+        .collect();
+    let ui = format!(
+        r#"<interface><object class="GtkShortcutsWindow" id="shortcuts_window"><property name="modal">1</property><child><object class="GtkShortcutsSection"><child><object class="GtkShortcutsGroup"><property name="title">{}</property>{}</object></child></object></child></object></interface>"#,
+        escape(&tr::tr!("General")),
+        shortcuts
+    );
+
+    let window: ShortcutsWindow = Builder::from_string(&ui)
+        .object("shortcuts_window")
+        .unwrap();
+    window.set_application(Some(app));
+    window.set_transient_for(app.active_window().as_ref());
+    window.show();
+}
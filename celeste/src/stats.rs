@@ -0,0 +1,334 @@
+//! The statistics window, which shows how much has been synced over the last
+//! few weeks, either for a single remote or for all of them together.
+use crate::{
+    db,
+    entities::{RemotesEntity, SyncPassesColumn, SyncPassesEntity},
+};
+use adw::{
+    glib,
+    gtk::{cairo, Align, Box, DrawingArea, Label, Orientation, StringList},
+    prelude::*,
+    ActionRow, Application, ComboRow, PreferencesGroup, PreferencesPage, PreferencesWindow,
+};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use std::{cell::RefCell, rc::Rc};
+
+/// The number of days shown in the statistics window.
+const DAYS: i32 = 30;
+
+/// The height of each chart, in pixels.
+const CHART_HEIGHT: i32 = 120;
+
+/// The colors of the chart bars, as RGB values.
+const UPLOAD_COLOR: (f64, f64, f64) = (0.21, 0.52, 0.89);
+const DOWNLOAD_COLOR: (f64, f64, f64) = (0.2, 0.82, 0.48);
+const FILES_COLOR: (f64, f64, f64) = (0.57, 0.25, 0.67);
+
+/// The totals of the sync passes that started on a single day.
+#[derive(Clone, Copy, Default)]
+struct DayStats {
+    bytes_uploaded: i64,
+    bytes_downloaded: i64,
+    files_synced: i64,
+}
+
+/// The statistics of the sync passes over the last [`DAYS`] days.
+#[derive(Default)]
+struct Stats {
+    /// The totals for each day, oldest first.
+    days: Vec<DayStats>,
+    passes: i64,
+    passes_with_errors: i64,
+    duration_ms: i64,
+}
+
+impl Stats {
+    /// Load the statistics of the sync passes over the remote with the ID of
+    /// `remote_id`, or over all remotes if that's [`None`].
+    fn load(db: &DatabaseConnection, remote_id: Option<i32>) -> Self {
+        // Passes are grouped by the local day they started on.
+        let now = glib::DateTime::now_local().unwrap();
+        let (year, month, day) = now.ymd();
+        let first_day = glib::DateTime::from_local(year, month, day, 0, 0, 0.0)
+            .and_then(|today| today.add_days(1 - DAYS))
+            .unwrap();
+        let day_keys: Vec<_> = (0..DAYS)
+            .map(|index| first_day.add_days(index).unwrap().format("%F").unwrap())
+            .collect();
+
+        let mut query =
+            SyncPassesEntity::find().filter(SyncPassesColumn::Started.gte(first_day.to_unix()));
+        if let Some(remote_id) = remote_id {
+            query = query.filter(SyncPassesColumn::RemoteId.eq(remote_id));
+        }
+
+        let mut stats = Self {
+            days: vec![DayStats::default(); day_keys.len()],
+            ..Default::default()
+        };
+        for pass in db::expect(|| query.clone().all(db)) {
+            let Some(index) = glib::DateTime::from_unix_local(pass.started)
+                .and_then(|started| started.format("%F"))
+                .ok()
+                .and_then(|key| day_keys.iter().position(|day_key| *day_key == key))
+            else {
+                continue;
+            };
+
+            let day = &mut stats.days[index];
+            day.bytes_uploaded += pass.bytes_uploaded;
+            day.bytes_downloaded += pass.bytes_downloaded;
+            day.files_synced += i64::from(pass.files_uploaded + pass.files_downloaded);
+            stats.passes += 1;
+            stats.passes_with_errors += i64::from(pass.errors != 0);
+            stats.duration_ms += pass.duration_ms;
+        }
+
+        stats
+    }
+}
+
+/// Draw a bar chart of `series`, each of which is a color and a value for each
+/// day. The bars for a day are drawn next to each other.
+fn draw_bar_chart(
+    area: &DrawingArea,
+    cr: &cairo::Context,
+    width: i32,
+    height: i32,
+    series: &[((f64, f64, f64), Vec<f64>)],
+) -> Result<(), cairo::Error> {
+    let (width, height) = (f64::from(width), f64::from(height));
+    let max = series
+        .iter()
+        .flat_map(|(_, values)| values.iter().copied())
+        .fold(0.0, f64::max);
+    let slot_width = width / f64::from(DAYS);
+    let bar_width = slot_width * 0.8 / series.len() as f64;
+
+    if max > 0.0 {
+        for (series_index, ((red, green, blue), values)) in series.iter().enumerate() {
+            cr.set_source_rgb(*red, *green, *blue);
+            for (day_index, value) in values.iter().enumerate() {
+                let bar_height = (height - 1.0) * value / max;
+                cr.rectangle(
+                    slot_width * (day_index as f64 + 0.1) + bar_width * series_index as f64,
+                    height - 1.0 - bar_height,
+                    bar_width,
+                    bar_height,
+                );
+            }
+            cr.fill()?;
+        }
+    }
+
+    // The baseline, in the theme's text color so it shows up in dark mode too.
+    let color = area.style_context().color();
+    cr.set_source_rgba(
+        color.red().into(),
+        color.green().into(),
+        color.blue().into(),
+        0.5,
+    );
+    cr.rectangle(0.0, height - 1.0, width, 1.0);
+    cr.fill()
+}
+
+/// Create a chart of the values returned by each of `series` for every day,
+/// along with a legend of the series' labels. The chart's drawing area is
+/// returned too, so that it can be redrawn when `stats` changes.
+fn chart(
+    stats: &Rc<RefCell<Stats>>,
+    series: Vec<(String, (f64, f64, f64), fn(&DayStats) -> i64)>,
+) -> (Box, DrawingArea) {
+    let legend = Box::builder()
+        .orientation(Orientation::Horizontal)
+        .spacing(15)
+        .build();
+    for (label, (red, green, blue), _) in &series {
+        let color = format!(
+            "#{:02x}{:02x}{:02x}",
+            (red * 255.0) as u8,
+            (green * 255.0) as u8,
+            (blue * 255.0) as u8
+        );
+        legend.append(
+            &Label::builder()
+                .label(&format!(
+                    "<span foreground=\"{color}\">■</span> {}",
+                    glib::markup_escape_text(label)
+                ))
+                .use_markup(true)
+                .css_classes(vec!["caption".to_string()])
+                .build(),
+        );
+    }
+
+    let area = DrawingArea::builder()
+        .content_height(CHART_HEIGHT)
+        .hexpand(true)
+        .build();
+    area.set_draw_func(
+        glib::clone!(@strong stats => move |area, cr, width, height| {
+            let stats = stats.borrow();
+            let series: Vec<_> = series
+                .iter()
+                .map(|(_, color, value)| {
+                    (*color, stats.days.iter().map(|day| value(day) as f64).collect())
+                })
+                .collect();
+            let _ = draw_bar_chart(area, cr, width, height, &series);
+        }),
+    );
+
+    // Show the range of days under the chart.
+    let range = Box::builder().orientation(Orientation::Horizontal).build();
+    range.append(
+        &Label::builder()
+            .label(&tr::tr!("{} days ago", DAYS - 1))
+            .hexpand(true)
+            .halign(Align::Start)
+            .css_classes(vec!["caption".to_string(), "dim-label".to_string()])
+            .build(),
+    );
+    range.append(
+        &Label::builder()
+            .label(&tr::tr!("Today"))
+            .css_classes(vec!["caption".to_string(), "dim-label".to_string()])
+            .build(),
+    );
+
+    let chart_content = Box::builder()
+        .orientation(Orientation::Vertical)
+        .spacing(6)
+        .margin_top(12)
+        .margin_bottom(12)
+        .margin_start(12)
+        .margin_end(12)
+        .build();
+    chart_content.append(&legend);
+    chart_content.append(&area);
+    chart_content.append(&range);
+    let chart = Box::builder()
+        .margin_top(6)
+        .css_classes(vec!["card".to_string()])
+        .build();
+    chart.append(&chart_content);
+
+    (chart, area)
+}
+
+/// Create a row showing a single statistic, returning it along with the label
+/// the statistic goes in.
+fn summary_row(title: &str) -> (ActionRow, Label) {
+    let label = Label::builder()
+        .css_classes(vec!["dim-label".to_string()])
+        .build();
+    let row = ActionRow::builder().title(title).build();
+    row.add_suffix(&label);
+    (row, label)
+}
+
+pub fn stats_window(app: &Application, db: &DatabaseConnection) {
+    let remotes = db::expect(|| RemotesEntity::find().all(db));
+    let stats = Rc::new(RefCell::new(Stats::load(db, None)));
+
+    // Which remote to show the statistics of. The first entry shows all of them.
+    let mut remote_labels = vec![tr::tr!("All remotes")];
+    remote_labels.extend(remotes.iter().map(|remote| remote.name.clone()));
+    let remote_model =
+        StringList::new(&remote_labels.iter().map(String::as_str).collect::<Vec<_>>());
+    let remote_row = ComboRow::builder()
+        .title(&tr::tr!("Remote"))
+        .model(&remote_model)
+        .build();
+    let remote_group = PreferencesGroup::new();
+    remote_group.add(&remote_row);
+
+    let (uploaded_row, uploaded_label) = summary_row(&tr::tr!("Data uploaded"));
+    let (downloaded_row, downloaded_label) = summary_row(&tr::tr!("Data downloaded"));
+    let (files_row, files_label) = summary_row(&tr::tr!("Files synced"));
+    let (error_rate_row, error_rate_label) = summary_row(&tr::tr!("Syncs with errors"));
+    let (duration_row, duration_label) = summary_row(&tr::tr!("Average sync duration"));
+    let summary_group = PreferencesGroup::builder()
+        .title(&tr::tr!("Last {} Days", DAYS))
+        .build();
+    summary_group.add(&uploaded_row);
+    summary_group.add(&downloaded_row);
+    summary_group.add(&files_row);
+    summary_group.add(&error_rate_row);
+    summary_group.add(&duration_row);
+
+    let (data_chart, data_area) = chart(
+        &stats,
+        vec![
+            (tr::tr!("Uploaded"), UPLOAD_COLOR, |day| day.bytes_uploaded),
+            (tr::tr!("Downloaded"), DOWNLOAD_COLOR, |day| {
+                day.bytes_downloaded
+            }),
+        ],
+    );
+    let data_group = PreferencesGroup::builder()
+        .title(&tr::tr!("Data Transferred per Day"))
+        .build();
+    data_group.add(&data_chart);
+
+    let (files_chart, files_area) = chart(
+        &stats,
+        vec![(tr::tr!("Files synced"), FILES_COLOR, |day| day.files_synced)],
+    );
+    let files_group = PreferencesGroup::builder()
+        .title(&tr::tr!("Files Synced per Day"))
+        .build();
+    files_group.add(&files_chart);
+
+    // Show the statistics currently in `stats`.
+    let update_ui = glib::clone!(@strong stats, @weak data_area, @weak files_area => move || {
+        let stats = stats.borrow();
+        let bytes_uploaded: i64 = stats.days.iter().map(|day| day.bytes_uploaded).sum();
+        let bytes_downloaded: i64 = stats.days.iter().map(|day| day.bytes_downloaded).sum();
+        let files_synced: i64 = stats.days.iter().map(|day| day.files_synced).sum();
+        uploaded_label.set_label(&glib::format_size(bytes_uploaded.try_into().unwrap_or(0)));
+        downloaded_label.set_label(&glib::format_size(bytes_downloaded.try_into().unwrap_or(0)));
+        files_label.set_label(&files_synced.to_string());
+
+        if stats.passes == 0 {
+            error_rate_label.set_label(&tr::tr!("No syncs yet"));
+            duration_label.set_label(&tr::tr!("No syncs yet"));
+        } else {
+            let error_rate = stats.passes_with_errors as f64 / stats.passes as f64 * 100.0;
+            let duration = stats.duration_ms as f64 / stats.passes as f64 / 1000.0;
+            error_rate_label.set_label(&format!("{error_rate:.0}%"));
+            duration_label.set_label(&tr::tr!("{} seconds", format!("{duration:.1}")));
+        }
+
+        data_area.queue_draw();
+        files_area.queue_draw();
+    });
+    update_ui();
+    remote_row.connect_selected_notify(
+        glib::clone!(@strong db, @strong stats, @strong update_ui => move |row| {
+            let remote_id = match row.selected() {
+                0 => None,
+                index => Some(remotes[index as usize - 1].id),
+            };
+            *stats.borrow_mut() = Stats::load(&db, remote_id);
+            update_ui();
+        }),
+    );
+
+    let page = PreferencesPage::new();
+    page.add(&remote_group);
+    page.add(&summary_group);
+    page.add(&data_group);
+    page.add(&files_group);
+
+    let window = PreferencesWindow::builder()
+        .application(app)
+        .title(&libceleste::get_title!("Statistics"))
+        .search_enabled(false)
+        .default_width(600)
+        .default_height(700)
+        .build();
+    window.add(&page);
+    window.show();
+}
@@ -0,0 +1,478 @@
+//! The storage that the sync engine syncs local items against. This is
+//! normally an Rclone remote, but can be swapped out so the engine can be run
+//! without a real cloud account.
+use crate::{
+    capabilities::RemoteCapabilities,
+    rclone::{self, RcloneError, RcloneListFilter, RcloneRemoteItem},
+};
+use std::{cell::OnceCell, iter, path::Path};
+#[cfg(test)]
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fs,
+};
+#[cfg(test)]
+use time::OffsetDateTime;
+
+/// The items returned by [`SyncBackend::list_stream`].
+pub type ListStream = Box<dyn Iterator<Item = Result<RcloneRemoteItem, RcloneError>>>;
+
+/// The operations the sync engine needs from a remote. Paths are relative to
+/// the root of the remote.
+pub trait SyncBackend {
+    /// Get statistics about a file or folder.
+    fn stat(&self, path: &str) -> Result<Option<RcloneRemoteItem>, RcloneError>;
+
+    /// Get statistics about a file, along with every type of hash the remote
+    /// has for it.
+    fn stat_with_hashes(&self, path: &str) -> Result<Option<RcloneRemoteItem>, RcloneError>;
+
+    /// List the files/folders in a path.
+    fn list(
+        &self,
+        path: &str,
+        recursive: bool,
+        filter: RcloneListFilter,
+    ) -> Result<Vec<RcloneRemoteItem>, RcloneError>;
+
+    /// List the files in a path and all of its subfolders, along with every
+    /// type of hash the remote has for them.
+    fn list_files_with_hashes(&self, path: &str) -> Result<Vec<RcloneRemoteItem>, RcloneError>;
+
+    /// List the files/folders directly in a path, getting them from the
+    /// remote as they're iterated over. This should be used over
+    /// [`SyncBackend::list`] for folders that could have a large number of
+    /// items, so they don't all have to be loaded into memory at once. If the
+    /// listing fails partway through, the last item is an [`Err`].
+    fn list_stream(&self, path: &str) -> ListStream;
+
+    /// Get the free space left on the remote, in bytes, or [`None`] if the
+    /// remote doesn't report it.
+    fn free_space(&self) -> Result<Option<u64>, RcloneError>;
+
+    /// Make a directory.
+    fn mkdir(&self, path: &str) -> Result<(), RcloneError>;
+
+    /// Delete a file.
+    fn delete(&self, path: &str) -> Result<(), RcloneError>;
+
+    /// Remove a directory and all of its contents.
+    fn purge(&self, path: &str) -> Result<(), RcloneError>;
+
+    /// Move a file to a new location.
+    fn move_file(&self, src: &str, dst: &str) -> Result<(), RcloneError>;
+
+    /// Whether the remote can copy files server-side, without their contents
+    /// being transferred again.
+    fn supports_server_side_copy(&self) -> bool;
+
+    /// Copy a file to a new location on the remote.
+    fn copy_file(&self, src: &str, dst: &str) -> Result<(), RcloneError>;
+
+    /// Copy a file from the local machine.
+    fn copy_to_remote(&self, local_file: &str, remote_destination: &str)
+        -> Result<(), RcloneError>;
+
+    /// Copy a file to the local machine.
+    fn copy_to_local(&self, local_destination: &str, remote_file: &str) -> Result<(), RcloneError>;
+}
+
+/// A backend that goes through Rclone.
+pub struct RcloneBackend {
+    remote_name: String,
+    /// What the remote supports, once it's been checked. This is [`None`] if
+    /// checking it failed.
+    capabilities: OnceCell<Option<RemoteCapabilities>>,
+}
+
+impl RcloneBackend {
+    /// Use the Rclone remote named `remote_name`.
+    pub fn new(remote_name: &str) -> Self {
+        Self {
+            remote_name: remote_name.to_owned(),
+            capabilities: OnceCell::new(),
+        }
+    }
+
+    /// Use `capabilities` as what the remote supports, such as ones stored in
+    /// the database, instead of checking it again. If `capabilities` is
+    /// [`None`], it gets checked when it's first needed.
+    pub fn with_capabilities(self, capabilities: Option<RemoteCapabilities>) -> Self {
+        if let Some(capabilities) = capabilities {
+            let _ = self.capabilities.set(Some(capabilities));
+        }
+        self
+    }
+
+    /// Use Rclone's local backend, with `root` as the root of the remote. This
+    /// runs the same Rclone code paths as a real remote, but against a
+    /// directory on this machine.
+    pub fn local(root: &Path) -> Self {
+        Self {
+            remote_name: root.to_string_lossy().into_owned(),
+            capabilities: OnceCell::new(),
+        }
+    }
+
+    /// Get what the remote supports, checking it the first time this is
+    /// called if it wasn't given.
+    fn capabilities(&self) -> Option<&RemoteCapabilities> {
+        self.capabilities
+            .get_or_init(|| match RemoteCapabilities::probe(&self.remote_name) {
+                Ok(capabilities) => Some(capabilities),
+                Err(err) => {
+                    hw_msg::warningln!("Unable to check what the remote supports: '{err}'.");
+                    None
+                }
+            })
+            .as_ref()
+    }
+}
+
+impl SyncBackend for RcloneBackend {
+    fn stat(&self, path: &str) -> Result<Option<RcloneRemoteItem>, RcloneError> {
+        rclone::sync::stat(&self.remote_name, path)
+    }
+
+    fn stat_with_hashes(&self, path: &str) -> Result<Option<RcloneRemoteItem>, RcloneError> {
+        // Only the hash that'll get compared is asked for if it's known, since
+        // some remotes have to compute the others on request.
+        let hash_types: Vec<&str> = self
+            .capabilities()
+            .and_then(RemoteCapabilities::preferred_hash)
+            .into_iter()
+            .collect();
+        rclone::sync::stat_with_hashes(&self.remote_name, path, &hash_types)
+    }
+
+    fn list(
+        &self,
+        path: &str,
+        recursive: bool,
+        filter: RcloneListFilter,
+    ) -> Result<Vec<RcloneRemoteItem>, RcloneError> {
+        rclone::sync::list(&self.remote_name, path, recursive, filter)
+    }
+
+    fn list_files_with_hashes(&self, path: &str) -> Result<Vec<RcloneRemoteItem>, RcloneError> {
+        rclone::sync::list_files_with_hashes(&self.remote_name, path)
+    }
+
+    fn list_stream(&self, path: &str) -> ListStream {
+        match rclone::sync::list_stream(&self.remote_name, path) {
+            Ok(stream) => Box::new(stream),
+            Err(err) => Box::new(iter::once(Err(err))),
+        }
+    }
+
+    fn free_space(&self) -> Result<Option<u64>, RcloneError> {
+        if self
+            .capabilities()
+            .map_or(false, |capabilities| !capabilities.about)
+        {
+            return Ok(None);
+        }
+
+        rclone::sync::about(&self.remote_name)
+            .map(|about| about.free.and_then(|free| u64::try_from(free).ok()))
+    }
+
+    fn mkdir(&self, path: &str) -> Result<(), RcloneError> {
+        rclone::sync::mkdir(&self.remote_name, path)
+    }
+
+    fn delete(&self, path: &str) -> Result<(), RcloneError> {
+        rclone::sync::delete(&self.remote_name, path)
+    }
+
+    fn purge(&self, path: &str) -> Result<(), RcloneError> {
+        rclone::sync::purge(&self.remote_name, path)
+    }
+
+    fn move_file(&self, src: &str, dst: &str) -> Result<(), RcloneError> {
+        rclone::sync::move_file(&self.remote_name, src, dst)
+    }
+
+    fn supports_server_side_copy(&self) -> bool {
+        self.capabilities()
+            .map_or(false, |capabilities| capabilities.server_side_copy)
+    }
+
+    fn copy_file(&self, src: &str, dst: &str) -> Result<(), RcloneError> {
+        rclone::sync::copy_file(&self.remote_name, src, dst)
+    }
+
+    fn copy_to_remote(
+        &self,
+        local_file: &str,
+        remote_destination: &str,
+    ) -> Result<(), RcloneError> {
+        rclone::sync::copy_to_remote(local_file, &self.remote_name, remote_destination)
+    }
+
+    fn copy_to_local(&self, local_destination: &str, remote_file: &str) -> Result<(), RcloneError> {
+        rclone::sync::copy_to_local(local_destination, &self.remote_name, remote_file)
+    }
+}
+
+/// An item stored in a [`MemoryBackend`].
+#[cfg(test)]
+#[derive(Clone, Debug)]
+struct MemoryItem {
+    /// The contents of the file, or [`None`] for a directory.
+    contents: Option<Vec<u8>>,
+    mod_time: OffsetDateTime,
+}
+
+/// A fake backend that keeps everything in memory, for testing the sync engine
+/// without touching Rclone at all.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryBackend {
+    /// The items on the remote, keyed by their path with no leading or
+    /// trailing slashes.
+    items: RefCell<BTreeMap<String, MemoryItem>>,
+}
+
+#[cfg(test)]
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file directly to the remote, along with any missing parent
+    /// directories.
+    pub fn insert_file(&self, path: &str, contents: &[u8], mod_time: OffsetDateTime) {
+        let path = libceleste::strip_slashes(path);
+        self.add_parents(&path, mod_time);
+        self.items.borrow_mut().insert(
+            path,
+            MemoryItem {
+                contents: Some(contents.to_vec()),
+                mod_time,
+            },
+        );
+    }
+
+    /// Get the contents of a file on the remote.
+    pub fn contents(&self, path: &str) -> Option<Vec<u8>> {
+        self.items
+            .borrow()
+            .get(&libceleste::strip_slashes(path))
+            .and_then(|item| item.contents.clone())
+    }
+
+    /// Create any of the parent directories of `path` that don't exist yet.
+    fn add_parents(&self, path: &str, mod_time: OffsetDateTime) {
+        let mut items = self.items.borrow_mut();
+
+        for dir in Path::new(path).ancestors().skip(1) {
+            if dir == Path::new("") {
+                break;
+            }
+
+            items
+                .entry(dir.to_string_lossy().into_owned())
+                .or_insert(MemoryItem {
+                    contents: None,
+                    mod_time,
+                });
+        }
+    }
+
+    fn not_found(path: &str) -> RcloneError {
+        RcloneError::NotFound(format!("object not found: {path}"))
+    }
+
+    fn to_remote_item(path: &str, item: &MemoryItem) -> RcloneRemoteItem {
+        RcloneRemoteItem {
+            is_dir: item.contents.is_none(),
+            path: path.to_owned(),
+            name: Path::new(path)
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned(),
+            size: item
+                .contents
+                .as_ref()
+                .map(|contents| contents.len() as i64)
+                .unwrap_or(-1),
+            mod_time: item.mod_time,
+            hashes: HashMap::new(),
+            metadata: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl SyncBackend for MemoryBackend {
+    fn stat(&self, path: &str) -> Result<Option<RcloneRemoteItem>, RcloneError> {
+        let path = libceleste::strip_slashes(path);
+        Ok(self
+            .items
+            .borrow()
+            .get(&path)
+            .map(|item| Self::to_remote_item(&path, item)))
+    }
+
+    fn stat_with_hashes(&self, path: &str) -> Result<Option<RcloneRemoteItem>, RcloneError> {
+        // Nothing gets corrupted in memory, so there's no need for any hashes.
+        self.stat(path)
+    }
+
+    fn list(
+        &self,
+        path: &str,
+        recursive: bool,
+        filter: RcloneListFilter,
+    ) -> Result<Vec<RcloneRemoteItem>, RcloneError> {
+        let path = libceleste::strip_slashes(path);
+        let items = self.items.borrow();
+
+        if !path.is_empty() && !items.contains_key(&path) {
+            return Err(Self::not_found(&path));
+        }
+
+        // Like Rclone, the returned paths are relative to the root of the remote,
+        // not to the listed directory.
+        Ok(items
+            .iter()
+            .filter(|(item_path, _)| {
+                let relative_path = if path.is_empty() {
+                    Some(item_path.as_str())
+                } else {
+                    item_path.strip_prefix(&format!("{path}/"))
+                };
+                relative_path.map_or(false, |relative_path| {
+                    recursive || !relative_path.contains('/')
+                })
+            })
+            .filter(|(_, item)| match filter {
+                RcloneListFilter::All => true,
+                RcloneListFilter::Dirs => item.contents.is_none(),
+                RcloneListFilter::Files => item.contents.is_some(),
+            })
+            .map(|(item_path, item)| Self::to_remote_item(item_path, item))
+            .collect())
+    }
+
+    fn list_files_with_hashes(&self, path: &str) -> Result<Vec<RcloneRemoteItem>, RcloneError> {
+        self.list(path, true, RcloneListFilter::Files)
+    }
+
+    fn list_stream(&self, path: &str) -> ListStream {
+        match self.list(path, false, RcloneListFilter::All) {
+            Ok(items) => Box::new(items.into_iter().map(Ok)),
+            Err(err) => Box::new(iter::once(Err(err))),
+        }
+    }
+
+    fn free_space(&self) -> Result<Option<u64>, RcloneError> {
+        Ok(None)
+    }
+
+    fn mkdir(&self, path: &str) -> Result<(), RcloneError> {
+        let path = libceleste::strip_slashes(path);
+        let now = OffsetDateTime::now_utc();
+        self.add_parents(&path, now);
+        self.items.borrow_mut().entry(path).or_insert(MemoryItem {
+            contents: None,
+            mod_time: now,
+        });
+        Ok(())
+    }
+
+    fn delete(&self, path: &str) -> Result<(), RcloneError> {
+        let path = libceleste::strip_slashes(path);
+        let mut items = self.items.borrow_mut();
+
+        match items.get(&path) {
+            Some(item) if item.contents.is_some() => {
+                items.remove(&path);
+                Ok(())
+            }
+            _ => Err(Self::not_found(&path)),
+        }
+    }
+
+    fn purge(&self, path: &str) -> Result<(), RcloneError> {
+        let path = libceleste::strip_slashes(path);
+        let mut items = self.items.borrow_mut();
+
+        if items.remove(&path).is_none() {
+            return Err(Self::not_found(&path));
+        }
+        items.retain(|item_path, _| !item_path.starts_with(&format!("{path}/")));
+        Ok(())
+    }
+
+    fn move_file(&self, src: &str, dst: &str) -> Result<(), RcloneError> {
+        let src = libceleste::strip_slashes(src);
+        let dst = libceleste::strip_slashes(dst);
+        let item = self
+            .items
+            .borrow_mut()
+            .remove(&src)
+            .ok_or_else(|| Self::not_found(&src))?;
+        self.add_parents(&dst, item.mod_time);
+        self.items.borrow_mut().insert(dst, item);
+        Ok(())
+    }
+
+    fn supports_server_side_copy(&self) -> bool {
+        true
+    }
+
+    fn copy_file(&self, src: &str, dst: &str) -> Result<(), RcloneError> {
+        let src = libceleste::strip_slashes(src);
+        let dst = libceleste::strip_slashes(dst);
+        let item = self
+            .items
+            .borrow()
+            .get(&src)
+            .cloned()
+            .ok_or_else(|| Self::not_found(&src))?;
+        self.add_parents(&dst, item.mod_time);
+        self.items.borrow_mut().insert(dst, item);
+        Ok(())
+    }
+
+    fn copy_to_remote(
+        &self,
+        local_file: &str,
+        remote_destination: &str,
+    ) -> Result<(), RcloneError> {
+        let to_error = |err: std::io::Error| RcloneError::Unknown(err.to_string());
+        let contents = fs::read(local_file).map_err(to_error)?;
+        let mod_time = fs::metadata(local_file)
+            .and_then(|metadata| metadata.modified())
+            .map_err(to_error)?;
+        self.insert_file(remote_destination, &contents, mod_time.into());
+        Ok(())
+    }
+
+    fn copy_to_local(&self, local_destination: &str, remote_file: &str) -> Result<(), RcloneError> {
+        let remote_file = libceleste::strip_slashes(remote_file);
+        let item = self
+            .items
+            .borrow()
+            .get(&remote_file)
+            .cloned()
+            .ok_or_else(|| Self::not_found(&remote_file))?;
+        let contents = item.contents.ok_or_else(|| Self::not_found(&remote_file))?;
+
+        // Like Rclone, keep the modification time of the remote item.
+        let to_error = |err: std::io::Error| RcloneError::Unknown(err.to_string());
+        if let Some(parent) = Path::new(local_destination).parent() {
+            fs::create_dir_all(parent).map_err(to_error)?;
+        }
+        fs::write(local_destination, contents).map_err(to_error)?;
+        fs::File::options()
+            .write(true)
+            .open(local_destination)
+            .and_then(|file| file.set_modified(item.mod_time.into()))
+            .map_err(to_error)
+    }
+}
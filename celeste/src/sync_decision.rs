@@ -0,0 +1,435 @@
+//! The decisions made when syncing an item, kept separate from the actual
+//! syncing so that they don't depend on the state of the local or remote
+//! side.
+use crate::{
+    capabilities::RemoteCapabilities,
+    entities::{RemotesModel, SettingsModel},
+    rclone,
+};
+use sea_orm::DatabaseConnection;
+
+/// How the modification times of local items get compared against those of
+/// remote ones. Timestamps from the same side are always compared exactly, but
+/// ones from different sides can disagree slightly from clock skew or from the
+/// remote storing them with less precision.
+#[derive(Clone, Copy, Debug)]
+pub struct TimestampComparison {
+    /// How many seconds apart a local and remote timestamp can be while still
+    /// counting as the same.
+    tolerance: i64,
+    /// The precision the remote stores timestamps with, in seconds.
+    remote_precision: i64,
+}
+
+impl TimestampComparison {
+    /// Get the comparison settings to use for `remote`.
+    pub fn new(remote: &RemotesModel, db: &DatabaseConnection) -> Self {
+        // Timestamps are only tracked to the second, so anything more precise than
+        // that doesn't matter. What Rclone reports for the remote is used if it's
+        // known, since it can differ between servers of the same type (such as
+        // WebDAV ones).
+        let remote_precision = RemoteCapabilities::get(db, remote)
+            .and_then(|capabilities| capabilities.mod_time_precision)
+            .or_else(|| {
+                rclone::get_remote(&remote.name)
+                    .map(|rclone_remote| rclone_remote.timestamp_precision())
+            })
+            .map_or(1, |precision| precision.as_secs())
+            .max(1);
+
+        Self::with_tolerance(
+            SettingsModel::get(db).timestamp_tolerance.into(),
+            remote_precision.try_into().unwrap(),
+        )
+    }
+
+    /// Compare timestamps with the given `tolerance` and `remote_precision`,
+    /// both in seconds.
+    pub fn with_tolerance(tolerance: i64, remote_precision: i64) -> Self {
+        Self {
+            tolerance,
+            remote_precision,
+        }
+    }
+
+    /// Round `timestamp` down to the precision the remote stores timestamps
+    /// with.
+    fn normalize(&self, timestamp: i64) -> i64 {
+        timestamp - timestamp.rem_euclid(self.remote_precision)
+    }
+
+    /// Whether the local timestamp is newer than the remote one.
+    fn local_is_newer(&self, local_timestamp: i64, remote_timestamp: i64) -> bool {
+        self.normalize(local_timestamp) - self.normalize(remote_timestamp) > self.tolerance
+    }
+
+    /// Whether the remote timestamp is newer than the local one.
+    fn remote_is_newer(&self, local_timestamp: i64, remote_timestamp: i64) -> bool {
+        self.normalize(remote_timestamp) - self.normalize(local_timestamp) > self.tolerance
+    }
+
+    /// Whether the local and remote timestamps count as the same.
+    pub fn matches(&self, local_timestamp: i64, remote_timestamp: i64) -> bool {
+        !self.local_is_newer(local_timestamp, remote_timestamp)
+            && !self.remote_is_newer(local_timestamp, remote_timestamp)
+    }
+}
+
+/// The state of an item on the local or remote side.
+#[derive(Clone, Copy, Debug)]
+pub struct ItemMeta {
+    /// The UNIX timestamp of the item.
+    pub timestamp: i64,
+    /// Whether the item is a directory.
+    pub is_dir: bool,
+}
+
+/// The timestamps of an item when it was last synced, as recorded in the
+/// database.
+#[derive(Clone, Copy, Debug)]
+pub struct SyncRecord {
+    /// The local UNIX timestamp of the item when last synced.
+    pub local_timestamp: i64,
+    /// The remote UNIX timestamp of the item when last synced.
+    pub remote_timestamp: i64,
+}
+
+/// What needs to be done to sync an item.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncDecision {
+    /// Copy the local item to the remote.
+    Upload,
+    /// Copy the remote item to the local side.
+    Download,
+    /// Both items changed since the last sync, so the user needs to pick which
+    /// to keep.
+    Conflict,
+    /// The remote item got deleted, so delete the local one.
+    DeleteLocal,
+    /// The local item got deleted, so delete the remote one.
+    DeleteRemote,
+    /// The items are already in sync.
+    Noop,
+}
+
+/// Which side wins for items that haven't been synced before, as chosen when
+/// setting up a sync directory whose local and remote folders both already
+/// had items in them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum InitialSync {
+    /// Keep the items from both sides, syncing whichever copy is newer.
+    #[default]
+    Merge,
+    /// Make the remote folder match the local one.
+    KeepLocal,
+    /// Make the local folder match the remote one.
+    KeepRemote,
+}
+
+impl InitialSync {
+    /// Get a choice from its name, as stored in the database. Directories
+    /// without one get merged.
+    pub fn from_setting(name: Option<&str>) -> Self {
+        match name {
+            Some("keep_local") => Self::KeepLocal,
+            Some("keep_remote") => Self::KeepRemote,
+            _ => Self::Merge,
+        }
+    }
+
+    /// Get the name of a choice, as stored in the database.
+    pub fn setting_name(&self) -> Option<&'static str> {
+        match self {
+            Self::Merge => None,
+            Self::KeepLocal => Some("keep_local"),
+            Self::KeepRemote => Some("keep_remote"),
+        }
+    }
+
+    /// Adjust the `merged` decision for an item that hasn't been synced before,
+    /// so that the chosen side wins. Directories on both sides still just get
+    /// their contents synced, and matching files are left alone.
+    fn resolve(
+        &self,
+        merged: SyncDecision,
+        local: Option<ItemMeta>,
+        remote: Option<ItemMeta>,
+    ) -> SyncDecision {
+        match (self, local, remote) {
+            (Self::Merge, _, _) | (_, None, None) => merged,
+            (_, Some(local), Some(remote)) if local.is_dir && remote.is_dir => merged,
+            (_, Some(_), Some(_)) if merged == SyncDecision::Noop => merged,
+            (Self::KeepLocal, Some(_), _) => SyncDecision::Upload,
+            (Self::KeepLocal, None, Some(_)) => SyncDecision::DeleteRemote,
+            (Self::KeepRemote, _, Some(_)) => SyncDecision::Download,
+            (Self::KeepRemote, Some(_), None) => SyncDecision::DeleteLocal,
+        }
+    }
+}
+
+/// Decide what needs to be done to sync an item, from the state of its `local`
+/// and `remote` copies and the `record` of when it was last synced. Items
+/// without a record are synced according to `initial_sync`.
+pub fn decide(
+    local: Option<ItemMeta>,
+    remote: Option<ItemMeta>,
+    record: Option<SyncRecord>,
+    timestamps: TimestampComparison,
+    initial_sync: InitialSync,
+) -> SyncDecision {
+    // With a record of the last sync, each side only needs to be compared against
+    // its own timestamp from then.
+    if let Some(record) = record {
+        let local_changed = local.map(|local| local.timestamp != record.local_timestamp);
+        let remote_changed = remote.map(|remote| remote.timestamp != record.remote_timestamp);
+
+        match (local_changed, remote_changed) {
+            (Some(true), Some(true)) => {
+                // There's no point in saying both directories changed, it's
+                // probably because one of the items inside them got updated
+                // anyway.
+                if local.unwrap().is_dir && remote.unwrap().is_dir {
+                    SyncDecision::Noop
+                } else {
+                    SyncDecision::Conflict
+                }
+            }
+            (Some(true), _) => SyncDecision::Upload,
+            (_, Some(true)) => SyncDecision::Download,
+            (Some(false), None) => SyncDecision::DeleteLocal,
+            (None, Some(false)) => SyncDecision::DeleteRemote,
+            (Some(false), Some(false)) | (None, None) => SyncDecision::Noop,
+        }
+    // Otherwise the local and remote timestamps have to be compared against
+    // each other.
+    } else {
+        let merged = match (local, remote) {
+            (Some(local), Some(remote)) => {
+                if timestamps.local_is_newer(local.timestamp, remote.timestamp) {
+                    SyncDecision::Upload
+                // Both items are files with timestamps within the tolerance, so
+                // they're already in sync.
+                } else if !local.is_dir
+                    && !remote.is_dir
+                    && !timestamps.remote_is_newer(local.timestamp, remote.timestamp)
+                {
+                    SyncDecision::Noop
+                } else {
+                    SyncDecision::Download
+                }
+            }
+            (Some(_), None) => SyncDecision::Upload,
+            (None, Some(_)) => SyncDecision::Download,
+            (None, None) => SyncDecision::Noop,
+        };
+        initial_sync.resolve(merged, local, remote)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_INITIAL_SYNCS: [InitialSync; 3] = [
+        InitialSync::Merge,
+        InitialSync::KeepLocal,
+        InitialSync::KeepRemote,
+    ];
+
+    fn file(timestamp: i64) -> Option<ItemMeta> {
+        Some(ItemMeta {
+            timestamp,
+            is_dir: false,
+        })
+    }
+
+    fn dir(timestamp: i64) -> Option<ItemMeta> {
+        Some(ItemMeta {
+            timestamp,
+            is_dir: true,
+        })
+    }
+
+    fn record(local_timestamp: i64, remote_timestamp: i64) -> Option<SyncRecord> {
+        Some(SyncRecord {
+            local_timestamp,
+            remote_timestamp,
+        })
+    }
+
+    fn exact() -> TimestampComparison {
+        TimestampComparison::with_tolerance(0, 1)
+    }
+
+    #[test]
+    fn decide_with_record() {
+        // Items with a record only get compared against their own side's timestamp,
+        // so how the first sync was set up doesn't matter.
+        let cases = [
+            (file(100), file(200), SyncDecision::Noop),
+            (file(150), file(200), SyncDecision::Upload),
+            (file(100), file(250), SyncDecision::Download),
+            (file(150), file(250), SyncDecision::Conflict),
+            (dir(150), dir(250), SyncDecision::Noop),
+            (dir(150), file(250), SyncDecision::Conflict),
+            (file(150), dir(250), SyncDecision::Conflict),
+            (dir(150), dir(200), SyncDecision::Upload),
+            (dir(100), dir(250), SyncDecision::Download),
+            (file(100), None, SyncDecision::DeleteLocal),
+            (file(150), None, SyncDecision::Upload),
+            (None, file(200), SyncDecision::DeleteRemote),
+            (None, file(250), SyncDecision::Download),
+            (None, None, SyncDecision::Noop),
+        ];
+
+        for (local, remote, expected) in cases {
+            for initial_sync in ALL_INITIAL_SYNCS {
+                assert_eq!(
+                    decide(local, remote, record(100, 200), exact(), initial_sync),
+                    expected,
+                    "local: {local:?}, remote: {remote:?}, initial sync: {initial_sync:?}",
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn decide_with_record_ignores_tolerance() {
+        // Timestamps from the same side are always compared exactly.
+        let timestamps = TimestampComparison::with_tolerance(60, 60);
+        assert_eq!(
+            decide(
+                file(101),
+                file(200),
+                record(100, 200),
+                timestamps,
+                InitialSync::Merge
+            ),
+            SyncDecision::Upload,
+        );
+        assert_eq!(
+            decide(
+                file(100),
+                file(201),
+                record(100, 200),
+                timestamps,
+                InitialSync::Merge
+            ),
+            SyncDecision::Download,
+        );
+    }
+
+    #[test]
+    fn decide_without_record() {
+        use InitialSync::*;
+        use SyncDecision::*;
+
+        let cases = [
+            // Merging syncs whichever side is newer.
+            (Merge, file(200), file(100), Upload),
+            (Merge, file(100), file(200), Download),
+            (Merge, file(100), file(100), Noop),
+            (Merge, dir(200), dir(100), Upload),
+            (Merge, dir(100), dir(100), Download),
+            (Merge, dir(100), file(100), Download),
+            (Merge, file(100), dir(100), Download),
+            (Merge, file(100), None, Upload),
+            (Merge, dir(100), None, Upload),
+            (Merge, None, file(100), Download),
+            (Merge, None, None, Noop),
+            // Keeping the local side makes the remote match it.
+            (KeepLocal, file(200), file(100), Upload),
+            (KeepLocal, file(100), file(200), Upload),
+            (KeepLocal, file(100), file(100), Noop),
+            (KeepLocal, dir(100), dir(200), Download),
+            (KeepLocal, dir(100), file(200), Upload),
+            (KeepLocal, file(100), None, Upload),
+            (KeepLocal, None, file(100), DeleteRemote),
+            (KeepLocal, None, dir(100), DeleteRemote),
+            (KeepLocal, None, None, Noop),
+            // Keeping the remote side makes the local match it.
+            (KeepRemote, file(200), file(100), Download),
+            (KeepRemote, file(100), file(200), Download),
+            (KeepRemote, file(100), file(100), Noop),
+            (KeepRemote, dir(200), dir(100), Upload),
+            (KeepRemote, file(200), dir(100), Download),
+            (KeepRemote, file(100), None, DeleteLocal),
+            (KeepRemote, dir(100), None, DeleteLocal),
+            (KeepRemote, None, file(100), Download),
+            (KeepRemote, None, None, Noop),
+        ];
+
+        for (initial_sync, local, remote, expected) in cases {
+            assert_eq!(
+                decide(local, remote, None, exact(), initial_sync),
+                expected,
+                "local: {local:?}, remote: {remote:?}, initial sync: {initial_sync:?}",
+            );
+        }
+    }
+
+    #[test]
+    fn decide_without_record_uses_tolerance() {
+        let timestamps = TimestampComparison::with_tolerance(2, 1);
+        let cases = [
+            (file(102), file(100), SyncDecision::Noop),
+            (file(100), file(102), SyncDecision::Noop),
+            (file(103), file(100), SyncDecision::Upload),
+            (file(100), file(103), SyncDecision::Download),
+        ];
+
+        for (local, remote, expected) in cases {
+            assert_eq!(
+                decide(local, remote, None, timestamps, InitialSync::Merge),
+                expected,
+                "local: {local:?}, remote: {remote:?}",
+            );
+        }
+    }
+
+    #[test]
+    fn decide_without_record_uses_remote_precision() {
+        let timestamps = TimestampComparison::with_tolerance(0, 2);
+        let cases = [
+            (file(101), file(100), SyncDecision::Noop),
+            (file(100), file(101), SyncDecision::Noop),
+            (file(102), file(101), SyncDecision::Upload),
+            (file(101), file(102), SyncDecision::Download),
+            // Timestamps from before the epoch get rounded down too.
+            (file(-1), file(-2), SyncDecision::Noop),
+            (file(-1), file(-3), SyncDecision::Upload),
+        ];
+
+        for (local, remote, expected) in cases {
+            assert_eq!(
+                decide(local, remote, None, timestamps, InitialSync::Merge),
+                expected,
+                "local: {local:?}, remote: {remote:?}",
+            );
+        }
+    }
+
+    #[test]
+    fn timestamps_match() {
+        let timestamps = TimestampComparison::with_tolerance(1, 1);
+        assert!(timestamps.matches(100, 100));
+        assert!(timestamps.matches(101, 100));
+        assert!(timestamps.matches(100, 101));
+        assert!(!timestamps.matches(102, 100));
+        assert!(!timestamps.matches(100, 102));
+    }
+
+    #[test]
+    fn initial_sync_setting_names() {
+        for initial_sync in ALL_INITIAL_SYNCS {
+            assert_eq!(
+                InitialSync::from_setting(initial_sync.setting_name()),
+                initial_sync
+            );
+        }
+        assert_eq!(
+            InitialSync::from_setting(Some("unknown")),
+            InitialSync::Merge
+        );
+    }
+}
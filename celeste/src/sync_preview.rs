@@ -0,0 +1,243 @@
+//! A preview of what the first sync of a sync directory would transfer and
+//! delete, worked out from a listing of each side before anything gets synced.
+//! The items on each side are run through the same decisions as in an actual
+//! sync, so the preview matches what a sync would do at the time it was made.
+use crate::{
+    entities::SyncDirsModel,
+    exclusions::{self, SyncFilter},
+    launch::system_time_to_timestamp,
+    paths,
+    rclone::{RcloneError, RcloneListFilter},
+    sync_backend::SyncBackend,
+    sync_decision::{self, InitialSync, ItemMeta, SyncDecision, TimestampComparison},
+};
+use std::{collections::HashMap, fs, path::Path};
+
+/// A number of files, along with their total size.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FileCount {
+    /// The number of files.
+    pub files: u64,
+    /// The total size of the files, in bytes.
+    pub bytes: u64,
+}
+
+impl FileCount {
+    /// Count another file of `bytes` in size.
+    fn add(&mut self, bytes: u64) {
+        self.files += 1;
+        self.bytes += bytes;
+    }
+
+    /// Whether there aren't any files.
+    pub fn is_empty(&self) -> bool {
+        self.files == 0
+    }
+}
+
+/// What a sync would do with the files in a sync directory.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Transfers {
+    /// The files that would be uploaded.
+    pub uploads: FileCount,
+    /// The files that would be downloaded.
+    pub downloads: FileCount,
+    /// The local files that would be deleted.
+    pub local_deletions: FileCount,
+    /// The remote files that would be deleted.
+    pub remote_deletions: FileCount,
+}
+
+/// A file on one side of a sync directory.
+struct PreviewFile {
+    /// The UNIX timestamp of the file.
+    timestamp: i64,
+    /// The size of the file, in bytes.
+    size: u64,
+}
+
+impl PreviewFile {
+    /// Get the [`ItemMeta`] to decide how to sync the file with.
+    fn meta(&self) -> ItemMeta {
+        ItemMeta {
+            timestamp: self.timestamp,
+            is_dir: false,
+        }
+    }
+}
+
+/// The choices for how to first sync a directory.
+const CHOICES: [InitialSync; 3] = [
+    InitialSync::Merge,
+    InitialSync::KeepLocal,
+    InitialSync::KeepRemote,
+];
+
+/// What the first sync of a sync directory would do, for each of the choices
+/// of which side wins.
+#[derive(Debug)]
+pub struct SyncPreview {
+    /// The files in the local folder.
+    pub local_files: FileCount,
+    /// The files in the remote folder.
+    pub remote_files: FileCount,
+    /// What a sync would do for each choice.
+    choices: HashMap<InitialSync, Transfers>,
+}
+
+/// Whether the item at `relative_path` (relative to the root of `sync_dir`)
+/// gets synced, including whether any of the directories it's in do.
+fn is_synced(
+    sync_dir: &SyncDirsModel,
+    filter: &SyncFilter,
+    relative_path: &str,
+    is_dir: bool,
+) -> bool {
+    if exclusions::is_builtin_exclusion(sync_dir, relative_path) {
+        return false;
+    }
+
+    let parents_synced = relative_path
+        .match_indices('/')
+        .all(|(index, _)| filter.includes(&relative_path[..index], true));
+    parents_synced && filter.includes(relative_path, is_dir)
+}
+
+/// Add the files inside of the local directory `dir` to `files`, keyed by
+/// their paths relative to the root of `sync_dir`.
+fn list_local_files(
+    dir: &Path,
+    sync_dir: &SyncDirsModel,
+    filter: &SyncFilter,
+    files: &mut HashMap<String, PreviewFile>,
+) {
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let Some(relative_path) =
+            paths::relative_local_path(sync_dir, &path).and_then(|path| path.to_str())
+        else {
+            continue;
+        };
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if !is_synced(sync_dir, filter, relative_path, file_type.is_dir()) {
+            continue;
+        }
+
+        if file_type.is_dir() {
+            list_local_files(&path, sync_dir, filter, files);
+        } else if let Ok(metadata) = entry.metadata()
+            && let Ok(modified) = metadata.modified()
+            && filter.includes_modified(system_time_to_timestamp(modified))
+        {
+            files.insert(
+                relative_path.to_owned(),
+                PreviewFile {
+                    timestamp: system_time_to_timestamp(modified),
+                    size: metadata.len(),
+                },
+            );
+        }
+    }
+}
+
+impl SyncPreview {
+    /// Generate the preview for `sync_dir`, whose items get synced according to
+    /// `filter`. This lists every file on both sides, so it can take a while on
+    /// large directories.
+    pub fn generate(
+        backend: &dyn SyncBackend,
+        sync_dir: &SyncDirsModel,
+        filter: &SyncFilter,
+        timestamps: TimestampComparison,
+    ) -> Result<Self, RcloneError> {
+        let mut local = HashMap::new();
+        list_local_files(
+            Path::new(&sync_dir.local_path),
+            sync_dir,
+            filter,
+            &mut local,
+        );
+
+        let mut remote = HashMap::new();
+        for item in backend.list(&sync_dir.remote_path, true, RcloneListFilter::Files)? {
+            let Some(relative_path) = paths::relative_remote_path(sync_dir, &item.path) else {
+                continue;
+            };
+            if is_synced(sync_dir, filter, relative_path, false)
+                && filter.includes_modified(item.mod_time.unix_timestamp())
+            {
+                remote.insert(
+                    relative_path.to_owned(),
+                    PreviewFile {
+                        timestamp: item.mod_time.unix_timestamp(),
+                        size: item.size.max(0).try_into().unwrap(),
+                    },
+                );
+            }
+        }
+
+        let mut preview = Self {
+            local_files: FileCount::default(),
+            remote_files: FileCount::default(),
+            choices: CHOICES
+                .into_iter()
+                .map(|choice| (choice, Transfers::default()))
+                .collect(),
+        };
+        local
+            .values()
+            .for_each(|file| preview.local_files.add(file.size));
+        remote
+            .values()
+            .for_each(|file| preview.remote_files.add(file.size));
+
+        let remote_only = remote
+            .iter()
+            .filter(|(relative_path, _)| !local.contains_key(*relative_path))
+            .map(|(_, remote_file)| (None, Some(remote_file)));
+        let pairs = local
+            .iter()
+            .map(|(relative_path, local_file)| (Some(local_file), remote.get(relative_path)))
+            .chain(remote_only);
+
+        for (local_file, remote_file) in pairs {
+            for (choice, transfers) in preview.choices.iter_mut() {
+                let decision = sync_decision::decide(
+                    local_file.map(PreviewFile::meta),
+                    remote_file.map(PreviewFile::meta),
+                    None,
+                    timestamps,
+                    *choice,
+                );
+                let local_size = local_file.map_or(0, |file| file.size);
+                let remote_size = remote_file.map_or(0, |file| file.size);
+
+                match decision {
+                    SyncDecision::Upload => transfers.uploads.add(local_size),
+                    SyncDecision::Download => transfers.downloads.add(remote_size),
+                    SyncDecision::DeleteLocal => transfers.local_deletions.add(local_size),
+                    SyncDecision::DeleteRemote => transfers.remote_deletions.add(remote_size),
+                    SyncDecision::Conflict | SyncDecision::Noop => (),
+                }
+            }
+        }
+
+        Ok(preview)
+    }
+
+    /// Get what a sync would do with `initial_sync` chosen.
+    pub fn transfers(&self, initial_sync: InitialSync) -> Transfers {
+        self.choices[&initial_sync]
+    }
+
+    /// Whether both sides already have files in them that differ, in which
+    /// case the user should choose which side wins.
+    pub fn has_differences(&self) -> bool {
+        let merge = self.transfers(InitialSync::Merge);
+        !self.local_files.is_empty()
+            && !self.remote_files.is_empty()
+            && !(merge.uploads.is_empty() && merge.downloads.is_empty())
+    }
+}
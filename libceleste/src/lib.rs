@@ -2,7 +2,7 @@ pub mod traits;
 
 use futures::future::Future;
 use glib::{self, MainContext};
-use std::path::PathBuf;
+use std::{env, path::PathBuf};
 
 /// The ID of the app.
 pub static APP_ID: &str = "com.hunterwittenborn.Celeste";
@@ -35,7 +35,7 @@ pub fn run_in_background<T: Send + 'static, F: FnOnce() -> T + Send + 'static>(f
 
 /// Format a directory with the user's home directory replaced with '~'.
 pub fn fmt_home(dir: &str) -> String {
-    let home_dir = glib::home_dir().into_os_string().into_string().unwrap();
+    let home_dir = glib::home_dir().to_string_lossy().into_owned();
 
     match dir.strip_prefix(&home_dir) {
         Some(string) => "~".to_string() + string,
@@ -50,6 +50,19 @@ pub fn get_config_dir() -> PathBuf {
     config_dir
 }
 
+/// Get the directory that translations are installed in.
+pub fn get_locale_dir() -> String {
+    // Snaps have their files installed under `$SNAP`.
+    let prefix = env::var("SNAP").unwrap_or_default();
+    format!("{prefix}/usr/share/locale")
+}
+
+/// Set up translations for the strings in this library. Each binary also needs
+/// to set up translations for its own strings.
+pub fn init_translations() {
+    tr::tr_init!(get_locale_dir());
+}
+
 /// Strip the slashes from the beginning and end of a string.
 pub fn strip_slashes(string: &str) -> String {
     let stripped_prefix = match string.strip_prefix('/') {